@@ -23,11 +23,21 @@ export type Platform = {
   /** Open directory picker dialog (native on Tauri, server-backed on web) */
   openDirectoryPickerDialog?(opts?: { title?: string; multiple?: boolean }): Promise<string | string[] | null>
 
-  /** Open native file picker dialog (Tauri only) */
-  openFilePickerDialog?(opts?: { title?: string; multiple?: boolean }): Promise<string | string[] | null>
-
-  /** Save file picker dialog (Tauri only) */
-  saveFilePickerDialog?(opts?: { title?: string; defaultPath?: string }): Promise<string | null>
+  /** Open native file picker dialog (Tauri only), optionally limited to some extensions */
+  openFilePickerDialog?(opts?: {
+    title?: string
+    multiple?: boolean
+    filters?: { name: string; extensions: string[] }[]
+    defaultPath?: string
+  }): Promise<string | string[] | null>
+
+  /** Save file picker dialog (Tauri only); a name typed without an extension gets the first filter's */
+  saveFilePickerDialog?(opts?: {
+    title?: string
+    defaultPath?: string
+    defaultDirectory?: string
+    filters?: { name: string; extensions: string[] }[]
+  }): Promise<string | null>
 
   /** Storage mechanism, defaults to localStorage */
   storage?: (name?: string) => SyncStorage | AsyncStorage
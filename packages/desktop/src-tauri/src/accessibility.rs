@@ -0,0 +1,372 @@
+//! System accessibility settings the UI should follow
+//!
+//! Webviews only expose `prefers-reduced-motion`,
+//! `prefers-reduced-transparency` and `forced-colors` on some platforms and
+//! never the pointer size, so they're read from the OS here instead. The
+//! settings are polled, as none of the three platforms has a single change
+//! notification for them.
+//!
+//! `announce` goes the other way, having screen readers speak a message
+//! such as a finished upload or a sync error wherever focus is.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+
+const ACCESSIBILITY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccessibilityPreferences {
+    /// Animations are turned off or reduced
+    pub reduce_motion: bool,
+    /// Translucent and blurred surfaces should be opaque
+    pub reduce_transparency: bool,
+    /// Pointer size as a multiple of the default, e.g. `1.5`
+    pub cursor_scale: f64,
+    /// A high contrast theme is on: a Windows contrast theme, which forces
+    /// its own colors on pages, Increase Contrast on macOS, or GNOME's high
+    /// contrast setting
+    pub high_contrast: bool,
+}
+
+/// How urgently a screen reader should speak an announcement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementPriority {
+    /// Waits for whatever is being read
+    Low,
+    #[default]
+    Medium,
+    /// Interrupts whatever is being read
+    High,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Announcement {
+    pub text: String,
+    pub priority: AnnouncementPriority,
+}
+
+impl Default for AccessibilityPreferences {
+    fn default() -> Self {
+        Self {
+            reduce_motion: false,
+            reduce_transparency: false,
+            cursor_scale: 1.0,
+            high_contrast: false,
+        }
+    }
+}
+
+/// Poll the accessibility settings, emitting `accessibility-changed` when
+/// any of them changes
+pub fn watch_accessibility(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(ACCESSIBILITY_POLL_INTERVAL);
+        let mut last: Option<AccessibilityPreferences> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(current) = tauri::async_runtime::spawn_blocking(os::read).await else {
+                continue;
+            };
+            if last.as_ref().is_some_and(|last| *last != current) {
+                events::publish(&app, Event::AccessibilityChanged(current.clone()));
+            }
+            last = Some(current);
+        }
+    });
+}
+
+/// Whether a high contrast theme is on, for the capabilities report
+pub fn high_contrast() -> bool {
+    os::read().high_contrast
+}
+
+/// The OS reduce motion, reduce transparency, pointer size and high
+/// contrast settings
+///
+/// Settings a platform doesn't have are left at their defaults: Linux has
+/// no reduce transparency setting, and is read through GNOME's settings.
+#[tauri::command]
+pub async fn get_accessibility_preferences() -> Result<AccessibilityPreferences, String> {
+    tauri::async_runtime::spawn_blocking(os::read)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Have screen readers speak a message, even when focus is elsewhere
+///
+/// Posted as an NSAccessibility announcement on macOS and a UI Automation
+/// notification on Windows. Linux screen readers only hear announcements
+/// from accessible widgets, which belong to the webview, so there, or if
+/// posting fails, it's sent as an `announcement` event for the frontend to
+/// put in an ARIA live region.
+#[tauri::command]
+pub async fn announce(
+    app: AppHandle,
+    text: String,
+    priority: Option<AnnouncementPriority>,
+) -> Result<(), String> {
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("Nothing to announce".to_string());
+    }
+    let announcement = Announcement {
+        text,
+        priority: priority.unwrap_or_default(),
+    };
+    if let Err(e) = os::announce(&app, &announcement) {
+        log::debug!("Announcing in the webview instead: {}", e);
+        events::publish(&app, Event::Announcement(announcement));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use super::{AccessibilityPreferences, Announcement, AnnouncementPriority};
+    use tauri::{AppHandle, Manager};
+    use windows::core::BSTR;
+    use windows::core::{w, BOOL, PCWSTR};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+    use windows::Win32::UI::Accessibility::{
+        NotificationKind_Other, NotificationProcessing_All, NotificationProcessing_ImportantAll,
+        NotificationProcessing_MostRecent, UiaClientsAreListening, UiaHostProviderFromHwnd,
+        UiaRaiseNotificationEvent, HCF_HIGHCONTRASTON, HIGHCONTRASTW,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST,
+        SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    fn dword(key: PCWSTR, value: PCWSTR) -> Option<u32> {
+        let mut data = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let result = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                key,
+                value,
+                RRF_RT_REG_DWORD,
+                None,
+                Some(&mut data as *mut u32 as *mut _),
+                Some(&mut size),
+            )
+        };
+        result.is_ok().then_some(data)
+    }
+
+    pub fn read() -> AccessibilityPreferences {
+        // "Animation effects" in Settings > Accessibility > Visual effects
+        let mut animations = BOOL(1);
+        let _ = unsafe {
+            SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                Some(&mut animations as *mut BOOL as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        };
+        let transparency = dword(
+            w!(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+            w!("EnableTransparency"),
+        );
+        // 1 to 15, growing the 32 pixel pointer by 16 pixels a step
+        let cursor_size = dword(w!(r"Software\Microsoft\Accessibility"), w!("CursorSize"));
+        // Contrast themes in Settings > Accessibility, which WebView2 turns
+        // into `forced-colors: active`
+        let mut contrast = HIGHCONTRASTW {
+            cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+            ..Default::default()
+        };
+        let high_contrast = unsafe {
+            SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                contrast.cbSize,
+                Some(&mut contrast as *mut HIGHCONTRASTW as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .is_ok()
+            && contrast.dwFlags.contains(HCF_HIGHCONTRASTON);
+
+        AccessibilityPreferences {
+            reduce_motion: !animations.as_bool(),
+            reduce_transparency: transparency == Some(0),
+            cursor_scale: cursor_size
+                .filter(|size| *size >= 1)
+                .map(|size| (size + 1) as f64 / 2.0)
+                .unwrap_or(1.0),
+            high_contrast,
+        }
+    }
+
+    /// Raised on a window's UI Automation host, which Narrator and NVDA
+    /// read whether or not it has focus
+    pub fn announce(app: &AppHandle, announcement: &Announcement) -> Result<(), String> {
+        let window = app
+            .get_webview_window("main")
+            .or_else(|| app.webview_windows().into_values().next())
+            .ok_or("No window to announce from")?;
+        let hwnd = window.hwnd().map_err(|e| e.to_string())?.0 as isize;
+        let text = announcement.text.clone();
+        let processing = match announcement.priority {
+            AnnouncementPriority::Low => NotificationProcessing_All,
+            AnnouncementPriority::Medium => NotificationProcessing_MostRecent,
+            AnnouncementPriority::High => NotificationProcessing_ImportantAll,
+        };
+        window
+            .run_on_main_thread(move || unsafe {
+                if !UiaClientsAreListening().as_bool() {
+                    return;
+                }
+                let result = UiaHostProviderFromHwnd(HWND(hwnd as _)).and_then(|provider| {
+                    UiaRaiseNotificationEvent(
+                        &provider,
+                        NotificationKind_Other,
+                        processing,
+                        &BSTR::from(text.as_str()),
+                        &BSTR::from("apollo-announcement"),
+                    )
+                });
+                if let Err(e) = result {
+                    log::warn!("Failed to announce: {}", e);
+                }
+            })
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use super::{AccessibilityPreferences, Announcement, AnnouncementPriority};
+    use cocoa::base::{id, nil, BOOL, NO};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::AppHandle;
+
+    pub fn read() -> AccessibilityPreferences {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let reduce_motion: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+            let reduce_transparency: BOOL =
+                msg_send![workspace, accessibilityDisplayShouldReduceTransparency];
+            let increase_contrast: BOOL =
+                msg_send![workspace, accessibilityDisplayShouldIncreaseContrast];
+
+            // "Pointer size" in Accessibility > Display, from 1.0 to 4.0
+            let suite = NSString::alloc(nil).init_str("com.apple.universalaccess");
+            let defaults: id = msg_send![class!(NSUserDefaults), alloc];
+            let defaults: id = msg_send![defaults, initWithSuiteName: suite];
+            let key = NSString::alloc(nil).init_str("mouseDriverCursorSize");
+            let cursor_size: f64 = if defaults == nil {
+                0.0
+            } else {
+                msg_send![defaults, doubleForKey: key]
+            };
+            let _: () = msg_send![defaults, release];
+            let _: () = msg_send![suite, release];
+            let _: () = msg_send![key, release];
+
+            AccessibilityPreferences {
+                reduce_motion: reduce_motion != NO,
+                reduce_transparency: reduce_transparency != NO,
+                cursor_scale: if cursor_size >= 1.0 { cursor_size } else { 1.0 },
+                high_contrast: increase_contrast != NO,
+            }
+        }
+    }
+
+    /// Posted on the application, so VoiceOver reads it whichever window,
+    /// if any, has focus
+    pub fn announce(app: &AppHandle, announcement: &Announcement) -> Result<(), String> {
+        let text = announcement.text.clone();
+        let priority: i64 = match announcement.priority {
+            AnnouncementPriority::Low => 10,
+            AnnouncementPriority::Medium => 50,
+            AnnouncementPriority::High => 90,
+        };
+        app.run_on_main_thread(move || unsafe {
+            let application: id = msg_send![class!(NSApplication), sharedApplication];
+            let text = NSString::alloc(nil).init_str(&text);
+            let priority: id = msg_send![class!(NSNumber), numberWithInteger: priority];
+            let info: id = msg_send![class!(NSMutableDictionary), dictionary];
+            let _: () = msg_send![info, setObject: text forKey: NSAccessibilityAnnouncementKey];
+            let _: () = msg_send![info, setObject: priority forKey: NSAccessibilityPriorityKey];
+            NSAccessibilityPostNotificationWithUserInfo(
+                application,
+                NSAccessibilityAnnouncementRequestedNotification,
+                info,
+            );
+            let _: () = msg_send![text, release];
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {
+        static NSAccessibilityAnnouncementRequestedNotification: id;
+        static NSAccessibilityAnnouncementKey: id;
+        static NSAccessibilityPriorityKey: id;
+        fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, info: id);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use super::{AccessibilityPreferences, Announcement};
+    use std::process::Command;
+    use tauri::AppHandle;
+
+    /// Size GNOME draws the pointer at unless the user changed it
+    const DEFAULT_CURSOR_SIZE: f64 = 24.0;
+
+    fn gsetting(schema: &str, key: &str) -> Option<String> {
+        Command::new("gsettings")
+            .args(["get", schema, key])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    }
+
+    pub fn read() -> AccessibilityPreferences {
+        let cursor_size = gsetting("org.gnome.desktop.interface", "cursor-size")
+            .and_then(|size| size.parse::<f64>().ok())
+            .filter(|size| *size > 0.0);
+
+        AccessibilityPreferences {
+            reduce_motion: gsetting("org.gnome.desktop.interface", "enable-animations").as_deref()
+                == Some("false"),
+            reduce_transparency: false,
+            cursor_scale: cursor_size
+                .map(|size| size / DEFAULT_CURSOR_SIZE)
+                .unwrap_or(1.0),
+            high_contrast: gsetting("org.gnome.desktop.a11y.interface", "high-contrast").as_deref()
+                == Some("true"),
+        }
+    }
+
+    pub fn announce(_app: &AppHandle, _announcement: &Announcement) -> Result<(), String> {
+        Err("AT-SPI announcements come from the webview's live regions".to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod os {
+    use super::{AccessibilityPreferences, Announcement};
+    use tauri::AppHandle;
+
+    pub fn read() -> AccessibilityPreferences {
+        AccessibilityPreferences::default()
+    }
+
+    pub fn announce(_app: &AppHandle, _announcement: &Announcement) -> Result<(), String> {
+        Err("Screen reader announcements aren't supported on this platform".to_string())
+    }
+}
@@ -0,0 +1,246 @@
+//! Server activity notifications while Apollo is in the tray
+//!
+//! With every window hidden or minimized there's no frontend polling the
+//! server, so the activity endpoint is polled from here instead and new
+//! shares, comments and memories are raised as native notifications. Ids
+//! already seen are kept in the store, so nothing is announced twice, even
+//! across restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::http_cache;
+use crate::notifications::{self, NotificationCategory};
+use crate::profiles;
+
+const ACTIVITY_SETTINGS_KEY: &str = "activityPolling";
+/// Activity ids already notified or skipped, newest last
+const ACTIVITY_SEEN_KEY: &str = "activitySeen";
+const MAX_SEEN: usize = 500;
+/// How often to check whether a poll is due
+const ACTIVITY_TICK: Duration = Duration::from_secs(60);
+const MIN_INTERVAL_MINUTES: u64 = 1;
+/// More than this many new events in one poll are only marked seen
+const MAX_NOTIFICATIONS_PER_POLL: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivitySettings {
+    pub enabled: bool,
+    /// Endpoint listing recent activity, newest first
+    pub activity_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// JSON pointer to the list in the response, e.g. `/items`; the
+    /// response itself when unset
+    pub items_pointer: Option<String>,
+    pub interval_minutes: u64,
+}
+
+impl Default for ActivitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            activity_url: None,
+            headers: HashMap::new(),
+            items_pointer: None,
+            interval_minutes: 5,
+        }
+    }
+}
+
+/// One entry of the activity list, or one event pushed by the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerActivity {
+    /// A string or a number
+    id: serde_json::Value,
+    /// `share`, `comment` or `memory`; other kinds aren't notified
+    #[serde(rename = "type")]
+    kind: String,
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    /// Opened when the notification is clicked
+    #[serde(default)]
+    url: Option<String>,
+}
+
+impl ServerActivity {
+    fn id(&self) -> String {
+        match &self.id {
+            serde_json::Value::String(id) => id.clone(),
+            id => id.to_string(),
+        }
+    }
+
+    fn category(&self) -> Option<NotificationCategory> {
+        match self.kind.as_str() {
+            "share" => Some(NotificationCategory::Shares),
+            "comment" => Some(NotificationCategory::Comments),
+            "memory" => Some(NotificationCategory::Memories),
+            _ => None,
+        }
+    }
+}
+
+fn read_settings(app: &AppHandle) -> Result<ActivitySettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(ACTIVITY_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(ActivitySettings::default()),
+    }
+}
+
+/// `None` until the first poll, which only records what's already there
+fn read_seen(app: &AppHandle) -> Option<Vec<String>> {
+    profiles::store(app)
+        .ok()?
+        .get(ACTIVITY_SEEN_KEY)
+        .and_then(|value| serde_json::from_value(value).ok())
+}
+
+fn write_seen(app: &AppHandle, seen: &[String]) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(ACTIVITY_SEEN_KEY, serde_json::json!(seen));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Whether every window is hidden or minimized, leaving only the tray
+pub fn in_background(app: &AppHandle) -> bool {
+    app.webview_windows().values().all(|window| {
+        !window.is_visible().unwrap_or(false) || window.is_minimized().unwrap_or(false)
+    })
+}
+
+async fn poll(app: &AppHandle, settings: &ActivitySettings) -> Result<(), String> {
+    let Some(url) = &settings.activity_url else {
+        return Ok(());
+    };
+    let response: serde_json::Value = http_cache::get_json(app, url, &settings.headers).await?;
+    let items = match &settings.items_pointer {
+        Some(pointer) => response
+            .pointer(pointer)
+            .ok_or_else(|| format!("The activity response has nothing at '{}'", pointer))?,
+        None => &response,
+    };
+    let activity: Vec<ServerActivity> =
+        serde_json::from_value(items.clone()).map_err(|e| e.to_string())?;
+
+    let seen = read_seen(app);
+    let first_poll = seen.is_none();
+    let mut seen = seen.unwrap_or_default();
+    let new: Vec<&ServerActivity> = activity
+        .iter()
+        .filter(|item| !seen.contains(&item.id()))
+        .collect();
+    if new.is_empty() && !first_poll {
+        return Ok(());
+    }
+
+    if !first_poll {
+        // The newest few, oldest first so they stack in the order they happened
+        for item in new.iter().take(MAX_NOTIFICATIONS_PER_POLL).rev() {
+            let Some(category) = item.category() else {
+                continue;
+            };
+            let _ = notifications::notify(
+                app.clone(),
+                item.title.clone(),
+                item.body.clone(),
+                item.url.clone(),
+                Some(category),
+            )
+            .await;
+        }
+        if new.len() > MAX_NOTIFICATIONS_PER_POLL {
+            log::info!(
+                "Skipped notifying {} older activity events",
+                new.len() - MAX_NOTIFICATIONS_PER_POLL
+            );
+        }
+    }
+
+    seen.extend(new.iter().rev().map(|item| item.id()));
+    let excess = seen.len().saturating_sub(MAX_SEEN);
+    seen.drain(..excess);
+    write_seen(app, &seen)
+}
+
+/// Raise a notification for an event pushed over the realtime connection,
+/// unless a poll already announced it
+///
+/// Pushed events are marked seen so a later poll doesn't announce them
+/// again, once the first poll has recorded what was already there.
+pub async fn push(app: &AppHandle, item: ServerActivity) -> Result<(), String> {
+    let mut seen = read_seen(app);
+    let id = item.id();
+    if seen.as_ref().is_some_and(|seen| seen.contains(&id)) {
+        return Ok(());
+    }
+
+    if let Some(category) = item.category() {
+        notifications::notify(app.clone(), item.title, item.body, item.url, Some(category)).await?;
+    }
+    let Some(seen) = seen.as_mut() else {
+        return Ok(());
+    };
+    seen.push(id);
+    let excess = seen.len().saturating_sub(MAX_SEEN);
+    seen.drain(..excess);
+    write_seen(app, seen)
+}
+
+/// Poll the activity endpoint while Apollo is only in the tray
+///
+/// Settings are read on every tick, so changes apply without a restart.
+pub fn watch_activity(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(ACTIVITY_TICK);
+        let mut last_poll: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(settings) = read_settings(&app) else {
+                continue;
+            };
+            let every =
+                Duration::from_secs(settings.interval_minutes.max(MIN_INTERVAL_MINUTES) * 60);
+            if !settings.enabled
+                || !in_background(&app)
+                || last_poll.is_some_and(|last| last.elapsed() < every)
+            {
+                continue;
+            }
+            last_poll = Some(Instant::now());
+            if let Err(e) = poll(&app, &settings).await {
+                log::warn!("Activity poll failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Get the background activity polling settings
+#[tauri::command]
+pub async fn get_activity_settings(app: AppHandle) -> Result<ActivitySettings, String> {
+    read_settings(&app)
+}
+
+/// Save the background activity polling settings
+///
+/// Changing the endpoint forgets the ids seen so far, and the next poll
+/// only records what's already there.
+#[tauri::command]
+pub async fn set_activity_settings(
+    app: AppHandle,
+    settings: ActivitySettings,
+) -> Result<(), String> {
+    let previous = read_settings(&app)?;
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    if previous.activity_url != settings.activity_url {
+        store.delete(ACTIVITY_SEEN_KEY);
+    }
+    store.set(ACTIVITY_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
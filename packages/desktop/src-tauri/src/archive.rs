@@ -0,0 +1,704 @@
+use chrono::{Datelike, Local, Timelike};
+use flate2::read::MultiGzDecoder;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, State};
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::events::{self, Event};
+use crate::export::{self, unique_path};
+use crate::fs_scope;
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const CANCELLED: &str = "Archive cancelled";
+/// Bytes written between progress events within a large file
+const PROGRESS_INTERVAL: u64 = 16 * 1024 * 1024;
+/// Formats that are already compressed, stored as they are
+const COMPRESSED: &[&str] = &[
+    "jpg", "jpeg", "heic", "heif", "avif", "webp", "png", "gif", "jxl", "mp4", "mov", "m4v", "mkv",
+    "webm", "avi", "mp3", "m4a", "aac", "zip", "gz", "7z",
+];
+
+/// Running archives, keyed by the id the frontend chose
+#[derive(Default)]
+pub struct Archives(Mutex<HashMap<String, Arc<AtomicBool>>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveProgress {
+    pub id: String,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Entry being written
+    pub name: String,
+}
+
+/// A file to add, under its name inside the archive
+struct Entry {
+    path: PathBuf,
+    name: String,
+    size: u64,
+}
+
+/// Files under `paths`, named relative to the folder each path is in
+///
+/// A folder keeps its own name as the top of its entries, like dragging it
+/// into a zip does in a file manager.
+fn collect(paths: &[PathBuf]) -> Result<Vec<Entry>, String> {
+    let mut used = HashSet::new();
+    let mut entries = Vec::new();
+    for path in paths {
+        let base = path.parent().unwrap_or(path);
+        for file in WalkDir::new(path).sort_by_file_name() {
+            let file = file.map_err(|e| e.to_string())?;
+            if !file.file_type().is_file() {
+                continue;
+            }
+            let relative = file.path().strip_prefix(base).unwrap_or(file.path());
+            let name = unique_path(relative.to_path_buf(), &mut used);
+            let name: Vec<String> = name
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            entries.push(Entry {
+                path: file.path().to_path_buf(),
+                name: name.join("/"),
+                size: file.metadata().map(|m| m.len()).unwrap_or(0),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Copy in chunks, checking for cancellation and reporting now and then how
+/// much was written
+fn copy(
+    from: &mut impl Read,
+    to: &mut impl Write,
+    buffer: &mut [u8],
+    cancelled: &AtomicBool,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, String> {
+    let mut written = 0u64;
+    let mut reported = 0u64;
+    loop {
+        let read = match from.read(buffer) {
+            Ok(0) => return Ok(written),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        to.write_all(&buffer[..read]).map_err(|e| e.to_string())?;
+        written += read as u64;
+        if written - reported >= PROGRESS_INTERVAL {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(CANCELLED.to_string());
+            }
+            progress(written);
+            reported = written;
+        }
+    }
+}
+
+/// Checksums what is written through it
+struct Checksummed<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buffer)?;
+        self.hasher.update(&buffer[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The CRC32 of a file on disk, as zip entries record it
+fn checksum(path: &Path, buffer: &mut [u8]) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    loop {
+        match file.read(buffer) {
+            Ok(0) => return Ok(hasher.finalize()),
+            Ok(read) => hasher.update(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn options(path: &Path) -> SimpleFileOptions {
+    let compressed = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| COMPRESSED.contains(&e.as_str()));
+    let mut options = SimpleFileOptions::default().compression_method(match compressed {
+        true => CompressionMethod::Stored,
+        false => CompressionMethod::Deflated,
+    });
+
+    // Zip times are local, without a time zone
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(chrono::DateTime::<Local>::from);
+    if let Ok(modified) = modified {
+        let time = DateTime::from_date_and_time(
+            modified.year().clamp(1980, 2107) as u16,
+            modified.month() as u8,
+            modified.day() as u8,
+            modified.hour() as u8,
+            modified.minute() as u8,
+            modified.second() as u8,
+        );
+        if let Ok(time) = time {
+            options = options.last_modified_time(time);
+        }
+    }
+    options
+}
+
+fn write_archive(
+    app: &AppHandle,
+    id: &str,
+    entries: &[Entry],
+    partial: &Path,
+    cancelled: &AtomicBool,
+) -> Result<(), String> {
+    let mut zip = ZipWriter::new(BufWriter::new(
+        File::create(partial).map_err(|e| e.to_string())?,
+    ))
+    .set_auto_large_file();
+    let bytes_total = entries.iter().map(|e| e.size).sum();
+    let mut bytes_done = 0u64;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+
+    let progress = |files_done, bytes_done, name: &str| {
+        events::publish(
+            app,
+            Event::ArchiveProgress(ArchiveProgress {
+                id: id.to_string(),
+                files_done,
+                files_total: entries.len(),
+                bytes_done,
+                bytes_total,
+                name: name.to_string(),
+            }),
+        );
+    };
+
+    for (i, entry) in entries.iter().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(CANCELLED.to_string());
+        }
+        let mut file = File::open(&entry.path).map_err(|e| e.to_string())?;
+        zip.start_file(entry.name.as_str(), options(&entry.path))
+            .map_err(|e| e.to_string())?;
+
+        let start = bytes_done;
+        bytes_done += copy(&mut file, &mut zip, &mut buffer, cancelled, |written| {
+            progress(i, start + written, &entry.name)
+        })?;
+        progress(i + 1, bytes_done, &entry.name);
+    }
+
+    zip.finish()
+        .map_err(|e| e.to_string())?
+        .flush()
+        .map_err(|e| e.to_string())
+}
+
+/// Zip files and folders into one archive, streaming them from disk
+///
+/// Photos and videos are stored as they are, since compressing them again
+/// only costs time; everything else is deflated. Emits `archive-progress`
+/// after each file and during large ones. Cancel with `cancel_archive` using
+/// the same id; nothing is left at `dest` when cancelled or failed.
+#[tauri::command]
+pub async fn create_archive(
+    app: AppHandle,
+    archives: State<'_, Archives>,
+    id: String,
+    paths: Vec<String>,
+    dest: String,
+) -> Result<String, String> {
+    let sources = fs_scope::check_all(&app, &paths)?;
+    let dest = fs_scope::check(&app, &dest)?;
+    if sources.iter().any(|source| dest.starts_with(source)) {
+        return Err("The archive can't be saved inside a folder it contains".to_string());
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    archives
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(id.clone(), cancelled.clone());
+
+    let partial = export::partial_path(&dest);
+    let result = {
+        let app = app.clone();
+        let id = id.clone();
+        let dest = dest.clone();
+        let partial = partial.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let entries = collect(&sources)?;
+            write_archive(&app, &id, &entries, &partial, &cancelled)?;
+            std::fs::rename(&partial, &dest).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+    };
+
+    archives
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    match result {
+        Ok(()) => Ok(dest.to_string_lossy().to_string()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Cancel a running archive
+#[tauri::command]
+pub async fn cancel_archive(archives: State<'_, Archives>, id: String) -> Result<(), String> {
+    match archives
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&id)
+    {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+        None => Err(format!("Archive '{}' not found", id)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractProgress {
+    pub id: String,
+    pub files_done: usize,
+    /// Unknown for tar archives, which are only read front to back
+    pub files_total: Option<usize>,
+    /// How far into the archive file extraction is
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Entry being extracted
+    pub name: String,
+}
+
+/// Summary returned once every entry has been extracted
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExtractReport {
+    pub extracted: usize,
+    /// Left from an earlier, interrupted extraction
+    pub skipped: usize,
+    /// Unsafe paths and links, which are never extracted
+    pub rejected: Vec<String>,
+    pub bytes: u64,
+}
+
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn detect(path: &Path) -> Result<Format, String> {
+    let mut header = [0u8; 262];
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let read = file.read(&mut header).map_err(|e| e.to_string())?;
+    let header = &header[..read];
+    if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        Ok(Format::Zip)
+    } else if header.starts_with(&[0x1f, 0x8b]) {
+        Ok(Format::TarGz)
+    } else if header.get(257..262) == Some(b"ustar") {
+        Ok(Format::Tar)
+    } else {
+        Err("Not a zip or tar archive".to_string())
+    }
+}
+
+/// The entry's path below the destination, or `None` if it would escape it
+fn enclosed(path: &Path) -> Option<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => enclosed.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!enclosed.as_os_str().is_empty()).then_some(enclosed)
+}
+
+/// Counts the bytes read from the archive file, even through gzip
+struct Counted<R> {
+    inner: R,
+    read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.read.set(self.read.get() + read as u64);
+        Ok(read)
+    }
+}
+
+struct Extractor<'a> {
+    /// Sends `extract-progress`
+    publish: &'a dyn Fn(ExtractProgress),
+    id: &'a str,
+    dest: &'a Path,
+    cancelled: &'a AtomicBool,
+    buffer: Vec<u8>,
+    files_total: Option<usize>,
+    bytes_total: u64,
+    report: ExtractReport,
+}
+
+impl Extractor<'_> {
+    fn progress(&self, bytes_done: u64, name: &str) {
+        (self.publish)(ExtractProgress {
+            id: self.id.to_string(),
+            files_done: self.report.extracted + self.report.skipped,
+            files_total: self.files_total,
+            bytes_done,
+            bytes_total: self.bytes_total,
+            name: name.to_string(),
+        });
+    }
+
+    /// Write one file, through a partial file so an interrupted run never
+    /// leaves a truncated one
+    ///
+    /// A file already at the output is kept only if it holds the same bytes:
+    /// checked against `crc`, the CRC32 zips record for each entry, or for
+    /// tars, which record none, against the entry once it has been read.
+    /// `position` maps bytes written to how far into the archive that is.
+    fn file(
+        &mut self,
+        reader: &mut impl Read,
+        relative: &Path,
+        size: u64,
+        crc: Option<u32>,
+        position: impl Fn(u64) -> u64,
+    ) -> Result<(), String> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(CANCELLED.to_string());
+        }
+        let name = relative.to_string_lossy().to_string();
+        let output = self.dest.join(relative);
+        let existing = std::fs::metadata(&output)
+            .is_ok_and(|m| m.is_file() && m.len() == size)
+            .then(|| checksum(&output, &mut self.buffer).ok())
+            .flatten();
+        if existing.is_some() && existing == crc {
+            self.report.skipped += 1;
+            self.progress(position(size), &name);
+            return Ok(());
+        }
+
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let partial = export::partial_path(&output);
+        let written = (|| -> Result<Option<u64>, String> {
+            let mut out = Checksummed {
+                inner: BufWriter::new(File::create(&partial).map_err(|e| e.to_string())?),
+                hasher: crc32fast::Hasher::new(),
+            };
+            let mut buffer = std::mem::take(&mut self.buffer);
+            let written = copy(reader, &mut out, &mut buffer, self.cancelled, |written| {
+                self.progress(position(written), &name)
+            });
+            self.buffer = buffer;
+            out.flush().map_err(|e| e.to_string())?;
+            let written = written?;
+            let Checksummed { inner, hasher } = out;
+            drop(inner);
+            if crc.is_none() && existing == Some(hasher.finalize()) {
+                std::fs::remove_file(&partial).map_err(|e| e.to_string())?;
+                return Ok(None);
+            }
+            std::fs::rename(&partial, &output).map_err(|e| e.to_string())?;
+            Ok(Some(written))
+        })();
+        let written = written.inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })?;
+
+        match written {
+            Some(written) => {
+                self.report.extracted += 1;
+                self.report.bytes += written;
+                self.progress(position(written), &name);
+            }
+            None => {
+                self.report.skipped += 1;
+                self.progress(position(size), &name);
+            }
+        }
+        Ok(())
+    }
+
+    fn zip(&mut self, path: &Path) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut zip = ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+        self.files_total = Some(zip.len());
+        let mut done = 0u64;
+
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index).map_err(|e| e.to_string())?;
+            let compressed = entry.compressed_size();
+            match entry.enclosed_name() {
+                Some(relative) if entry.is_dir() => {
+                    std::fs::create_dir_all(self.dest.join(relative)).map_err(|e| e.to_string())?;
+                }
+                Some(relative) if entry.is_file() => {
+                    let (size, crc) = (entry.size(), entry.crc32());
+                    self.file(&mut entry, &relative, size, Some(crc), |written| {
+                        done + written.min(compressed)
+                    })?;
+                }
+                _ => self
+                    .report
+                    .rejected
+                    .push(String::from_utf8_lossy(entry.name_raw()).to_string()),
+            }
+            done += compressed;
+        }
+        Ok(())
+    }
+
+    fn tar(&mut self, reader: impl Read, read: Rc<Cell<u64>>) -> Result<(), String> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(|e| e.to_string())? {
+            let mut entry = entry.map_err(|e| e.to_string())?;
+            let path = entry.path().map_err(|e| e.to_string())?.into_owned();
+            let kind = entry.header().entry_type();
+            match enclosed(&path) {
+                Some(relative) if kind.is_dir() => {
+                    std::fs::create_dir_all(self.dest.join(relative)).map_err(|e| e.to_string())?;
+                }
+                Some(relative) if kind.is_file() => {
+                    let size = entry.size();
+                    let read = read.clone();
+                    self.file(&mut entry, &relative, size, None, move |_| read.get())?;
+                }
+                // Metadata the tar reader already applied to the next entry
+                _ if kind.is_pax_global_extensions() || kind.is_pax_local_extensions() => {}
+                // Links could point anywhere, so they're never made
+                _ => self
+                    .report
+                    .rejected
+                    .push(path.to_string_lossy().to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extract a zip or tar export from the server into a folder, streaming it
+///
+/// Plain and gzipped tars are both read. Entries that would land outside
+/// `dest`, and links, are skipped and listed in the report. Files already
+/// extracted by an earlier run are kept when their contents match, so after a
+/// failure or a cancel the same call picks up where it stopped. Emits
+/// `extract-progress` after each file and during large ones. Cancel with
+/// `cancel_archive` using the same id.
+#[tauri::command]
+pub async fn extract_archive(
+    app: AppHandle,
+    archives: State<'_, Archives>,
+    id: String,
+    path: String,
+    dest: String,
+) -> Result<ExtractReport, String> {
+    let source = fs_scope::check(&app, &path)?;
+    let dest = fs_scope::check(&app, &dest)?;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    archives
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(id.clone(), cancelled.clone());
+
+    let result = {
+        let app = app.clone();
+        let id = id.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let format = detect(&source)?;
+            std::fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+            let publish = |progress| events::publish(&app, Event::ExtractProgress(progress));
+            let mut extractor = Extractor {
+                publish: &publish,
+                id: &id,
+                dest: &dest,
+                cancelled: &cancelled,
+                buffer: vec![0u8; CHUNK_SIZE],
+                files_total: None,
+                bytes_total: std::fs::metadata(&source)
+                    .map(|m| m.len())
+                    .map_err(|e| e.to_string())?,
+                report: ExtractReport::default(),
+            };
+
+            let read = Rc::new(Cell::new(0));
+            let counted = || -> Result<_, String> {
+                Ok(BufReader::new(Counted {
+                    inner: File::open(&source).map_err(|e| e.to_string())?,
+                    read: read.clone(),
+                }))
+            };
+            match format {
+                Format::Zip => extractor.zip(&source)?,
+                Format::Tar => extractor.tar(counted()?, read.clone())?,
+                Format::TarGz => extractor.tar(MultiGzDecoder::new(counted()?), read.clone())?,
+            }
+            Ok(extractor.report)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+    };
+
+    archives
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder under the temporary directory
+    fn temp_folder(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "apollo-archive-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_zip(path: &Path, name: &str, contents: &[u8]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+        zip.finish().unwrap();
+    }
+
+    fn write_tar(path: &Path, name: &str, contents: &[u8]) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, name, contents).unwrap();
+        tar.finish().unwrap();
+    }
+
+    /// Extract `archive` into `dest`, which already holds `name` as `existing`
+    fn extract_over(archive: &Path, dest: &Path, name: &str, existing: &[u8]) -> ExtractReport {
+        std::fs::create_dir_all(dest).unwrap();
+        std::fs::write(dest.join(name), existing).unwrap();
+        let cancelled = AtomicBool::new(false);
+        let mut extractor = Extractor {
+            publish: &|_| {},
+            id: "test",
+            dest,
+            cancelled: &cancelled,
+            buffer: vec![0u8; CHUNK_SIZE],
+            files_total: None,
+            bytes_total: 0,
+            report: ExtractReport::default(),
+        };
+        match detect(archive).unwrap() {
+            Format::Zip => extractor.zip(archive).unwrap(),
+            Format::Tar => extractor
+                .tar(File::open(archive).unwrap(), Rc::new(Cell::new(0)))
+                .unwrap(),
+            Format::TarGz => unreachable!(),
+        }
+        extractor.report
+    }
+
+    #[test]
+    fn zip_keeps_a_matching_file() {
+        let dir = temp_folder("zip-match");
+        let archive = dir.join("export.zip");
+        write_zip(&archive, "photo.jpg", b"original");
+        let dest = dir.join("out");
+        let report = extract_over(&archive, &dest, "photo.jpg", b"original");
+        assert_eq!((report.skipped, report.extracted), (1, 0));
+        assert_eq!(std::fs::read(dest.join("photo.jpg")).unwrap(), b"original");
+    }
+
+    #[test]
+    fn zip_replaces_a_different_file_of_the_same_size() {
+        let dir = temp_folder("zip-differ");
+        let archive = dir.join("export.zip");
+        write_zip(&archive, "photo.jpg", b"original");
+        let dest = dir.join("out");
+        let report = extract_over(&archive, &dest, "photo.jpg", b"tampered");
+        assert_eq!((report.skipped, report.extracted), (0, 1));
+        assert_eq!(std::fs::read(dest.join("photo.jpg")).unwrap(), b"original");
+    }
+
+    #[test]
+    fn tar_keeps_a_matching_file() {
+        let dir = temp_folder("tar-match");
+        let archive = dir.join("export.tar");
+        write_tar(&archive, "photo.jpg", b"original");
+        let dest = dir.join("out");
+        let report = extract_over(&archive, &dest, "photo.jpg", b"original");
+        assert_eq!((report.skipped, report.extracted), (1, 0));
+        assert_eq!(std::fs::read(dest.join("photo.jpg")).unwrap(), b"original");
+        assert!(!export::partial_path(&dest.join("photo.jpg")).exists());
+    }
+
+    #[test]
+    fn tar_replaces_a_different_file_of_the_same_size() {
+        let dir = temp_folder("tar-differ");
+        let archive = dir.join("export.tar");
+        write_tar(&archive, "photo.jpg", b"original");
+        let dest = dir.join("out");
+        let report = extract_over(&archive, &dest, "photo.jpg", b"tampered");
+        assert_eq!((report.skipped, report.extracted), (0, 1));
+        assert_eq!(std::fs::read(dest.join("photo.jpg")).unwrap(), b"original");
+    }
+}
@@ -0,0 +1,449 @@
+//! Audio output devices
+//!
+//! Webviews play through the system's default output and can't be pointed
+//! at another one, so choosing where playback goes, such as a TV over HDMI
+//! instead of the headphones, switches the default output device. Devices
+//! are polled, and `audio-devices-changed` is sent when one appears,
+//! disappears, or becomes the default.
+
+use serde::Serialize;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+
+const AUDIO_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A device audio can be played through
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioDevice {
+    /// Opaque identifier to pass back to `set_audio_output`
+    pub id: String,
+    pub name: String,
+    /// Whether playback currently goes to this device
+    pub default: bool,
+}
+
+/// Poll the output devices, emitting `audio-devices-changed` when the list
+/// or the default changes
+pub fn watch_audio_devices(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(AUDIO_POLL_INTERVAL);
+        let mut last: Option<Vec<AudioDevice>> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(Ok(devices)) = tauri::async_runtime::spawn_blocking(os::list).await else {
+                continue;
+            };
+            if last.as_ref().is_some_and(|last| *last != devices) {
+                events::publish(&app, Event::AudioDevicesChanged(devices.clone()));
+            }
+            last = Some(devices);
+        }
+    });
+}
+
+/// List the audio output devices
+#[tauri::command]
+pub async fn list_audio_devices() -> Result<Vec<AudioDevice>, String> {
+    tauri::async_runtime::spawn_blocking(os::list)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Play audio through a device from `list_audio_devices`
+///
+/// This makes it the system's default output, so other apps follow too.
+#[tauri::command]
+pub async fn set_audio_output(id: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || os::set_output(&id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use super::AudioDevice;
+    use policy::IPolicyConfig;
+    use windows::core::{HSTRING, PCWSTR, PWSTR};
+    use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+    use windows::Win32::Media::Audio::{
+        eCommunications, eConsole, eMultimedia, eRender, IMMDevice, IMMDeviceEnumerator,
+        MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
+    };
+    use windows::Win32::System::Com::StructuredStorage::{PropVariantClear, PropVariantToBSTR};
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL,
+        COINIT_MULTITHREADED, STGM_READ,
+    };
+
+    /// The undocumented interface Windows' own sound settings use to change
+    /// the default device
+    #[allow(non_snake_case)]
+    mod policy {
+        use std::ffi::c_void;
+        use windows::core::{interface, IUnknown, IUnknown_Vtbl, GUID, HRESULT, PCWSTR};
+        use windows::Win32::Media::Audio::ERole;
+
+        pub const CLIENT: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
+
+        /// Only `SetDefaultEndpoint` is called; the rest keep the vtable in order
+        #[interface("f8679f50-850a-41cf-9c72-430f290290c8")]
+        pub unsafe trait IPolicyConfig: IUnknown {
+            fn GetMixFormat(&self, device: PCWSTR, format: *mut *mut c_void) -> HRESULT;
+            fn GetDeviceFormat(
+                &self,
+                device: PCWSTR,
+                default: i32,
+                format: *mut *mut c_void,
+            ) -> HRESULT;
+            fn ResetDeviceFormat(&self, device: PCWSTR) -> HRESULT;
+            fn SetDeviceFormat(
+                &self,
+                device: PCWSTR,
+                endpoint: *const c_void,
+                mix: *const c_void,
+            ) -> HRESULT;
+            fn GetProcessingPeriod(
+                &self,
+                device: PCWSTR,
+                default: i32,
+                default_period: *mut i64,
+                minimum_period: *mut i64,
+            ) -> HRESULT;
+            fn SetProcessingPeriod(&self, device: PCWSTR, period: *const i64) -> HRESULT;
+            fn GetShareMode(&self, device: PCWSTR, mode: *mut c_void) -> HRESULT;
+            fn SetShareMode(&self, device: PCWSTR, mode: *const c_void) -> HRESULT;
+            fn GetPropertyValue(
+                &self,
+                device: PCWSTR,
+                key: *const c_void,
+                value: *mut c_void,
+            ) -> HRESULT;
+            fn SetPropertyValue(
+                &self,
+                device: PCWSTR,
+                key: *const c_void,
+                value: *const c_void,
+            ) -> HRESULT;
+            pub fn SetDefaultEndpoint(&self, device: PCWSTR, role: ERole) -> HRESULT;
+            fn SetEndpointVisibility(&self, device: PCWSTR, visible: i32) -> HRESULT;
+        }
+    }
+
+    /// COM initialisation for the current thread, undone on drop
+    struct Com(bool);
+
+    impl Com {
+        fn init() -> Self {
+            Self(unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok())
+        }
+    }
+
+    impl Drop for Com {
+        fn drop(&mut self) {
+            if self.0 {
+                unsafe { CoUninitialize() };
+            }
+        }
+    }
+
+    /// Convert and free a COM-allocated string
+    unsafe fn take(value: PWSTR) -> String {
+        if value.is_null() {
+            return String::new();
+        }
+        let text = unsafe { value.to_string() }.unwrap_or_default();
+        unsafe { CoTaskMemFree(Some(value.0 as _)) };
+        text
+    }
+
+    fn name(device: &IMMDevice) -> Option<String> {
+        unsafe {
+            let store = device.OpenPropertyStore(STGM_READ).ok()?;
+            let mut value = store.GetValue(&PKEY_Device_FriendlyName).ok()?;
+            let name = PropVariantToBSTR(&value).ok().map(|name| name.to_string());
+            let _ = PropVariantClear(&mut value);
+            name
+        }
+    }
+
+    pub fn list() -> Result<Vec<AudioDevice>, String> {
+        let _com = Com::init();
+        unsafe {
+            let enumerator: IMMDeviceEnumerator =
+                CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                    .map_err(|e| e.to_string())?;
+            let default = enumerator
+                .GetDefaultAudioEndpoint(eRender, eConsole)
+                .and_then(|device| device.GetId())
+                .map(|id| take(id))
+                .ok();
+            let collection = enumerator
+                .EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)
+                .map_err(|e| e.to_string())?;
+
+            let mut devices = Vec::new();
+            for index in 0..collection.GetCount().map_err(|e| e.to_string())? {
+                let Ok(device) = collection.Item(index) else {
+                    continue;
+                };
+                let Ok(id) = device.GetId().map(|id| take(id)) else {
+                    continue;
+                };
+                devices.push(AudioDevice {
+                    name: name(&device).unwrap_or_else(|| id.clone()),
+                    default: default.as_deref() == Some(id.as_str()),
+                    id,
+                });
+            }
+            Ok(devices)
+        }
+    }
+
+    pub fn set_output(id: &str) -> Result<(), String> {
+        let _com = Com::init();
+        let id = HSTRING::from(id);
+        unsafe {
+            let policy: IPolicyConfig =
+                CoCreateInstance(&policy::CLIENT, None, CLSCTX_ALL).map_err(|e| e.to_string())?;
+            for role in [eConsole, eMultimedia, eCommunications] {
+                policy
+                    .SetDefaultEndpoint(PCWSTR(id.as_ptr()), role)
+                    .ok()
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use super::AudioDevice;
+    use cocoa::base::id;
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::{c_void, CStr};
+    use std::os::raw::c_char;
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        fn AudioObjectGetPropertyDataSize(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+        ) -> i32;
+        fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+        fn AudioObjectSetPropertyData(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: u32,
+            data: *const c_void,
+        ) -> i32;
+    }
+
+    /// `AudioObjectPropertyAddress`
+    #[repr(C)]
+    struct PropertyAddress {
+        selector: u32,
+        scope: u32,
+        element: u32,
+    }
+
+    const fn code(name: &[u8; 4]) -> u32 {
+        u32::from_be_bytes(*name)
+    }
+
+    const SYSTEM_OBJECT: u32 = 1;
+    const DEVICES: u32 = code(b"dev#");
+    const DEFAULT_OUTPUT_DEVICE: u32 = code(b"dOut");
+    const STREAMS: u32 = code(b"stm#");
+    const NAME: u32 = code(b"lnam");
+    const DEVICE_UID: u32 = code(b"uid ");
+    const SCOPE_GLOBAL: u32 = code(b"glob");
+    const SCOPE_OUTPUT: u32 = code(b"outp");
+    const ELEMENT_MAIN: u32 = 0;
+
+    fn address(selector: u32, scope: u32) -> PropertyAddress {
+        PropertyAddress {
+            selector,
+            scope,
+            element: ELEMENT_MAIN,
+        }
+    }
+
+    fn size(object: u32, address: &PropertyAddress) -> Option<u32> {
+        let mut size = 0u32;
+        let status = unsafe {
+            AudioObjectGetPropertyDataSize(object, address, 0, std::ptr::null(), &mut size)
+        };
+        (status == 0).then_some(size)
+    }
+
+    fn ids(object: u32, address: &PropertyAddress) -> Option<Vec<u32>> {
+        let mut size = size(object, address)?;
+        let mut ids = vec![0u32; size as usize / std::mem::size_of::<u32>()];
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                object,
+                address,
+                0,
+                std::ptr::null(),
+                &mut size,
+                ids.as_mut_ptr() as *mut c_void,
+            )
+        };
+        (status == 0).then_some(ids)
+    }
+
+    /// Read a CFString property, releasing it
+    fn string(object: u32, selector: u32) -> Option<String> {
+        let mut value: id = std::ptr::null_mut();
+        let mut size = std::mem::size_of::<id>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                object,
+                &address(selector, SCOPE_GLOBAL),
+                0,
+                std::ptr::null(),
+                &mut size,
+                &mut value as *mut id as *mut c_void,
+            )
+        };
+        if status != 0 || value.is_null() {
+            return None;
+        }
+        unsafe {
+            let utf8: *const c_char = msg_send![value, UTF8String];
+            let text =
+                (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned());
+            let _: () = msg_send![value, release];
+            text
+        }
+    }
+
+    fn default_output() -> Option<u32> {
+        ids(SYSTEM_OBJECT, &address(DEFAULT_OUTPUT_DEVICE, SCOPE_GLOBAL))?
+            .first()
+            .copied()
+    }
+
+    /// Output devices by CoreAudio object id, skipping input-only ones
+    fn outputs() -> Result<Vec<(u32, String)>, String> {
+        let devices = ids(SYSTEM_OBJECT, &address(DEVICES, SCOPE_GLOBAL))
+            .ok_or("Couldn't list the audio devices")?;
+        Ok(devices
+            .into_iter()
+            .filter(|device| size(*device, &address(STREAMS, SCOPE_OUTPUT)).unwrap_or(0) > 0)
+            .filter_map(|device| Some((device, string(device, DEVICE_UID)?)))
+            .collect())
+    }
+
+    pub fn list() -> Result<Vec<AudioDevice>, String> {
+        let default = default_output();
+        Ok(outputs()?
+            .into_iter()
+            .map(|(device, uid)| AudioDevice {
+                name: string(device, NAME).unwrap_or_else(|| uid.clone()),
+                default: default == Some(device),
+                id: uid,
+            })
+            .collect())
+    }
+
+    pub fn set_output(id: &str) -> Result<(), String> {
+        let device = outputs()?
+            .into_iter()
+            .find(|(_, uid)| uid == id)
+            .map(|(device, _)| device)
+            .ok_or_else(|| format!("No audio output '{}'", id))?;
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                SYSTEM_OBJECT,
+                &address(DEFAULT_OUTPUT_DEVICE, SCOPE_GLOBAL),
+                0,
+                std::ptr::null(),
+                std::mem::size_of::<u32>() as u32,
+                &device as *const u32 as *const c_void,
+            )
+        };
+        match status {
+            0 => Ok(()),
+            status => Err(format!("Couldn't switch the audio output ({})", status)),
+        }
+    }
+}
+
+/// PulseAudio, or PipeWire through its PulseAudio server
+#[cfg(target_os = "linux")]
+mod os {
+    use super::AudioDevice;
+    use std::process::Command;
+
+    fn pactl(args: &[&str]) -> Result<String, String> {
+        let output = Command::new("pactl")
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn list() -> Result<Vec<AudioDevice>, String> {
+        let default = pactl(&["info"])?
+            .lines()
+            .find_map(|line| line.strip_prefix("Default Sink: "))
+            .map(|sink| sink.trim().to_string());
+
+        // Each sink is a block of indented `Key: value` lines
+        let mut devices: Vec<AudioDevice> = Vec::new();
+        for line in pactl(&["list", "sinks"])?.lines() {
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix("Name: ") {
+                devices.push(AudioDevice {
+                    id: name.to_string(),
+                    name: name.to_string(),
+                    default: default.as_deref() == Some(name),
+                });
+            } else if let Some(description) = line.strip_prefix("Description: ") {
+                if let Some(device) = devices.last_mut() {
+                    device.name = description.to_string();
+                }
+            }
+        }
+        Ok(devices)
+    }
+
+    pub fn set_output(id: &str) -> Result<(), String> {
+        pactl(&["set-default-sink", id]).map(|_| ())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod os {
+    use super::AudioDevice;
+
+    pub fn list() -> Result<Vec<AudioDevice>, String> {
+        Ok(Vec::new())
+    }
+
+    pub fn set_output(_id: &str) -> Result<(), String> {
+        Err("Not supported on this platform".to_string())
+    }
+}
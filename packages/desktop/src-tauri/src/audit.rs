@@ -0,0 +1,300 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, Manager, Url, Wry};
+
+use crate::db::{now, Database};
+
+/// Entries older than this are dropped at startup
+const MAX_AGE_SECS: i64 = 90 * 24 * 60 * 60;
+/// Repeated use of the same credentials from a window is recorded at most this often
+const CREDENTIAL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_LIMIT: u32 = 500;
+
+/// Request headers whose presence means a command is sending credentials
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A link opened in the browser
+    Link,
+    /// A process started or stopped, or the app restarted or updated
+    Shell,
+    FileWrite,
+    FileDelete,
+    /// Credentials sent with a request on the frontend's behalf
+    Credential,
+    /// Any other privileged command a window was not allowed to use
+    Privileged,
+}
+
+impl AuditAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Link => "link",
+            AuditAction::Shell => "shell",
+            AuditAction::FileWrite => "file_write",
+            AuditAction::FileDelete => "file_delete",
+            AuditAction::Credential => "credential",
+            AuditAction::Privileged => "privileged",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "link" => AuditAction::Link,
+            "shell" => AuditAction::Shell,
+            "file_write" => AuditAction::FileWrite,
+            "file_delete" => AuditAction::FileDelete,
+            "credential" => AuditAction::Credential,
+            _ => AuditAction::Privileged,
+        }
+    }
+}
+
+/// One recorded privileged operation
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unix time in seconds
+    pub at: i64,
+    /// Label of the window that invoked the command
+    pub window: String,
+    pub action: AuditAction,
+    pub command: String,
+    /// URL, path, or name the operation acted on, if any
+    pub target: Option<String>,
+    /// False when the window lacked the capability and the command was rejected
+    pub allowed: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub window: Option<String>,
+    /// Unix time in seconds
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+/// When credentials were last recorded, keyed by window, command, and host
+#[derive(Default)]
+pub struct Audit(Mutex<HashMap<String, Instant>>);
+
+fn string_arg(args: &Value, name: &str) -> Option<String> {
+    args.get(name)
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+}
+
+/// The audited action a command performs and what it acts on
+fn classify(command: &str, args: &Value) -> Option<(AuditAction, Option<String>)> {
+    let action = match command {
+        "open_link" | "confirm_open_link" => (AuditAction::Link, string_arg(args, "url")),
+        "start_sidecar" | "stop_sidecar" => (AuditAction::Shell, string_arg(args, "name")),
+        "restart" | "install_update" | "install_update_now" | "open_ssh_tunnel"
+        | "close_ssh_tunnel" => (AuditAction::Shell, None),
+        "export_items" | "import_from_device" | "import_takeout" => {
+            (AuditAction::FileWrite, string_arg(args, "target"))
+        }
+        "transcode_video" => (AuditAction::FileWrite, string_arg(args, "output")),
+        "migrate_data_dir" | "set_media_cache_dir" => {
+            (AuditAction::FileWrite, string_arg(args, "path"))
+        }
+        "sync_folder_pair" => (AuditAction::FileWrite, string_arg(args, "albumId")),
+        "enqueue_transfers" => {
+            let downloads: Vec<&str> = args
+                .get("transfers")
+                .and_then(|t| t.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|t| t.get("direction").and_then(|d| d.as_str()) == Some("download"))
+                .filter_map(|t| t.get("path").and_then(|p| p.as_str()))
+                .collect();
+            if downloads.is_empty() {
+                return None;
+            }
+            (AuditAction::FileWrite, Some(downloads.join(", ")))
+        }
+        "unsync_album" if args.get("deleteFiles").and_then(|d| d.as_bool()) == Some(true) => {
+            (AuditAction::FileDelete, string_arg(args, "albumId"))
+        }
+        "unpin_album_offline" | "delete_profile" => {
+            (AuditAction::FileDelete, string_arg(args, "id"))
+        }
+        "clear_server_data" => (AuditAction::FileDelete, string_arg(args, "profile")),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Whether any `headers` map in the arguments carries credentials
+fn sends_credentials(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(key, value)| {
+            let credentials = key == "headers"
+                && value.as_object().is_some_and(|headers| {
+                    headers
+                        .keys()
+                        .any(|name| CREDENTIAL_HEADERS.contains(&name.to_lowercase().as_str()))
+                });
+            credentials || sends_credentials(value)
+        }),
+        Value::Array(items) => items.iter().any(sends_credentials),
+        _ => false,
+    }
+}
+
+/// Origin of the first URL-like argument, so credentials can be tied to a server
+fn credential_origin(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(map) => map.iter().find_map(|(key, value)| match value {
+            Value::String(url) if key.to_lowercase().ends_with("url") => Url::parse(url)
+                .ok()
+                .map(|u| u.origin().ascii_serialization()),
+            value => credential_origin(value),
+        }),
+        Value::Array(items) => items.iter().find_map(credential_origin),
+        _ => None,
+    }
+}
+
+fn insert(
+    app: &AppHandle,
+    window: &str,
+    action: AuditAction,
+    command: &str,
+    target: Option<&str>,
+    allowed: bool,
+) {
+    let result = app.state::<Database>().conn().execute(
+        "INSERT INTO audit_log (at, window, action, command, target, allowed)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![now(), window, action.as_str(), command, target, allowed],
+    );
+    if let Err(e) = result {
+        log::warn!("Failed to record audit entry for '{}': {}", command, e);
+    }
+}
+
+/// Record an invoke if it performs a privileged operation
+///
+/// Called for every command before it runs, with whether the calling window
+/// was allowed to use it. Header values are never stored.
+pub fn record_invoke(invoke: &Invoke<Wry>, allowed: bool) {
+    let command = invoke.message.command();
+    let webview = invoke.message.webview_ref();
+    let app = webview.app_handle();
+    let window = webview.label();
+    let args = match invoke.message.payload() {
+        InvokeBody::Json(args) => args,
+        InvokeBody::Raw(_) => &Value::Null,
+    };
+
+    match classify(command, args) {
+        Some((action, target)) => insert(app, window, action, command, target.as_deref(), allowed),
+        None if !allowed => insert(app, window, AuditAction::Privileged, command, None, false),
+        None => {}
+    }
+
+    if sends_credentials(args) {
+        let origin = credential_origin(args);
+        let key = format!(
+            "{}\n{}\n{}",
+            window,
+            command,
+            origin.as_deref().unwrap_or("")
+        );
+        let due = {
+            let audit = app.state::<Audit>();
+            let mut recent = audit.0.lock().unwrap_or_else(|e| e.into_inner());
+            recent.retain(|_, at| at.elapsed() < CREDENTIAL_INTERVAL);
+            match recent.contains_key(&key) {
+                true => false,
+                false => {
+                    recent.insert(key, Instant::now());
+                    true
+                }
+            }
+        };
+        if due || !allowed {
+            insert(
+                app,
+                window,
+                AuditAction::Credential,
+                command,
+                origin.as_deref(),
+                allowed,
+            );
+        }
+    }
+}
+
+/// Drop entries past the retention period
+pub fn prune(app: &AppHandle) {
+    let _ = app.state::<Database>().conn().execute(
+        "DELETE FROM audit_log WHERE at < ?1",
+        params![now() - MAX_AGE_SECS],
+    );
+}
+
+/// Query the audit log of privileged operations, newest first
+///
+/// Covers links opened, processes launched, files written or deleted, and
+/// credentials sent, along with the window each came from. Entries are kept
+/// for 90 days.
+#[tauri::command]
+pub async fn get_audit_log(
+    app: AppHandle,
+    filter: Option<AuditFilter>,
+) -> Result<Vec<AuditEntry>, String> {
+    let filter = filter.unwrap_or_default();
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, at, window, action, command, target, allowed FROM audit_log
+             WHERE (?1 IS NULL OR action = ?1)
+               AND (?2 IS NULL OR window = ?2)
+               AND (?3 IS NULL OR at >= ?3)
+               AND (?4 IS NULL OR at <= ?4)
+             ORDER BY id DESC LIMIT ?5",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            params![
+                filter.action.map(|a| a.as_str()),
+                filter.window,
+                filter.since,
+                filter.until,
+                filter.limit.unwrap_or(DEFAULT_LIMIT)
+            ],
+            |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    at: row.get(1)?,
+                    window: row.get(2)?,
+                    action: AuditAction::parse(&row.get::<_, String>(3)?),
+                    command: row.get(4)?,
+                    target: row.get(5)?,
+                    allowed: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::profiles;
+use crate::schedule::{self, TransferPolicy};
+use crate::server_headers;
+
+const BANDWIDTH_SETTINGS_KEY: &str = "bandwidthLimits";
+/// Bucket for the quiet hours limit, shared by every transfer
+const SCHEDULE_BUCKET: &str = "";
+
+/// Speed cap for transfers to one of a server's addresses
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthProfile {
+    pub server: String,
+    /// Server address the profile applies to, e.g. `http://192.168.1.10:2283`
+    pub url: String,
+    /// Bytes per second; unlimited when unset
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthSettings {
+    pub profiles: Vec<BandwidthProfile>,
+}
+
+/// Saved profiles and when each speed limit next has capacity
+#[derive(Default)]
+pub struct Bandwidth {
+    settings: Mutex<BandwidthSettings>,
+    buckets: Mutex<HashMap<String, Instant>>,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<BandwidthSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(BANDWIDTH_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(BandwidthSettings::default()),
+    }
+}
+
+/// Load the saved profiles so transfers don't read the store for every chunk
+pub fn load(app: &AppHandle) {
+    match read_settings(app) {
+        Ok(settings) => {
+            *app.state::<Bandwidth>()
+                .settings
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = settings
+        }
+        Err(e) => log::warn!("Failed to load bandwidth limits: {}", e),
+    }
+}
+
+/// The profile for the address a URL points at; the longest address wins
+fn profile_for(settings: &BandwidthSettings, url: &str) -> Option<BandwidthProfile> {
+    server_headers::best_match(&settings.profiles, url, |p| p.url.as_str()).cloned()
+}
+
+/// Reserve time for `bytes` in a bucket and return when the reservation ends
+fn reserve(buckets: &mut HashMap<String, Instant>, key: &str, bytes: u64, limit: u64) -> Instant {
+    let now = Instant::now();
+    let start = buckets
+        .get(key)
+        .copied()
+        .filter(|next| *next > now)
+        .unwrap_or(now);
+    let until = start + Duration::from_secs_f64(bytes as f64 / limit.max(1) as f64);
+    buckets.insert(key.to_string(), until);
+    until
+}
+
+/// Hold back transfer I/O to the limits that apply to `url`
+///
+/// Each profile's limit is shared by all transfers to its address, and the
+/// quiet hours limit by all transfers; the stricter of the two wins.
+pub async fn throttle(app: &AppHandle, url: &str, bytes: u64) {
+    let profile = {
+        let state = app.state::<Bandwidth>();
+        let settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
+        profile_for(&settings, url)
+    };
+    let scheduled = match schedule::policy(app) {
+        TransferPolicy::Throttle { limit } => Some(limit),
+        _ => None,
+    };
+
+    let until = {
+        let state = app.state::<Bandwidth>();
+        let mut buckets = state.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let mut until = None;
+        if let Some(limit) = scheduled {
+            until = Some(reserve(&mut buckets, SCHEDULE_BUCKET, bytes, limit));
+        }
+        if let Some(BandwidthProfile {
+            url,
+            limit: Some(limit),
+            ..
+        }) = &profile
+        {
+            until = until.max(Some(reserve(&mut buckets, url, bytes, *limit)));
+        }
+        until
+    };
+    if let Some(until) = until {
+        tokio::time::sleep_until(until.into()).await;
+    }
+}
+
+/// Get the per-server bandwidth limits
+#[tauri::command]
+pub async fn get_bandwidth_limits(app: AppHandle) -> Result<BandwidthSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the per-server bandwidth limits; running transfers pick them up immediately
+#[tauri::command]
+pub async fn set_bandwidth_limits(
+    app: AppHandle,
+    settings: BandwidthSettings,
+) -> Result<(), String> {
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(BANDWIDTH_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    load(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(urls: &[&str]) -> BandwidthSettings {
+        BandwidthSettings {
+            profiles: urls
+                .iter()
+                .enumerate()
+                .map(|(i, url)| BandwidthProfile {
+                    server: "home".to_string(),
+                    url: url.to_string(),
+                    limit: Some(i as u64 + 1),
+                })
+                .collect(),
+        }
+    }
+
+    fn limit_for(settings: &BandwidthSettings, url: &str) -> Option<u64> {
+        profile_for(settings, url).and_then(|p| p.limit)
+    }
+
+    #[test]
+    fn longest_matching_address_wins() {
+        let settings = settings(&["http://192.168.1.10:2283", "http://192.168.1.10:2283/api"]);
+        assert_eq!(
+            limit_for(&settings, "http://192.168.1.10:2283/api/assets"),
+            Some(2)
+        );
+        assert_eq!(
+            limit_for(&settings, "http://192.168.1.10:2283/photos"),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn other_addresses_have_no_profile() {
+        let settings = settings(&["http://192.168.1.10:2283", ""]);
+        assert_eq!(limit_for(&settings, "http://192.168.1.10:8080/api"), None);
+        assert_eq!(limit_for(&settings, "http://192.168.1.100:2283/api"), None);
+        assert_eq!(limit_for(&settings, "https://photos.example.com/api"), None);
+    }
+}
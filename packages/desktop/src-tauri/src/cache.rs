@@ -0,0 +1,241 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::data_dir;
+use crate::db::{self, Database};
+use crate::events::Event;
+use crate::notifications::{self, NotificationCategory};
+use crate::profiles;
+use crate::sync::SyncEngine;
+use crate::transfers;
+
+/// Every cache subdirectory, so they can be moved together
+pub const SUBDIRS: &[&str] = &["media", "previews"];
+
+/// Custom location for the media cache, e.g. on a larger secondary drive
+const MEDIA_CACHE_KEY: &str = "mediaCacheDirectory";
+/// Free space to leave on the cache volume after moving the cache there
+const MIN_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Where the media cache lives this session, decided at startup
+#[derive(Default)]
+pub struct MediaCache(Mutex<MediaLocation>);
+
+#[derive(Debug, Clone, Default)]
+struct MediaLocation {
+    custom: Option<PathBuf>,
+    /// The custom location was missing at startup, so the default is used
+    fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaCacheInfo {
+    pub path: String,
+    /// The configured location, even when falling back from it
+    pub configured: Option<String>,
+    pub fallback: bool,
+    pub available_bytes: Option<u64>,
+}
+
+fn subdir(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = profiles::cache_dir(app)?.join(name);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn location(app: &AppHandle) -> MediaLocation {
+    app.state::<MediaCache>()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+fn configured(app: &AppHandle) -> Option<PathBuf> {
+    let store = profiles::store(app).ok()?;
+    store
+        .get(MEDIA_CACHE_KEY)
+        .and_then(|v| v.as_str().map(PathBuf::from))
+}
+
+/// Directory holding offline copies of remote media
+pub fn media_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match location(app).custom {
+        // Never create it: a missing directory means the volume was unplugged
+        Some(dir) if dir.is_dir() => Ok(dir),
+        Some(dir) => Err(format!(
+            "The media cache at '{}' is not available; reconnect its drive",
+            dir.display()
+        )),
+        None => subdir(app, "media"),
+    }
+}
+
+/// Directory holding previews generated from local files (e.g. RAW thumbnails)
+pub fn previews_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    subdir(app, "previews")
+}
+
+/// Whether the configured media cache volume was missing at startup
+pub fn is_fallback(app: &AppHandle) -> bool {
+    location(app).fallback
+}
+
+/// Pick the media cache location for this session
+///
+/// Falls back to the default cache, with a notification, if the configured
+/// volume isn't mounted.
+pub fn init(app: &AppHandle) {
+    let media = match configured(app) {
+        Some(dir) if dir.is_dir() => MediaLocation {
+            custom: Some(dir),
+            fallback: false,
+        },
+        Some(dir) => {
+            log::warn!(
+                "Media cache {} is missing, using the default location",
+                dir.display()
+            );
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = notifications::notify(
+                    app,
+                    "Media cache drive not found".to_string(),
+                    Some(format!(
+                        "Offline copies in {} are unavailable until the drive is reconnected.",
+                        dir.display()
+                    )),
+                    None,
+                    Some(NotificationCategory::Errors),
+                )
+                .await;
+            });
+            MediaLocation {
+                custom: None,
+                fallback: true,
+            }
+        }
+        None => MediaLocation::default(),
+    };
+    *app.state::<MediaCache>()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = media;
+}
+
+/// Check that a directory exists, or can be created, and is writable
+fn ensure_writable(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let probe = dir.join(".apollo-write-test");
+    std::fs::write(&probe, b"")
+        .map_err(|e| format!("'{}' is not writable: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(probe);
+    Ok(())
+}
+
+/// Where the media cache is and how much room is left on its volume
+#[tauri::command]
+pub async fn get_media_cache_dir(app: AppHandle) -> Result<MediaCacheInfo, String> {
+    let path = media_dir(&app)?;
+    Ok(MediaCacheInfo {
+        available_bytes: data_dir::available_space(&path),
+        path: path.to_string_lossy().to_string(),
+        configured: configured(&app).map(|d| d.to_string_lossy().to_string()),
+        fallback: is_fallback(&app),
+    })
+}
+
+/// Move only the media cache to another directory, or back to the default
+///
+/// The target must be empty and have room for the cache plus 1 GiB. Syncs and
+/// transfers pause while files are copied, emitting `media-cache-progress`.
+#[tauri::command]
+pub async fn set_media_cache_dir(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<MediaCacheInfo, String> {
+    let default = profiles::cache_dir(&app)?.join("media");
+    let target = match &path {
+        Some(path) if !Path::new(path).is_absolute() => {
+            return Err(format!("'{}' is not an absolute path", path));
+        }
+        Some(path) => PathBuf::from(path),
+        None => default.clone(),
+    };
+    let current = media_dir(&app)?;
+    if target == current {
+        return get_media_cache_dir(app).await;
+    }
+    if target.starts_with(&current) || current.starts_with(&target) {
+        return Err("The new location can't be inside the current one, or contain it".to_string());
+    }
+    let is_default = target == default;
+    if !is_default
+        && std::fs::read_dir(&target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false)
+    {
+        return Err(format!("'{}' is not empty", target.display()));
+    }
+    ensure_writable(&target)?;
+
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+    transfers::suspend(&app);
+
+    let (copied, result) = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        let current = current.clone();
+        let target = target.clone();
+        move || {
+            let files = data_dir::plan_copy(&[(current.clone(), target.clone())]);
+            let result = (|| {
+                let needed: u64 = files.iter().map(|(_, _, size)| size).sum();
+                if let Some(available) = data_dir::available_space(&target) {
+                    if available < needed + MIN_FREE_BYTES {
+                        return Err(format!(
+                            "Not enough free space: {} bytes needed plus 1 GiB spare, {} available",
+                            needed, available
+                        ));
+                    }
+                }
+                data_dir::copy_all(&app, &files, Event::MediaCacheProgress)?;
+                db::rewrite_paths(&app.state::<Database>().conn(), &current, &target)
+            })();
+            (files, result)
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if let Err(e) = result {
+        // The chosen folder was empty, so planned files found there came from this move
+        if !is_default {
+            data_dir::remove_copied(&copied, &target);
+        }
+        transfers::wake(&app);
+        return Err(e);
+    }
+
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    match &path {
+        Some(path) => store.set(MEDIA_CACHE_KEY, serde_json::json!(path)),
+        None => {
+            store.delete(MEDIA_CACHE_KEY);
+        }
+    }
+    store.save().map_err(|e| e.to_string())?;
+    *app.state::<MediaCache>()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = MediaLocation {
+        custom: path.map(PathBuf::from),
+        fallback: false,
+    };
+
+    let _ = tauri::async_runtime::spawn_blocking(move || std::fs::remove_dir_all(current)).await;
+    transfers::wake(&app);
+    get_media_cache_dir(app.clone()).await
+}
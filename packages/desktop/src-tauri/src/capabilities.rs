@@ -0,0 +1,404 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Window, WindowEvent, Wry};
+
+use crate::audit;
+use crate::usage;
+use crate::window;
+
+/// The window created from `tauri.conf.json`, which may use every command
+const MAIN_WINDOW: &str = "main";
+/// The only commands guest windows may call, since the rest read the owner's data
+const GUEST_COMMANDS: &[&str] = &[
+    "get_os",
+    "get_version",
+    "get_window_capabilities",
+    "get_displays",
+    "get_display_color_info",
+];
+
+/// Commands any window may call; one missing here and from `required` is
+/// refused everywhere, so a new command can't slip through unclassified
+const UNPRIVILEGED: &[&str] = &[
+    "announce",
+    "check_update",
+    "close_quick_look",
+    "get_accessibility_preferences",
+    "get_activity_settings",
+    "get_bandwidth_limits",
+    "get_certificate_pins",
+    "get_data_dir",
+    "get_default_server_url",
+    "get_display_color_info",
+    "get_displays",
+    "get_doh_settings",
+    "get_downloads_dir",
+    "get_folder_pairs",
+    "get_fs_scope_settings",
+    "get_host_overrides",
+    "get_integrated_auth",
+    "get_link_allowlist",
+    "get_media_cache_dir",
+    "get_menubar_settings",
+    "get_notification_permission",
+    "get_notification_preferences",
+    "get_offline_albums",
+    "get_os",
+    "get_platform_capabilities",
+    "get_player_state",
+    "get_presentation_mode",
+    "get_quick_capture_settings",
+    "get_sandbox_info",
+    "get_screenshot_folder",
+    "get_screenshot_settings",
+    "get_search_index_settings",
+    "get_server_update_settings",
+    "get_shortcut_support",
+    "get_shortcuts",
+    "get_ssh_tunnel_settings",
+    "get_ssh_tunnel_status",
+    "get_sync_selections",
+    "get_transfer_policy",
+    "get_transfer_schedule",
+    "get_transfers_paused",
+    "get_ui_scale",
+    "get_update_settings",
+    "get_update_status",
+    "get_user_agent",
+    "get_version",
+    "get_video_tracks",
+    "get_virtual_drive",
+    "get_vpn_settings",
+    "get_vpn_status",
+    "get_window_capabilities",
+    "get_window_profile",
+    "get_window_zoom",
+    "hide_quick_search",
+    "list_audio_devices",
+    "list_mtp_devices",
+    "list_profiles",
+    "list_recent_items",
+    "list_removable_volumes",
+    "list_sidecars",
+    "list_transfers",
+    "reverse_geocode",
+    "set_snap_layout_area",
+    "show_context_menu",
+    "subscribe_events",
+    "take_opened_search_result",
+    "unsubscribe_events",
+];
+
+/// Groups of privileged commands a window can be allowed to invoke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// Reading or writing local files, including file pickers
+    Fs,
+    /// Requests to servers and control of the transfer queue
+    Network,
+    /// Opening links, launching processes, and installing updates
+    Shell,
+    /// Changing saved preferences, opening windows, and reading the audit
+    /// log, usage stats, and notification history
+    Settings,
+}
+
+pub const ALL: [Capability; 4] = [
+    Capability::Fs,
+    Capability::Network,
+    Capability::Shell,
+    Capability::Settings,
+];
+
+impl Capability {
+    fn as_str(self) -> &'static str {
+        match self {
+            Capability::Fs => "fs",
+            Capability::Network => "network",
+            Capability::Shell => "shell",
+            Capability::Settings => "settings",
+        }
+    }
+}
+
+/// The capability a command needs, or `None` for those in `UNPRIVILEGED`
+/// and unknown ones
+fn required(command: &str) -> Option<Capability> {
+    match command {
+        "export_items"
+        | "export_album_pdf"
+        | "create_archive"
+        | "extract_archive"
+        | "set_wallpaper"
+        | "set_lock_screen_image"
+        | "mount_virtual_drive"
+        | "quick_look"
+        | "enqueue_transfers"
+        | "enable_sync"
+        | "unsync_album"
+        | "pin_album_offline"
+        | "unpin_album_offline"
+        | "set_folder_pair"
+        | "remove_folder_pair"
+        | "preview_folder_sync"
+        | "sync_folder_pair"
+        | "verify_backup"
+        | "scan_import_folder"
+        | "import_from_device"
+        | "import_takeout"
+        | "find_duplicates"
+        | "extract_raw_preview"
+        | "transcode_video"
+        | "analyze_local_images"
+        | "set_screenshot_settings"
+        | "set_quick_capture_settings"
+        | "open_directory_picker"
+        | "open_file_picker"
+        | "save_file_picker"
+        | "get_fs_scope"
+        | "migrate_data_dir"
+        | "set_media_cache_dir"
+        | "cancel_archive"
+        | "cancel_transcode"
+        | "unmount_virtual_drive" => Some(Capability::Fs),
+        "cached_fetch"
+        | "coalesced_fetch"
+        | "batched_fetch"
+        | "check_server_compatibility"
+        | "wake_server"
+        | "sync_now"
+        | "cancel_transfer"
+        | "retry_transfer"
+        | "clear_finished_transfers"
+        | "pause_transfers"
+        | "resume_transfers"
+        | "quick_capture"
+        | "start_drag"
+        | "copy_image_from_url"
+        | "create_share_shortcut"
+        | "player_enqueue"
+        | "open_video_player"
+        | "estimate_sync_size"
+        | "player_control"
+        | "video_control" => Some(Capability::Network),
+        "open_link"
+        | "confirm_open_link"
+        | "start_sidecar"
+        | "stop_sidecar"
+        | "open_ssh_tunnel"
+        | "close_ssh_tunnel"
+        | "open_downloads_folder"
+        | "restart"
+        | "install_update"
+        | "install_update_now" => Some(Capability::Shell),
+        "set_transfer_schedule"
+        | "set_bandwidth_limits"
+        | "clear_http_cache"
+        | "set_link_allowlist"
+        | "set_notification_preferences"
+        | "get_notification_history"
+        | "mark_notifications_read"
+        | "clear_notification_history"
+        | "set_activity_settings"
+        | "get_realtime_settings"
+        | "set_realtime_settings"
+        | "request_notification_permission"
+        | "set_default_server_url"
+        | "set_fs_scope_settings"
+        | "set_downloads_dir"
+        | "add_recent_item"
+        | "clear_recent_items"
+        | "pick_downloads_dir"
+        | "set_content_protection"
+        | "move_window_to_display"
+        | "set_audio_output"
+        | "set_window_zoom"
+        | "set_ui_scale"
+        | "open_private_window"
+        | "open_guest_window"
+        | "start_external_slideshow"
+        | "stop_external_slideshow"
+        | "enable_presentation_mode"
+        | "disable_presentation_mode"
+        | "open_profile_window"
+        | "toggle_quick_search"
+        | "set_menubar_settings"
+        | "set_search_index_settings"
+        | "index_album"
+        | "set_update_settings"
+        | "set_server_update_settings"
+        | "get_audit_log"
+        | "get_usage_stats"
+        | "declare_shortcuts"
+        | "set_shortcut"
+        | "reset_shortcuts"
+        | "create_profile"
+        | "switch_profile"
+        | "clear_server_data"
+        | "set_user_agent"
+        | "set_custom_headers"
+        | "set_integrated_auth"
+        | "set_certificate_pins"
+        | "set_doh_settings"
+        | "set_host_overrides"
+        | "set_vpn_settings"
+        | "set_ssh_tunnel_settings"
+        | "set_server_credentials"
+        | "remove_server_credentials"
+        | "delete_profile"
+        | "get_custom_headers"
+        | "list_server_credentials"
+        | "remove_indexed_album"
+        | "notify"
+        | "control_slideshow" => Some(Capability::Settings),
+        _ => None,
+    }
+}
+
+/// Capabilities granted to windows other than the main one, keyed by label
+///
+/// Windows that were never granted anything may only call unprivileged commands.
+#[derive(Default)]
+pub struct Capabilities(Mutex<HashMap<String, HashSet<Capability>>>);
+
+impl Capabilities {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HashSet<Capability>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Allow a window to use the given command groups
+///
+/// Call before the window loads so its first commands are already permitted.
+pub fn grant(app: &AppHandle, label: &str, capabilities: &[Capability]) {
+    app.state::<Capabilities>()
+        .lock()
+        .insert(label.to_string(), capabilities.iter().copied().collect());
+}
+
+/// Remove everything granted to a window
+pub fn revoke(app: &AppHandle, label: &str) {
+    app.state::<Capabilities>().lock().remove(label);
+}
+
+fn granted(app: &AppHandle, label: &str) -> HashSet<Capability> {
+    if label == MAIN_WINDOW {
+        return ALL.into_iter().collect();
+    }
+    app.state::<Capabilities>()
+        .lock()
+        .get(label)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the rejection message if the calling window may not use the command
+fn check(invoke: &Invoke<Wry>) -> Result<(), String> {
+    let webview = invoke.message.webview_ref();
+    let label = webview.label();
+    let command = invoke.message.command();
+    if window::is_guest(label) && !GUEST_COMMANDS.contains(&command) {
+        log::warn!("Blocked '{}' from guest window '{}'", command, label);
+        return Err(format!("Guest windows can't use '{}'", command));
+    }
+
+    let Some(capability) = required(command) else {
+        if UNPRIVILEGED.contains(&command) {
+            return Ok(());
+        }
+        log::error!("Blocked '{}', which has no capability assigned", command);
+        return Err(format!("'{}' has no capability assigned", command));
+    };
+    if granted(webview.app_handle(), label).contains(&capability) {
+        return Ok(());
+    }
+
+    log::warn!(
+        "Blocked '{}' from window '{}' without the {} capability",
+        command,
+        label,
+        capability.as_str()
+    );
+    Err(format!(
+        "Window '{}' is not allowed to use {} commands",
+        label,
+        capability.as_str()
+    ))
+}
+
+/// Wrap the command handler so every invoke is checked against the calling
+/// window's capabilities, and audited, before it runs
+pub fn guard<F>(handler: F) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let result = check(&invoke);
+        audit::record_invoke(&invoke, result.is_ok());
+        match result {
+            Ok(()) => {
+                usage::record_invoke(&invoke);
+                handler(invoke)
+            }
+            Err(e) => {
+                invoke.resolver.reject(e);
+                true
+            }
+        }
+    }
+}
+
+/// Forget a window's grants when it closes, so a reused label starts fresh
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) {
+        revoke(window.app_handle(), window.label());
+    }
+}
+
+/// Command groups the calling window may use
+#[tauri::command]
+pub async fn get_window_capabilities(
+    app: AppHandle,
+    window: Window,
+) -> Result<Vec<Capability>, String> {
+    let granted = granted(&app, window.label());
+    Ok(ALL.into_iter().filter(|c| granted.contains(c)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The commands registered in `generate_handler!`, by their bare names
+    fn registered_commands() -> Vec<&'static str> {
+        let main = include_str!("main.rs");
+        let start = main
+            .find("generate_handler![")
+            .expect("no generate_handler! in main.rs");
+        let list = &main[start + "generate_handler![".len()..];
+        let list = &list[..list.find(']').unwrap()];
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|path| path.rsplit("::").next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_command_is_classified() {
+        let commands = registered_commands();
+        assert!(commands.len() > 100, "generate_handler! wasn't parsed");
+        let unclassified: Vec<_> = commands
+            .into_iter()
+            .filter(|name| required(name).is_none() && !UNPRIVILEGED.contains(name))
+            .collect();
+        assert!(
+            unclassified.is_empty(),
+            "commands without a capability or an UNPRIVILEGED entry: {:?}",
+            unclassified
+        );
+    }
+}
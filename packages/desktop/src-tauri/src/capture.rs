@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Manager};
+
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::notifications::{self, NotificationCategory};
+use crate::profiles;
+use crate::shortcuts::{self, ShortcutAction};
+use crate::upload;
+
+const QUICK_CAPTURE_KEY: &str = "quickCapture";
+/// Id of the hotkey in the shortcut registry, where the user can remap it
+const SHORTCUT_ID: &str = "quick-capture";
+const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Alt+Shift+S";
+/// Where the share link is in the upload response unless configured otherwise
+const DEFAULT_LINK_POINTER: &str = "/url";
+
+/// A capture is in progress, so a repeated hotkey press is ignored
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Global hotkey that captures a screen region and uploads it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickCaptureSettings {
+    pub enabled: bool,
+    /// Where captures are saved; defaults to `Pictures/Apollo Captures`
+    pub folder: Option<String>,
+    /// Endpoint accepting a multipart upload with `file` and `album` parts
+    pub upload_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub album: Option<String>,
+    /// JSON pointer to the share link in the upload response, e.g. `/url`
+    pub link_pointer: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickCapture {
+    pub path: String,
+    /// Share link returned by the server
+    pub link: Option<String>,
+    /// Set when the upload failed; the capture is still saved
+    pub error: Option<String>,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<QuickCaptureSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(QUICK_CAPTURE_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(QuickCaptureSettings::default()),
+    }
+}
+
+fn folder(app: &AppHandle, settings: &QuickCaptureSettings) -> Result<PathBuf, String> {
+    match &settings.folder {
+        Some(folder) => Ok(PathBuf::from(folder)),
+        None => app
+            .path()
+            .picture_dir()
+            .map(|dir| dir.join("Apollo Captures"))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Register or remove the hotkey to match the saved settings
+pub fn apply(app: &AppHandle) -> Result<(), String> {
+    let settings = read_settings(app)?;
+    if settings.enabled {
+        shortcuts::declare_native(
+            app,
+            ShortcutAction {
+                id: SHORTCUT_ID.to_string(),
+                label: "Quick capture".to_string(),
+                default: Some(DEFAULT_SHORTCUT.to_string()),
+                global: true,
+            },
+            on_shortcut,
+        );
+    } else {
+        shortcuts::remove_native(app, SHORTCUT_ID);
+    }
+    shortcuts::refresh(app);
+    Ok(())
+}
+
+fn on_shortcut(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = capture(&app).await {
+            log::warn!("Quick capture failed: {}", e);
+        }
+    });
+}
+
+/// Let the user select a screen region and save it as a PNG
+///
+/// Returns false if the user cancelled the selection.
+fn capture_region(path: &Path) -> Result<bool, String> {
+    #[cfg(target_os = "macos")]
+    {
+        // -i selects a region interactively, -x skips the shutter sound
+        let status = std::process::Command::new("screencapture")
+            .args(["-i", "-x", "-t", "png"])
+            .arg(path)
+            .status()
+            .map_err(|e| e.to_string())?;
+        Ok(status.success() && path.is_file())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        win::capture_region(path)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::capture_region(path)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        let _ = path;
+        Err("Screen capture is not supported on this platform".to_string())
+    }
+}
+
+async fn upload_capture(
+    settings: &QuickCaptureSettings,
+    path: &Path,
+) -> Result<Option<String>, String> {
+    let url = settings
+        .upload_url
+        .as_deref()
+        .ok_or_else(|| "No upload URL configured".to_string())?;
+    let mut fields = Vec::new();
+    if let Some(album) = &settings.album {
+        fields.push(("album", album.clone()));
+    }
+    let response = upload::upload_file_json(url, &settings.headers, path, &fields).await?;
+    let pointer = settings
+        .link_pointer
+        .as_deref()
+        .unwrap_or(DEFAULT_LINK_POINTER);
+    Ok(response
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string()))
+}
+
+/// Capture a region, save it, upload it, and notify with the share link
+///
+/// Returns `None` if the user cancelled or a capture was already running.
+pub async fn capture(app: &AppHandle) -> Result<Option<QuickCapture>, String> {
+    if CAPTURING.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let result = capture_and_upload(app).await;
+    CAPTURING.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn capture_and_upload(app: &AppHandle) -> Result<Option<QuickCapture>, String> {
+    let settings = read_settings(app)?;
+    let folder = folder(app, &settings)?;
+    std::fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+    let name = chrono::Local::now()
+        .format("Capture %Y-%m-%d at %H.%M.%S.png")
+        .to_string();
+    let path = folder.join(name);
+
+    let captured = tauri::async_runtime::spawn_blocking({
+        let path = path.clone();
+        move || capture_region(&path)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if !captured {
+        return Ok(None);
+    }
+
+    let (link, error) = match upload_capture(&settings, &path).await {
+        Ok(link) => (link, None),
+        Err(e) => (None, Some(e)),
+    };
+    let (title, body, category) = match (&link, &error) {
+        (_, Some(e)) => (
+            "Capture saved but not uploaded",
+            Some(e.clone()),
+            Some(NotificationCategory::Errors),
+        ),
+        (Some(link), None) => ("Capture uploaded", Some(link.clone()), None),
+        (None, None) => ("Capture uploaded", None, None),
+    };
+    let _ =
+        notifications::notify(app.clone(), title.to_string(), body, link.clone(), category).await;
+
+    let capture = QuickCapture {
+        path: path.to_string_lossy().to_string(),
+        link,
+        error,
+    };
+    events::publish(app, Event::QuickCapture(capture.clone()));
+    Ok(Some(capture))
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+    use windows::Win32::Foundation::HGLOBAL;
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, GetClipboardData, GetClipboardSequenceNumber, IsClipboardFormatAvailable,
+        OpenClipboard,
+    };
+    use windows::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+    use windows::Win32::System::Ole::CF_DIB;
+
+    /// How long to wait for the user to finish selecting a region
+    const SELECTION_TIMEOUT: Duration = Duration::from_secs(120);
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+    const BI_BITFIELDS: u32 = 3;
+
+    /// Use the system Snip overlay, which puts the selection on the clipboard
+    pub fn capture_region(path: &Path) -> Result<bool, String> {
+        let before = unsafe { GetClipboardSequenceNumber() };
+        std::process::Command::new("explorer")
+            .arg("ms-screenclip:")
+            .spawn()
+            .map_err(|e| e.to_string())?;
+
+        let started = Instant::now();
+        while started.elapsed() < SELECTION_TIMEOUT {
+            std::thread::sleep(POLL_INTERVAL);
+            if unsafe { GetClipboardSequenceNumber() } == before {
+                continue;
+            }
+            let Some(dib) = read_dib() else {
+                continue;
+            };
+            let image = image::load_from_memory_with_format(&to_bmp(&dib), image::ImageFormat::Bmp)
+                .map_err(|e| e.to_string())?;
+            image.save(path).map_err(|e| e.to_string())?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn read_dib() -> Option<Vec<u8>> {
+        unsafe {
+            IsClipboardFormatAvailable(CF_DIB.0 as u32).ok()?;
+            OpenClipboard(None).ok()?;
+            let data = GetClipboardData(CF_DIB.0 as u32).ok().and_then(|handle| {
+                let memory = HGLOBAL(handle.0);
+                let pointer = GlobalLock(memory) as *const u8;
+                if pointer.is_null() {
+                    return None;
+                }
+                let bytes = std::slice::from_raw_parts(pointer, GlobalSize(memory)).to_vec();
+                let _ = GlobalUnlock(memory);
+                Some(bytes)
+            });
+            let _ = CloseClipboard();
+            data
+        }
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .unwrap_or(0)
+    }
+
+    /// Prepend the file header a clipboard DIB lacks, making it a BMP file
+    fn to_bmp(dib: &[u8]) -> Vec<u8> {
+        let header_size = read_u32(dib, 0);
+        let bit_count = dib
+            .get(14..16)
+            .map_or(0, |b| u16::from_le_bytes([b[0], b[1]]));
+        let compression = read_u32(dib, 16);
+        let colors_used = read_u32(dib, 32);
+        let masks = match header_size == 40 && compression == BI_BITFIELDS {
+            true => 12,
+            false => 0,
+        };
+        let palette = match (colors_used, bit_count) {
+            (0, bits) if bits <= 8 => (1u32 << bits) * 4,
+            (used, _) => used * 4,
+        };
+        let offset = 14 + header_size + masks + palette;
+
+        let mut bmp = Vec::with_capacity(14 + dib.len());
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&(14 + dib.len() as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&offset.to_le_bytes());
+        bmp.extend_from_slice(dib);
+        bmp
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io::ErrorKind;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Run the first region screenshot tool that is installed
+    pub fn capture_region(path: &Path) -> Result<bool, String> {
+        let target = path.to_string_lossy().to_string();
+        let tools: [(&str, Vec<&str>); 3] = [
+            ("gnome-screenshot", vec!["--area", "--file", &target]),
+            (
+                "spectacle",
+                vec![
+                    "--background",
+                    "--nonotify",
+                    "--region",
+                    "--output",
+                    &target,
+                ],
+            ),
+            ("grimshot", vec!["save", "area", &target]),
+        ];
+        for (program, args) in tools {
+            match Command::new(program).args(&args).status() {
+                // Cancelling the selection exits with an error on some tools
+                Ok(status) if status.success() => return Ok(path.is_file()),
+                Ok(_) => {
+                    let _ = std::fs::remove_file(path);
+                    return Ok(false);
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Err(
+            "No screenshot tool found; install gnome-screenshot, spectacle, or grimshot"
+                .to_string(),
+        )
+    }
+}
+
+/// Get the quick-capture hotkey settings
+#[tauri::command]
+pub async fn get_quick_capture_settings(app: AppHandle) -> Result<QuickCaptureSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the quick-capture settings and register or remove the hotkey
+///
+/// The hotkey itself is remapped like any other shortcut, under the
+/// `quick-capture` action.
+#[tauri::command]
+pub async fn set_quick_capture_settings(
+    app: AppHandle,
+    settings: QuickCaptureSettings,
+) -> Result<(), String> {
+    if let Some(folder) = &settings.folder {
+        fs_scope::check(&app, folder)?;
+    }
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(QUICK_CAPTURE_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+    apply(&app)
+}
+
+/// Capture a screen region now, as if the hotkey was pressed
+#[tauri::command]
+pub async fn quick_capture(app: AppHandle) -> Result<Option<QuickCapture>, String> {
+    capture(&app).await
+}
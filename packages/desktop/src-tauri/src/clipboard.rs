@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use tauri::image::Image;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::http;
+
+/// Copy an image from the server to the clipboard as pixels, not as a link
+///
+/// The image is fetched with the native client, so it works for photos that
+/// need the server's credentials, and pastes into editors and chats as an
+/// image. Formats the `image` crate can't decode, like HEIC, are rejected.
+#[tauri::command]
+pub async fn copy_image_from_url(
+    app: AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    let headers = headers.unwrap_or_default();
+    let request = http::with_headers(http::client()?.get(&url), &url, &headers)?;
+    let bytes = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let image = tauri::async_runtime::spawn_blocking(move || {
+        image::load_from_memory(&bytes)
+            .map(|image| image.into_rgba8())
+            .map_err(|e| format!("Not an image that can be copied: {}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let (width, height) = image.dimensions();
+    app.clipboard()
+        .write_image(&Image::new_owned(image.into_raw(), width, height))
+        .map_err(|e| e.to_string())
+}
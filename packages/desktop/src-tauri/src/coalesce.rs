@@ -0,0 +1,296 @@
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+
+use crate::http;
+use crate::http_cache::{self, CachedResponse};
+
+/// How long a response is reused for identical requests
+const CACHE_TTL: Duration = Duration::from_secs(2);
+/// How long to wait for more ids before sending a batch
+const BATCH_WINDOW: Duration = Duration::from_millis(15);
+const MAX_BATCH_SIZE: usize = 100;
+
+type Fetch = Shared<BoxFuture<'static, Result<CachedResponse, String>>>;
+type Waiter = oneshot::Sender<Result<Value, String>>;
+
+/// Ids waiting to be sent in one batched request
+struct PendingBatch {
+    url: String,
+    headers: HashMap<String, String>,
+    waiters: Vec<(String, Waiter)>,
+    /// Tells the batch window's timer whether the batch it was started for
+    /// is still the one pending, or was already sent for being full
+    generation: u64,
+}
+
+/// In-flight and recently completed metadata requests from the frontend
+#[derive(Default)]
+pub struct Coalescer {
+    inflight: Mutex<HashMap<String, Fetch>>,
+    responses: Mutex<HashMap<String, (Instant, CachedResponse)>>,
+    batches: Mutex<HashMap<String, PendingBatch>>,
+    items: Mutex<HashMap<String, (Instant, Value)>>,
+    generations: AtomicU64,
+}
+
+fn fresh<T: Clone>(cache: &Mutex<HashMap<String, (Instant, T)>>, key: &str) -> Option<T> {
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    cache.get(key).map(|(_, value)| value.clone())
+}
+
+/// GET a URL, sharing one request between identical concurrent callers
+async fn fetch(
+    app: &AppHandle,
+    url: String,
+    headers: HashMap<String, String>,
+) -> Result<CachedResponse, String> {
+    let key = format!("{}\n{}", http_cache::variant(&headers), url);
+    let coalescer = app.state::<Coalescer>();
+    if let Some(response) = fresh(&coalescer.responses, &key) {
+        return Ok(response);
+    }
+
+    let shared = {
+        let mut inflight = coalescer.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        inflight
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let app = app.clone();
+                async move { http_cache::get(&app, &url, &headers).await }
+                    .boxed()
+                    .shared()
+            })
+            .clone()
+    };
+    let result = shared.await;
+
+    coalescer
+        .inflight
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&key);
+    if let Ok(response) = &result {
+        coalescer
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (Instant::now(), response.clone()));
+    }
+    result
+}
+
+/// Split a batch response into items by id
+///
+/// Accepts either an object keyed by id or an array of objects with an `id` field.
+fn items_by_id(body: Value) -> HashMap<String, Value> {
+    match body {
+        Value::Object(map) => map.into_iter().collect(),
+        Value::Array(items) => items
+            .into_iter()
+            .filter_map(|item| {
+                let id = match item.get("id")? {
+                    Value::String(id) => id.clone(),
+                    id => id.to_string(),
+                };
+                Some((id, item))
+            })
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+/// POST `{"ids": [...]}` for a batch and hand each waiter its item
+async fn send_batch(app: AppHandle, key: String, batch: PendingBatch) {
+    let mut ids: Vec<&String> = batch.waiters.iter().map(|(id, _)| id).collect();
+    ids.sort();
+    ids.dedup();
+
+    let result = async {
+        let request = http::client()?
+            .post(&batch.url)
+            .json(&serde_json::json!({ "ids": ids }));
+        http::with_headers(request, &batch.url, &batch.headers)?
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json::<Value>()
+            .await
+            .map_err(|e| e.to_string())
+    }
+    .await
+    .map(items_by_id);
+
+    match result {
+        Ok(items) => {
+            let now = Instant::now();
+            let coalescer = app.state::<Coalescer>();
+            let mut cache = coalescer.items.lock().unwrap_or_else(|e| e.into_inner());
+            for (id, waiter) in batch.waiters {
+                let item = items.get(&id).cloned().unwrap_or(Value::Null);
+                cache.insert(format!("{}\n{}", key, id), (now, item.clone()));
+                let _ = waiter.send(Ok(item));
+            }
+        }
+        Err(error) => {
+            for (_, waiter) in batch.waiters {
+                let _ = waiter.send(Err(error.clone()));
+            }
+        }
+    }
+}
+
+/// Take the batch a window's timer was started for, if it is still pending
+///
+/// A later batch under the same key has its own window, so a timer whose
+/// batch was already sent for being full leaves it alone.
+fn window_closed(
+    batches: &mut HashMap<String, PendingBatch>,
+    key: &str,
+    generation: u64,
+) -> Option<PendingBatch> {
+    match batches.get(key) {
+        Some(batch) if batch.generation == generation => batches.remove(key),
+        _ => None,
+    }
+}
+
+/// Queue an id for the next batched request to `url`
+async fn fetch_item(
+    app: &AppHandle,
+    url: String,
+    id: String,
+    headers: HashMap<String, String>,
+) -> Result<Value, String> {
+    let key = format!("{}\n{}", http_cache::variant(&headers), url);
+    let coalescer = app.state::<Coalescer>();
+    if let Some(item) = fresh(&coalescer.items, &format!("{}\n{}", key, id)) {
+        return Ok(item);
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let full = {
+        let mut batches = coalescer.batches.lock().unwrap_or_else(|e| e.into_inner());
+        let first = !batches.contains_key(&key);
+        let batch = batches.entry(key.clone()).or_insert_with(|| PendingBatch {
+            url,
+            headers,
+            waiters: Vec::new(),
+            generation: coalescer.generations.fetch_add(1, Ordering::Relaxed),
+        });
+        let generation = batch.generation;
+        batch.waiters.push((id, tx));
+
+        if batch.waiters.len() >= MAX_BATCH_SIZE {
+            batches.remove(&key)
+        } else {
+            if first {
+                // The first caller owns the batch window; later ones just join it
+                let app = app.clone();
+                let key = key.clone();
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(BATCH_WINDOW).await;
+                    let batch = {
+                        let coalescer = app.state::<Coalescer>();
+                        let mut batches =
+                            coalescer.batches.lock().unwrap_or_else(|e| e.into_inner());
+                        window_closed(&mut batches, &key, generation)
+                    };
+                    if let Some(batch) = batch {
+                        send_batch(app, key, batch).await;
+                    }
+                });
+            }
+            None
+        }
+    };
+    if let Some(batch) = full {
+        tauri::async_runtime::spawn(send_batch(app.clone(), key, batch));
+    }
+
+    rx.await.map_err(|e| e.to_string())?
+}
+
+/// GET server metadata, merging identical requests made at the same time
+///
+/// Concurrent callers share one request and its response is reused for a
+/// couple of seconds, which stops thundering herds when scrolling fast.
+#[tauri::command]
+pub async fn coalesced_fetch(
+    app: AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<CachedResponse, String> {
+    fetch(&app, url, headers.unwrap_or_default()).await
+}
+
+/// Fetch one item through a batch endpoint
+///
+/// Ids requested for the same `url` within a few milliseconds are sent
+/// together as `{"ids": [...]}`; the server answers with an object keyed by
+/// id or an array of objects with an `id` field. Unknown ids resolve to `null`.
+#[tauri::command]
+pub async fn batched_fetch(
+    app: AppHandle,
+    url: String,
+    id: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<Value, String> {
+    fetch_item(&app, url, id, headers.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(generation: u64) -> PendingBatch {
+        PendingBatch {
+            url: "https://photos.example.com/api/items".to_string(),
+            headers: HashMap::new(),
+            waiters: Vec::new(),
+            generation,
+        }
+    }
+
+    #[test]
+    fn timer_sends_its_own_batch() {
+        let mut batches = HashMap::from([("key".to_string(), batch(3))]);
+        let sent = window_closed(&mut batches, "key", 3);
+        assert_eq!(sent.map(|b| b.generation), Some(3));
+        assert!(batches.is_empty());
+    }
+
+    #[test]
+    fn stale_timer_leaves_a_later_batch_pending() {
+        // The batch the timer was started for filled up and was sent, and a
+        // new one started under the same key
+        let mut batches = HashMap::from([("key".to_string(), batch(4))]);
+        assert!(window_closed(&mut batches, "key", 3).is_none());
+        assert_eq!(batches.get("key").map(|b| b.generation), Some(4));
+    }
+
+    #[test]
+    fn timer_for_a_sent_batch_does_nothing() {
+        let mut batches = HashMap::new();
+        assert!(window_closed(&mut batches, "key", 3).is_none());
+    }
+
+    #[test]
+    fn batch_responses_are_split_by_id() {
+        let object = items_by_id(serde_json::json!({ "a": 1, "b": 2 }));
+        assert_eq!(object.get("b"), Some(&serde_json::json!(2)));
+
+        let array = items_by_id(serde_json::json!([{ "id": "a" }, { "id": 7 }]));
+        assert_eq!(array.get("a"), Some(&serde_json::json!({ "id": "a" })));
+        assert_eq!(array.get("7"), Some(&serde_json::json!({ "id": 7 })));
+
+        assert!(items_by_id(serde_json::json!("nope")).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Native right-click menus
+//!
+//! Unlike menus drawn in the webview, these can extend past the window's
+//! edges and look like every other menu on the system.
+//!
+//! Item ids are prefixed with the menu they belong to, so a click on a menu
+//! that was already replaced is ignored. On macOS and Windows the system
+//! blocks while the menu is open, so a dismissed menu resolves to `None` as
+//! soon as it closes. GTK doesn't report dismissal; there a dismissed menu
+//! resolves when the next one opens or after a timeout.
+
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::menu::{
+    CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu,
+};
+use tauri::{AppHandle, Window, Wry};
+use tokio::sync::oneshot;
+
+const ID_PREFIX: &str = "context-menu:";
+/// How long a click may take to arrive after the menu closes
+#[cfg(not(target_os = "linux"))]
+const SELECTION_GRACE: Duration = Duration::from_millis(500);
+/// How long to wait for a click on a GTK menu
+#[cfg(target_os = "linux")]
+const OPEN_TIMEOUT: Duration = Duration::from_secs(120);
+
+static NEXT_MENU: AtomicU64 = AtomicU64::new(1);
+/// The open menu and where its selection goes
+static PENDING: Mutex<Option<(u64, oneshot::Sender<String>)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ContextMenuItem {
+    Item {
+        id: String,
+        label: String,
+        #[serde(default = "enabled")]
+        enabled: bool,
+    },
+    Check {
+        id: String,
+        label: String,
+        checked: bool,
+        #[serde(default = "enabled")]
+        enabled: bool,
+    },
+    Separator,
+    Submenu {
+        label: String,
+        #[serde(default = "enabled")]
+        enabled: bool,
+        items: Vec<ContextMenuItem>,
+    },
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn item_id(menu: u64, id: &str) -> String {
+    format!("{}{}:{}", ID_PREFIX, menu, id)
+}
+
+fn build(
+    app: &AppHandle,
+    menu: u64,
+    items: &[ContextMenuItem],
+) -> tauri::Result<Vec<Box<dyn IsMenuItem<Wry>>>> {
+    items
+        .iter()
+        .map(|item| -> tauri::Result<Box<dyn IsMenuItem<Wry>>> {
+            Ok(match item {
+                ContextMenuItem::Item { id, label, enabled } => Box::new(MenuItem::with_id(
+                    app,
+                    item_id(menu, id),
+                    label,
+                    *enabled,
+                    None::<&str>,
+                )?),
+                ContextMenuItem::Check {
+                    id,
+                    label,
+                    checked,
+                    enabled,
+                } => Box::new(CheckMenuItem::with_id(
+                    app,
+                    item_id(menu, id),
+                    label,
+                    *enabled,
+                    *checked,
+                    None::<&str>,
+                )?),
+                ContextMenuItem::Separator => Box::new(PredefinedMenuItem::separator(app)?),
+                ContextMenuItem::Submenu {
+                    label,
+                    enabled,
+                    items,
+                } => {
+                    let children = build(app, menu, items)?;
+                    let children: Vec<&dyn IsMenuItem<Wry>> =
+                        children.iter().map(|child| child.as_ref()).collect();
+                    Box::new(Submenu::with_items(app, label, *enabled, &children)?)
+                }
+            })
+        })
+        .collect()
+}
+
+fn build_menu(app: &AppHandle, menu: u64, items: &[ContextMenuItem]) -> tauri::Result<Menu<Wry>> {
+    let children = build(app, menu, items)?;
+    let children: Vec<&dyn IsMenuItem<Wry>> = children.iter().map(|child| child.as_ref()).collect();
+    Menu::with_items(app, &children)
+}
+
+/// Pass a click on a context menu item to the `show_context_menu` call waiting for it
+pub fn handle_menu_event(_app: &AppHandle, event: MenuEvent) {
+    let Some((menu, id)) = event
+        .id
+        .as_ref()
+        .strip_prefix(ID_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+    else {
+        return;
+    };
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if pending
+        .as_ref()
+        .is_some_and(|(open, _)| open.to_string() == menu)
+    {
+        if let Some((_, tx)) = pending.take() {
+            let _ = tx.send(id.to_string());
+        }
+    }
+}
+
+/// Show a native menu at the cursor and return the id of the chosen item
+///
+/// Resolves to `None` when the menu is dismissed. Opening another menu
+/// dismisses the one before.
+#[tauri::command]
+pub async fn show_context_menu(
+    app: AppHandle,
+    window: Window,
+    items: Vec<ContextMenuItem>,
+) -> Result<Option<String>, String> {
+    let id = NEXT_MENU.fetch_add(1, Ordering::Relaxed);
+    let menu = build_menu(&app, id, &items).map_err(|e| e.to_string())?;
+
+    let (tx, rx) = oneshot::channel();
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner()) = Some((id, tx));
+
+    // On the main thread the popup runs inline, returning once the menu closes
+    let (closed_tx, closed_rx) = oneshot::channel::<tauri::Result<()>>();
+    let target = window.clone();
+    let shown_menu = menu.clone();
+    window
+        .run_on_main_thread(move || {
+            let _ = closed_tx.send(target.popup_menu(&shown_menu));
+        })
+        .map_err(|e| e.to_string())?;
+    closed_rx
+        .await
+        .map_err(|_| "The context menu was not shown".to_string())?
+        .map_err(|e| e.to_string())?;
+
+    #[cfg(not(target_os = "linux"))]
+    let wait = SELECTION_GRACE;
+    #[cfg(target_os = "linux")]
+    let wait = OPEN_TIMEOUT;
+    let selected = tokio::time::timeout(wait, rx)
+        .await
+        .ok()
+        .and_then(|selected| selected.ok());
+    // Kept alive until now, as GTK menus are still open here
+    drop(menu);
+
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.as_ref().is_some_and(|(open, _)| *open == id) {
+        *pending = None;
+    }
+    Ok(selected)
+}
@@ -0,0 +1,419 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::Disks;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+use walkdir::WalkDir;
+
+use crate::cache;
+use crate::db::{self, Database};
+use crate::events::{self, Event};
+use crate::profiles;
+use crate::sandbox;
+use crate::sync::SyncEngine;
+use crate::transfers;
+use crate::STORE_NAME;
+
+/// Custom location for the database, caches, and models
+const DATA_DIRECTORY_KEY: &str = "dataDirectory";
+/// Previous location to delete once the app has started from the new one
+const DATA_DIRECTORY_CLEANUP_KEY: &str = "dataDirectoryCleanup";
+/// Subdirectory of a custom data directory that holds the caches
+const CACHE_SUBDIR: &str = "cache";
+/// Entries of the data directory other than the database that are moved
+const DATA_ENTRIES: &[&str] = &["models", profiles::PROFILES_SUBDIR];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataDirInfo {
+    pub path: String,
+    pub cache_path: String,
+    pub default_path: String,
+    /// A custom location is configured
+    pub custom: bool,
+    /// The custom location was missing at startup and the default is in use
+    pub fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationProgress {
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+fn default_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+fn custom_dir(app: &AppHandle) -> Option<PathBuf> {
+    let store = app.store(STORE_NAME).ok()?;
+    store
+        .get(DATA_DIRECTORY_KEY)
+        .and_then(|v| v.as_str().map(PathBuf::from))
+}
+
+/// Directory holding the default profile's database, other profiles, and models
+///
+/// Settings always stay in the default location, since they record where
+/// everything else lives. Falls back to the default if the custom location
+/// is missing, e.g. an unplugged drive.
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match custom_dir(app) {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        _ => default_dir(app),
+    }
+}
+
+/// Directory holding caches, inside a custom data directory when one is set
+pub fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match custom_dir(app) {
+        Some(dir) if dir.is_dir() => Ok(dir.join(CACHE_SUBDIR)),
+        _ => app.path().app_cache_dir().map_err(|e| e.to_string()),
+    }
+}
+
+/// What belongs to Apollo in a data and cache directory, as (from, to) pairs
+///
+/// Only these are moved or deleted, since the default directories can also
+/// hold webview data.
+fn owned_paths(
+    from_data: &Path,
+    from_cache: &Path,
+    to_data: &Path,
+    to_cache: &Path,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut paths: Vec<(PathBuf, PathBuf)> = DATA_ENTRIES
+        .iter()
+        .map(|name| (from_data.join(name), to_data.join(name)))
+        .collect();
+    paths.extend(
+        cache::SUBDIRS
+            .iter()
+            .chain([&profiles::PROFILES_SUBDIR])
+            .map(|name| (from_cache.join(name), to_cache.join(name))),
+    );
+    paths
+}
+
+/// Delete the previous location after a migration, once the new one has opened
+pub fn cleanup(app: &AppHandle) {
+    let Ok(store) = app.store(STORE_NAME) else {
+        return;
+    };
+    let Some(old) = store
+        .get(DATA_DIRECTORY_CLEANUP_KEY)
+        .and_then(|v| serde_json::from_value::<Vec<PathBuf>>(v).ok())
+    else {
+        return;
+    };
+    store.delete(DATA_DIRECTORY_CLEANUP_KEY);
+    let _ = store.save();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        for path in old {
+            let result = match path.is_dir() {
+                true => std::fs::remove_dir_all(&path),
+                false => std::fs::remove_file(&path),
+            };
+            if let Err(e) = result {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to remove old data {}: {}", path.display(), e);
+                }
+            }
+        }
+    });
+}
+
+/// Files under the owned paths, with their destinations and sizes
+pub fn plan_copy(paths: &[(PathBuf, PathBuf)]) -> Vec<(PathBuf, PathBuf, u64)> {
+    let mut files = Vec::new();
+    for (from, to) in paths {
+        for entry in WalkDir::new(from).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(from) else {
+                continue;
+            };
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push((entry.path().to_path_buf(), to.join(relative), size));
+        }
+    }
+    files
+}
+
+/// Free space on the volume holding `path`
+pub fn available_space(path: &Path) -> Option<u64> {
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| d.available_space())
+}
+
+/// Copy planned files, publishing `progress` after each one
+pub fn copy_all(
+    app: &AppHandle,
+    files: &[(PathBuf, PathBuf, u64)],
+    progress: fn(MigrationProgress) -> Event,
+) -> Result<u64, String> {
+    let total_bytes = files.iter().map(|(_, _, size)| size).sum();
+    let mut copied_bytes = 0;
+    for (from, to, size) in files {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::copy(from, to).map_err(|e| format!("{}: {}", from.display(), e))?;
+        copied_bytes += size;
+        events::publish(
+            app,
+            progress(MigrationProgress {
+                copied_bytes,
+                total_bytes,
+                file: from.to_string_lossy().to_string(),
+            }),
+        );
+    }
+    Ok(total_bytes)
+}
+
+/// Undo a `copy_all` into `root` that failed partway
+///
+/// Removes the destinations that were copied and then the folders created
+/// for them, deepest first. Folders that still hold other files are kept,
+/// and so is `root` itself.
+pub fn remove_copied(files: &[(PathBuf, PathBuf, u64)], root: &Path) {
+    let mut dirs = Vec::new();
+    for (_, to, _) in files {
+        if let Err(e) = std::fs::remove_file(to) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to remove copied file {}: {}", to.display(), e);
+            }
+        }
+        dirs.extend(
+            to.ancestors()
+                .skip(1)
+                .take_while(|dir| *dir != root && dir.starts_with(root))
+                .map(Path::to_path_buf),
+        );
+    }
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+    for dir in dirs {
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+/// Where the database, caches, and models are stored
+#[tauri::command]
+pub async fn get_data_dir(app: AppHandle) -> Result<DataDirInfo, String> {
+    let custom = custom_dir(&app);
+    Ok(DataDirInfo {
+        path: data_dir(&app)?.to_string_lossy().to_string(),
+        cache_path: cache_dir(&app)?.to_string_lossy().to_string(),
+        default_path: default_dir(&app)?.to_string_lossy().to_string(),
+        fallback: custom.as_ref().is_some_and(|dir| !dir.is_dir()),
+        custom: custom.is_some(),
+    })
+}
+
+/// Move the database, caches, and models of every profile to another directory
+///
+/// Transfers and syncs are paused while files are copied, emitting
+/// `data-migration-progress`. The switch happens only after everything was
+/// copied, and the app then restarts from the new location and deletes the
+/// old copies. The target must be empty unless it is the default location.
+#[tauri::command]
+pub async fn migrate_data_dir(app: AppHandle, path: String) -> Result<MigrationReport, String> {
+    let target = PathBuf::from(&path);
+    if !target.is_absolute() {
+        return Err(format!("'{}' is not an absolute path", path));
+    }
+    sandbox::check_data_dir(&target)?;
+    let default = default_dir(&app)?;
+    let from_data = data_dir(&app)?;
+    let from_cache = cache_dir(&app)?;
+    if target == from_data {
+        return Err(format!("Data is already stored in '{}'", path));
+    }
+    if target.starts_with(&from_data) || from_data.starts_with(&target) {
+        return Err("The new location can't be inside the current one, or contain it".to_string());
+    }
+    let is_default = target == default;
+    if !is_default
+        && std::fs::read_dir(&target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false)
+    {
+        return Err(format!("'{}' is not empty", path));
+    }
+
+    let to_cache = match is_default {
+        true => app.path().app_cache_dir().map_err(|e| e.to_string())?,
+        false => target.join(CACHE_SUBDIR),
+    };
+    let database = |dir: &Path| -> Vec<PathBuf> {
+        ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| dir.join(format!("{}{}", db::DATABASE_NAME, suffix)))
+            .collect()
+    };
+    let owned = owned_paths(&from_data, &from_cache, &target, &to_cache);
+    // The open database is copied with VACUUM INTO, the others as plain files
+    let profile_dir = profiles::relative(&app);
+    let open_database = database(&from_data.join(&profile_dir));
+    let closed_database: Vec<(PathBuf, PathBuf)> = match profile_dir.as_os_str().is_empty() {
+        true => Vec::new(),
+        false => database(&from_data)
+            .into_iter()
+            .zip(database(&target))
+            .collect(),
+    };
+
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+    transfers::suspend(&app);
+
+    let result = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        let target = target.clone();
+        let owned = owned.clone();
+        let open_database = open_database.clone();
+        move || {
+            let mut files = plan_copy(&owned);
+            files.retain(|(from, _, _)| !open_database.contains(from));
+            files.extend(closed_database.into_iter().filter_map(|(from, to)| {
+                let size = std::fs::metadata(&from).ok()?.len();
+                Some((from, to, size))
+            }));
+            let needed: u64 = files.iter().map(|(_, _, size)| size).sum();
+            std::fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+            if let Some(available) = available_space(&target) {
+                if available < needed {
+                    return Err(format!(
+                        "Not enough free space: {} bytes needed, {} available",
+                        needed, available
+                    ));
+                }
+            }
+
+            let bytes = copy_all(&app, &files, Event::DataMigrationProgress)?;
+            // Leftovers from before an earlier migration would make VACUUM INTO fail
+            let database_dir = target.join(&profile_dir);
+            for stale in database(&database_dir) {
+                let _ = std::fs::remove_file(stale);
+            }
+            std::fs::create_dir_all(&database_dir).map_err(|e| e.to_string())?;
+            let database_path = database_dir.join(db::DATABASE_NAME);
+            app.state::<Database>()
+                .conn()
+                .execute("VACUUM INTO ?1", params![database_path.to_string_lossy()])
+                .map_err(|e| e.to_string())?;
+
+            // Synced files and queued downloads are tracked by absolute path
+            let databases = files
+                .iter()
+                .map(|(_, to, _)| to.clone())
+                .filter(|to| to.file_name() == Some(db::DATABASE_NAME.as_ref()))
+                .chain([database_path]);
+            for path in databases {
+                let moved = Connection::open(&path).map_err(|e| e.to_string())?;
+                for (from, to) in &owned {
+                    db::rewrite_paths(&moved, from, to)?;
+                }
+            }
+            Ok::<_, String>((files.len(), bytes))
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let (files, bytes) = match result {
+        Ok(done) => done,
+        Err(e) => {
+            if !is_default {
+                let _ = std::fs::remove_dir_all(&target);
+            }
+            transfers::wake(&app);
+            return Err(e);
+        }
+    };
+
+    // Saving the new location is the switch-over; until then the old copy is in use
+    let mut old: Vec<PathBuf> = owned.into_iter().map(|(from, _)| from).collect();
+    old.extend(database(&from_data));
+    let store = app.store(STORE_NAME).map_err(|e| e.to_string())?;
+    if is_default {
+        store.delete(DATA_DIRECTORY_KEY);
+    } else {
+        store.set(DATA_DIRECTORY_KEY, serde_json::json!(path));
+    }
+    store.set(DATA_DIRECTORY_CLEANUP_KEY, serde_json::json!(old));
+    store.save().map_err(|e| e.to_string())?;
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        handle.restart();
+    });
+
+    Ok(MigrationReport { path, files, bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "apollo-data-dir-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn removing_a_copy_keeps_the_target_and_other_files() {
+        let from = temp_dir("from");
+        let to = temp_dir("to");
+        std::fs::create_dir_all(from.join("a/b")).unwrap();
+        std::fs::write(from.join("a/b/one.jpg"), b"one").unwrap();
+        std::fs::write(from.join("a/two.jpg"), b"two").unwrap();
+        std::fs::write(from.join("three.jpg"), b"three").unwrap();
+
+        let files = plan_copy(&[(from.clone(), to.clone())]);
+        assert_eq!(files.len(), 3);
+        // Only some of the files made it before the copy failed
+        for (source, destination, _) in files.iter().take(2) {
+            std::fs::create_dir_all(destination.parent().unwrap()).unwrap();
+            std::fs::copy(source, destination).unwrap();
+        }
+        std::fs::create_dir_all(to.join("other")).unwrap();
+        std::fs::write(to.join("other/keep.txt"), b"keep").unwrap();
+
+        remove_copied(&files, &to);
+
+        assert!(to.is_dir());
+        assert!(!to.join("a").exists());
+        assert!(!to.join("three.jpg").exists());
+        assert_eq!(std::fs::read(to.join("other/keep.txt")).unwrap(), b"keep");
+        assert_eq!(std::fs::read(from.join("a/b/one.jpg")).unwrap(), b"one");
+
+        std::fs::remove_dir_all(&from).unwrap();
+        std::fs::remove_dir_all(&to).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use rusqlite::{params, Connection};
+use std::path::{Path, MAIN_SEPARATOR};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::profiles;
+
+pub const DATABASE_NAME: &str = "apollo.db";
+
+/// Schema migrations, applied in order and tracked with `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    // Hashes of local files, keyed by path and invalidated by size/mtime
+    "CREATE TABLE file_hashes (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        checksum TEXT NOT NULL
+    );
+    CREATE INDEX file_hashes_checksum ON file_hashes (checksum);
+    -- Checksums known to exist on a server
+    CREATE TABLE remote_checksums (
+        server TEXT NOT NULL,
+        checksum TEXT NOT NULL,
+        PRIMARY KEY (server, checksum)
+    );",
+    "CREATE TABLE perceptual_hashes (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        dhash INTEGER NOT NULL
+    );",
+    // Upload/download queue, kept across restarts
+    "CREATE TABLE transfers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        direction TEXT NOT NULL,
+        url TEXT NOT NULL,
+        path TEXT NOT NULL,
+        headers TEXT NOT NULL,
+        fields TEXT NOT NULL,
+        status TEXT NOT NULL,
+        bytes_done INTEGER NOT NULL DEFAULT 0,
+        bytes_total INTEGER,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        error TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX transfers_status ON transfers (status, id);",
+    // Local copies written by selective sync, so unsyncing only removes our files
+    "CREATE TABLE synced_files (
+        server TEXT NOT NULL,
+        album_id TEXT NOT NULL,
+        remote_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        PRIMARY KEY (server, album_id, remote_id)
+    );",
+    // Files known to be in sync between a local folder and an album
+    "CREATE TABLE folder_pair_files (
+        server TEXT NOT NULL,
+        album_id TEXT NOT NULL,
+        remote_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        PRIMARY KEY (server, album_id, remote_id)
+    );",
+    // Conditional-request cache, keyed by URL and a hash of the request headers
+    "CREATE TABLE http_cache (
+        url TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        etag TEXT,
+        last_modified TEXT,
+        content_type TEXT,
+        body BLOB NOT NULL,
+        fetched_at INTEGER NOT NULL,
+        PRIMARY KEY (url, variant)
+    );",
+    // Change-feed position of each synced album, for delta syncs
+    "CREATE TABLE sync_cursors (
+        server TEXT NOT NULL,
+        album_id TEXT NOT NULL,
+        cursor TEXT NOT NULL,
+        PRIMARY KEY (server, album_id)
+    );",
+    // Privileged operations invoked from windows, for `get_audit_log`
+    "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        window TEXT NOT NULL,
+        action TEXT NOT NULL,
+        command TEXT NOT NULL,
+        target TEXT,
+        allowed INTEGER NOT NULL
+    );
+    CREATE INDEX audit_log_at ON audit_log (at);",
+    // Local usage statistics for `get_usage_stats`, grouped by local date
+    "CREATE TABLE usage_sessions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        day TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL
+    );
+    CREATE TABLE usage_features (
+        day TEXT NOT NULL,
+        command TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (day, command)
+    );
+    CREATE TABLE usage_transfers (
+        day TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        files INTEGER NOT NULL,
+        bytes INTEGER NOT NULL,
+        PRIMARY KEY (day, direction)
+    );",
+    // Where to find an upload's link in the server's response, and the link
+    "ALTER TABLE transfers ADD COLUMN link_pointer TEXT;
+    ALTER TABLE transfers ADD COLUMN link TEXT;",
+    // Notifications the app has shown, for `get_notification_history`
+    "CREATE TABLE notification_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        body TEXT,
+        href TEXT,
+        category TEXT,
+        read INTEGER NOT NULL DEFAULT 0
+    );",
+];
+
+/// SQLite database for native state, stored in the app data directory
+pub struct Database(Mutex<Connection>);
+
+impl Database {
+    pub fn open(app: &AppHandle) -> Result<Self, String> {
+        let dir = profiles::data_dir(app)?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let mut conn = Connection::open(dir.join(DATABASE_NAME)).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| e.to_string())?;
+        migrate(&mut conn)?;
+
+        Ok(Self(Mutex::new(conn)))
+    }
+
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Seconds since the Unix epoch, as timestamps are stored
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Point stored file paths under `from` at `to` instead, after the files were moved
+pub fn rewrite_paths(conn: &Connection, from: &Path, to: &Path) -> Result<(), String> {
+    let from = format!("{}{}", from.to_string_lossy(), MAIN_SEPARATOR);
+    let to = format!("{}{}", to.to_string_lossy(), MAIN_SEPARATOR);
+    for table in ["synced_files", "folder_pair_files", "transfers"] {
+        conn.execute(
+            &format!(
+                "UPDATE {} SET path = ?2 || substr(path, length(?1) + 1)
+                 WHERE substr(path, 1, length(?1)) = ?1",
+                table
+            ),
+            params![from, to],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration).map_err(|e| e.to_string())?;
+        tx.pragma_update(None, "user_version", index + 1)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
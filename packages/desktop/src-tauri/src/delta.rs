@@ -0,0 +1,164 @@
+//! Binary-diff update packages
+//!
+//! Each full package Apollo installs is kept in the cache directory. When the
+//! update manifest lists a patch from the running version, only the patch is
+//! downloaded and applied to that copy:
+//!
+//! ```json
+//! "darwin-aarch64": {
+//!   "url": "https://…/Apollo_1.5.0_aarch64.app.tar.gz",
+//!   "signature": "…",
+//!   "deltas": [{ "from": "1.4.0", "url": "https://…/1.4.0-1.5.0.patch.gz" }]
+//! }
+//! ```
+//!
+//! Patches are gzipped `bsdiff` output. The rebuilt package must match the
+//! full package's signature, so a bad patch or a stale copy is caught before
+//! install and the full package is downloaded instead.
+
+use base64::Engine;
+use flate2::read::GzDecoder;
+use futures_util::StreamExt;
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::PathBuf;
+use tauri::AppHandle;
+use tauri_plugin_updater::Update;
+
+use crate::data_dir;
+use crate::http;
+
+const PACKAGE_EXTENSION: &str = "pkg";
+
+#[derive(Debug, Deserialize)]
+struct DeltaEntry {
+    from: String,
+    url: String,
+}
+
+fn packages_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(data_dir::cache_dir(app)?.join("updates"))
+}
+
+fn package_path(app: &AppHandle, version: &str) -> Result<PathBuf, String> {
+    Ok(packages_dir(app)?.join(format!("{}.{}", version, PACKAGE_EXTENSION)))
+}
+
+/// Keep a full package as the base for the next patch, replacing older ones
+pub fn keep(app: &AppHandle, version: &str, bytes: &[u8]) {
+    let result = (|| -> Result<(), String> {
+        let dir = packages_dir(app)?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = package_path(app, version)?;
+        std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        for entry in std::fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .flatten()
+        {
+            if entry.path() != path {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        log::warn!("Failed to keep update package for delta updates: {}", e);
+    }
+}
+
+/// The manifest's patch from the running version, for this platform
+///
+/// Static manifests list it under `platforms.<target>`, dynamic servers at
+/// the top level next to `url` and `signature`.
+fn find_delta(update: &Update) -> Option<DeltaEntry> {
+    let platform = update
+        .raw_json
+        .get("platforms")
+        .and_then(|platforms| platforms.get(&update.target))
+        .unwrap_or(&update.raw_json);
+    let deltas: Vec<DeltaEntry> = serde_json::from_value(platform.get("deltas")?.clone()).ok()?;
+    deltas.into_iter().find(|delta| {
+        delta.from.trim_start_matches('v') == update.current_version.trim_start_matches('v')
+    })
+}
+
+fn verify(bytes: &[u8], signature: &str, pubkey: &str) -> Result<(), String> {
+    let decode = |value: &str| -> Result<String, String> {
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(decoded).map_err(|e| e.to_string())
+    };
+    let key = PublicKey::decode(&decode(pubkey)?).map_err(|e| e.to_string())?;
+    let signature = Signature::decode(&decode(signature)?).map_err(|e| e.to_string())?;
+    key.verify(bytes, &signature, true)
+        .map_err(|e| e.to_string())
+}
+
+/// Rebuild the update's full package from a patch, if the manifest has one
+///
+/// Returns `None` when there is no usable patch or it didn't produce the
+/// signed package, so the caller downloads the full one.
+pub async fn download<C: FnMut(usize, Option<u64>)>(
+    app: &AppHandle,
+    update: &Update,
+    pubkey: &str,
+    mut on_chunk: C,
+) -> Option<Vec<u8>> {
+    let delta = find_delta(update)?;
+    let base = std::fs::read(package_path(app, &update.current_version).ok()?).ok()?;
+
+    let result = async {
+        let response = http::client()?
+            .get(&delta.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+        let total = response.content_length();
+        let mut patch = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            on_chunk(chunk.len(), total);
+            patch.extend_from_slice(&chunk);
+        }
+
+        let signature = update.signature.clone();
+        let pubkey = pubkey.to_string();
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(patch.as_slice())
+                .read_to_end(&mut decompressed)
+                .map_err(|e| e.to_string())?;
+            let mut rebuilt = Vec::new();
+            bsdiff::patch(&base, &mut decompressed.as_slice(), &mut rebuilt)
+                .map_err(|e| e.to_string())?;
+            verify(&rebuilt, &signature, &pubkey)?;
+            Ok::<_, String>(rebuilt)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+    .await;
+
+    match result {
+        Ok(rebuilt) => {
+            log::info!(
+                "Rebuilt the {} update package from a delta against {}",
+                update.version,
+                update.current_version
+            );
+            Some(rebuilt)
+        }
+        Err(e) => {
+            log::warn!(
+                "Delta update from {} failed, downloading the full package: {}",
+                update.current_version,
+                e
+            );
+            None
+        }
+    }
+}
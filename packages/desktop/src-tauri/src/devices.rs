@@ -0,0 +1,554 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+use crate::export::{sanitize_component, ExportFailure, ExportProgress};
+use crate::fs_scope;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceProtocol {
+    Mtp,
+    Ptp,
+}
+
+/// A phone or camera connected over MTP/PTP
+#[derive(Debug, Clone, Serialize)]
+pub struct PortableDevice {
+    /// Opaque identifier to pass back to `import_from_device`
+    pub id: String,
+    pub name: String,
+    pub manufacturer: Option<String>,
+    pub protocol: DeviceProtocol,
+}
+
+/// A media file on a device
+#[derive(Debug, Clone)]
+pub struct DeviceFile {
+    /// Platform handle used to read the file (object id or mounted path)
+    pub source: String,
+    /// Location on the device, starting with the storage name
+    pub relative: PathBuf,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeviceImportOptions {
+    /// Skip files already present in the target with the same size
+    pub skip_existing: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeviceImportReport {
+    pub imported: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<ExportFailure>,
+    pub bytes: u64,
+}
+
+/// Windows Portable Devices, which is how phones and cameras appear on Windows
+#[cfg(target_os = "windows")]
+mod win {
+    use super::{DeviceFile, DeviceProtocol, PortableDevice};
+    use crate::import::is_media;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use windows::core::{w, HSTRING, PCWSTR, PWSTR};
+    use windows::Win32::Devices::PortableDevices::*;
+    use windows::Win32::Foundation::{GENERIC_READ, S_OK};
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, IStream,
+        CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, STGM_READ,
+    };
+
+    const MAX_DEPTH: usize = 32;
+    const BATCH: usize = 64;
+
+    /// COM initialisation for the current thread, undone on drop
+    struct Com(bool);
+
+    impl Com {
+        fn init() -> Self {
+            Self(unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok())
+        }
+    }
+
+    impl Drop for Com {
+        fn drop(&mut self) {
+            if self.0 {
+                unsafe { CoUninitialize() };
+            }
+        }
+    }
+
+    /// Convert and free a COM-allocated string
+    unsafe fn take(value: PWSTR) -> String {
+        if value.is_null() {
+            return String::new();
+        }
+        let text = unsafe { value.to_string() }.unwrap_or_default();
+        unsafe { CoTaskMemFree(Some(value.0 as _)) };
+        text
+    }
+
+    /// Call a manager getter twice: once for the length, then into a buffer
+    fn manager_string(
+        get: impl Fn(PWSTR, *mut u32) -> windows::core::Result<()>,
+    ) -> Option<String> {
+        let mut len = 0u32;
+        get(PWSTR::null(), &mut len).ok()?;
+        if len == 0 {
+            return None;
+        }
+        let mut buffer = vec![0u16; len as usize];
+        get(PWSTR(buffer.as_mut_ptr()), &mut len).ok()?;
+        let text = String::from_utf16_lossy(&buffer);
+        let text = text.trim_end_matches('\0').trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn open_device(id: &HSTRING) -> windows::core::Result<IPortableDevice> {
+        unsafe {
+            let client: IPortableDeviceValues =
+                CoCreateInstance(&PortableDeviceValues, None, CLSCTX_INPROC_SERVER)?;
+            client.SetStringValue(&WPD_CLIENT_NAME, w!("Apollo"))?;
+            client.SetUnsignedIntegerValue(&WPD_CLIENT_DESIRED_ACCESS, GENERIC_READ.0)?;
+
+            let device: IPortableDevice =
+                CoCreateInstance(&PortableDeviceFTM, None, CLSCTX_INPROC_SERVER)?;
+            device.Open(id, &client)?;
+            Ok(device)
+        }
+    }
+
+    /// Protocol string of an open device, e.g. "MTP: 1.00"
+    fn protocol(device: &IPortableDevice) -> Option<String> {
+        unsafe {
+            let values = device
+                .Content()
+                .ok()?
+                .Properties()
+                .ok()?
+                .GetValues(WPD_DEVICE_OBJECT_ID, None)
+                .ok()?;
+            values
+                .GetStringValue(&WPD_DEVICE_PROTOCOL)
+                .ok()
+                .map(|p| take(p))
+        }
+    }
+
+    pub fn list() -> Result<Vec<PortableDevice>, String> {
+        let _com = Com::init();
+        let mut devices = Vec::new();
+
+        unsafe {
+            let manager: IPortableDeviceManager =
+                CoCreateInstance(&PortableDeviceManager, None, CLSCTX_INPROC_SERVER)
+                    .map_err(|e| e.to_string())?;
+            let mut count = 0u32;
+            manager
+                .GetDevices(std::ptr::null_mut(), &mut count)
+                .map_err(|e| e.to_string())?;
+            let mut ids = vec![PWSTR::null(); count as usize];
+            manager
+                .GetDevices(ids.as_mut_ptr(), &mut count)
+                .map_err(|e| e.to_string())?;
+            ids.truncate(count as usize);
+
+            for id in ids.into_iter().map(|id| take(id)) {
+                let handle = HSTRING::from(id.as_str());
+                let protocol = open_device(&handle).ok().and_then(|d| {
+                    let protocol = protocol(&d);
+                    let _ = d.Close();
+                    protocol
+                });
+                let protocol = match protocol.as_deref() {
+                    Some(p) if p.starts_with("PTP") => DeviceProtocol::Ptp,
+                    // Mass storage devices already mount as drives
+                    Some(p) if p.starts_with("MSC") => continue,
+                    _ => DeviceProtocol::Mtp,
+                };
+
+                let name =
+                    manager_string(|buf, len| manager.GetDeviceFriendlyName(&handle, buf, len))
+                        .unwrap_or_else(|| id.clone());
+                let manufacturer =
+                    manager_string(|buf, len| manager.GetDeviceManufacturer(&handle, buf, len));
+
+                devices.push(PortableDevice {
+                    id,
+                    name,
+                    manufacturer,
+                    protocol,
+                });
+            }
+        }
+
+        Ok(devices)
+    }
+
+    pub struct Session {
+        device: IPortableDevice,
+        content: IPortableDeviceContent,
+        // Declared last so COM outlives the interfaces above when dropped
+        _com: Com,
+    }
+
+    impl Session {
+        pub fn open(id: &str) -> Result<Self, String> {
+            let com = Com::init();
+            let device = open_device(&HSTRING::from(id)).map_err(|e| e.to_string())?;
+            let content = unsafe { device.Content() }.map_err(|e| e.to_string())?;
+            Ok(Self {
+                device,
+                content,
+                _com: com,
+            })
+        }
+
+        pub fn files(&self) -> Result<Vec<DeviceFile>, String> {
+            let mut files = Vec::new();
+            unsafe {
+                let properties = self.content.Properties().map_err(|e| e.to_string())?;
+                let keys: IPortableDeviceKeyCollection =
+                    CoCreateInstance(&PortableDeviceKeyCollection, None, CLSCTX_INPROC_SERVER)
+                        .map_err(|e| e.to_string())?;
+                for key in [
+                    &WPD_OBJECT_CONTENT_TYPE,
+                    &WPD_OBJECT_NAME,
+                    &WPD_OBJECT_ORIGINAL_FILE_NAME,
+                    &WPD_OBJECT_SIZE,
+                ] {
+                    keys.Add(key).map_err(|e| e.to_string())?;
+                }
+
+                self.walk(
+                    &properties,
+                    &keys,
+                    WPD_DEVICE_OBJECT_ID,
+                    PathBuf::new(),
+                    0,
+                    &mut files,
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Ok(files)
+        }
+
+        unsafe fn walk(
+            &self,
+            properties: &IPortableDeviceProperties,
+            keys: &IPortableDeviceKeyCollection,
+            parent: PCWSTR,
+            path: PathBuf,
+            depth: usize,
+            files: &mut Vec<DeviceFile>,
+        ) -> windows::core::Result<()> {
+            if depth > MAX_DEPTH {
+                return Ok(());
+            }
+            let objects = unsafe { self.content.EnumObjects(0, parent, None) }?;
+
+            loop {
+                let mut batch = [PWSTR::null(); BATCH];
+                let mut fetched = 0u32;
+                let hr = unsafe { objects.Next(&mut batch, &mut fetched) };
+                let ids: Vec<String> = batch[..fetched as usize]
+                    .iter()
+                    .map(|id| unsafe { take(*id) })
+                    .collect();
+
+                for id in ids {
+                    let handle = HSTRING::from(id.as_str());
+                    let Ok(values) = (unsafe { properties.GetValues(&handle, keys) }) else {
+                        continue;
+                    };
+                    let name = unsafe {
+                        values
+                            .GetStringValue(&WPD_OBJECT_ORIGINAL_FILE_NAME)
+                            .or_else(|_| values.GetStringValue(&WPD_OBJECT_NAME))
+                            .map(|n| take(n))
+                            .unwrap_or_default()
+                    };
+                    let kind = unsafe { values.GetGuidValue(&WPD_OBJECT_CONTENT_TYPE) }
+                        .unwrap_or_default();
+
+                    if kind == WPD_CONTENT_TYPE_FOLDER || kind == WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT
+                    {
+                        // Storages and folders; a bad folder shouldn't abort the whole walk
+                        let _ = unsafe {
+                            self.walk(
+                                properties,
+                                keys,
+                                PCWSTR(handle.as_ptr()),
+                                path.join(&name),
+                                depth + 1,
+                                files,
+                            )
+                        };
+                    } else if is_media(Path::new(&name)) {
+                        let size = unsafe { values.GetUnsignedLargeIntegerValue(&WPD_OBJECT_SIZE) }
+                            .unwrap_or_default();
+                        files.push(DeviceFile {
+                            source: id,
+                            relative: path.join(&name),
+                            size,
+                        });
+                    }
+                }
+
+                if hr != S_OK || fetched == 0 {
+                    return Ok(());
+                }
+            }
+        }
+
+        pub fn copy(&self, file: &DeviceFile, target: &Path) -> Result<u64, String> {
+            let stream = unsafe {
+                let resources = self.content.Transfer().map_err(|e| e.to_string())?;
+                let mut optimal = 0u32;
+                let mut stream: Option<IStream> = None;
+                resources
+                    .GetStream(
+                        &HSTRING::from(file.source.as_str()),
+                        &WPD_RESOURCE_DEFAULT,
+                        STGM_READ.0,
+                        &mut optimal,
+                        &mut stream,
+                    )
+                    .map_err(|e| e.to_string())?;
+                stream.ok_or_else(|| "Device returned no data".to_string())?
+            };
+
+            let mut output = std::fs::File::create(target).map_err(|e| e.to_string())?;
+            let mut buffer = vec![0u8; 256 * 1024];
+            let mut written = 0u64;
+            loop {
+                let mut read = 0u32;
+                unsafe {
+                    stream
+                        .Read(
+                            buffer.as_mut_ptr() as _,
+                            buffer.len() as u32,
+                            Some(&mut read),
+                        )
+                        .ok()
+                        .map_err(|e| e.to_string())?;
+                }
+                if read == 0 {
+                    break;
+                }
+                output
+                    .write_all(&buffer[..read as usize])
+                    .map_err(|e| e.to_string())?;
+                written += read as u64;
+            }
+            Ok(written)
+        }
+    }
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            let _ = unsafe { self.device.Close() };
+        }
+    }
+}
+
+/// Devices mounted by GVFS (GNOME and most desktops using gio)
+#[cfg(target_os = "linux")]
+mod gvfs {
+    use super::{DeviceFile, DeviceProtocol, PortableDevice};
+    use crate::import::walk_media;
+    use std::path::{Path, PathBuf};
+
+    fn root() -> PathBuf {
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() })));
+        runtime.join("gvfs")
+    }
+
+    /// `mtp:host=Google_Pixel_8_1A2B3C` -> `Google Pixel 8 1A2B3C`
+    fn display_name(host: &str) -> String {
+        host.replace('_', " ")
+    }
+
+    pub fn list() -> Result<Vec<PortableDevice>, String> {
+        let Ok(entries) = std::fs::read_dir(root()) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(entries
+            .filter_map(|e| e.ok())
+            .filter_map(|entry| {
+                let id = entry.file_name().to_string_lossy().to_string();
+                let (protocol, host) = if let Some(host) = id.strip_prefix("mtp:host=") {
+                    (DeviceProtocol::Mtp, host)
+                } else if let Some(host) = id.strip_prefix("gphoto2:host=") {
+                    (DeviceProtocol::Ptp, host)
+                } else {
+                    return None;
+                };
+                Some(PortableDevice {
+                    name: display_name(host),
+                    manufacturer: None,
+                    protocol,
+                    id,
+                })
+            })
+            .collect())
+    }
+
+    pub struct Session {
+        root: PathBuf,
+    }
+
+    impl Session {
+        pub fn open(id: &str) -> Result<Self, String> {
+            let root = root().join(id);
+            if id.contains('/') || !root.is_dir() {
+                return Err(format!("Device '{}' is not connected", id));
+            }
+            Ok(Self { root })
+        }
+
+        pub fn files(&self) -> Result<Vec<DeviceFile>, String> {
+            let (files, _) = walk_media(&self.root);
+            Ok(files
+                .into_iter()
+                .map(|path| DeviceFile {
+                    relative: path
+                        .strip_prefix(&self.root)
+                        .map(Path::to_path_buf)
+                        .unwrap_or_default(),
+                    size: std::fs::metadata(&path)
+                        .map(|m| m.len())
+                        .unwrap_or_default(),
+                    source: path.to_string_lossy().to_string(),
+                })
+                .collect())
+        }
+
+        pub fn copy(&self, file: &DeviceFile, target: &Path) -> Result<u64, String> {
+            std::fs::copy(&file.source, target).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// macOS exposes cameras through Image Capture rather than MTP
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod unsupported {
+    use super::{DeviceFile, PortableDevice};
+    use std::path::Path;
+
+    pub fn list() -> Result<Vec<PortableDevice>, String> {
+        Ok(Vec::new())
+    }
+
+    pub struct Session;
+
+    impl Session {
+        pub fn open(_id: &str) -> Result<Self, String> {
+            Err("MTP/PTP import is not supported on this platform".to_string())
+        }
+
+        pub fn files(&self) -> Result<Vec<DeviceFile>, String> {
+            Ok(Vec::new())
+        }
+
+        pub fn copy(&self, _file: &DeviceFile, _target: &Path) -> Result<u64, String> {
+            Err("MTP/PTP import is not supported on this platform".to_string())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+use gvfs as platform;
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+use unsupported as platform;
+#[cfg(target_os = "windows")]
+use win as platform;
+
+/// Target path for a device file, keeping its folder structure under `target`
+fn target_path(target: &Path, file: &DeviceFile) -> PathBuf {
+    file.relative
+        .components()
+        .map(|c| sanitize_component(&c.as_os_str().to_string_lossy()))
+        .filter(|c| !c.is_empty())
+        .fold(target.to_path_buf(), |path, c| path.join(c))
+}
+
+/// List phones and cameras connected over MTP/PTP
+#[tauri::command]
+pub async fn list_mtp_devices() -> Result<Vec<PortableDevice>, String> {
+    tauri::async_runtime::spawn_blocking(platform::list)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Copy a device's photos and videos into a local folder
+///
+/// Folder structure on the device is kept under `target`, ready for
+/// `scan_import_folder`. Emits `device-import-progress` after each file.
+#[tauri::command]
+pub async fn import_from_device(
+    app: AppHandle,
+    device_id: String,
+    target: String,
+    options: Option<DeviceImportOptions>,
+) -> Result<DeviceImportReport, String> {
+    let options = options.unwrap_or_default();
+    let root = fs_scope::check(&app, &target)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let session = platform::Session::open(&device_id)?;
+        let files = session.files()?;
+        let total = files.len();
+        let mut report = DeviceImportReport::default();
+
+        for (index, file) in files.iter().enumerate() {
+            let path = target_path(&root, file);
+            let filename = file.relative.to_string_lossy().to_string();
+
+            let existing = std::fs::metadata(&path).map(|m| m.len()).ok();
+            if options.skip_existing && existing == Some(file.size) {
+                report.skipped.push(path.to_string_lossy().to_string());
+            } else {
+                let result = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| session.copy(file, &path));
+                match result {
+                    Ok(bytes) => {
+                        report.bytes += bytes;
+                        report.imported.push(path.to_string_lossy().to_string());
+                    }
+                    Err(error) => {
+                        let _ = std::fs::remove_file(&path);
+                        report.failed.push(ExportFailure {
+                            filename: filename.clone(),
+                            error,
+                        });
+                    }
+                }
+            }
+
+            events::publish(
+                &app,
+                Event::DeviceImportProgress(ExportProgress {
+                    completed: index + 1,
+                    total,
+                    filename,
+                }),
+            );
+        }
+
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
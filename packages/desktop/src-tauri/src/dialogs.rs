@@ -0,0 +1,203 @@
+//! File dialogs through the XDG desktop portal
+//!
+//! Inside Flatpak or Snap the GTK dialog can only see the sandbox, and under Wayland
+//! it ignores the desktop's own file chooser. The portal fixes both: the
+//! desktop shows its native dialog and grants access to whatever is picked.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A named group of file types a picker can be limited to
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileFilter {
+    /// Shown in the dialog, like `Photos and videos`
+    pub name: String,
+    /// Extensions without the dot, like `jpg` or `heic`
+    pub extensions: Vec<String>,
+}
+
+impl FileFilter {
+    /// The extensions with any `.` or `*.` the caller put in front removed
+    pub fn extensions(&self) -> Vec<&str> {
+        self.extensions
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('*').trim_start_matches('.'))
+            .filter(|ext| !ext.is_empty())
+            .collect()
+    }
+}
+
+/// Where the user chose to save a file
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedFile {
+    pub path: String,
+    /// Name of the filter the file's extension belongs to
+    pub filter: Option<String>,
+}
+
+impl SavedFile {
+    /// A saved path with the filter its extension matches
+    ///
+    /// When `fix_extension` is set and the user typed a name without an
+    /// extension, the first filter's first extension is added so the file
+    /// opens with the right app.
+    pub fn new(path: String, filters: &[FileFilter], fix_extension: bool) -> Self {
+        let extension = Path::new(&path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string());
+        let Some(extension) = extension else {
+            let first = filters.iter().find_map(|filter| {
+                let ext = filter.extensions().first()?.to_string();
+                Some((filter.name.clone(), ext))
+            });
+            return match first {
+                Some((filter, ext)) if fix_extension => Self {
+                    path: format!("{}.{}", path, ext),
+                    filter: Some(filter),
+                },
+                _ => Self { path, filter: None },
+            };
+        };
+        let filter = filters
+            .iter()
+            .find(|filter| {
+                filter
+                    .extensions()
+                    .iter()
+                    .any(|ext| ext.eq_ignore_ascii_case(&extension))
+            })
+            .map(|filter| filter.name.clone());
+        Self { path, filter }
+    }
+}
+
+/// Whether file dialogs should go through the portal
+#[cfg(target_os = "linux")]
+fn use_portal() -> bool {
+    crate::sandbox::kind().is_some() || crate::shortcuts::is_wayland()
+}
+
+/// Pick files or folders through the portal
+///
+/// Returns `None` when the portal isn't used or isn't running, so the caller
+/// falls back to the regular dialog.
+pub async fn pick(
+    title: Option<&str>,
+    directory: bool,
+    multiple: bool,
+    filters: &[FileFilter],
+    folder: Option<&Path>,
+) -> Option<Result<Option<Vec<String>>, String>> {
+    #[cfg(target_os = "linux")]
+    if use_portal() {
+        return portal::fallback(portal::pick(title, directory, multiple, filters, folder).await);
+    }
+    let _ = (title, directory, multiple, filters, folder);
+    None
+}
+
+/// Choose where to save a file through the portal
+///
+/// Returns `None` when the portal isn't used or isn't running.
+///
+/// The portal only grants access to the exact path chosen, so it's returned
+/// as is rather than given a missing extension.
+pub async fn save(
+    title: Option<&str>,
+    folder: Option<&Path>,
+    file_name: Option<&str>,
+    filters: &[FileFilter],
+) -> Option<Result<Option<SavedFile>, String>> {
+    #[cfg(target_os = "linux")]
+    if use_portal() {
+        let saved = portal::save(title, folder, file_name, filters)
+            .await
+            .map(|paths| paths.map(|mut paths| SavedFile::new(paths.remove(0), filters, false)));
+        return portal::fallback(saved);
+    }
+    let _ = (title, folder, file_name, filters);
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod portal {
+    use ashpd::desktop::file_chooser::{FileFilter, SelectedFiles};
+    use ashpd::desktop::ResponseError;
+    use std::path::Path;
+
+    /// Treat a cancelled dialog as nothing picked, and a missing portal as unusable
+    pub fn fallback<T>(result: ashpd::Result<Option<T>>) -> Option<Result<Option<T>, String>> {
+        match result {
+            Ok(picked) => Some(Ok(picked)),
+            Err(ashpd::Error::Response(ResponseError::Cancelled)) => Some(Ok(None)),
+            Err(e @ (ashpd::Error::PortalNotFound(_) | ashpd::Error::Zbus(_))) => {
+                log::warn!(
+                    "File chooser portal unavailable, using the GTK dialog: {}",
+                    e
+                );
+                None
+            }
+            Err(e) => Some(Err(e.to_string())),
+        }
+    }
+
+    /// Local paths of the picked files; the portal only returns `file://` URIs
+    fn paths(files: SelectedFiles) -> Option<Vec<String>> {
+        let paths: Vec<String> = files
+            .uris()
+            .iter()
+            .filter_map(|uri| tauri::Url::parse(uri.as_str()).ok()?.to_file_path().ok())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        Some(paths).filter(|paths| !paths.is_empty())
+    }
+
+    /// A portal filter; globs are case-sensitive, so upper-case ones are added too
+    fn filter(filter: &super::FileFilter) -> FileFilter {
+        filter
+            .extensions()
+            .into_iter()
+            .flat_map(|ext| [ext.to_lowercase(), ext.to_uppercase()])
+            .fold(FileFilter::new(&filter.name), |portal_filter, ext| {
+                portal_filter.glob(&format!("*.{}", ext))
+            })
+    }
+
+    pub async fn pick(
+        title: Option<&str>,
+        directory: bool,
+        multiple: bool,
+        filters: &[super::FileFilter],
+        folder: Option<&Path>,
+    ) -> ashpd::Result<Option<Vec<String>>> {
+        let files = SelectedFiles::open_file()
+            .title(title)
+            .modal(true)
+            .directory(directory)
+            .multiple(multiple)
+            .filters(filters.iter().map(filter))
+            .current_folder::<&Path>(folder)?
+            .send()
+            .await?
+            .response()?;
+        Ok(paths(files))
+    }
+
+    pub async fn save(
+        title: Option<&str>,
+        folder: Option<&Path>,
+        file_name: Option<&str>,
+        filters: &[super::FileFilter],
+    ) -> ashpd::Result<Option<Vec<String>>> {
+        let files = SelectedFiles::save_file()
+            .title(title)
+            .modal(true)
+            .current_folder::<&Path>(folder)?
+            .current_name(file_name)
+            .filters(filters.iter().map(filter))
+            .send()
+            .await?
+            .response()?;
+        Ok(paths(files))
+    }
+}
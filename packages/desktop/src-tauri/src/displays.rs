@@ -0,0 +1,556 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow, Window, WindowEvent,
+};
+
+use crate::menubar;
+use crate::profiles;
+use crate::quick_search;
+
+const WINDOW_PLACEMENTS_KEY: &str = "windowPlacements";
+const DISPLAY_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Display {
+    /// Stable identifier for the session: the OS monitor name, or its index when unnamed
+    pub id: String,
+    pub name: Option<String>,
+    /// Full monitor bounds in physical pixels
+    pub bounds: Bounds,
+    /// Bounds excluding the taskbar/dock/menu bar, in physical pixels
+    pub work_area: Bounds,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+fn same_monitor(a: &Monitor, b: &Monitor) -> bool {
+    a.name() == b.name() && a.position() == b.position() && a.size() == b.size()
+}
+
+/// How a window is placed on its target display
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementMode {
+    /// Keep the window's offset relative to the display origin
+    #[default]
+    Keep,
+    Center,
+    Maximize,
+    Fullscreen,
+}
+
+/// The display a window last lived on and its offset from that display's origin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WindowPlacement {
+    display_id: String,
+    x: i32,
+    y: i32,
+}
+
+/// List all monitors, in the order reported by the OS
+pub fn list_displays(app: &AppHandle) -> Result<Vec<(Display, Monitor)>, String> {
+    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
+    let primary = app.primary_monitor().map_err(|e| e.to_string())?;
+
+    Ok(monitors
+        .into_iter()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let display = Display {
+                id: monitor.name().cloned().unwrap_or_else(|| index.to_string()),
+                name: monitor.name().cloned(),
+                bounds: Bounds {
+                    x: monitor.position().x,
+                    y: monitor.position().y,
+                    width: monitor.size().width,
+                    height: monitor.size().height,
+                },
+                work_area: Bounds {
+                    x: monitor.work_area().position.x,
+                    y: monitor.work_area().position.y,
+                    width: monitor.work_area().size.width,
+                    height: monitor.work_area().size.height,
+                },
+                scale_factor: monitor.scale_factor(),
+                primary: primary.as_ref().is_some_and(|p| same_monitor(p, &monitor)),
+            };
+            (display, monitor)
+        })
+        .collect())
+}
+
+/// Get all connected displays with bounds, scale factor, and primary flag
+#[tauri::command]
+pub async fn get_displays(app: AppHandle) -> Result<Vec<Display>, String> {
+    Ok(list_displays(&app)?.into_iter().map(|(d, _)| d).collect())
+}
+
+fn read_placements(app: &AppHandle) -> HashMap<String, WindowPlacement> {
+    profiles::store(app)
+        .ok()
+        .and_then(|store| store.get(WINDOW_PLACEMENTS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn write_placement(app: &AppHandle, label: &str, placement: WindowPlacement) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    let mut placements = read_placements(app);
+    placements.insert(label.to_string(), placement);
+    store.set(WINDOW_PLACEMENTS_KEY, serde_json::json!(placements));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Find the display that contains the window's center
+fn display_for_window(displays: &[(Display, Monitor)], window: &WebviewWindow) -> Option<Display> {
+    let position = window.outer_position().ok()?;
+    let size = window.outer_size().ok()?;
+    let cx = position.x + size.width as i32 / 2;
+    let cy = position.y + size.height as i32 / 2;
+
+    displays
+        .iter()
+        .find(|(d, _)| {
+            cx >= d.bounds.x
+                && cx < d.bounds.x + d.bounds.width as i32
+                && cy >= d.bounds.y
+                && cy < d.bounds.y + d.bounds.height as i32
+        })
+        .map(|(d, _)| d.clone())
+}
+
+fn record_placement(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    displays: &[(Display, Monitor)],
+) -> Result<(), String> {
+    let Some(display) = display_for_window(displays, window) else {
+        return Ok(());
+    };
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+
+    write_placement(
+        app,
+        window.label(),
+        WindowPlacement {
+            display_id: display.id.clone(),
+            x: position.x - display.bounds.x,
+            y: position.y - display.bounds.y,
+        },
+    )
+}
+
+/// Move a window onto a display's work area, shrinking it to fit if needed
+fn place_window(
+    window: &WebviewWindow,
+    display: &Display,
+    mode: PlacementMode,
+    offset: Option<(i32, i32)>,
+) -> Result<(), String> {
+    let area = &display.work_area;
+
+    if window.is_fullscreen().unwrap_or(false) {
+        window.set_fullscreen(false).map_err(|e| e.to_string())?;
+    }
+    if window.is_maximized().unwrap_or(false) {
+        window.unmaximize().map_err(|e| e.to_string())?;
+    }
+
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let width = size.width.min(area.width);
+    let height = size.height.min(area.height);
+    if width != size.width || height != size.height {
+        window
+            .set_size(PhysicalSize::new(width, height))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let (x, y) = match (mode, offset) {
+        (PlacementMode::Keep, Some((x, y))) => (display.bounds.x + x, display.bounds.y + y),
+        _ => (
+            area.x + (area.width - width) as i32 / 2,
+            area.y + (area.height - height) as i32 / 2,
+        ),
+    };
+
+    // Clamp so the whole window stays inside the work area
+    let x = x.clamp(area.x, area.x + (area.width - width) as i32);
+    let y = y.clamp(area.y, area.y + (area.height - height) as i32);
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())?;
+
+    match mode {
+        PlacementMode::Maximize => window.maximize().map_err(|e| e.to_string()),
+        PlacementMode::Fullscreen => window.set_fullscreen(true).map_err(|e| e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Popups placed next to the cursor or tray icon each time they open, never remembered
+fn is_anchored(label: &str) -> bool {
+    label == quick_search::LABEL || label == menubar::POPOVER_LABEL
+}
+
+/// Put a window back on the display it last lived on, or pull it back
+/// on-screen when that display is gone
+fn restore_window(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    displays: &[(Display, Monitor)],
+) -> Result<(), String> {
+    if is_anchored(window.label()) {
+        return Ok(());
+    }
+    let placements = read_placements(app);
+    let remembered = placements.get(window.label()).and_then(|p| {
+        displays
+            .iter()
+            .find(|(d, _)| d.id == p.display_id)
+            .map(|(d, _)| (d, (p.x, p.y)))
+    });
+
+    if let Some((display, offset)) = remembered {
+        if display_for_window(displays, window).is_some_and(|d| d.id == display.id) {
+            return Ok(());
+        }
+        return place_window(window, display, PlacementMode::Keep, Some(offset));
+    }
+
+    if display_for_window(displays, window).is_some() {
+        return Ok(());
+    }
+
+    let fallback = displays
+        .iter()
+        .find(|(d, _)| d.primary)
+        .or_else(|| displays.first());
+    match fallback {
+        Some((display, _)) => place_window(window, display, PlacementMode::Center, None),
+        None => Ok(()),
+    }
+}
+
+/// Restore every open window to its remembered display
+pub fn restore_windows(app: &AppHandle) {
+    let Ok(displays) = list_displays(app) else {
+        return;
+    };
+
+    for window in app.webview_windows().values() {
+        let _ = restore_window(app, window, &displays);
+    }
+}
+
+/// Poll the monitor layout and re-place windows when displays are added or removed
+pub fn watch_displays(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(DISPLAY_POLL_INTERVAL);
+        let mut last: Option<Vec<String>> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(displays) = list_displays(&app) else {
+                continue;
+            };
+            let layout: Vec<String> = displays
+                .iter()
+                .map(|(d, _)| {
+                    format!(
+                        "{}@{},{}:{}x{}",
+                        d.id, d.bounds.x, d.bounds.y, d.bounds.width, d.bounds.height
+                    )
+                })
+                .collect();
+
+            if last.as_ref().is_some_and(|l| *l != layout) {
+                for window in app.webview_windows().values() {
+                    let _ = restore_window(&app, window, &displays);
+                }
+            }
+            last = Some(layout);
+        }
+    });
+}
+
+/// Remember which display a window is on whenever it moves
+///
+/// While a window's remembered display is disconnected, moves are not
+/// recorded, so the window returns to it once it is plugged back in.
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Moved(_)) || is_anchored(window.label()) {
+        return;
+    }
+
+    let app = window.app_handle();
+    let Some(window) = app.get_webview_window(window.label()) else {
+        return;
+    };
+    let Ok(displays) = list_displays(app) else {
+        return;
+    };
+
+    let placements = read_placements(app);
+    if let Some(placement) = placements.get(window.label()) {
+        if !displays.iter().any(|(d, _)| d.id == placement.display_id) {
+            return;
+        }
+    }
+
+    let _ = record_placement(app, &window, &displays);
+}
+
+/// Move a window to a display and remember it there
+#[tauri::command]
+pub async fn move_window_to_display(
+    app: AppHandle,
+    label: String,
+    display_id: String,
+    mode: Option<PlacementMode>,
+) -> Result<(), String> {
+    let window = crate::window::get_window(&app, &label)?;
+    let displays = list_displays(&app)?;
+    let (display, _) = displays
+        .iter()
+        .find(|(d, _)| d.id == display_id)
+        .ok_or_else(|| format!("Display '{}' not found", display_id))?;
+
+    let offset = window.current_monitor().ok().flatten().and_then(|current| {
+        let position = window.outer_position().ok()?;
+        Some((
+            position.x - current.position().x,
+            position.y - current.position().y,
+        ))
+    });
+
+    place_window(&window, display, mode.unwrap_or_default(), offset)?;
+    record_placement(&app, &window, &displays)
+}
+
+/// Color capabilities of a display; fields are `None` where the OS does not report them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DisplayColorInfo {
+    pub display_id: String,
+    pub hdr_supported: Option<bool>,
+    pub hdr_enabled: Option<bool>,
+    pub bits_per_color: Option<u32>,
+    pub icc_profile: Option<String>,
+}
+
+/// Get HDR capability, color depth, and ICC profile for a display
+/// (the primary display when no id is given)
+#[tauri::command]
+pub async fn get_display_color_info(
+    app: AppHandle,
+    display_id: Option<String>,
+) -> Result<DisplayColorInfo, String> {
+    let displays = list_displays(&app)?;
+    let (display, _) = match &display_id {
+        Some(id) => displays.iter().find(|(d, _)| d.id == *id),
+        None => displays
+            .iter()
+            .find(|(d, _)| d.primary)
+            .or_else(|| displays.first()),
+    }
+    .ok_or_else(|| "Display not found".to_string())?;
+
+    let mut info = DisplayColorInfo {
+        display_id: display.id.clone(),
+        ..Default::default()
+    };
+
+    #[cfg(target_os = "windows")]
+    if let Some(name) = &display.name {
+        win::fill_color_info(name, &mut info);
+    }
+
+    #[cfg(target_os = "macos")]
+    if let Some(name) = &display.name {
+        macos::fill_color_info(name, &mut info);
+    }
+
+    Ok(info)
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    use super::DisplayColorInfo;
+    use std::path::Path;
+    use windows::core::{HSTRING, PWSTR};
+    use windows::Win32::Devices::Display::{
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+        DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+        DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO,
+        DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_SOURCE_DEVICE_NAME,
+        QDC_ONLY_ACTIVE_PATHS,
+    };
+    use windows::Win32::Foundation::ERROR_SUCCESS;
+    use windows::Win32::Graphics::Gdi::{CreateDCW, DeleteDC};
+    use windows::Win32::UI::ColorSystem::GetICMProfileW;
+
+    fn wide_to_string(wide: &[u16]) -> String {
+        let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+        String::from_utf16_lossy(&wide[..len])
+    }
+
+    /// `device` is the GDI device name (e.g. `\\.\DISPLAY1`), which is also
+    /// what the monitor reports as its name on Windows
+    pub fn fill_color_info(device: &str, info: &mut DisplayColorInfo) {
+        unsafe {
+            fill_advanced_color(device, info);
+            info.icc_profile = icc_profile(device);
+        }
+    }
+
+    unsafe fn fill_advanced_color(device: &str, info: &mut DisplayColorInfo) {
+        let mut path_count = 0;
+        let mut mode_count = 0;
+        if GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+            != ERROR_SUCCESS
+        {
+            return;
+        }
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+        if QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        ) != ERROR_SUCCESS
+        {
+            return;
+        }
+
+        for path in paths.iter().take(path_count as usize) {
+            let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME::default();
+            source.header.r#type = DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME;
+            source.header.size = std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32;
+            source.header.adapterId = path.sourceInfo.adapterId;
+            source.header.id = path.sourceInfo.id;
+            if DisplayConfigGetDeviceInfo(&mut source.header) != 0
+                || wide_to_string(&source.viewGdiDeviceName) != device
+            {
+                continue;
+            }
+
+            let mut color = DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO::default();
+            color.header.r#type = DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO;
+            color.header.size = std::mem::size_of::<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO>() as u32;
+            color.header.adapterId = path.targetInfo.adapterId;
+            color.header.id = path.targetInfo.id;
+            if DisplayConfigGetDeviceInfo(&mut color.header) != 0 {
+                return;
+            }
+
+            // Bit 0: advancedColorSupported, bit 1: advancedColorEnabled
+            let flags = color.Anonymous.value;
+            info.hdr_supported = Some(flags & 0x1 != 0);
+            info.hdr_enabled = Some(flags & 0x2 != 0);
+            info.bits_per_color = Some(color.bitsPerColorChannel);
+            return;
+        }
+    }
+
+    unsafe fn icc_profile(device: &str) -> Option<String> {
+        let hdc = CreateDCW(
+            &HSTRING::from("DISPLAY"),
+            &HSTRING::from(device),
+            None,
+            None,
+        );
+        if hdc.is_invalid() {
+            return None;
+        }
+
+        let mut buffer = [0u16; 260];
+        let mut len = buffer.len() as u32;
+        let ok = GetICMProfileW(hdc, &mut len, Some(PWSTR(buffer.as_mut_ptr()))).as_bool();
+        let _ = DeleteDC(hdc);
+        if !ok {
+            return None;
+        }
+
+        let path = wide_to_string(&buffer);
+        Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::DisplayColorInfo;
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+
+    unsafe fn to_string(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let ptr = NSString::UTF8String(value);
+        if ptr.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
+    }
+
+    /// Screens are matched by their localized name, which is what the
+    /// monitor reports as its name on macOS
+    pub fn fill_color_info(name: &str, info: &mut DisplayColorInfo) {
+        unsafe {
+            let screens: id = msg_send![class!(NSScreen), screens];
+            let count: usize = msg_send![screens, count];
+
+            for i in 0..count {
+                let screen: id = msg_send![screens, objectAtIndex: i];
+                let localized: id = msg_send![screen, localizedName];
+                if to_string(localized).as_deref() != Some(name) {
+                    continue;
+                }
+
+                // Values above 1.0 mean the screen can show extended dynamic range
+                let potential: f64 = msg_send![
+                    screen,
+                    maximumPotentialExtendedDynamicRangeColorComponentValue
+                ];
+                let current: f64 =
+                    msg_send![screen, maximumExtendedDynamicRangeColorComponentValue];
+                info.hdr_supported = Some(potential > 1.0);
+                info.hdr_enabled = Some(current > 1.0);
+
+                let depth: i32 = msg_send![screen, depth];
+                let bits = NSBitsPerSampleFromDepth(depth);
+                info.bits_per_color = u32::try_from(bits).ok().filter(|b| *b > 0);
+
+                let space: id = msg_send![screen, colorSpace];
+                if space != nil {
+                    let space_name: id = msg_send![space, localizedName];
+                    info.icc_profile = to_string(space_name);
+                }
+                return;
+            }
+        }
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {
+        fn NSBitsPerSampleFromDepth(depth: i32) -> i64;
+    }
+}
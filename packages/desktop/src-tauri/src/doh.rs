@@ -0,0 +1,329 @@
+//! DNS over HTTPS for native requests
+//!
+//! When enabled, host names are looked up with RFC 8484 queries to the
+//! configured provider instead of the network's DNS server, which may block or
+//! rewrite answers for self-hosted domains. Names the provider can't know are
+//! still left to the system: `.local` and single-label names, and names the
+//! provider has no addresses for, such as ones only the home router serves. So
+//! is everything when the provider can't be reached, rather than failing every
+//! request.
+//!
+//! Webviews keep using the system resolver.
+
+use base64::Engine;
+use futures_util::future::join;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::hosts;
+use crate::http;
+use crate::profiles;
+
+const DOH_SETTINGS_KEY: &str = "dnsOverHttps";
+const DEFAULT_PROVIDER: &str = "https://cloudflare-dns.com/dns-query";
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Answers are cached for their TTL, but at least this long
+const MIN_TTL: Duration = Duration::from_secs(30);
+const MAX_CACHED_NAMES: usize = 512;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Settings in effect, read on every lookup
+static SETTINGS: Mutex<Option<DohSettings>> = Mutex::new(None);
+/// Plain client for talking to the provider; its own lookups use the system
+static CLIENT: Mutex<Option<reqwest::Client>> = Mutex::new(None);
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+/// Addresses by lower-cased name, with when they expire
+type Cache = HashMap<String, (Vec<IpAddr>, Instant)>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DohSettings {
+    pub enabled: bool,
+    /// RFC 8484 endpoint, like `https://dns.google/dns-query` or
+    /// `https://dns.quad9.net/dns-query`
+    pub provider: String,
+}
+
+impl Default for DohSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: DEFAULT_PROVIDER.to_string(),
+        }
+    }
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<DohSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(DOH_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(DohSettings::default()),
+    }
+}
+
+/// Load the saved settings so lookups don't read the store
+pub fn load(app: &AppHandle) {
+    match read_settings(app) {
+        Ok(settings) => *SETTINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(settings),
+        Err(e) => log::warn!("Failed to load DNS over HTTPS settings: {}", e),
+    }
+    *CACHE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn provider() -> Option<String> {
+    SETTINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .filter(|settings| settings.enabled)
+        .map(|settings| settings.provider.clone())
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    let mut cached = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = cached.as_ref() {
+        return Ok(client.clone());
+    }
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let client = reqwest::Client::builder()
+        .timeout(QUERY_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    *cached = Some(client.clone());
+    Ok(client)
+}
+
+/// A DNS query for one name and record type, in wire format
+fn encode_query(name: &str, record_type: u16) -> Result<Vec<u8>, String> {
+    // ID 0 so responses cache well over HTTP; recursion desired; one question
+    let mut message = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("'{}' is not a valid host name", name));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.to_be_bytes());
+    // Class IN
+    message.extend_from_slice(&1u16.to_be_bytes());
+    Ok(message)
+}
+
+/// Position just past a possibly compressed name
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        pos += 1;
+        if len == 0 {
+            return Some(pos);
+        }
+        pos += len as usize;
+    }
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(pos)?,
+        *message.get(pos + 1)?,
+    ]))
+}
+
+/// Addresses and their TTLs from a response; CNAMEs along the way are skipped
+fn decode_response(message: &[u8]) -> Result<Vec<(IpAddr, u32)>, String> {
+    let malformed = || "Malformed DNS response".to_string();
+    let rcode = message.get(3).ok_or_else(malformed)? & 0x0f;
+    if rcode == RCODE_NXDOMAIN {
+        return Ok(Vec::new());
+    }
+    if rcode != 0 {
+        return Err(format!("DNS server answered with error code {}", rcode));
+    }
+    let questions = read_u16(message, 4).ok_or_else(malformed)?;
+    let answers = read_u16(message, 6).ok_or_else(malformed)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos).ok_or_else(malformed)? + 4;
+    }
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(message, pos).ok_or_else(malformed)?;
+        let record_type = read_u16(message, pos).ok_or_else(malformed)?;
+        let ttl = message
+            .get(pos + 4..pos + 8)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(malformed)?;
+        let len = read_u16(message, pos + 8).ok_or_else(malformed)? as usize;
+        pos += 10;
+        let data = message.get(pos..pos + len).ok_or_else(malformed)?;
+        match (record_type, len) {
+            (TYPE_A, 4) => {
+                let octets: [u8; 4] = data.try_into().map_err(|_| malformed())?;
+                addresses.push((IpAddr::V4(Ipv4Addr::from(octets)), ttl));
+            }
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().map_err(|_| malformed())?;
+                addresses.push((IpAddr::V6(Ipv6Addr::from(octets)), ttl));
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+    Ok(addresses)
+}
+
+async fn query(provider: &str, name: &str, record_type: u16) -> Result<Vec<(IpAddr, u32)>, String> {
+    let encoded =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(encode_query(name, record_type)?);
+    let mut url = reqwest::Url::parse(provider).map_err(|e| e.to_string())?;
+    url.query_pairs_mut().append_pair("dns", &encoded);
+    let body = client()?
+        .get(url)
+        .header("Accept", "application/dns-message")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    decode_response(&body)
+}
+
+/// Look a name up over HTTPS, from the cache while its answers are fresh
+async fn resolve_doh(provider: &str, name: &str) -> Result<Vec<IpAddr>, String> {
+    let key = name.to_ascii_lowercase();
+    if let Some((addresses, expires)) = CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|cache| cache.get(&key).cloned())
+    {
+        if expires > Instant::now() {
+            return Ok(addresses);
+        }
+    }
+
+    let (v4, v6) = join(
+        query(provider, name, TYPE_A),
+        query(provider, name, TYPE_AAAA),
+    )
+    .await;
+    let mut answers = v4?;
+    answers.extend(v6.unwrap_or_default());
+    let ttl = answers
+        .iter()
+        .map(|(_, ttl)| Duration::from_secs(*ttl as u64))
+        .min()
+        .unwrap_or_default()
+        .max(MIN_TTL);
+    let addresses: Vec<IpAddr> = answers.into_iter().map(|(ip, _)| ip).collect();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if cache.len() >= MAX_CACHED_NAMES {
+        let now = Instant::now();
+        cache.retain(|_, (_, expires)| *expires > now);
+    }
+    if cache.len() < MAX_CACHED_NAMES {
+        cache.insert(key, (addresses.clone(), Instant::now() + ttl));
+    }
+    Ok(addresses)
+}
+
+async fn resolve_system(name: &str) -> Result<Vec<IpAddr>, String> {
+    tokio::net::lookup_host((name, 0))
+        .await
+        .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// Whether only the local network can answer for a name
+fn is_local_name(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    !name.contains('.') || name.ends_with(".local")
+}
+
+async fn lookup(name: &str) -> Result<Vec<IpAddr>, String> {
+    if let Some(addresses) = hosts::lookup(name) {
+        return Ok(addresses);
+    }
+    let Some(provider) = provider().filter(|_| !is_local_name(name)) else {
+        return resolve_system(name).await;
+    };
+    match resolve_doh(&provider, name).await {
+        Ok(addresses) if !addresses.is_empty() => Ok(addresses),
+        Ok(_) => resolve_system(name).await,
+        Err(e) => {
+            log::warn!(
+                "DNS over HTTPS lookup of {} failed, using the system resolver: {}",
+                name,
+                e
+            );
+            resolve_system(name).await
+        }
+    }
+}
+
+/// The shared client's resolver
+///
+/// Host overrides come first, then DNS over HTTPS when it's on, then the system.
+pub struct Resolver;
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let name = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses = lookup(&name).await?;
+            let addrs: Addrs = Box::new(
+                addresses
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, 0))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            );
+            Ok(addrs)
+        })
+    }
+}
+
+/// Get the DNS over HTTPS settings
+#[tauri::command]
+pub async fn get_doh_settings(app: AppHandle) -> Result<DohSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the DNS over HTTPS settings
+///
+/// Pooled connections are dropped so the next request looks its server up
+/// the new way.
+#[tauri::command]
+pub async fn set_doh_settings(app: AppHandle, settings: DohSettings) -> Result<(), String> {
+    let url = reqwest::Url::parse(&settings.provider)
+        .map_err(|_| format!("'{}' is not a valid URL", settings.provider))?;
+    if url.scheme() != "https" {
+        return Err("The DNS over HTTPS provider must use https".to_string());
+    }
+
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(DOH_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    load(&app);
+    http::reset();
+    Ok(())
+}
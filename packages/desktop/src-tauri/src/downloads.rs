@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::dialogs;
+use crate::fs_scope;
+use crate::profiles;
+
+const DOWNLOADS_DIR_KEY: &str = "downloadsDir";
+
+/// The folder chosen with `set_downloads_dir`, if any
+pub fn saved_dir(app: &AppHandle) -> Option<PathBuf> {
+    profiles::store(app)
+        .ok()?
+        .get(DOWNLOADS_DIR_KEY)?
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Where downloads and saved files go by default: the chosen folder, or the
+/// system's Downloads folder
+pub fn dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match saved_dir(app) {
+        Some(dir) => Ok(dir),
+        None => app.path().download_dir().map_err(|e| e.to_string()),
+    }
+}
+
+/// A download destination, with bare file names placed in the downloads folder
+pub fn resolve(app: &AppHandle, path: &str) -> Result<String, String> {
+    if Path::new(path).is_absolute() {
+        return Ok(path.to_string());
+    }
+    Ok(dir(app)?.join(path).to_string_lossy().to_string())
+}
+
+/// Save the downloads folder, which becomes a root of the file access scope
+fn save_dir(app: &AppHandle, dir: Option<&Path>) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match dir {
+        Some(dir) => {
+            if !dir.is_dir() {
+                return Err(format!("'{}' is not a folder", dir.display()));
+            }
+            let dir = fs_scope::check_root(app, dir)?;
+            store.set(DOWNLOADS_DIR_KEY, serde_json::json!(dir.to_string_lossy()));
+        }
+        None => {
+            store.delete(DOWNLOADS_DIR_KEY);
+        }
+    }
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Get the folder downloads and saved files go to by default
+#[tauri::command]
+pub async fn get_downloads_dir(app: AppHandle) -> Result<String, String> {
+    Ok(dir(&app)?.to_string_lossy().to_string())
+}
+
+/// Set the downloads folder, or go back to the system's with `None`
+///
+/// The folder must already be inside the file access scope; use
+/// `pick_downloads_dir` to choose one outside it.
+#[tauri::command]
+pub async fn set_downloads_dir(app: AppHandle, path: Option<String>) -> Result<(), String> {
+    let dir = path.map(|path| fs_scope::check(&app, path)).transpose()?;
+    save_dir(&app, dir.as_deref())
+}
+
+/// Choose the downloads folder with a folder picker, returning it unless cancelled
+#[tauri::command]
+pub async fn pick_downloads_dir(app: AppHandle) -> Result<Option<String>, String> {
+    let title = "Choose a downloads folder";
+    let folder = dir(&app)?;
+    let picked = match dialogs::pick(Some(title), true, false, &[], Some(&folder)).await {
+        Some(picked) => picked?.and_then(|mut paths| paths.pop()),
+        None => app
+            .dialog()
+            .file()
+            .set_title(title)
+            .set_directory(folder)
+            .blocking_pick_folder()
+            .and_then(|path| path.into_path().ok())
+            .map(|path| path.to_string_lossy().to_string()),
+    };
+    if let Some(path) = &picked {
+        save_dir(&app, Some(Path::new(path)))?;
+    }
+    Ok(picked)
+}
+
+/// Open the downloads folder in the file manager
+#[tauri::command]
+pub async fn open_downloads_folder(app: AppHandle) -> Result<(), String> {
+    let dir = dir(&app)?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    open::that(&dir).map_err(|e| e.to_string())
+}
@@ -0,0 +1,823 @@
+//! Dragging photos out of the app into other apps
+//!
+//! The webview can only drag data it already has, so the frontend calls
+//! `start_drag` from `dragstart` instead. On macOS and Windows the drag
+//! carries promised files, downloaded only once a drop target asks for them:
+//! an `NSFilePromiseProvider` writes each original straight into the folder
+//! it was dropped on, and a delay-rendered `CF_HDROP` downloads them into a
+//! drag folder in the cache. Linux file managers don't take promised files,
+//! so there the originals are downloaded into the drag folder before the drag
+//! starts. Apps like mail clients read the file after the drop, so the folder
+//! is only emptied on the next launch.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(not(target_os = "macos"))]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, WebviewWindow};
+
+#[cfg(not(target_os = "macos"))]
+use crate::export::{self, sanitize_component};
+use crate::http;
+use crate::profiles;
+
+const DRAG_SUBDIR: &str = "drag";
+/// Shown under the cursor when no preview is given
+const DEFAULT_PREVIEW: &[u8] = include_bytes!("../icons/32x32.png");
+
+/// A remote file to drag
+#[derive(Debug, Clone, Deserialize)]
+pub struct DragFile {
+    /// URL of the original file
+    pub url: String,
+    pub filename: String,
+}
+
+/// Reports whether the drag ended in a drop; only the first call counts
+type Finish = Arc<dyn Fn(Result<bool, String>) + Send + Sync>;
+
+fn drag_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::cache_dir(app)?.join(DRAG_SUBDIR))
+}
+
+/// Remove files left from drags in earlier runs
+pub fn cleanup(app: &AppHandle) {
+    let Ok(dir) = drag_dir(app) else {
+        return;
+    };
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to clear dragged files: {}", e);
+        }
+    }
+}
+
+/// A folder of its own for one drag's files
+#[cfg(not(target_os = "macos"))]
+fn drag_folder(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(drag_dir(app)?.join(chrono::Utc::now().timestamp_millis().to_string()))
+}
+
+/// Download files into folders of their own, so equal names never collide
+#[cfg(not(target_os = "macos"))]
+async fn materialize(
+    dir: &Path,
+    files: &[DragFile],
+    headers: &HashMap<String, String>,
+) -> Result<Vec<PathBuf>, String> {
+    let client = http::client()?;
+    let mut paths = Vec::with_capacity(files.len());
+    for (i, file) in files.iter().enumerate() {
+        let path = dir
+            .join(i.to_string())
+            .join(sanitize_component(&file.filename));
+        export::download_to(&client, &file.url, headers, &path).await?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+async fn preview(url: Option<&str>, headers: &HashMap<String, String>) -> Vec<u8> {
+    let Some(url) = url else {
+        return DEFAULT_PREVIEW.to_vec();
+    };
+    let fetched = async {
+        let request = http::with_headers(http::client()?.get(url), url, headers)?;
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?;
+        response.bytes().await.map_err(|e| e.to_string())
+    };
+    match fetched.await {
+        Ok(bytes) => bytes.to_vec(),
+        Err(e) => {
+            log::warn!("Failed to load the drag preview: {}", e);
+            DEFAULT_PREVIEW.to_vec()
+        }
+    }
+}
+
+/// Drag files out of the window, returning whether they were dropped
+///
+/// Call it from the webview's `dragstart` while the mouse button is still
+/// down. On Linux the files are downloaded before the drag begins, so there a
+/// large original, such as a long video, holds the drag back until it has
+/// fully downloaded.
+#[tauri::command]
+pub async fn start_drag(
+    app: AppHandle,
+    window: WebviewWindow,
+    files: Vec<DragFile>,
+    preview_url: Option<String>,
+    headers: Option<HashMap<String, String>>,
+) -> Result<bool, String> {
+    if files.is_empty() {
+        return Err("Nothing to drag".to_string());
+    }
+    let headers = headers.unwrap_or_default();
+    let image = preview(preview_url.as_deref(), &headers).await;
+    let drag = os::prepare(&app, files, headers).await?;
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<bool, String>>();
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let finish: Finish = Arc::new(move |result| {
+        if let Some(tx) = tx.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = tx.send(result);
+        }
+    });
+
+    // Drags have to start on the main thread, which runs the system's drag loop
+    let target = window.clone();
+    window
+        .run_on_main_thread(move || {
+            if let Err(e) = os::start_drag(&target, drag, image, finish.clone()) {
+                finish(Err(e));
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+    rx.await
+        .map_err(|_| "The drag ended without a result".to_string())?
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use super::{DragFile, Finish};
+    use block::Block;
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSPoint, NSRect, NSSize, NSString};
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Protocol, Sel};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::collections::HashMap;
+    use std::ffi::{c_void, CStr};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Once, OnceLock};
+    use tauri::{AppHandle, WebviewWindow};
+
+    use crate::export::{self, sanitize_component};
+    use crate::http;
+
+    const PROMISE_CLASS: &str = "ApolloFilePromise";
+    const SOURCE_CLASS: &str = "ApolloDragSource";
+    /// `NSDragOperationCopy`
+    const DRAG_OPERATION_COPY: usize = 1;
+    /// `NSEventTypeLeftMouseDragged`
+    const LEFT_MOUSE_DRAGGED: usize = 6;
+
+    pub struct Drag {
+        files: Vec<DragFile>,
+        headers: HashMap<String, String>,
+    }
+
+    /// One promised file, kept by the delegate of its provider
+    struct Promise {
+        drag: Arc<Drag>,
+        index: usize,
+    }
+
+    pub async fn prepare(
+        _app: &AppHandle,
+        files: Vec<DragFile>,
+        headers: HashMap<String, String>,
+    ) -> Result<Drag, String> {
+        Ok(Drag { files, headers })
+    }
+
+    unsafe fn ns(value: &str) -> id {
+        let value = NSString::alloc(nil).init_str(value);
+        msg_send![value, autorelease]
+    }
+
+    unsafe fn to_string(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let ptr = NSString::UTF8String(value);
+        if ptr.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
+    }
+
+    unsafe fn error(message: &str) -> id {
+        let info: id = msg_send![
+            class!(NSDictionary),
+            dictionaryWithObject: ns(message)
+            forKey: ns("NSLocalizedDescription")
+        ];
+        msg_send![class!(NSError), errorWithDomain: ns("Apollo") code: 1isize userInfo: info]
+    }
+
+    /// The type identifier promised for a file, from its extension
+    fn file_type(filename: &str) -> &'static str {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "jpg" | "jpeg" => "public.jpeg",
+            "png" => "public.png",
+            "heic" => "public.heic",
+            "gif" => "com.compuserve.gif",
+            "tif" | "tiff" => "public.tiff",
+            "mov" => "com.apple.quicktime-movie",
+            "mp4" | "m4v" => "public.mpeg-4",
+            _ => "public.data",
+        }
+    }
+
+    unsafe fn promise(this: &Object) -> &Promise {
+        &*(*this.get_ivar::<*mut c_void>("promise") as *const Promise)
+    }
+
+    extern "C" fn file_name(this: &Object, _: Sel, _provider: id, _file_type: id) -> id {
+        unsafe {
+            let promise = promise(this);
+            ns(&sanitize_component(
+                &promise.drag.files[promise.index].filename,
+            ))
+        }
+    }
+
+    /// Runs on the provider's operation queue, so the download can block it
+    extern "C" fn write_promise(this: &Object, _: Sel, _provider: id, url: id, completion: id) {
+        unsafe {
+            let promise = promise(this);
+            let file = &promise.drag.files[promise.index];
+            let path: id = msg_send![url, path];
+            let written = match to_string(path) {
+                Some(path) => tauri::async_runtime::block_on(async {
+                    let client = http::client()?;
+                    let path = PathBuf::from(path);
+                    export::download_to(&client, &file.url, &promise.drag.headers, &path).await
+                }),
+                None => Err("The drop target gave no file to write".to_string()),
+            };
+            let result = match written {
+                Ok(_) => nil,
+                Err(e) => {
+                    log::warn!("Failed to download dropped {}: {}", file.filename, e);
+                    error(&e)
+                }
+            };
+            let completion = &*(completion as *const Block<(id,), ()>);
+            completion.call((result,));
+        }
+    }
+
+    /// Downloads run on a queue of their own, not the main thread
+    extern "C" fn operation_queue(_this: &Object, _: Sel, _provider: id) -> id {
+        static QUEUE: OnceLock<usize> = OnceLock::new();
+        *QUEUE.get_or_init(|| unsafe {
+            let queue: id = msg_send![class!(NSOperationQueue), new];
+            queue as usize
+        }) as id
+    }
+
+    extern "C" fn dealloc_promise(this: &Object, _: Sel) {
+        unsafe {
+            let promise = *this.get_ivar::<*mut c_void>("promise");
+            if !promise.is_null() {
+                drop(Box::from_raw(promise as *mut Promise));
+            }
+            let _: () = msg_send![super(this, class!(NSObject)), dealloc];
+        }
+    }
+
+    extern "C" fn operation_mask(_this: &Object, _: Sel, _session: id, _context: isize) -> usize {
+        DRAG_OPERATION_COPY
+    }
+
+    extern "C" fn drag_ended(this: &Object, _: Sel, _session: id, _at: NSPoint, operation: usize) {
+        unsafe {
+            let finish = &*(*this.get_ivar::<*mut c_void>("finish") as *const Finish);
+            finish(Ok(operation != 0));
+        }
+    }
+
+    extern "C" fn dealloc_source(this: &Object, _: Sel) {
+        unsafe {
+            let finish = *this.get_ivar::<*mut c_void>("finish");
+            if !finish.is_null() {
+                drop(Box::from_raw(finish as *mut Finish));
+            }
+            let _: () = msg_send![super(this, class!(NSObject)), dealloc];
+        }
+    }
+
+    fn declare(
+        name: &str,
+        protocol: &str,
+        add: impl FnOnce(&mut ClassDecl),
+    ) -> Option<&'static Class> {
+        if let Some(mut decl) = ClassDecl::new(name, class!(NSObject)) {
+            if let Some(protocol) = Protocol::get(protocol) {
+                decl.add_protocol(protocol);
+            }
+            add(&mut decl);
+            decl.register();
+        }
+        Class::get(name)
+    }
+
+    /// The delegate of one `NSFilePromiseProvider`
+    fn promise_class() -> Option<&'static Class> {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            declare(
+                PROMISE_CLASS,
+                "NSFilePromiseProviderDelegate",
+                |decl| unsafe {
+                    decl.add_ivar::<*mut c_void>("promise");
+                    decl.add_method(
+                        sel!(filePromiseProvider:fileNameForType:),
+                        file_name as extern "C" fn(&Object, Sel, id, id) -> id,
+                    );
+                    decl.add_method(
+                        sel!(filePromiseProvider:writePromiseToURL:completionHandler:),
+                        write_promise as extern "C" fn(&Object, Sel, id, id, id),
+                    );
+                    decl.add_method(
+                        sel!(operationQueueForFilePromiseProvider:),
+                        operation_queue as extern "C" fn(&Object, Sel, id) -> id,
+                    );
+                    decl.add_method(
+                        sel!(dealloc),
+                        dealloc_promise as extern "C" fn(&Object, Sel),
+                    );
+                },
+            );
+        });
+        Class::get(PROMISE_CLASS)
+    }
+
+    fn source_class() -> Option<&'static Class> {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            declare(SOURCE_CLASS, "NSDraggingSource", |decl| unsafe {
+                decl.add_ivar::<*mut c_void>("finish");
+                decl.add_method(
+                    sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+                    operation_mask as extern "C" fn(&Object, Sel, id, isize) -> usize,
+                );
+                decl.add_method(
+                    sel!(draggingSession:endedAtPoint:operation:),
+                    drag_ended as extern "C" fn(&Object, Sel, id, NSPoint, usize),
+                );
+                decl.add_method(sel!(dealloc), dealloc_source as extern "C" fn(&Object, Sel));
+            });
+        });
+        Class::get(SOURCE_CLASS)
+    }
+
+    pub fn start_drag(
+        window: &WebviewWindow,
+        drag: Drag,
+        image: Vec<u8>,
+        finish: Finish,
+    ) -> Result<(), String> {
+        let (Some(promise_class), Some(source_class)) = (promise_class(), source_class()) else {
+            return Err("Couldn't set up the drag".to_string());
+        };
+        let ns_window = window.ns_window().map_err(|e| e.to_string())? as id;
+        let drag = Arc::new(drag);
+
+        unsafe {
+            let view: id = msg_send![ns_window, contentView];
+            let location: NSPoint = msg_send![ns_window, mouseLocationOutsideOfEventStream];
+
+            let data: id = msg_send![
+                class!(NSData),
+                dataWithBytes: image.as_ptr() as *const c_void
+                length: image.len()
+            ];
+            let image: id = msg_send![class!(NSImage), alloc];
+            let image: id = msg_send![image, initWithData: data];
+            let size: NSSize = if image == nil {
+                NSSize::new(0.0, 0.0)
+            } else {
+                msg_send![image, size]
+            };
+            let frame = NSRect::new(
+                NSPoint::new(
+                    location.x - size.width / 2.0,
+                    location.y - size.height / 2.0,
+                ),
+                size,
+            );
+
+            let items: id = msg_send![class!(NSMutableArray), array];
+            for (index, file) in drag.files.iter().enumerate() {
+                let delegate: id = msg_send![promise_class, new];
+                let promise = Box::new(Promise {
+                    drag: drag.clone(),
+                    index,
+                });
+                (*delegate)
+                    .set_ivar::<*mut c_void>("promise", Box::into_raw(promise) as *mut c_void);
+
+                let provider: id = msg_send![class!(NSFilePromiseProvider), alloc];
+                let provider: id = msg_send![
+                    provider,
+                    initWithFileType: ns(file_type(&file.filename))
+                    delegate: delegate
+                ];
+                // Providers only hold their delegate weakly, so each one keeps
+                // its own as its user info until the system lets go of it
+                let _: () = msg_send![provider, setUserInfo: delegate];
+                let _: () = msg_send![delegate, release];
+
+                let item: id = msg_send![class!(NSDraggingItem), alloc];
+                let item: id = msg_send![item, initWithPasteboardWriter: provider];
+                let _: () = msg_send![item, setDraggingFrame: frame contents: image];
+                let _: () = msg_send![items, addObject: item];
+                let _: () = msg_send![item, release];
+                let _: () = msg_send![provider, release];
+            }
+            if image != nil {
+                let _: () = msg_send![image, release];
+            }
+
+            let application: id = msg_send![class!(NSApplication), sharedApplication];
+            let current: id = msg_send![application, currentEvent];
+            let timestamp: f64 = if current == nil {
+                0.0
+            } else {
+                msg_send![current, timestamp]
+            };
+            let window_number: isize = msg_send![ns_window, windowNumber];
+            let event: id = msg_send![
+                class!(NSEvent),
+                mouseEventWithType: LEFT_MOUSE_DRAGGED
+                location: location
+                modifierFlags: 0usize
+                timestamp: timestamp
+                windowNumber: window_number
+                context: nil
+                eventNumber: 0isize
+                clickCount: 1isize
+                pressure: 1.0f32
+            ];
+            if event == nil {
+                return Err("Couldn't start the drag".to_string());
+            }
+
+            let source: id = msg_send![source_class, new];
+            (*source)
+                .set_ivar::<*mut c_void>("finish", Box::into_raw(Box::new(finish)) as *mut c_void);
+            let session: id = msg_send![
+                view,
+                beginDraggingSessionWithItems: items
+                event: event
+                source: source
+            ];
+            // The session keeps its source until the drag ends
+            let _: () = msg_send![source, release];
+            if session == nil {
+                return Err("Couldn't start the drag".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use super::{drag_folder, materialize, DragFile, Finish};
+    use std::collections::HashMap;
+    use std::mem::ManuallyDrop;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+    use tauri::{AppHandle, WebviewWindow};
+    use windows::core::{implement, Ref, BOOL, HRESULT};
+    use windows::Win32::Foundation::{
+        COLORREF, DRAGDROP_S_CANCEL, DRAGDROP_S_DROP, DRAGDROP_S_USEDEFAULTCURSORS, DV_E_FORMATETC,
+        E_FAIL, E_NOTIMPL, HGLOBAL, OLE_E_ADVISENOTSUPPORTED, POINT, SIZE, S_OK,
+    };
+    use windows::Win32::Graphics::Gdi::{
+        CreateDIBSection, DeleteObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+    };
+    use windows::Win32::System::Com::{
+        CoCreateInstance, IAdviseSink, IDataObject, IDataObject_Impl, IEnumFORMATETC,
+        IEnumSTATDATA, CLSCTX_INPROC_SERVER, DATADIR_GET, DVASPECT_CONTENT, FORMATETC, STGMEDIUM,
+        STGMEDIUM_0, TYMED_HGLOBAL,
+    };
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use windows::Win32::System::Ole::{
+        DoDragDrop, IDropSource, IDropSource_Impl, CF_HDROP, DROPEFFECT, DROPEFFECT_COPY,
+    };
+    use windows::Win32::System::SystemServices::{MK_LBUTTON, MODIFIERKEYS_FLAGS};
+    use windows::Win32::UI::Shell::{
+        CLSID_DragDropHelper, IDragSourceHelper, SHCreateDataObject, SHCreateStdEnumFmtEtc,
+        DROPFILES, SHDRAGIMAGE,
+    };
+
+    pub struct Drag {
+        dir: PathBuf,
+        files: Vec<DragFile>,
+        headers: HashMap<String, String>,
+    }
+
+    pub async fn prepare(
+        app: &AppHandle,
+        files: Vec<DragFile>,
+        headers: HashMap<String, String>,
+    ) -> Result<Drag, String> {
+        Ok(Drag {
+            dir: drag_folder(app)?,
+            files,
+            headers,
+        })
+    }
+
+    fn hdrop_format() -> FORMATETC {
+        FORMATETC {
+            cfFormat: CF_HDROP.0,
+            ptd: std::ptr::null_mut(),
+            dwAspect: DVASPECT_CONTENT.0,
+            lindex: -1,
+            tymed: TYMED_HGLOBAL.0 as u32,
+        }
+    }
+
+    fn is_hdrop(format: *const FORMATETC) -> bool {
+        unsafe { format.as_ref() }.is_some_and(|format| {
+            format.cfFormat == CF_HDROP.0
+                && format.dwAspect == DVASPECT_CONTENT.0
+                && format.tymed & TYMED_HGLOBAL.0 as u32 != 0
+        })
+    }
+
+    /// A `DROPFILES` list of the paths, owned by whoever receives it
+    fn drop_files(paths: &[PathBuf]) -> windows::core::Result<HGLOBAL> {
+        let mut list: Vec<u16> = Vec::new();
+        for path in paths {
+            list.extend(path.as_os_str().encode_wide());
+            list.push(0);
+        }
+        list.push(0);
+
+        let header = std::mem::size_of::<DROPFILES>();
+        unsafe {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, header + list.len() * 2)?;
+            let pointer = GlobalLock(memory) as *mut u8;
+            if pointer.is_null() {
+                return Err(E_FAIL.into());
+            }
+            pointer.cast::<DROPFILES>().write(DROPFILES {
+                pFiles: header as u32,
+                fWide: true.into(),
+                ..Default::default()
+            });
+            std::ptr::copy_nonoverlapping(
+                list.as_ptr().cast::<u8>(),
+                pointer.add(header),
+                list.len() * 2,
+            );
+            let _ = GlobalUnlock(memory);
+            Ok(memory)
+        }
+    }
+
+    /// The files as a `CF_HDROP` rendered only when a drop target asks for
+    /// it, which downloads them while the target waits
+    #[implement(IDataObject)]
+    struct PromisedFiles {
+        drag: Drag,
+        paths: OnceLock<Result<Vec<PathBuf>, String>>,
+        /// Holds everything else set on the drag, like the shell's drag image
+        shell: IDataObject,
+    }
+
+    impl IDataObject_Impl for PromisedFiles_Impl {
+        fn GetData(&self, format: *const FORMATETC) -> windows::core::Result<STGMEDIUM> {
+            if !is_hdrop(format) {
+                return unsafe { self.shell.GetData(format) };
+            }
+            let paths = self.paths.get_or_init(|| {
+                let drag = &self.drag;
+                tauri::async_runtime::block_on(materialize(&drag.dir, &drag.files, &drag.headers))
+            });
+            match paths {
+                Ok(paths) => Ok(STGMEDIUM {
+                    tymed: TYMED_HGLOBAL.0 as u32,
+                    u: STGMEDIUM_0 {
+                        hGlobal: drop_files(paths)?,
+                    },
+                    pUnkForRelease: ManuallyDrop::new(None),
+                }),
+                Err(e) => {
+                    log::warn!("Failed to download the dropped files: {}", e);
+                    Err(E_FAIL.into())
+                }
+            }
+        }
+
+        fn GetDataHere(
+            &self,
+            _format: *const FORMATETC,
+            _medium: *mut STGMEDIUM,
+        ) -> windows::core::Result<()> {
+            Err(DV_E_FORMATETC.into())
+        }
+
+        fn QueryGetData(&self, format: *const FORMATETC) -> HRESULT {
+            if is_hdrop(format) {
+                S_OK
+            } else {
+                unsafe { self.shell.QueryGetData(format) }
+            }
+        }
+
+        fn GetCanonicalFormatEtc(
+            &self,
+            _format: *const FORMATETC,
+            canonical: *mut FORMATETC,
+        ) -> HRESULT {
+            if let Some(canonical) = unsafe { canonical.as_mut() } {
+                canonical.ptd = std::ptr::null_mut();
+            }
+            E_NOTIMPL
+        }
+
+        fn SetData(
+            &self,
+            format: *const FORMATETC,
+            medium: *const STGMEDIUM,
+            release: BOOL,
+        ) -> windows::core::Result<()> {
+            unsafe { self.shell.SetData(format, medium, release.as_bool()) }
+        }
+
+        fn EnumFormatEtc(&self, direction: u32) -> windows::core::Result<IEnumFORMATETC> {
+            if direction != DATADIR_GET.0 as u32 {
+                return Err(E_NOTIMPL.into());
+            }
+            unsafe { SHCreateStdEnumFmtEtc(&[hdrop_format()]) }
+        }
+
+        fn DAdvise(
+            &self,
+            _format: *const FORMATETC,
+            _advf: u32,
+            _sink: Ref<IAdviseSink>,
+        ) -> windows::core::Result<u32> {
+            Err(OLE_E_ADVISENOTSUPPORTED.into())
+        }
+
+        fn DUnadvise(&self, _connection: u32) -> windows::core::Result<()> {
+            Err(OLE_E_ADVISENOTSUPPORTED.into())
+        }
+
+        fn EnumDAdvise(&self) -> windows::core::Result<IEnumSTATDATA> {
+            Err(OLE_E_ADVISENOTSUPPORTED.into())
+        }
+    }
+
+    #[implement(IDropSource)]
+    struct DropSource;
+
+    impl IDropSource_Impl for DropSource_Impl {
+        fn QueryContinueDrag(&self, escape: BOOL, keys: MODIFIERKEYS_FLAGS) -> HRESULT {
+            if escape.as_bool() {
+                DRAGDROP_S_CANCEL
+            } else if keys & MK_LBUTTON == MODIFIERKEYS_FLAGS(0) {
+                DRAGDROP_S_DROP
+            } else {
+                S_OK
+            }
+        }
+
+        fn GiveFeedback(&self, _effect: DROPEFFECT) -> HRESULT {
+            DRAGDROP_S_USEDEFAULTCURSORS
+        }
+    }
+
+    /// The preview as the bitmap the shell draws under the cursor
+    fn drag_image(bytes: &[u8]) -> Option<SHDRAGIMAGE> {
+        let image = image::load_from_memory(bytes).ok()?.to_rgba8();
+        let (width, height) = (image.width() as i32, image.height() as i32);
+        let info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // Negative for rows from the top down
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bits = std::ptr::null_mut();
+        let bitmap =
+            unsafe { CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0) }.ok()?;
+        // Premultiplied BGRA
+        let pixels = unsafe { std::slice::from_raw_parts_mut(bits as *mut u8, image.len()) };
+        for (out, pixel) in pixels.chunks_exact_mut(4).zip(image.pixels()) {
+            let [r, g, b, a] = pixel.0;
+            let scale = |c: u8| (c as u16 * a as u16 / 255) as u8;
+            out.copy_from_slice(&[scale(b), scale(g), scale(r), a]);
+        }
+        Some(SHDRAGIMAGE {
+            sizeDragImage: SIZE {
+                cx: width,
+                cy: height,
+            },
+            ptOffset: POINT {
+                x: width / 2,
+                y: height / 2,
+            },
+            hbmpDragImage: bitmap,
+            // CLR_NONE, as the alpha channel says what's transparent
+            crColorKey: COLORREF(0xFFFFFFFF),
+        })
+    }
+
+    /// Runs the system's drag loop, returning once the drag ends
+    pub fn start_drag(
+        _window: &WebviewWindow,
+        drag: Drag,
+        image: Vec<u8>,
+        finish: Finish,
+    ) -> Result<(), String> {
+        unsafe {
+            let shell: IDataObject =
+                SHCreateDataObject(None, None, None).map_err(|e| e.to_string())?;
+            let data: IDataObject = PromisedFiles {
+                drag,
+                paths: OnceLock::new(),
+                shell,
+            }
+            .into();
+            let source: IDropSource = DropSource.into();
+
+            if let Some(image) = drag_image(&image) {
+                let shown = CoCreateInstance(&CLSID_DragDropHelper, None, CLSCTX_INPROC_SERVER)
+                    .and_then(|helper: IDragSourceHelper| {
+                        helper.InitializeFromBitmap(&image, &data)
+                    });
+                // The helper only takes ownership of the bitmap when it succeeds
+                if shown.is_err() {
+                    let _ = DeleteObject(image.hbmpDragImage.into());
+                }
+            }
+
+            let mut effect = DROPEFFECT::default();
+            let result = DoDragDrop(&data, &source, DROPEFFECT_COPY, &mut effect);
+            if result.is_err() {
+                return Err(result.message());
+            }
+            finish(Ok(result == DRAGDROP_S_DROP));
+        }
+        Ok(())
+    }
+}
+
+/// Linux file managers don't take promised files, so the originals are
+/// downloaded first and dragged as ordinary files
+#[cfg(target_os = "linux")]
+mod os {
+    use super::{drag_folder, materialize, DragFile, Finish};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use tauri::{AppHandle, WebviewWindow};
+
+    pub type Drag = Vec<PathBuf>;
+
+    pub async fn prepare(
+        app: &AppHandle,
+        files: Vec<DragFile>,
+        headers: HashMap<String, String>,
+    ) -> Result<Drag, String> {
+        materialize(&drag_folder(app)?, &files, &headers).await
+    }
+
+    pub fn start_drag(
+        window: &WebviewWindow,
+        paths: Drag,
+        image: Vec<u8>,
+        finish: Finish,
+    ) -> Result<(), String> {
+        let gtk = window.gtk_window().map_err(|e| e.to_string())?;
+        let on_drop = move |result, _position| {
+            finish(Ok(matches!(result, drag::DragResult::Dropped)));
+        };
+        drag::start_drag(
+            &gtk,
+            drag::DragItem::Files(paths),
+            drag::Image::Raw(image),
+            on_drop,
+            Default::default(),
+        )
+        .map_err(|e| e.to_string())
+    }
+}
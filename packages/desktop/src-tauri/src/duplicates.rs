@@ -0,0 +1,276 @@
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::cache;
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::import::{self, cached_hash, walk_media};
+
+/// Default maximum Hamming distance between two dHashes to call images similar
+const DEFAULT_THRESHOLD: u32 = 5;
+const PERCEPTUAL_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DuplicateOptions {
+    /// Files or folders pending import
+    pub paths: Vec<String>,
+    /// Also scan the offline media cache
+    pub include_cache: bool,
+    /// Group visually similar images using a perceptual hash
+    pub perceptual: bool,
+    pub threshold: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateSource {
+    Import,
+    Cache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKind {
+    Exact,
+    Similar,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateFile {
+    pub path: String,
+    pub size: u64,
+    pub source: DuplicateSource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    /// Shared checksum for exact groups
+    pub checksum: Option<String>,
+    pub files: Vec<DuplicateFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateProgress {
+    pub processed: usize,
+    pub total: usize,
+}
+
+struct Entry {
+    file: DuplicateFile,
+    checksum: String,
+    modified: i64,
+    dhash: Option<u64>,
+}
+
+/// 64-bit difference hash: compares adjacent pixels of a 9x8 grayscale thumbnail
+pub fn dhash(path: &Path) -> Option<u64> {
+    let image = image::open(path).ok()?;
+    let small = image
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    Some(hash)
+}
+
+fn cached_dhash(db: &Database, path: &Path, size: u64, modified: i64) -> Option<u64> {
+    let key = path.to_string_lossy().to_string();
+    let cached: Option<i64> = db
+        .conn()
+        .query_row(
+            "SELECT dhash FROM perceptual_hashes WHERE path = ?1 AND size = ?2 AND modified = ?3",
+            params![key, size as i64, modified],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten();
+    if let Some(hash) = cached {
+        return Some(hash as u64);
+    }
+
+    let hash = dhash(path)?;
+    let _ = db.conn().execute(
+        "INSERT OR REPLACE INTO perceptual_hashes (path, size, modified, dhash) VALUES (?1, ?2, ?3, ?4)",
+        params![key, size as i64, modified, hash as i64],
+    );
+    Some(hash)
+}
+
+fn supports_perceptual(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| PERCEPTUAL_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn collect(paths: &[String]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .map(PathBuf::from)
+        .flat_map(|path| {
+            if path.is_dir() {
+                walk_media(&path).0
+            } else if import::is_media(&path) {
+                vec![path]
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+fn find(root: &mut Vec<usize>, i: usize) -> usize {
+    if root[i] != i {
+        let r = find(root, root[i]);
+        root[i] = r;
+    }
+    root[i]
+}
+
+/// Group files with identical checksums
+fn exact_groups(entries: &[Entry]) -> (Vec<DuplicateGroup>, Vec<usize>) {
+    let mut by_checksum: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        by_checksum.entry(&entry.checksum).or_default().push(index);
+    }
+
+    let mut groups = Vec::new();
+    // One representative per distinct checksum, for perceptual comparison
+    let mut representatives = Vec::new();
+
+    for (checksum, indices) in by_checksum {
+        representatives.push(indices[0]);
+        if indices.len() > 1 {
+            groups.push(DuplicateGroup {
+                kind: DuplicateKind::Exact,
+                checksum: Some(checksum.to_string()),
+                files: indices.iter().map(|&i| entries[i].file.clone()).collect(),
+            });
+        }
+    }
+
+    (groups, representatives)
+}
+
+/// Group distinct files whose perceptual hashes are within `threshold` bits
+fn similar_groups(entries: &[Entry], candidates: &[usize], threshold: u32) -> Vec<DuplicateGroup> {
+    let hashed: Vec<(usize, u64)> = candidates
+        .iter()
+        .filter_map(|&i| entries[i].dhash.map(|h| (i, h)))
+        .collect();
+    let mut root: Vec<usize> = (0..hashed.len()).collect();
+
+    for a in 0..hashed.len() {
+        for b in (a + 1)..hashed.len() {
+            if (hashed[a].1 ^ hashed[b].1).count_ones() <= threshold {
+                let (ra, rb) = (find(&mut root, a), find(&mut root, b));
+                root[ra] = rb;
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, (entry, _)) in hashed.iter().enumerate() {
+        let r = find(&mut root, i);
+        clusters.entry(r).or_default().push(*entry);
+    }
+
+    clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| DuplicateGroup {
+            kind: DuplicateKind::Similar,
+            checksum: None,
+            files: members.iter().map(|&i| entries[i].file.clone()).collect(),
+        })
+        .collect()
+}
+
+/// Find duplicate media across pending imports and the offline cache
+///
+/// Exact duplicates share a SHA-1 checksum; with `perceptual` enabled,
+/// visually similar images are grouped too. Emits `duplicates-progress`
+/// while hashing.
+#[tauri::command]
+pub async fn find_duplicates(
+    app: AppHandle,
+    options: DuplicateOptions,
+) -> Result<Vec<DuplicateGroup>, String> {
+    fs_scope::check_all(&app, &options.paths)?;
+    let cache_dir = match options.include_cache {
+        true => Some(cache::media_dir(&app)?),
+        false => None,
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = app.state::<Database>();
+        let mut files: Vec<(PathBuf, DuplicateSource)> = collect(&options.paths)
+            .into_iter()
+            .map(|p| (p, DuplicateSource::Import))
+            .collect();
+        if let Some(dir) = cache_dir {
+            files.extend(
+                walk_media(&dir)
+                    .0
+                    .into_iter()
+                    .map(|p| (p, DuplicateSource::Cache)),
+            );
+        }
+
+        let total = files.len();
+        let mut entries = Vec::with_capacity(total);
+
+        for (index, (path, source)) in files.into_iter().enumerate() {
+            if let Ok((checksum, size, modified)) = cached_hash(&db, &path) {
+                entries.push(Entry {
+                    file: DuplicateFile {
+                        path: path.to_string_lossy().to_string(),
+                        size,
+                        source,
+                    },
+                    checksum,
+                    modified,
+                    dhash: None,
+                });
+            }
+            events::publish(
+                &app,
+                Event::DuplicatesProgress(DuplicateProgress {
+                    processed: index + 1,
+                    total,
+                }),
+            );
+        }
+
+        let (mut groups, representatives) = exact_groups(&entries);
+
+        if options.perceptual {
+            for &i in &representatives {
+                let path = PathBuf::from(&entries[i].file.path);
+                if supports_perceptual(&path) {
+                    entries[i].dhash =
+                        cached_dhash(&db, &path, entries[i].file.size, entries[i].modified);
+                }
+            }
+            let threshold = options.threshold.unwrap_or(DEFAULT_THRESHOLD);
+            groups.extend(similar_groups(&entries, &representatives, threshold));
+        }
+
+        groups
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
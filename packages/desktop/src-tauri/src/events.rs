@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Window, WindowEvent};
+
+use crate::accessibility::{AccessibilityPreferences, Announcement};
+use crate::archive::{ArchiveProgress, ExtractProgress};
+use crate::audio::AudioDevice;
+use crate::capture::QuickCapture;
+use crate::data_dir::MigrationProgress;
+use crate::duplicates::DuplicateProgress;
+use crate::export::ExportProgress;
+use crate::folder_sync::PairPlan;
+use crate::import::ScanProgress;
+use crate::links::LinkConfirmation;
+use crate::ml::AnalyzeProgress;
+use crate::notifications::NotificationCategory;
+use crate::pinning::CertificateMismatch;
+use crate::player::PlayerState;
+use crate::schedule::TransferPolicy;
+use crate::screenshots::ScreenshotUpload;
+use crate::search_index::SearchResult;
+use crate::server_auth::AuthRequest;
+use crate::share::ShareLink;
+use crate::sidecar::SidecarInfo;
+use crate::slideshow::SlideshowCommand;
+use crate::ssh_tunnel::TunnelStatus;
+use crate::sync::SyncPass;
+use crate::transcode::TranscodeProgress;
+use crate::transfers::Transfer;
+use crate::updates::UpdateStatus;
+use crate::verify::VerifyProgress;
+use crate::video_player::VideoPosition;
+use crate::volumes::Volume;
+use crate::vpn::VpnStatus;
+
+/// The one event name every native event is emitted under
+pub const CHANNEL: &str = "apollo://event";
+
+/// Version of the event envelope and payloads
+///
+/// Bumped whenever a payload changes shape in a way old frontends can't read.
+pub const VERSION: u32 = 1;
+
+/// Groups of events a window can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topic {
+    Transfers,
+    Sync,
+    /// Progress of long-running commands such as exports and imports
+    Jobs,
+    Devices,
+    Sidecars,
+    Links,
+    Updates,
+    Notifications,
+    Shortcuts,
+    /// Servers asking for a sign-in
+    Auth,
+    /// VPN connections, SSH tunnels, the server address picked for them, and
+    /// certificates that no longer match their pins
+    Network,
+    /// Commands for the slideshow on another display, and its end
+    Slideshow,
+    /// System accessibility settings
+    Accessibility,
+    /// The native music and video players
+    Player,
+}
+
+/// An event sent from the native side to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "payload", rename_all = "kebab-case")]
+pub enum Event {
+    TransferProgress(Transfer),
+    TransfersPaused {
+        paused: bool,
+    },
+    TransferPolicy(TransferPolicy),
+    ScreenshotUpload(ScreenshotUpload),
+    QuickCapture(QuickCapture),
+    SyncPass(SyncPass),
+    FolderSync(PairPlan),
+    ExportProgress(ExportProgress),
+    PdfExportProgress(ExportProgress),
+    ArchiveProgress(ArchiveProgress),
+    ExtractProgress(ExtractProgress),
+    TakeoutProgress(ExportProgress),
+    DeviceImportProgress(ExportProgress),
+    ImportScanProgress(ScanProgress),
+    DuplicatesProgress(DuplicateProgress),
+    VerifyProgress(VerifyProgress),
+    MlProgress(AnalyzeProgress),
+    TranscodeProgress(TranscodeProgress),
+    DataMigrationProgress(MigrationProgress),
+    MediaCacheProgress(MigrationProgress),
+    VolumeMounted(Volume),
+    VolumeUnmounted {
+        mount_point: String,
+    },
+    AudioDevicesChanged(Vec<AudioDevice>),
+    SidecarStatus(SidecarInfo),
+    OpenLinkConfirm(LinkConfirmation),
+    UpdateProgress {
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    UpdateStatus(UpdateStatus),
+    Notification {
+        title: String,
+        body: Option<String>,
+        href: Option<String>,
+        category: Option<NotificationCategory>,
+    },
+    ShortcutTriggered {
+        action: String,
+    },
+    AuthRequired(AuthRequest),
+    VpnStatus(VpnStatus),
+    SshTunnelStatus(TunnelStatus),
+    CertificateChanged(CertificateMismatch),
+    SlideshowControl(SlideshowCommand),
+    SlideshowClosed,
+    /// A Spotlight result was chosen, which only happens on macOS
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    SearchResultOpened(SearchResult),
+    ShareLinkCreated(ShareLink),
+    AccessibilityChanged(AccessibilityPreferences),
+    Announcement(Announcement),
+    /// Only sent with the `native-audio` feature
+    #[cfg_attr(not(feature = "native-audio"), allow(dead_code))]
+    PlayerState(PlayerState),
+    /// Only sent with the `mpv-player` feature
+    #[cfg_attr(not(feature = "mpv-player"), allow(dead_code))]
+    VideoPosition(VideoPosition),
+    #[cfg_attr(not(feature = "mpv-player"), allow(dead_code))]
+    VideoBuffering {
+        buffering: bool,
+        /// How full the cache is before playback resumes
+        percent: i64,
+    },
+    #[cfg_attr(not(feature = "mpv-player"), allow(dead_code))]
+    VideoPlayerClosed,
+}
+
+impl Event {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Event::TransferProgress(_)
+            | Event::TransfersPaused { .. }
+            | Event::TransferPolicy(_)
+            | Event::ScreenshotUpload(_)
+            | Event::QuickCapture(_) => Topic::Transfers,
+            Event::SyncPass(_) | Event::FolderSync(_) => Topic::Sync,
+            Event::ExportProgress(_)
+            | Event::PdfExportProgress(_)
+            | Event::ArchiveProgress(_)
+            | Event::ExtractProgress(_)
+            | Event::TakeoutProgress(_)
+            | Event::DeviceImportProgress(_)
+            | Event::ImportScanProgress(_)
+            | Event::DuplicatesProgress(_)
+            | Event::VerifyProgress(_)
+            | Event::MlProgress(_)
+            | Event::TranscodeProgress(_)
+            | Event::DataMigrationProgress(_)
+            | Event::MediaCacheProgress(_) => Topic::Jobs,
+            Event::VolumeMounted(_)
+            | Event::VolumeUnmounted { .. }
+            | Event::AudioDevicesChanged(_) => Topic::Devices,
+            Event::SidecarStatus(_) => Topic::Sidecars,
+            Event::OpenLinkConfirm(_)
+            | Event::SearchResultOpened(_)
+            | Event::ShareLinkCreated(_) => Topic::Links,
+            Event::UpdateProgress { .. } | Event::UpdateStatus(_) => Topic::Updates,
+            Event::Notification { .. } => Topic::Notifications,
+            Event::ShortcutTriggered { .. } => Topic::Shortcuts,
+            Event::AuthRequired(_) => Topic::Auth,
+            Event::VpnStatus(_) | Event::SshTunnelStatus(_) | Event::CertificateChanged(_) => {
+                Topic::Network
+            }
+            Event::SlideshowControl(_) | Event::SlideshowClosed => Topic::Slideshow,
+            Event::AccessibilityChanged(_) | Event::Announcement(_) => Topic::Accessibility,
+            Event::PlayerState(_)
+            | Event::VideoPosition(_)
+            | Event::VideoBuffering { .. }
+            | Event::VideoPlayerClosed => Topic::Player,
+        }
+    }
+}
+
+/// What is actually emitted on `CHANNEL`
+///
+/// Serializes as `{ "version", "topic", "type", "payload" }`.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    topic: Topic,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Topics each window has subscribed to, keyed by window label
+///
+/// `None` means every topic.
+#[derive(Default)]
+pub struct EventBus(Mutex<HashMap<String, Option<HashSet<Topic>>>>);
+
+impl EventBus {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Option<HashSet<Topic>>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Send an event to every window subscribed to its topic
+pub fn publish(app: &AppHandle, event: Event) {
+    let topic = event.topic();
+    let labels: Vec<String> = app
+        .state::<EventBus>()
+        .lock()
+        .iter()
+        .filter(|(_, topics)| match topics {
+            Some(topics) => topics.contains(&topic),
+            None => true,
+        })
+        .map(|(label, _)| label.clone())
+        .collect();
+    if labels.is_empty() {
+        return;
+    }
+
+    let envelope = Envelope {
+        version: VERSION,
+        topic,
+        event: &event,
+    };
+    for label in labels {
+        let _ = app.emit_to(label.as_str(), CHANNEL, &envelope);
+    }
+}
+
+/// Drop a window's subscription when it closes
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) {
+        window
+            .app_handle()
+            .state::<EventBus>()
+            .lock()
+            .remove(window.label());
+    }
+}
+
+/// Receive native events on `apollo://event` in the calling window
+///
+/// Replaces the window's previous subscription, so a reloaded page can call
+/// this again safely. Omitting `topics` subscribes to everything. Events
+/// published before a window subscribes are not replayed. Returns `VERSION`
+/// so the frontend can tell whether it understands the payloads.
+#[tauri::command]
+pub async fn subscribe_events(
+    app: AppHandle,
+    window: Window,
+    topics: Option<Vec<Topic>>,
+) -> Result<u32, String> {
+    app.state::<EventBus>().lock().insert(
+        window.label().to_string(),
+        topics.map(|t| t.into_iter().collect()),
+    );
+    Ok(VERSION)
+}
+
+/// Stop sending native events to the calling window
+#[tauri::command]
+pub async fn unsubscribe_events(app: AppHandle, window: Window) -> Result<(), String> {
+    app.state::<EventBus>().lock().remove(window.label());
+    Ok(())
+}
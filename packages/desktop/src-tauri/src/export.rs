@@ -0,0 +1,298 @@
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tokio::io::AsyncWriteExt;
+
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::http;
+use crate::quick_look;
+use crate::recent;
+
+const DEFAULT_TEMPLATE: &str = "{album}/{YYYY}/{MM}/{filename}";
+const CONCURRENCY: usize = 4;
+
+/// A remote item to export
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportItem {
+    /// URL of the original file
+    pub url: String,
+    pub filename: String,
+    pub album: Option<String>,
+    /// Capture date as an ISO 8601 string
+    pub taken_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Path template relative to the target folder, e.g. `{album}/{YYYY}/{MM}/{filename}`
+    pub template: Option<String>,
+    pub skip_existing: bool,
+    /// Extra headers (e.g. auth) sent with every download
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub filename: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportFailure {
+    pub filename: String,
+    pub error: String,
+}
+
+/// Summary returned once every item has been processed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportReport {
+    pub exported: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<ExportFailure>,
+    pub bytes: u64,
+}
+
+enum Outcome {
+    Exported(PathBuf, u64),
+    Skipped(PathBuf),
+}
+
+/// Replace characters that are invalid in file names on any platform
+pub fn sanitize_component(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+
+    match cleaned.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => cleaned,
+    }
+}
+
+/// Render the relative path for an item from a naming template
+///
+/// Supported tokens: `{album}`, `{YYYY}`, `{MM}`, `{DD}`, `{filename}`,
+/// `{name}` (file stem) and `{ext}`.
+pub fn render_template(template: &str, item: &ExportItem) -> PathBuf {
+    let date = item.taken_at.as_deref().unwrap_or_default();
+    let part = |range: std::ops::Range<usize>| {
+        date.get(range)
+            .filter(|p| p.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or("Unknown")
+            .to_string()
+    };
+    let filename = Path::new(&item.filename);
+    let name = filename
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = filename
+        .extension()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let values = [
+        (
+            "{album}",
+            item.album.clone().unwrap_or_else(|| "Unsorted".to_string()),
+        ),
+        ("{YYYY}", part(0..4)),
+        ("{MM}", part(5..7)),
+        ("{DD}", part(8..10)),
+        ("{filename}", item.filename.clone()),
+        ("{name}", name),
+        ("{ext}", ext),
+    ];
+
+    let path: PathBuf = template
+        .split(['/', '\\'])
+        .filter(|segment| !segment.trim().is_empty())
+        .map(|segment| {
+            let rendered = values
+                .iter()
+                .fold(segment.to_string(), |acc, (token, value)| {
+                    acc.replace(token, &sanitize_component(value))
+                });
+            sanitize_component(&rendered)
+        })
+        .collect();
+
+    if path.as_os_str().is_empty() {
+        return PathBuf::from(sanitize_component(&item.filename));
+    }
+    path
+}
+
+/// Append ` (n)` to the file stem until the path is unused in this export
+pub fn unique_path(path: PathBuf, used: &mut HashSet<PathBuf>) -> PathBuf {
+    if used.insert(path.clone()) {
+        return path;
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path.extension().map(|s| s.to_string_lossy().to_string());
+
+    (2..)
+        .map(|n| {
+            let name = match &ext {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| used.insert(candidate.clone()))
+        .expect("unbounded range always yields a free path")
+}
+
+/// Temporary path a download is written to before being moved into place
+pub fn partial_path(path: &Path) -> PathBuf {
+    path.with_extension(match path.extension() {
+        Some(ext) => format!("{}.part", ext.to_string_lossy()),
+        None => "part".to_string(),
+    })
+}
+
+pub async fn download_to(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+) -> Result<u64, String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let request = http::with_headers(client.get(url), url, headers)?;
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    // Write to a temporary file first so an interrupted download never
+    // leaves a truncated file that skip-existing would treat as complete
+    let partial = partial_path(path);
+    let mut file = tokio::fs::File::create(&partial)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut bytes = 0u64;
+    let mut body = response.bytes_stream();
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        bytes += chunk.len() as u64;
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    drop(file);
+
+    tokio::fs::rename(&partial, path)
+        .await
+        .map_err(|e| e.to_string())?;
+    quick_look::tag(path, url);
+    Ok(bytes)
+}
+
+/// Download originals into a folder using a naming template
+///
+/// Emits `export-progress` after each item and returns a report of what was
+/// exported, skipped, and failed. The folder is added to recent exports.
+#[tauri::command]
+pub async fn export_items(
+    app: AppHandle,
+    items: Vec<ExportItem>,
+    target: String,
+    options: Option<ExportOptions>,
+) -> Result<ExportReport, String> {
+    let options = options.unwrap_or_default();
+    let template = options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    let target = fs_scope::check(&app, &target)?;
+    let client = http::client()?;
+    let total = items.len();
+
+    tokio::fs::create_dir_all(&target)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut used = HashSet::new();
+    let jobs: Vec<(ExportItem, PathBuf)> = items
+        .into_iter()
+        .map(|item| {
+            let path = unique_path(target.join(render_template(template, &item)), &mut used);
+            (item, path)
+        })
+        .collect();
+
+    let mut results = stream::iter(jobs)
+        .map(|(item, path)| {
+            let client = &client;
+            let options = &options;
+            async move {
+                if options.skip_existing && tokio::fs::try_exists(&path).await.unwrap_or(false) {
+                    return (item, Ok(Outcome::Skipped(path)));
+                }
+                let result = download_to(client, &item.url, &options.headers, &path)
+                    .await
+                    .map(|bytes| Outcome::Exported(path, bytes));
+                (item, result)
+            }
+        })
+        .buffer_unordered(CONCURRENCY);
+
+    let mut report = ExportReport::default();
+    let mut completed = 0;
+
+    while let Some((item, result)) = results.next().await {
+        completed += 1;
+
+        match result {
+            Ok(Outcome::Exported(path, bytes)) => {
+                report.exported.push(path.to_string_lossy().to_string());
+                report.bytes += bytes;
+            }
+            Ok(Outcome::Skipped(path)) => report.skipped.push(path.to_string_lossy().to_string()),
+            Err(error) => report.failed.push(ExportFailure {
+                filename: item.filename.clone(),
+                error,
+            }),
+        }
+
+        events::publish(
+            &app,
+            Event::ExportProgress(ExportProgress {
+                completed,
+                total,
+                filename: item.filename,
+            }),
+        );
+    }
+
+    if !report.exported.is_empty() {
+        let label = target
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let target = target.to_string_lossy();
+        if let Err(e) = recent::add(&app, recent::RecentKind::Export, &target, &label) {
+            log::warn!("Failed to add the export to recent items: {}", e);
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,414 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::export::{partial_path, sanitize_component, unique_path};
+use crate::fs_scope;
+use crate::import::{cached_hash, walk_media};
+use crate::profiles;
+use crate::sync::{self, SyncEngine, SyncItem, SyncSelection};
+use crate::transfers::{self, TransferDirection, TransferRequest};
+use crate::verify::normalize_checksum;
+
+const FOLDER_PAIRS_KEY: &str = "folderPairs";
+
+/// A local folder kept in two-way sync with an album
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FolderPair {
+    pub server: String,
+    pub album_id: String,
+    pub folder: String,
+    /// Endpoint answering with a JSON list of `SyncItem` for the album
+    pub list_url: String,
+    /// Endpoint accepting a multipart upload with `file` and `album` parts;
+    /// without it, new local files are not uploaded
+    pub upload_url: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairActionKind {
+    /// Added on the server; download into the folder
+    Download,
+    /// Added locally; upload to the album
+    Upload,
+    /// Deleted on the server; remove the local copy
+    DeleteLocal,
+    /// Already on both sides; start tracking it
+    Link,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairAction {
+    pub kind: PairActionKind,
+    pub path: String,
+    pub remote_id: Option<String>,
+    #[serde(skip)]
+    url: Option<String>,
+}
+
+/// Changes a sync pass over a folder pair makes, or would make in a dry run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PairPlan {
+    pub server: String,
+    pub album_id: String,
+    pub actions: Vec<PairAction>,
+    /// Files already present on both sides
+    pub in_sync: usize,
+    /// Remote items whose tracked local copy was deleted; local deletions
+    /// aren't sent to the server, and the item isn't downloaded again
+    pub deleted_locally: usize,
+    pub error: Option<String>,
+    /// Tracked items gone from the server whose local copy is already gone
+    #[serde(skip)]
+    forget: Vec<String>,
+}
+
+pub fn read_pairs(app: &AppHandle) -> Result<Vec<FolderPair>, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(FOLDER_PAIRS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn write_pairs(app: &AppHandle, pairs: &[FolderPair]) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(FOLDER_PAIRS_KEY, serde_json::json!(pairs));
+    store.save().map_err(|e| e.to_string())
+}
+
+fn find_pair(app: &AppHandle, server: &str, album_id: &str) -> Result<FolderPair, String> {
+    read_pairs(app)?
+        .into_iter()
+        .find(|p| p.server == server && p.album_id == album_id)
+        .ok_or_else(|| format!("No folder is paired with album '{}'", album_id))
+}
+
+fn tracked_files(app: &AppHandle, server: &str, album_id: &str) -> HashMap<String, PathBuf> {
+    app.state::<Database>()
+        .conn()
+        .prepare(
+            "SELECT remote_id, path FROM folder_pair_files WHERE server = ?1 AND album_id = ?2",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(params![server, album_id], |row| {
+                Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+            })?
+            .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn name_key(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Work out what a sync pass would change, without touching anything
+///
+/// Untracked local files are matched to remote items by checksum, or by
+/// name and size when the server doesn't report checksums.
+fn plan_pair(app: &AppHandle, pair: &FolderPair, mut items: Vec<SyncItem>) -> PairPlan {
+    let folder = PathBuf::from(&pair.folder);
+    let tracked = tracked_files(app, &pair.server, &pair.album_id);
+    let tracked_paths: HashSet<&PathBuf> = tracked.values().collect();
+    let (local, _) = walk_media(&folder);
+
+    let mut by_checksum: HashMap<String, PathBuf> = HashMap::new();
+    let mut by_name: HashMap<(String, u64), PathBuf> = HashMap::new();
+    let mut untracked: Vec<(PathBuf, String)> = Vec::new();
+    for path in local.iter().filter(|p| !tracked_paths.contains(p)) {
+        let Ok((checksum, size, _)) = cached_hash(&app.state::<Database>(), path) else {
+            continue;
+        };
+        by_checksum
+            .entry(checksum.clone())
+            .or_insert_with(|| path.clone());
+        by_name
+            .entry((name_key(path), size))
+            .or_insert_with(|| path.clone());
+        untracked.push((path.clone(), checksum));
+    }
+
+    let mut plan = PairPlan {
+        server: pair.server.clone(),
+        album_id: pair.album_id.clone(),
+        ..Default::default()
+    };
+    let mut used: HashSet<PathBuf> = local.iter().cloned().collect();
+    used.extend(tracked.values().cloned());
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    let mut remote_checksums = HashSet::new();
+
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    for item in &items {
+        let checksum = item.checksum.as_deref().map(normalize_checksum);
+        remote_checksums.extend(checksum.clone());
+
+        if let Some(path) = tracked.get(&item.id) {
+            if path.exists() || transfers::is_pending(app, path) {
+                plan.in_sync += 1;
+            } else {
+                plan.deleted_locally += 1;
+            }
+            continue;
+        }
+
+        let matched = checksum
+            .as_ref()
+            .and_then(|c| by_checksum.get(c))
+            .or_else(|| {
+                let size = item.size?;
+                by_name.get(&(item.filename.to_lowercase(), size))
+            })
+            .filter(|path| !claimed.contains(*path))
+            .cloned();
+        let (kind, path) = match matched {
+            Some(path) => (PairActionKind::Link, path),
+            None => (
+                PairActionKind::Download,
+                unique_path(folder.join(sanitize_component(&item.filename)), &mut used),
+            ),
+        };
+        claimed.insert(path.clone());
+        plan.actions.push(PairAction {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            remote_id: Some(item.id.clone()),
+            url: Some(item.url.clone()),
+        });
+    }
+
+    let remote_ids: HashSet<&str> = items.iter().map(|i| i.id.as_str()).collect();
+    let mut removed: Vec<(&String, &PathBuf)> = tracked
+        .iter()
+        .filter(|(id, _)| !remote_ids.contains(id.as_str()))
+        .collect();
+    removed.sort();
+    for (id, path) in removed {
+        if path.exists() {
+            plan.actions.push(PairAction {
+                kind: PairActionKind::DeleteLocal,
+                path: path.to_string_lossy().to_string(),
+                remote_id: Some(id.clone()),
+                url: None,
+            });
+        } else {
+            plan.forget.push(id.clone());
+        }
+    }
+
+    if pair.upload_url.is_some() {
+        for (path, checksum) in untracked {
+            if claimed.contains(&path)
+                || remote_checksums.contains(&checksum)
+                || transfers::is_pending(app, &path)
+            {
+                continue;
+            }
+            plan.actions.push(PairAction {
+                kind: PairActionKind::Upload,
+                path: path.to_string_lossy().to_string(),
+                remote_id: None,
+                url: None,
+            });
+        }
+    }
+
+    plan
+}
+
+async fn preview(app: &AppHandle, pair: &FolderPair) -> Result<PairPlan, String> {
+    if !Path::new(&pair.folder).is_dir() {
+        return Err(format!("'{}' is not a directory", pair.folder));
+    }
+    let selection = SyncSelection {
+        server: pair.server.clone(),
+        album_id: pair.album_id.clone(),
+        list_url: pair.list_url.clone(),
+        headers: pair.headers.clone(),
+        ..Default::default()
+    };
+    let items = sync::fetch_items(app, &selection).await?;
+
+    let app = app.clone();
+    let pair = pair.clone();
+    tauri::async_runtime::spawn_blocking(move || plan_pair(&app, &pair, items))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn track(app: &AppHandle, pair: &FolderPair, remote_id: &str, path: &str) {
+    let _ = app.state::<Database>().conn().execute(
+        "INSERT OR REPLACE INTO folder_pair_files (server, album_id, remote_id, path)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![pair.server, pair.album_id, remote_id, path],
+    );
+}
+
+fn untrack(app: &AppHandle, pair: &FolderPair, remote_id: &str) {
+    let _ = app.state::<Database>().conn().execute(
+        "DELETE FROM folder_pair_files WHERE server = ?1 AND album_id = ?2 AND remote_id = ?3",
+        params![pair.server, pair.album_id, remote_id],
+    );
+}
+
+/// Apply a plan, queueing uploads and downloads on the transfer queue
+fn apply(app: &AppHandle, pair: &FolderPair, plan: &PairPlan) -> Result<(), String> {
+    let mut transfers = Vec::new();
+
+    for action in &plan.actions {
+        let remote_id = action.remote_id.as_deref().unwrap_or_default();
+        match action.kind {
+            PairActionKind::Link => track(app, pair, remote_id, &action.path),
+            PairActionKind::Download => {
+                track(app, pair, remote_id, &action.path);
+                transfers.push(TransferRequest {
+                    direction: TransferDirection::Download,
+                    url: action.url.clone().unwrap_or_default(),
+                    path: action.path.clone(),
+                    headers: pair.headers.clone(),
+                    fields: HashMap::new(),
+                    link_pointer: None,
+                });
+            }
+            PairActionKind::DeleteLocal => {
+                let path = PathBuf::from(&action.path);
+                transfers::cancel_paths(app, std::slice::from_ref(&path))?;
+                let _ = std::fs::remove_file(partial_path(&path));
+                if let Err(e) = std::fs::remove_file(&path) {
+                    log::warn!("Failed to remove {}: {}", path.display(), e);
+                }
+                untrack(app, pair, remote_id);
+            }
+            PairActionKind::Upload => {
+                let Some(url) = &pair.upload_url else {
+                    continue;
+                };
+                transfers.push(TransferRequest {
+                    direction: TransferDirection::Upload,
+                    url: url.clone(),
+                    path: action.path.clone(),
+                    headers: pair.headers.clone(),
+                    fields: HashMap::from([("album".to_string(), pair.album_id.clone())]),
+                    link_pointer: None,
+                });
+            }
+        }
+    }
+    for remote_id in &plan.forget {
+        untrack(app, pair, remote_id);
+    }
+
+    transfers::enqueue(app, &transfers)?;
+    Ok(())
+}
+
+/// Sync every folder pair, emitting `folder-sync` with each applied plan
+pub async fn run_pairs(app: &AppHandle) -> Result<Vec<PairPlan>, String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let mut plans = Vec::new();
+    for pair in read_pairs(app)? {
+        let result = match preview(app, &pair).await {
+            Ok(plan) => apply(app, &pair, &plan).map(|_| plan),
+            Err(e) => Err(e),
+        };
+        let plan = result.unwrap_or_else(|error| PairPlan {
+            server: pair.server.clone(),
+            album_id: pair.album_id.clone(),
+            error: Some(error),
+            ..Default::default()
+        });
+        events::publish(app, Event::FolderSync(plan.clone()));
+        plans.push(plan);
+    }
+    Ok(plans)
+}
+
+/// List folders paired with albums for two-way sync
+#[tauri::command]
+pub async fn get_folder_pairs(app: AppHandle) -> Result<Vec<FolderPair>, String> {
+    read_pairs(&app)
+}
+
+/// Pair a folder with an album, replacing any existing pair for the album
+///
+/// Nothing is synced until the next pass; use `preview_folder_sync` to see
+/// what it will change first.
+#[tauri::command]
+pub async fn set_folder_pair(app: AppHandle, pair: FolderPair) -> Result<(), String> {
+    fs_scope::check(&app, &pair.folder)?;
+    if !Path::new(&pair.folder).is_dir() {
+        return Err(format!("'{}' is not a directory", pair.folder));
+    }
+    let mut pairs = read_pairs(&app)?;
+    pairs.retain(|p| !(p.server == pair.server && p.album_id == pair.album_id));
+    pairs.push(pair);
+    write_pairs(&app, &pairs)
+}
+
+/// Stop syncing a folder pair; local files are left in place
+#[tauri::command]
+pub async fn remove_folder_pair(
+    app: AppHandle,
+    server: String,
+    album_id: String,
+) -> Result<(), String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let mut pairs = read_pairs(&app)?;
+    pairs.retain(|p| !(p.server == server && p.album_id == album_id));
+    write_pairs(&app, &pairs)?;
+
+    let paths: Vec<PathBuf> = tracked_files(&app, &server, &album_id)
+        .into_values()
+        .collect();
+    transfers::cancel_paths(&app, &paths)?;
+    app.state::<Database>()
+        .conn()
+        .execute(
+            "DELETE FROM folder_pair_files WHERE server = ?1 AND album_id = ?2",
+            params![server, album_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Dry run: list what syncing a folder pair would download, upload and delete
+#[tauri::command]
+pub async fn preview_folder_sync(
+    app: AppHandle,
+    server: String,
+    album_id: String,
+) -> Result<PairPlan, String> {
+    let pair = find_pair(&app, &server, &album_id)?;
+    preview(&app, &pair).await
+}
+
+/// Sync a folder pair now and return the changes made
+#[tauri::command]
+pub async fn sync_folder_pair(
+    app: AppHandle,
+    server: String,
+    album_id: String,
+) -> Result<PairPlan, String> {
+    let pair = find_pair(&app, &server, &album_id)?;
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let plan = preview(&app, &pair).await?;
+    apply(&app, &pair, &plan)?;
+    Ok(plan)
+}
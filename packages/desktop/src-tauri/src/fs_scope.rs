@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::cache;
+use crate::downloads;
+use crate::folder_sync;
+use crate::profiles;
+use crate::screenshots;
+use crate::sync;
+use crate::volumes;
+
+const FS_SCOPE_KEY: &str = "fsScope";
+
+/// Directories the user added to the scope, including folders chosen in a picker
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FsScopeSettings {
+    pub directories: Vec<String>,
+}
+
+/// Why a directory is in the scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeSource {
+    /// Downloads, Pictures, Videos, and Apollo's own data and cache
+    Default,
+    /// A synced, paired, or screenshots folder
+    Watched,
+    /// A mounted removable volume
+    Volume,
+    /// Added by the user or chosen in a picker
+    Configured,
+    /// A single file chosen in a picker this session
+    Picked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScopeEntry {
+    pub path: String,
+    pub source: ScopeSource,
+}
+
+/// Files chosen in a picker since launch
+#[derive(Default)]
+pub struct FsScope(Mutex<HashSet<PathBuf>>);
+
+pub fn read_settings(app: &AppHandle) -> Result<FsScopeSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(FS_SCOPE_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(FsScopeSettings::default()),
+    }
+}
+
+fn write_settings(app: &AppHandle, settings: &FsScopeSettings) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(FS_SCOPE_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Make a path absolute and free of symlinks and `..`, even if it doesn't exist yet
+///
+/// The deepest existing ancestor is canonicalized and the rest appended, so a
+/// file about to be written resolves the same way as its folder.
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err(format!("'{}' is not an absolute path", path.display()));
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err(format!("'{}' must not contain '..'", path.display()));
+    }
+
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                return Ok(rest.iter().rev().fold(canonical, |p, c| p.join(c)));
+            }
+            Err(_) => match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name.to_os_string());
+                    existing = parent;
+                }
+                _ => return Ok(path.to_path_buf()),
+            },
+        }
+    }
+}
+
+/// Whether a resolved folder is a filesystem root, the home folder, or a
+/// folder containing it
+fn covers_too_much(path: &Path, home: Option<&Path>) -> bool {
+    path.parent().is_none() || home.is_some_and(|home| home.starts_with(path))
+}
+
+/// Whether a folder would open up too much as a scope root
+fn too_broad(app: &AppHandle, path: &Path) -> bool {
+    let Ok(path) = resolve(path) else {
+        return true;
+    };
+    let home = app
+        .path()
+        .home_dir()
+        .ok()
+        .and_then(|home| resolve(&home).ok());
+    covers_too_much(&path, home.as_deref())
+}
+
+/// Check that a folder can be added to the scope as a root
+///
+/// Returns the resolved folder to store in place of the original.
+pub fn check_root(app: &AppHandle, path: impl AsRef<Path>) -> Result<PathBuf, String> {
+    let path = path.as_ref();
+    if too_broad(app, path) {
+        return Err(format!(
+            "'{}' would give Apollo access to too much. Choose a folder inside it instead.",
+            path.display()
+        ));
+    }
+    resolve(path)
+}
+
+fn roots(app: &AppHandle) -> Vec<(PathBuf, ScopeSource)> {
+    let mut roots = Vec::new();
+
+    let resolver = app.path();
+    for dir in [
+        resolver.download_dir().ok(),
+        downloads::saved_dir(app).filter(|dir| !too_broad(app, dir)),
+        resolver.picture_dir().ok(),
+        resolver.video_dir().ok(),
+        profiles::data_dir(app).ok(),
+        profiles::cache_dir(app).ok(),
+        cache::media_dir(app).ok(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        roots.push((dir, ScopeSource::Default));
+    }
+
+    if let Ok(selections) = sync::read_selections(app) {
+        for selection in &selections {
+            if let Ok(folder) = sync::folder(app, selection) {
+                roots.push((folder, ScopeSource::Watched));
+            }
+        }
+    }
+    if let Ok(pairs) = folder_sync::read_pairs(app) {
+        roots.extend(
+            pairs
+                .into_iter()
+                .map(|p| (PathBuf::from(p.folder), ScopeSource::Watched)),
+        );
+    }
+    if let Ok(settings) = screenshots::read_settings(app) {
+        if let Some(folder) = screenshots::folder(app, &settings) {
+            roots.push((folder, ScopeSource::Watched));
+        }
+    }
+
+    roots.extend(
+        volumes::list_volumes()
+            .into_iter()
+            .map(|v| (PathBuf::from(v.mount_point), ScopeSource::Volume)),
+    );
+
+    if let Ok(settings) = read_settings(app) {
+        roots.extend(
+            settings
+                .directories
+                .into_iter()
+                .map(PathBuf::from)
+                .filter(|d| !too_broad(app, d))
+                .map(|d| (d, ScopeSource::Configured)),
+        );
+    }
+
+    roots
+        .into_iter()
+        .filter_map(|(root, source)| resolve(&root).ok().map(|root| (root, source)))
+        .collect()
+}
+
+/// The allowed directories and picked files at one moment
+struct Scope {
+    roots: Vec<PathBuf>,
+    picked: HashSet<PathBuf>,
+}
+
+impl Scope {
+    fn current(app: &AppHandle) -> Self {
+        Scope {
+            roots: roots(app).into_iter().map(|(root, _)| root).collect(),
+            picked: app
+                .state::<FsScope>()
+                .0
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+
+    fn check(&self, path: &Path) -> Result<PathBuf, String> {
+        let resolved = resolve(path)?;
+        if self.picked.contains(&resolved) || self.roots.iter().any(|r| resolved.starts_with(r)) {
+            return Ok(resolved);
+        }
+        Err(format!(
+            "'{}' is outside the folders Apollo is allowed to access. \
+             Choose it in a file picker or add its folder in settings.",
+            path.display()
+        ))
+    }
+}
+
+/// Check that a path from the webview is inside the allowed directories
+///
+/// Returns the resolved path to use in place of the original.
+pub fn check(app: &AppHandle, path: impl AsRef<Path>) -> Result<PathBuf, String> {
+    Scope::current(app).check(path.as_ref())
+}
+
+/// Like `check`, for several paths at once
+pub fn check_all<P: AsRef<Path>>(app: &AppHandle, paths: &[P]) -> Result<Vec<PathBuf>, String> {
+    let scope = Scope::current(app);
+    paths.iter().map(|p| scope.check(p.as_ref())).collect()
+}
+
+/// Add a folder the user chose in a picker, remembering it across restarts
+pub fn allow_directory(app: &AppHandle, path: &Path) -> Result<(), String> {
+    let path = check_root(app, path)?;
+    let mut settings = read_settings(app)?;
+    let path = path.to_string_lossy().to_string();
+    if !settings.directories.contains(&path) {
+        settings.directories.push(path);
+        write_settings(app, &settings)?;
+    }
+    Ok(())
+}
+
+/// Allow a single file the user chose in a picker until the app quits
+pub fn allow_file(app: &AppHandle, path: &Path) {
+    if let Ok(resolved) = resolve(path) {
+        app.state::<FsScope>()
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(resolved);
+    }
+}
+
+/// Every directory and file native commands may currently touch
+#[tauri::command]
+pub async fn get_fs_scope(app: AppHandle) -> Result<Vec<ScopeEntry>, String> {
+    let mut entries: Vec<ScopeEntry> = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || {
+            roots(&app)
+                .into_iter()
+                .map(|(path, source)| ScopeEntry {
+                    path: path.to_string_lossy().to_string(),
+                    source,
+                })
+                .collect()
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let picked = app
+        .state::<FsScope>()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    entries.extend(picked.into_iter().map(|path| ScopeEntry {
+        path: path.to_string_lossy().to_string(),
+        source: ScopeSource::Picked,
+    }));
+    Ok(entries)
+}
+
+/// Get the directories the user added to the scope
+#[tauri::command]
+pub async fn get_fs_scope_settings(app: AppHandle) -> Result<FsScopeSettings, String> {
+    read_settings(&app)
+}
+
+/// Replace the directories the user added to the scope
+///
+/// Default and watched folders are always allowed and can't be removed here.
+/// Filesystem roots, the home folder, and folders containing it are refused.
+#[tauri::command]
+pub async fn set_fs_scope_settings(
+    app: AppHandle,
+    settings: FsScopeSettings,
+) -> Result<(), String> {
+    for directory in &settings.directories {
+        check_root(&app, directory)?;
+    }
+    write_settings(&app, &settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, canonical folder under the temporary directory
+    fn temp_folder(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().canonicalize().unwrap().join(format!(
+            "apollo-fs-scope-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn filesystem_roots_cover_too_much() {
+        let root = std::env::temp_dir()
+            .ancestors()
+            .last()
+            .unwrap()
+            .to_path_buf();
+        assert!(covers_too_much(&root, None));
+        assert!(!covers_too_much(&temp_folder("roots"), None));
+    }
+
+    #[test]
+    fn home_and_its_parents_cover_too_much() {
+        let base = temp_folder("home");
+        let home = base.join("users").join("me");
+        assert!(covers_too_much(&home, Some(&home)));
+        assert!(covers_too_much(&base.join("users"), Some(&home)));
+        assert!(covers_too_much(&base, Some(&home)));
+        assert!(!covers_too_much(&home.join("Pictures"), Some(&home)));
+        assert!(!covers_too_much(
+            &base.join("users").join("other"),
+            Some(&home)
+        ));
+        // A shared prefix isn't a parent
+        assert!(!covers_too_much(&base.join("users").join("m"), Some(&home)));
+    }
+
+    #[test]
+    fn resolve_refuses_relative_paths_and_parent_components() {
+        assert!(resolve(Path::new("photos")).is_err());
+        let base = temp_folder("parent");
+        assert!(resolve(&base.join("a").join("..").join("b")).is_err());
+    }
+
+    #[test]
+    fn resolve_keeps_missing_components() {
+        let base = temp_folder("missing");
+        let path = base.join("new").join("photo.jpg");
+        assert_eq!(resolve(&path).unwrap(), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_follows_symlinks() {
+        let base = temp_folder("symlink");
+        let target = base.join("target");
+        std::fs::create_dir(&target).unwrap();
+        let link = base.join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert_eq!(
+            resolve(&link.join("photo.jpg")).unwrap(),
+            target.join("photo.jpg")
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use exif::{In, Reader, Tag, Value};
+use isocountry::CountryCode;
+use reverse_geocoder::ReverseGeocoder;
+use serde::Serialize;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Squared chord distance on the unit sphere beyond which a position is too
+/// far from any known place to label it (~100 km)
+const MAX_DISTANCE: f64 = 0.000_25;
+
+/// Shorter names for countries whose ISO name is unwieldy in a label
+const COUNTRY_NAMES: &[(&str, &str)] = &[
+    ("BO", "Bolivia"),
+    ("BQ", "Caribbean Netherlands"),
+    ("CD", "DR Congo"),
+    ("FM", "Micronesia"),
+    ("GB", "United Kingdom"),
+    ("IR", "Iran"),
+    ("KP", "North Korea"),
+    ("KR", "South Korea"),
+    ("LA", "Laos"),
+    ("MD", "Moldova"),
+    ("PS", "Palestine"),
+    ("RU", "Russia"),
+    ("SY", "Syria"),
+    ("TW", "Taiwan"),
+    ("TZ", "Tanzania"),
+    ("US", "United States"),
+    ("VA", "Vatican City"),
+    ("VE", "Venezuela"),
+    ("VN", "Vietnam"),
+];
+
+static GEOCODER: OnceLock<ReverseGeocoder> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Metres above sea level
+    pub altitude: Option<f64>,
+}
+
+/// Nearest known place to a position, from the bundled GeoNames dataset
+#[derive(Debug, Clone, Serialize)]
+pub struct Place {
+    pub name: String,
+    pub admin1: String,
+    pub admin2: String,
+    pub country_code: String,
+    pub country: String,
+    /// Display label, e.g. "Paris, France"
+    pub label: String,
+}
+
+fn country_name(code: &str) -> String {
+    COUNTRY_NAMES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, name)| name.to_string())
+        .or_else(|| {
+            CountryCode::for_alpha2(code)
+                .ok()
+                .map(|c| c.name().to_string())
+        })
+        .unwrap_or_else(|| code.to_string())
+}
+
+/// Degrees/minutes/seconds rationals to signed decimal degrees
+fn degrees(exif: &exif::Exif, tag: Tag, reference: Tag, negative: u8) -> Option<f64> {
+    let Value::Rational(dms) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let [d, m, s] = dms.as_slice() else {
+        return None;
+    };
+    let value = d.to_f64() + m.to_f64() / 60.0 + s.to_f64() / 3600.0;
+    if !value.is_finite() {
+        return None;
+    }
+
+    let sign = match &exif.get_field(reference, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Ascii(parts)) if parts.first().and_then(|p| p.first()) == Some(&negative) => {
+            -1.0
+        }
+        _ => 1.0,
+    };
+    Some(value * sign)
+}
+
+/// Read the GPS position from a file's EXIF metadata
+pub fn read_gps(path: &Path) -> Option<GpsPosition> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let latitude = degrees(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = degrees(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+    // 0,0 is what many cameras write when they have no fix
+    if latitude == 0.0 && longitude == 0.0 {
+        return None;
+    }
+
+    let altitude = exif
+        .get_field(Tag::GPSAltitude, In::PRIMARY)
+        .and_then(|field| match &field.value {
+            Value::Rational(v) => v.first().map(|r| r.to_f64()),
+            _ => None,
+        })
+        .map(
+            |metres| match exif.get_field(Tag::GPSAltitudeRef, In::PRIMARY) {
+                // Reference 1 means below sea level
+                Some(field) if matches!(&field.value, Value::Byte(b) if b.first() == Some(&1)) => {
+                    -metres
+                }
+                _ => metres,
+            },
+        );
+
+    Some(GpsPosition {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Find the nearest place to a position without leaving the machine
+///
+/// The dataset is parsed on first use, so call this off the main thread.
+pub fn lookup(latitude: f64, longitude: f64) -> Option<Place> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+
+    let result = GEOCODER
+        .get_or_init(ReverseGeocoder::new)
+        .search((latitude, longitude));
+    if result.distance > MAX_DISTANCE {
+        return None;
+    }
+
+    let record = result.record;
+    let country = country_name(&record.cc);
+    Some(Place {
+        label: format!("{}, {}", record.name, country),
+        name: record.name.clone(),
+        admin1: record.admin1.clone(),
+        admin2: record.admin2.clone(),
+        country_code: record.cc.clone(),
+        country,
+    })
+}
+
+/// Look up the nearest place to a coordinate using the bundled dataset
+#[tauri::command]
+pub async fn reverse_geocode(latitude: f64, longitude: f64) -> Result<Option<Place>, String> {
+    tauri::async_runtime::spawn_blocking(move || lookup(latitude, longitude))
+        .await
+        .map_err(|e| e.to_string())
+}
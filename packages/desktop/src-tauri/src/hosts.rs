@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Window};
+
+use crate::http;
+use crate::profiles;
+
+const HOST_OVERRIDES_KEY: &str = "hostOverrides";
+
+/// Overrides of the active profile, read on every native lookup
+static OVERRIDES: Mutex<Vec<HostOverride>> = Mutex::new(Vec::new());
+
+/// Addresses to use for a host name instead of looking it up
+///
+/// Like a hosts file entry for native requests only, for example to reach
+/// `photos.example.com` at its LAN address while at home.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostOverride {
+    /// Host name, or `*.example.com` for every subdomain
+    pub host: String,
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostOverrides {
+    pub overrides: Vec<HostOverride>,
+}
+
+/// A profile's host overrides
+pub fn read(app: &AppHandle, id: &str) -> Result<HostOverrides, String> {
+    let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+    match store.get(HOST_OVERRIDES_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(HostOverrides::default()),
+    }
+}
+
+/// Load the active profile's overrides so lookups don't read the store
+pub fn load(app: &AppHandle) {
+    match read(app, &profiles::active(app)) {
+        Ok(settings) => *OVERRIDES.lock().unwrap_or_else(|e| e.into_inner()) = settings.overrides,
+        Err(e) => log::warn!("Failed to load host overrides: {}", e),
+    }
+}
+
+fn matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+/// The overridden addresses for a host, if it has any
+///
+/// An exact entry wins over a wildcard one.
+pub fn lookup(host: &str) -> Option<Vec<IpAddr>> {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = overrides
+        .iter()
+        .filter(|entry| matches(&entry.host, &host))
+        .max_by_key(|entry| !entry.host.trim().starts_with("*."))?;
+    let addresses: Vec<IpAddr> = entry
+        .addresses
+        .iter()
+        .filter_map(|address| address.trim().parse().ok())
+        .collect();
+    (!addresses.is_empty()).then_some(addresses)
+}
+
+/// Get the calling window's profile's host overrides
+#[tauri::command]
+pub async fn get_host_overrides(app: AppHandle, window: Window) -> Result<HostOverrides, String> {
+    read(&app, &profiles::for_window(&app, window.label()))
+}
+
+/// Save the calling window's profile's host overrides
+///
+/// Pooled connections are dropped so the next request connects to the new
+/// addresses.
+#[tauri::command]
+pub async fn set_host_overrides(
+    app: AppHandle,
+    window: Window,
+    settings: HostOverrides,
+) -> Result<(), String> {
+    for entry in &settings.overrides {
+        if entry.host.trim().is_empty() {
+            return Err("Every host override needs a host name".to_string());
+        }
+        if entry.addresses.is_empty() {
+            return Err(format!("No addresses given for {}", entry.host));
+        }
+        if let Some(bad) = entry
+            .addresses
+            .iter()
+            .find(|address| address.trim().parse::<IpAddr>().is_err())
+        {
+            return Err(format!("'{}' is not an IP address", bad));
+        }
+    }
+
+    let id = profiles::for_window(&app, window.label());
+    let store = profiles::profile_store(&app, &id).map_err(|e| e.to_string())?;
+    store.set(HOST_OVERRIDES_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    if id == profiles::active(&app) {
+        load(&app);
+        http::reset();
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use reqwest::header::AUTHORIZATION;
+use reqwest::RequestBuilder;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::doh;
+use crate::integrated_auth;
+use crate::pinning;
+use crate::server_auth;
+use crate::server_headers;
+
+static CLIENT: Mutex<Option<reqwest::Client>> = Mutex::new(None);
+/// Override of `DEFAULT_USER_AGENT` from the active profile's settings
+static USER_AGENT: Mutex<Option<String>> = Mutex::new(None);
+
+const DEFAULT_USER_AGENT: &str = concat!("Apollo/", env!("CARGO_PKG_VERSION"));
+
+/// Idle connections kept open per host; sync and offline pinning fetch many
+/// small files from the same server in bursts
+const POOL_MAX_IDLE_PER_HOST: usize = 32;
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Shared HTTP client for native requests to the server
+///
+/// Downloads, uploads and API calls all go through this one connection pool.
+/// HTTPS servers that offer HTTP/2 via ALPN get a single multiplexed
+/// connection, so thousands of small requests skip repeated TLS handshakes.
+/// Clones share the pool.
+pub fn client() -> Result<reqwest::Client, String> {
+    let mut cached = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = cached.as_ref() {
+        return Ok(client.clone());
+    }
+    let user_agent = USER_AGENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+
+    // reqwest is built without a bundled crypto provider; use ring like the updater does
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let client = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_nodelay(true)
+        .tcp_keepalive(KEEP_ALIVE_INTERVAL)
+        // Grow HTTP/2 flow-control windows with throughput so large originals
+        // aren't capped by the default 64 KiB stream window
+        .http2_adaptive_window(true)
+        .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+        .http2_keep_alive_while_idle(true)
+        .tls_backend_preconfigured(pinning::tls_config()?)
+        .dns_resolver(doh::Resolver)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    *cached = Some(client.clone());
+    Ok(client)
+}
+
+/// Send a different `User-Agent`, or the default with `None`
+///
+/// The client is rebuilt on next use; requests already holding a clone finish
+/// with the old header.
+pub fn set_user_agent(user_agent: Option<String>) {
+    *USER_AGENT.lock().unwrap_or_else(|e| e.into_inner()) = user_agent;
+    reset();
+}
+
+/// Drop the client and its pooled connections so the next request starts fresh
+pub fn reset() {
+    *CLIENT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Add the caller's headers to a request, and any the user configured for its server
+///
+/// Configured headers go first and give way to ones the caller sets itself.
+/// Requests that end up without `Authorization` get the server's saved
+/// Basic or Digest sign-in, if any, or else a Kerberos ticket for
+/// allowlisted servers.
+pub fn with_headers(
+    mut request: RequestBuilder,
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<RequestBuilder, String> {
+    for (name, value) in server_headers::for_url(url) {
+        if !headers.keys().any(|own| own.eq_ignore_ascii_case(&name)) {
+            request = request.header(name, value);
+        }
+    }
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let (client, request) = request.build_split();
+    let mut request = request.map_err(|e| e.to_string())?;
+    if !request.headers().contains_key(AUTHORIZATION) {
+        if let Some(value) = server_auth::authorization(request.method(), request.url())
+            .or_else(|| integrated_auth::authorization(request.url()))
+        {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+    }
+    Ok(RequestBuilder::from_parts(client, request))
+}
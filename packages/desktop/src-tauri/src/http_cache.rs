@@ -0,0 +1,199 @@
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use rusqlite::{params, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{now, Database};
+use crate::http;
+use crate::server_auth;
+
+/// Entries not revalidated for this long are dropped at startup
+const MAX_AGE_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// A response body, possibly served from the conditional-request cache
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub body: String,
+    pub content_type: Option<String>,
+    /// The server answered `304 Not Modified` and the cached body was used
+    pub cached: bool,
+}
+
+struct Entry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Fingerprint of the request headers, so responses for different
+/// credentials are cached separately
+pub fn variant(headers: &HashMap<String, String>) -> String {
+    let sorted: BTreeMap<String, &String> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value))
+        .collect();
+    let mut hasher = Sha1::new();
+    for (name, value) in sorted {
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn lookup(app: &AppHandle, url: &str, variant: &str) -> Option<Entry> {
+    app.state::<Database>()
+        .conn()
+        .query_row(
+            "SELECT etag, last_modified, content_type, body FROM http_cache
+             WHERE url = ?1 AND variant = ?2",
+            params![url, variant],
+            |row| {
+                Ok(Entry {
+                    etag: row.get(0)?,
+                    last_modified: row.get(1)?,
+                    content_type: row.get(2)?,
+                    body: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+        .ok()
+        .flatten()
+}
+
+/// GET a URL, revalidating a cached copy with `If-None-Match`/`If-Modified-Since`
+///
+/// Only responses carrying an `ETag` or `Last-Modified` header are cached.
+pub async fn get(
+    app: &AppHandle,
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<CachedResponse, String> {
+    let variant = variant(headers);
+    let entry = lookup(app, url, &variant);
+
+    let mut request = http::with_headers(http::client()?.get(url), url, headers)?;
+    if let Some(entry) = &entry {
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    server_auth::handle_challenge(app, url, &response);
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(entry) = entry {
+            let _ = app.state::<Database>().conn().execute(
+                "UPDATE http_cache SET fetched_at = ?3 WHERE url = ?1 AND variant = ?2",
+                params![url, variant, now()],
+            );
+            return Ok(CachedResponse {
+                status: StatusCode::OK.as_u16(),
+                body: String::from_utf8_lossy(&entry.body).to_string(),
+                content_type: entry.content_type,
+                cached: true,
+            });
+        }
+    }
+
+    let response = response.error_for_status().map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let content_type = header(CONTENT_TYPE);
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    if etag.is_some() || last_modified.is_some() {
+        let _ = conn.execute(
+            "INSERT OR REPLACE INTO http_cache
+             (url, variant, etag, last_modified, content_type, body, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                url,
+                variant,
+                etag,
+                last_modified,
+                content_type,
+                body.as_ref(),
+                now()
+            ],
+        );
+    } else {
+        let _ = conn.execute(
+            "DELETE FROM http_cache WHERE url = ?1 AND variant = ?2",
+            params![url, variant],
+        );
+    }
+
+    Ok(CachedResponse {
+        status,
+        body: String::from_utf8_lossy(&body).to_string(),
+        content_type,
+        cached: false,
+    })
+}
+
+/// Like `get`, parsing the body as JSON
+pub async fn get_json<T: DeserializeOwned>(
+    app: &AppHandle,
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<T, String> {
+    let response = get(app, url, headers).await?;
+    serde_json::from_str(&response.body).map_err(|e| e.to_string())
+}
+
+/// Drop entries that haven't been revalidated recently
+pub fn prune(app: &AppHandle) {
+    let _ = app.state::<Database>().conn().execute(
+        "DELETE FROM http_cache WHERE fetched_at < ?1",
+        params![now() - MAX_AGE_SECS],
+    );
+}
+
+/// Fetch server metadata through the conditional-request cache
+///
+/// Unchanged responses come back as `304 Not Modified` and are answered
+/// from the cache, which keeps startup traffic down on large libraries.
+#[tauri::command]
+pub async fn cached_fetch(
+    app: AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<CachedResponse, String> {
+    get(&app, &url, &headers.unwrap_or_default()).await
+}
+
+/// Remove every cached response
+#[tauri::command]
+pub async fn clear_http_cache(app: AppHandle) -> Result<(), String> {
+    app.state::<Database>()
+        .conn()
+        .execute("DELETE FROM http_cache", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,398 @@
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+use walkdir::WalkDir;
+
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::geo::{self, GpsPosition, Place};
+use crate::http;
+use crate::live::{self, LivePhoto};
+
+/// File extensions treated as importable media
+pub const MEDIA_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "heif", "avif", "tif", "tiff", "bmp", "dng",
+    "cr2", "cr3", "nef", "arw", "raf", "orf", "rw2", "mp4", "mov", "m4v", "avi", "mkv", "webm",
+    "3gp", "mts", "m2ts",
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Server the plan is for; checksums confirmed there are cached under this key
+    pub server: Option<String>,
+    /// Endpoint that accepts `{ "checksums": [...] }` and answers `{ "existing": [...] }`
+    pub check_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// Read GPS positions and label them with the offline place dataset
+    pub geocode: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    New,
+    /// Already on the server
+    DuplicateRemote,
+    /// Same content as an earlier file in this scan
+    DuplicateInScan,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportCandidate {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: i64,
+    pub checksum: String,
+    pub status: ImportStatus,
+    /// Motion component uploaded together with this still
+    pub live_photo: Option<LivePhoto>,
+    pub gps: Option<GpsPosition>,
+    pub place: Option<Place>,
+}
+
+/// Candidates sharing a place label
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaceGroup {
+    pub label: String,
+    pub country_code: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub error: String,
+}
+
+/// What an import would do, produced before any upload starts
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportPlan {
+    pub root: String,
+    pub files: Vec<ImportCandidate>,
+    pub skipped: Vec<SkippedFile>,
+    pub new_count: usize,
+    pub duplicate_count: usize,
+    /// Number of candidates that are live or motion photos
+    pub live_photo_count: usize,
+    /// Total size of the files that would be uploaded
+    pub new_bytes: u64,
+    /// Groupings by place, largest first; empty unless geocoding was requested
+    pub places: Vec<PlaceGroup>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanProgress {
+    pub hashed: usize,
+    pub total: usize,
+}
+
+#[derive(Deserialize)]
+struct CheckResponse {
+    existing: Vec<String>,
+}
+
+pub fn is_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Collect media files under `root`, skipping hidden files and directories
+pub fn walk_media(root: &Path) -> (Vec<PathBuf>, Vec<SkippedFile>) {
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| !is_hidden(e))
+    {
+        match entry {
+            Ok(entry) if entry.file_type().is_file() && is_media(entry.path()) => {
+                files.push(entry.into_path());
+            }
+            Ok(_) => {}
+            Err(e) => skipped.push(SkippedFile {
+                path: e
+                    .path()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    (files, skipped)
+}
+
+/// SHA-1 of a file's contents, hex encoded
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Hash a file, reusing the cached checksum when its size and mtime are unchanged
+pub fn cached_hash(db: &Database, path: &Path) -> std::io::Result<(String, u64, i64)> {
+    let metadata = std::fs::metadata(path)?;
+    let size = metadata.len();
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let key = path.to_string_lossy().to_string();
+
+    let cached: Option<String> = db
+        .conn()
+        .query_row(
+            "SELECT checksum FROM file_hashes WHERE path = ?1 AND size = ?2 AND modified = ?3",
+            params![key, size as i64, modified],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten();
+    if let Some(checksum) = cached {
+        return Ok((checksum, size, modified));
+    }
+
+    let checksum = hash_file(path)?;
+    let _ = db.conn().execute(
+        "INSERT OR REPLACE INTO file_hashes (path, size, modified, checksum) VALUES (?1, ?2, ?3, ?4)",
+        params![key, size as i64, modified, checksum],
+    );
+    Ok((checksum, size, modified))
+}
+
+/// Ask the server which checksums it already has, caching positive answers
+async fn check_remote(
+    db: &Database,
+    options: &ScanOptions,
+    checksums: Vec<String>,
+) -> Result<HashSet<String>, String> {
+    let server = options.server.clone().unwrap_or_default();
+    let mut existing = HashSet::new();
+    let mut unknown = Vec::new();
+
+    {
+        let conn = db.conn();
+        let mut stmt = conn
+            .prepare("SELECT 1 FROM remote_checksums WHERE server = ?1 AND checksum = ?2")
+            .map_err(|e| e.to_string())?;
+        for checksum in checksums {
+            if stmt.exists(params![server, checksum]).unwrap_or(false) {
+                existing.insert(checksum);
+            } else {
+                unknown.push(checksum);
+            }
+        }
+    }
+
+    let Some(url) = &options.check_url else {
+        return Ok(existing);
+    };
+    if unknown.is_empty() {
+        return Ok(existing);
+    }
+
+    let request = http::client()?
+        .post(url)
+        .json(&serde_json::json!({ "checksums": unknown }));
+    let response: CheckResponse = http::with_headers(request, url, &options.headers)?
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let conn = db.conn();
+    for checksum in response.existing {
+        let _ = conn.execute(
+            "INSERT OR IGNORE INTO remote_checksums (server, checksum) VALUES (?1, ?2)",
+            params![server, checksum],
+        );
+        existing.insert(checksum);
+    }
+
+    Ok(existing)
+}
+
+/// Walk a folder, hash its media files, and work out which ones are new
+///
+/// Emits `import-scan-progress` while hashing. Still/video pairs sharing a
+/// name and Android motion photos become a single candidate with
+/// `live_photo` set. Nothing is uploaded; the returned plan is meant to be
+/// reviewed first.
+#[tauri::command]
+pub async fn scan_import_folder(
+    app: AppHandle,
+    path: String,
+    options: Option<ScanOptions>,
+) -> Result<ImportPlan, String> {
+    let options = options.unwrap_or_default();
+    let root = fs_scope::check(&app, &path)?;
+    if !root.is_dir() {
+        return Err(format!("'{}' is not a directory", path));
+    }
+
+    let handle = app.clone();
+    let geocode = options.geocode;
+    let (hashed, skipped) = tauri::async_runtime::spawn_blocking(move || {
+        let db = handle.state::<Database>();
+        let (files, mut skipped) = walk_media(&root);
+        let total = files.len();
+        let mut hashed = Vec::with_capacity(total);
+
+        for (index, file) in files.into_iter().enumerate() {
+            match cached_hash(&db, &file) {
+                Ok(entry) => {
+                    let embedded = live::embedded_video(&file);
+                    let gps = geocode.then(|| geo::read_gps(&file)).flatten();
+                    let place = gps.and_then(|p| geo::lookup(p.latitude, p.longitude));
+                    hashed.push((file, entry, (embedded, gps, place)));
+                }
+                Err(e) => skipped.push(SkippedFile {
+                    path: file.to_string_lossy().to_string(),
+                    error: e.to_string(),
+                }),
+            }
+            events::publish(
+                &handle,
+                Event::ImportScanProgress(ScanProgress {
+                    hashed: index + 1,
+                    total,
+                }),
+            );
+        }
+
+        (hashed, skipped)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let checksums = hashed.iter().map(|(_, (c, _, _), _)| c.clone()).collect();
+    let remote = check_remote(&app.state::<Database>(), &options, checksums).await?;
+
+    let mut plan = ImportPlan {
+        root: path,
+        skipped,
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+
+    let paths: Vec<PathBuf> = hashed.iter().map(|(file, _, _)| file.clone()).collect();
+    let pairs = live::pair_by_name(&paths);
+    let videos: HashSet<&PathBuf> = pairs.values().collect();
+    let motion: HashMap<&PathBuf, (String, u64)> = hashed
+        .iter()
+        .filter(|(file, _, _)| videos.contains(file))
+        .map(|(file, (checksum, size, _), _)| (file, (checksum.clone(), *size)))
+        .collect();
+
+    let mut places: HashMap<String, PlaceGroup> = HashMap::new();
+
+    for (file, (checksum, size, modified), (embedded, gps, place)) in &hashed {
+        if motion.contains_key(file) {
+            continue;
+        }
+
+        let live_photo = match (pairs.get(file), embedded) {
+            (Some(video), _) => motion.get(video).map(|(checksum, size)| LivePhoto {
+                video: Some(video.to_string_lossy().to_string()),
+                video_offset: None,
+                video_size: *size,
+                video_checksum: Some(checksum.clone()),
+            }),
+            (None, Some((offset, length))) => Some(LivePhoto {
+                video: None,
+                video_offset: Some(*offset),
+                video_size: *length,
+                video_checksum: None,
+            }),
+            (None, None) => None,
+        };
+
+        let status = if remote.contains(checksum) {
+            ImportStatus::DuplicateRemote
+        } else if !seen.insert(checksum.clone()) {
+            ImportStatus::DuplicateInScan
+        } else {
+            ImportStatus::New
+        };
+
+        if status == ImportStatus::New {
+            plan.new_count += 1;
+            plan.new_bytes += size;
+            // Embedded videos are already part of the still's size
+            if let Some(LivePhoto {
+                video: Some(_),
+                video_size,
+                ..
+            }) = &live_photo
+            {
+                plan.new_bytes += video_size;
+            }
+        } else {
+            plan.duplicate_count += 1;
+        }
+        if live_photo.is_some() {
+            plan.live_photo_count += 1;
+        }
+        if let Some(place) = place {
+            places
+                .entry(place.label.clone())
+                .or_insert_with(|| PlaceGroup {
+                    label: place.label.clone(),
+                    country_code: place.country_code.clone(),
+                    count: 0,
+                })
+                .count += 1;
+        }
+
+        plan.files.push(ImportCandidate {
+            path: file.to_string_lossy().to_string(),
+            size: *size,
+            modified: *modified,
+            checksum: checksum.clone(),
+            status,
+            live_photo,
+            gps: *gps,
+            place: place.clone(),
+        });
+    }
+
+    plan.places = places.into_values().collect();
+    plan.places
+        .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+
+    Ok(plan)
+}
@@ -0,0 +1,208 @@
+//! Integrated authentication for servers behind Kerberos or NTLM
+//!
+//! Corporate servers often sit behind IIS or a proxy that signs users in with
+//! their Windows or Kerberos account. Like browsers, Apollo only offers that
+//! account to servers on the profile's allowlist.
+//!
+//! - **Native requests** send `Authorization: Negotiate` with a Kerberos
+//!   ticket for `HTTP/<host>`, from SSPI on Windows and GSSAPI elsewhere. This
+//!   needs the `integrated-auth` feature, off by default because GSSAPI builds
+//!   need the krb5 headers. The pooled client can't hold a connection through
+//!   NTLM's challenge round trip, so NTLM-only servers work in webviews only.
+//! - **Webviews** on Windows pass the allowlist to WebView2 as
+//!   `--auth-server-allowlist`, which covers both Kerberos and NTLM. WKWebView
+//!   and WebKitGTK follow the system's own settings.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "windows")]
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, WebviewWindowBuilder, Window, Wry};
+
+use crate::profiles;
+
+const INTEGRATED_AUTH_KEY: &str = "integratedAuth";
+
+/// Allowlist of the active profile, read on every native request
+static SERVERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// WebView2 arguments each profile's webviews were first created with
+///
+/// WebView2 refuses to create a webview whose options differ from another one
+/// sharing its data directory, so changes apply after a restart.
+#[cfg(target_os = "windows")]
+static BROWSER_ARGS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// wry's own arguments, which setting ours replaces
+#[cfg(target_os = "windows")]
+const DEFAULT_BROWSER_ARGS: &str = "--disable-features=msWebOOUI,msPdfOOUI,msSmartScreenProtection";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegratedAuthSettings {
+    /// Hosts allowed to sign in with the OS account, like `apollo.corp.example`
+    /// or `*.corp.example`
+    pub servers: Vec<String>,
+}
+
+/// A profile's integrated authentication settings
+pub fn read(app: &AppHandle, id: &str) -> Result<IntegratedAuthSettings, String> {
+    let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+    match store.get(INTEGRATED_AUTH_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(IntegratedAuthSettings::default()),
+    }
+}
+
+/// Load the active profile's allowlist so requests don't read the store
+pub fn load(app: &AppHandle) {
+    match read(app, &profiles::active(app)) {
+        Ok(settings) => *SERVERS.lock().unwrap_or_else(|e| e.into_inner()) = settings.servers,
+        Err(e) => log::warn!("Failed to load integrated authentication settings: {}", e),
+    }
+}
+
+/// Whether a host matches an allowlist entry; `*` matches any prefix
+fn matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix('*') {
+        Some(suffix) => host.ends_with(suffix),
+        None => !pattern.is_empty() && host == pattern,
+    }
+}
+
+fn allowed(host: &str) -> bool {
+    SERVERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|pattern| matches(pattern, host))
+}
+
+/// `Negotiate` header value for a request to an allowlisted server
+///
+/// `None` for other hosts, or when no ticket could be had for the server,
+/// so the request goes out unauthenticated and the server's 401 comes back.
+pub fn authorization(url: &reqwest::Url) -> Option<reqwest::header::HeaderValue> {
+    let host = url.host_str()?;
+    if !allowed(host) {
+        return None;
+    }
+    match negotiate_token(host) {
+        Ok(token) => {
+            let token = base64::engine::general_purpose::STANDARD.encode(token);
+            reqwest::header::HeaderValue::from_str(&format!("Negotiate {}", token)).ok()
+        }
+        Err(e) => {
+            log::warn!("Integrated authentication for {} failed: {}", host, e);
+            None
+        }
+    }
+}
+
+/// The first SPNEGO token for `HTTP/<host>`, from the OS credential cache
+#[cfg(feature = "integrated-auth")]
+fn negotiate_token(host: &str) -> Result<Vec<u8>, String> {
+    let (_pending, token) = cross_krb5::ClientCtx::new(
+        cross_krb5::InitiateFlags::NEGOTIATE_TOKEN,
+        None,
+        &format!("HTTP/{}", host),
+        None,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(token.to_vec())
+}
+
+#[cfg(not(feature = "integrated-auth"))]
+fn negotiate_token(_host: &str) -> Result<Vec<u8>, String> {
+    Err("This build of Apollo doesn't include integrated authentication".to_string())
+}
+
+/// Let a profile's webviews sign in to its allowlisted servers
+#[cfg(target_os = "windows")]
+pub fn with_browser_args<'a>(
+    app: &AppHandle,
+    builder: WebviewWindowBuilder<'a, Wry, AppHandle>,
+    id: &str,
+) -> WebviewWindowBuilder<'a, Wry, AppHandle> {
+    let mut cached = BROWSER_ARGS.lock().unwrap_or_else(|e| e.into_inner());
+    let args = cached.get_or_insert_with(HashMap::new);
+    let args = args.entry(id.to_string()).or_insert_with(|| {
+        let servers: Vec<String> = read(app, id)
+            .map(|settings| settings.servers)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|server| !server.is_empty())
+            .collect();
+        if servers.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{} --auth-server-allowlist={}",
+                DEFAULT_BROWSER_ARGS,
+                servers.join(",")
+            )
+        }
+    });
+    if args.is_empty() {
+        builder
+    } else {
+        builder.additional_browser_args(args)
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn with_browser_args<'a>(
+    _app: &AppHandle,
+    builder: WebviewWindowBuilder<'a, Wry, AppHandle>,
+    _id: &str,
+) -> WebviewWindowBuilder<'a, Wry, AppHandle> {
+    builder
+}
+
+/// Get the calling window's profile's integrated authentication allowlist
+#[tauri::command]
+pub async fn get_integrated_auth(
+    app: AppHandle,
+    window: Window,
+) -> Result<IntegratedAuthSettings, String> {
+    read(&app, &profiles::for_window(&app, window.label()))
+}
+
+/// Save the calling window's profile's integrated authentication allowlist
+///
+/// Native requests of the active profile pick it up right away; webviews after
+/// Apollo restarts.
+#[tauri::command]
+pub async fn set_integrated_auth(
+    app: AppHandle,
+    window: Window,
+    mut settings: IntegratedAuthSettings,
+) -> Result<(), String> {
+    settings.servers = settings
+        .servers
+        .iter()
+        .map(|server| server.trim().to_string())
+        .filter(|server| !server.is_empty())
+        .collect();
+    // WebView2 takes the list as one comma-separated argument
+    if let Some(server) = settings
+        .servers
+        .iter()
+        .find(|server| server.contains([',', ' ', '"', '/']))
+    {
+        return Err(format!("'{}' is not a host name or pattern", server));
+    }
+
+    let id = profiles::for_window(&app, window.label());
+    let store = profiles::profile_store(&app, &id).map_err(|e| e.to_string())?;
+    store.set(INTEGRATED_AUTH_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    if id == profiles::active(&app) {
+        load(&app);
+    }
+    Ok(())
+}
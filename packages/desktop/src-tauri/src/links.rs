@@ -0,0 +1,270 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Url};
+
+use crate::events::{self, Event};
+use crate::profiles;
+use crate::DEFAULT_SERVER_KEY;
+
+const LINK_ALLOWLIST_KEY: &str = "linkAllowlist";
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Links sent out for confirmation that `confirm_open_link` may open
+static PENDING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Payload emitted when a link points at a host that is not on the allowlist
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkConfirmation {
+    pub url: String,
+    pub host: String,
+    pub server: Option<String>,
+}
+
+/// Parse a URL and reject schemes that could trigger arbitrary protocol handlers
+fn parse_link(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+
+    if !ALLOWED_SCHEMES.contains(&parsed.scheme()) {
+        return Err(format!("URL scheme '{}' is not allowed", parsed.scheme()));
+    }
+
+    Ok(parsed)
+}
+
+fn server_host(server: &str) -> Option<String> {
+    Url::parse(server)
+        .ok()?
+        .host_str()
+        .map(|h| h.to_lowercase())
+}
+
+fn read_allowlists(app: &AppHandle) -> Result<HashMap<String, Vec<String>>, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(LINK_ALLOWLIST_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn write_allowlists(app: &AppHandle, lists: &HashMap<String, Vec<String>>) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(LINK_ALLOWLIST_KEY, serde_json::json!(lists));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Resolve the server a link was opened from, falling back to the default server
+fn resolve_server(app: &AppHandle, server: Option<String>) -> Result<Option<String>, String> {
+    if server.is_some() {
+        return Ok(server);
+    }
+
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    Ok(store
+        .get(DEFAULT_SERVER_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string())))
+}
+
+/// Check whether the host is allowed for the server. Servers without an
+/// allowlist permit every host, and the server's own host is always permitted.
+fn host_allowed(lists: &HashMap<String, Vec<String>>, server: Option<&str>, host: &str) -> bool {
+    let Some(server) = server else {
+        return true;
+    };
+
+    if server_host(server).as_deref() == Some(host) {
+        return true;
+    }
+
+    match lists.get(server) {
+        Some(hosts) if !hosts.is_empty() => hosts.iter().any(|h| h.eq_ignore_ascii_case(host)),
+        _ => true,
+    }
+}
+
+fn is_host_allowed(app: &AppHandle, server: Option<&str>, host: &str) -> Result<bool, String> {
+    if server.is_none() {
+        return Ok(true);
+    }
+    Ok(host_allowed(&read_allowlists(app)?, server, host))
+}
+
+/// Remember a link sent out for confirmation
+fn await_confirmation(url: &Url) {
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashSet::new)
+        .insert(url.to_string());
+}
+
+/// Whether a link was waiting for confirmation; it can only be confirmed once
+fn take_confirmation(url: &Url) -> bool {
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .is_some_and(|pending| pending.remove(url.as_str()))
+}
+
+/// Open a URL in the default browser
+///
+/// Links to hosts outside the server's allowlist are not opened directly;
+/// instead an `open-link-confirm` event is emitted so the UI can ask the user.
+#[tauri::command]
+pub async fn open_link(app: AppHandle, url: String, server: Option<String>) -> Result<(), String> {
+    let parsed = parse_link(&url)?;
+
+    if let Some(host) = parsed.host_str().map(|h| h.to_lowercase()) {
+        let server = resolve_server(&app, server)?;
+
+        if !is_host_allowed(&app, server.as_deref(), &host)? {
+            await_confirmation(&parsed);
+            events::publish(
+                &app,
+                Event::OpenLinkConfirm(LinkConfirmation { url, host, server }),
+            );
+            return Ok(());
+        }
+    }
+
+    open::that(parsed.as_str()).map_err(|e| e.to_string())
+}
+
+/// Open a link the user confirmed, optionally remembering its host for the server
+///
+/// Only links `open_link` asked to confirm are opened, and the scheme check
+/// still applies; confirming only lifts the host allowlist.
+#[tauri::command]
+pub async fn confirm_open_link(
+    app: AppHandle,
+    url: String,
+    server: Option<String>,
+    remember: Option<bool>,
+) -> Result<(), String> {
+    let parsed = parse_link(&url)?;
+
+    if !take_confirmation(&parsed) {
+        return Err("This link wasn't waiting for confirmation".to_string());
+    }
+
+    if remember.unwrap_or(false) {
+        if let (Some(server), Some(host)) = (resolve_server(&app, server)?, parsed.host_str()) {
+            let mut lists = read_allowlists(&app)?;
+            let hosts = lists.entry(server).or_default();
+
+            if !hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+                hosts.push(host.to_lowercase());
+                write_allowlists(&app, &lists)?;
+            }
+        }
+    }
+
+    open::that(parsed.as_str()).map_err(|e| e.to_string())
+}
+
+/// Get the link allowlist for a server
+#[tauri::command]
+pub async fn get_link_allowlist(app: AppHandle, server: String) -> Result<Vec<String>, String> {
+    Ok(read_allowlists(&app)?.remove(&server).unwrap_or_default())
+}
+
+/// Set the link allowlist for a server (an empty list disables the check)
+#[tauri::command]
+pub async fn set_link_allowlist(
+    app: AppHandle,
+    server: String,
+    hosts: Vec<String>,
+) -> Result<(), String> {
+    let mut lists = read_allowlists(&app)?;
+
+    if hosts.is_empty() {
+        lists.remove(&server);
+    } else {
+        lists.insert(
+            server,
+            hosts.into_iter().map(|h| h.trim().to_lowercase()).collect(),
+        );
+    }
+
+    write_allowlists(&app, &lists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = "https://photos.example.com";
+
+    fn lists(hosts: &[&str]) -> HashMap<String, Vec<String>> {
+        HashMap::from([(
+            SERVER.to_string(),
+            hosts.iter().map(|h| h.to_string()).collect(),
+        )])
+    }
+
+    #[test]
+    fn only_web_and_mail_links_open() {
+        assert!(parse_link("https://example.com/page").is_ok());
+        assert!(parse_link("mailto:someone@example.com").is_ok());
+        assert!(parse_link("file:///etc/passwd").is_err());
+        assert!(parse_link("javascript:alert(1)").is_err());
+        assert!(parse_link("not a url").is_err());
+    }
+
+    #[test]
+    fn allowlisted_hosts_are_allowed() {
+        let lists = lists(&["docs.example.org", "Wiki.Example.org"]);
+        assert!(host_allowed(&lists, Some(SERVER), "docs.example.org"));
+        assert!(host_allowed(&lists, Some(SERVER), "wiki.example.org"));
+        assert!(!host_allowed(&lists, Some(SERVER), "evil.example.net"));
+        assert!(!host_allowed(
+            &lists,
+            Some(SERVER),
+            "docs.example.org.evil.net"
+        ));
+    }
+
+    #[test]
+    fn server_host_is_always_allowed() {
+        let lists = lists(&["docs.example.org"]);
+        assert!(host_allowed(&lists, Some(SERVER), "photos.example.com"));
+    }
+
+    #[test]
+    fn servers_without_an_allowlist_allow_every_host() {
+        assert!(host_allowed(
+            &HashMap::new(),
+            Some(SERVER),
+            "anything.example.net"
+        ));
+        assert!(host_allowed(
+            &lists(&[]),
+            Some(SERVER),
+            "anything.example.net"
+        ));
+        assert!(host_allowed(
+            &lists(&["docs.example.org"]),
+            None,
+            "anything.example.net"
+        ));
+    }
+
+    #[test]
+    fn unasked_links_cannot_be_confirmed() {
+        let url = parse_link("https://never-asked.example.net/").unwrap();
+        assert!(!take_confirmation(&url));
+    }
+
+    #[test]
+    fn asked_links_can_be_confirmed_once() {
+        let url = parse_link("https://asked.example.net/page").unwrap();
+        await_confirmation(&url);
+        assert!(!take_confirmation(
+            &parse_link("https://asked.example.net/other").unwrap()
+        ));
+        assert!(take_confirmation(&url));
+        assert!(!take_confirmation(&url));
+    }
+}
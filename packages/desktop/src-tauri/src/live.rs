@@ -0,0 +1,114 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Stills that can carry a motion component
+const STILL_EXTENSIONS: &[&str] = &["heic", "heif", "jpg", "jpeg"];
+/// Videos that pair with a still of the same name (Apple Live Photos)
+const MOTION_EXTENSIONS: &[&str] = &["mov", "mp4"];
+/// Motion photo XMP sits near the start of the JPEG
+const XMP_SCAN_BYTES: usize = 256 * 1024;
+
+/// Video half of a live photo
+#[derive(Debug, Clone, Serialize)]
+pub struct LivePhoto {
+    /// Separate video file paired by name; `None` when embedded in the still
+    pub video: Option<String>,
+    /// Byte offset of an embedded video (Android motion photos)
+    pub video_offset: Option<u64>,
+    pub video_size: u64,
+    pub video_checksum: Option<String>,
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    extension(path).is_some_and(|e| extensions.contains(&e.as_str()))
+}
+
+/// Read an XMP attribute value, e.g. `GCamera:MicroVideoOffset="1234"`
+fn xmp_attribute<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    let start = xmp.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = xmp[start..].find('"')?;
+    Some(&xmp[start..start + end])
+}
+
+/// Length of the trailing video declared by a motion photo's XMP
+fn xmp_video_length(xmp: &str) -> Option<u64> {
+    // Legacy Google "MicroVideo" format: offset counted from the end of the file
+    if xmp_attribute(xmp, "GCamera:MicroVideo") == Some("1") {
+        return xmp_attribute(xmp, "GCamera:MicroVideoOffset")?.parse().ok();
+    }
+
+    // Motion Photo format: a container directory item with the MotionPhoto semantic
+    let flagged = ["GCamera:MotionPhoto", "Camera:MotionPhoto"]
+        .iter()
+        .any(|name| xmp_attribute(xmp, name) == Some("1"));
+    if !flagged {
+        return None;
+    }
+    xmp.split("<Container:Item")
+        .skip(1)
+        .find(|item| xmp_attribute(item, "Item:Semantic") == Some("MotionPhoto"))
+        .and_then(|item| xmp_attribute(item, "Item:Length"))
+        .and_then(|length| length.parse().ok())
+}
+
+/// Locate the MP4 appended to an Android motion photo, as `(offset, length)`
+pub fn embedded_video(path: &Path) -> Option<(u64, u64)> {
+    if !matches!(extension(path).as_deref(), Some("jpg" | "jpeg")) {
+        return None;
+    }
+
+    let file = std::fs::File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut head = Vec::with_capacity(XMP_SCAN_BYTES);
+    file.take(XMP_SCAN_BYTES as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+
+    let xmp = String::from_utf8_lossy(&head);
+    let length = xmp_video_length(&xmp)?;
+    if length == 0 || length >= size {
+        return None;
+    }
+    Some((size - length, length))
+}
+
+/// Stills and videos sharing a folder and file stem
+type NameGroup<'a> = (Vec<&'a PathBuf>, Vec<&'a PathBuf>);
+
+/// Match stills with videos of the same name in the same folder
+///
+/// Returns `still -> video` for every folder/name with exactly one of each;
+/// anything more ambiguous is left unpaired.
+pub fn pair_by_name(files: &[PathBuf]) -> HashMap<PathBuf, PathBuf> {
+    let mut groups: HashMap<(Option<&Path>, String), NameGroup> = HashMap::new();
+
+    for file in files {
+        let Some(stem) = file.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let key = (file.parent(), stem.to_lowercase());
+        if has_extension(file, STILL_EXTENSIONS) {
+            groups.entry(key).or_default().0.push(file);
+        } else if has_extension(file, MOTION_EXTENSIONS) {
+            groups.entry(key).or_default().1.push(file);
+        }
+    }
+
+    groups
+        .into_values()
+        .filter_map(
+            |(stills, videos)| match (stills.as_slice(), videos.as_slice()) {
+                ([still], [video]) => Some(((*still).clone(), (*video).clone())),
+                _ => None,
+            },
+        )
+        .collect()
+}
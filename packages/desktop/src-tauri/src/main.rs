@@ -1,13 +1,94 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::{Manager, AppHandle, WebviewWindow};
+use tauri::{Manager, AppHandle, WebviewWindow, Window};
 use tauri_plugin_dialog::DialogExt;
-use tauri_plugin_notification::NotificationExt;
-use tauri_plugin_store::StoreExt;
 use serde::{Deserialize, Serialize};
 use std::env;
 
+mod accessibility;
+mod activity;
+mod archive;
+mod audio;
+mod audit;
+mod bandwidth;
+mod cache;
+mod capabilities;
+mod capture;
+mod clipboard;
+mod coalesce;
+mod context_menu;
+mod data_dir;
+mod db;
+mod delta;
+mod devices;
+mod dialogs;
+mod displays;
+mod doh;
+mod downloads;
+mod drag_out;
+mod duplicates;
+mod events;
+mod export;
+mod folder_sync;
+mod fs_scope;
+mod geo;
+mod hosts;
+mod http;
+mod http_cache;
+mod import;
+mod integrated_auth;
+mod links;
+mod live;
+mod menubar;
+mod metered;
+mod ml;
+mod notifications;
+mod offline;
+mod pdf;
+mod pinning;
+mod platform;
+mod player;
+mod presentation;
+mod profiles;
+mod quick_look;
+mod quick_search;
+mod raw;
+mod realtime;
+mod recent;
+mod rollout;
+mod sandbox;
+mod schedule;
+mod screenshots;
+mod search_index;
+mod server;
+mod server_auth;
+mod server_headers;
+mod share;
+mod shortcuts;
+mod sidecar;
+mod slideshow;
+mod snap_layouts;
+mod ssh_tunnel;
+mod sync;
+mod takeout;
+mod transcode;
+mod transfers;
+mod tray;
+mod updates;
+mod upload;
+mod usage;
+mod user_agent;
+mod verify;
+mod video_player;
+mod virtual_drive;
+mod volumes;
+mod vpn;
+mod wake;
+mod wallpaper;
+mod window;
+mod zoom;
+
 const STORE_NAME: &str = "settings.json";
 const DEFAULT_SERVER_KEY: &str = "defaultServerUrl";
 
@@ -15,6 +96,10 @@ const DEFAULT_SERVER_KEY: &str = "defaultServerUrl";
 pub struct UpdateInfo {
     pub update_available: bool,
     pub version: Option<String>,
+    /// A newer version exists but its staged rollout hasn't reached this
+    /// machine; `install_update_now` installs it anyway
+    #[serde(default)]
+    pub held_back: bool,
 }
 
 /// Get the current OS name
@@ -36,12 +121,6 @@ fn get_version(app: AppHandle) -> String {
     app.package_info().version.to_string()
 }
 
-/// Open a URL in the default browser
-#[tauri::command]
-async fn open_link(url: String) -> Result<(), String> {
-    open::that(&url).map_err(|e| e.to_string())
-}
-
 /// Restart the application
 #[tauri::command]
 async fn restart(app: AppHandle) -> Result<(), String> {
@@ -49,24 +128,6 @@ async fn restart(app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-/// Send a system notification
-#[tauri::command]
-async fn notify(
-    app: AppHandle,
-    title: String,
-    body: Option<String>,
-    _href: Option<String>,
-) -> Result<(), String> {
-    let mut notification = app.notification().builder();
-    notification = notification.title(&title);
-
-    if let Some(b) = body {
-        notification = notification.body(&b);
-    }
-
-    notification.show().map_err(|e| e.to_string())
-}
-
 /// Open directory picker dialog
 #[tauri::command]
 async fn open_directory_picker(
@@ -74,75 +135,146 @@ async fn open_directory_picker(
     title: Option<String>,
     multiple: Option<bool>,
 ) -> Result<Option<Vec<String>>, String> {
-    let mut dialog = app.dialog().file();
+    let multiple = multiple.unwrap_or(false);
+    let picked = match dialogs::pick(title.as_deref(), true, multiple, &[], None).await {
+        Some(picked) => picked?,
+        None => {
+            let mut dialog = app.dialog().file();
 
-    if let Some(t) = title {
-        dialog = dialog.set_title(&t);
-    }
+            if let Some(t) = title {
+                dialog = dialog.set_title(&t);
+            }
 
-    if multiple.unwrap_or(false) {
-        let result = dialog.pick_folders();
-        match result {
-            Some(paths) => Ok(Some(paths.iter().map(|p| p.to_string_lossy().to_string()).collect())),
-            None => Ok(None),
+            if multiple {
+                let result = dialog.pick_folders();
+                result.map(|paths| paths.iter().map(|p| p.to_string_lossy().to_string()).collect())
+            } else {
+                let result = dialog.pick_folder();
+                result.map(|path| vec![path.to_string_lossy().to_string()])
+            }
         }
-    } else {
-        let result = dialog.pick_folder();
-        match result {
-            Some(path) => Ok(Some(vec![path.to_string_lossy().to_string()])),
-            None => Ok(None),
+    };
+
+    // Folders the user chose themselves become part of the fs scope, unless
+    // they would open up too much; they are returned all the same
+    for path in picked.iter().flatten() {
+        if let Err(e) = fs_scope::allow_directory(&app, std::path::Path::new(path)) {
+            log::warn!("Not adding {} to the file access scope: {}", path, e);
         }
     }
+    Ok(picked)
 }
 
-/// Open file picker dialog
+/// Open file picker dialog, optionally limited to some file types and
+/// starting in a given folder
 #[tauri::command]
 async fn open_file_picker(
     app: AppHandle,
     title: Option<String>,
     multiple: Option<bool>,
+    filters: Option<Vec<dialogs::FileFilter>>,
+    default_path: Option<String>,
 ) -> Result<Option<Vec<String>>, String> {
-    let mut dialog = app.dialog().file();
+    let multiple = multiple.unwrap_or(false);
+    let filters = filters.unwrap_or_default();
+    let folder = default_path.as_deref().map(std::path::Path::new);
+    let picked = match dialogs::pick(title.as_deref(), false, multiple, &filters, folder).await {
+        Some(picked) => picked?,
+        None => {
+            let mut dialog = app.dialog().file();
 
-    if let Some(t) = title {
-        dialog = dialog.set_title(&t);
-    }
+            if let Some(t) = title {
+                dialog = dialog.set_title(&t);
+            }
 
-    if multiple.unwrap_or(false) {
-        let result = dialog.pick_files();
-        match result {
-            Some(paths) => Ok(Some(paths.iter().map(|p| p.to_string_lossy().to_string()).collect())),
-            None => Ok(None),
-        }
-    } else {
-        let result = dialog.pick_file();
-        match result {
-            Some(path) => Ok(Some(vec![path.to_string_lossy().to_string()])),
-            None => Ok(None),
+            for filter in &filters {
+                dialog = dialog.add_filter(&filter.name, &filter.extensions());
+            }
+
+            if let Some(folder) = folder {
+                dialog = dialog.set_directory(folder);
+            }
+
+            if multiple {
+                let result = dialog.pick_files();
+                result.map(|paths| paths.iter().map(|p| p.to_string_lossy().to_string()).collect())
+            } else {
+                let result = dialog.pick_file();
+                result.map(|path| vec![path.to_string_lossy().to_string()])
+            }
         }
+    };
+
+    for path in picked.iter().flatten() {
+        fs_scope::allow_file(&app, std::path::Path::new(path));
     }
+    Ok(picked)
 }
 
 /// Save file picker dialog
+///
+/// `default_path` may be a file name or a full path; its folder, then
+/// `default_directory`, then the downloads folder is where the dialog starts.
+/// Returns the chosen path and the filter its extension belongs to.
 #[tauri::command]
 async fn save_file_picker(
     app: AppHandle,
     title: Option<String>,
     default_path: Option<String>,
-) -> Result<Option<String>, String> {
+    default_directory: Option<String>,
+    filters: Option<Vec<dialogs::FileFilter>>,
+) -> Result<Option<dialogs::SavedFile>, String> {
+    let filters = filters.unwrap_or_default();
+    let default_path = default_path.as_deref().map(std::path::Path::new);
+    let file_name = default_path
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string());
+    let folder = default_path
+        .and_then(|path| path.parent())
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(|parent| parent.to_path_buf())
+        .or_else(|| default_directory.map(std::path::PathBuf::from))
+        .or_else(|| downloads::dir(&app).ok());
+    if let Some(saved) = dialogs::save(
+        title.as_deref(),
+        folder.as_deref(),
+        file_name.as_deref(),
+        &filters,
+    )
+    .await
+    {
+        let saved = saved?;
+        if let Some(saved) = &saved {
+            fs_scope::allow_file(&app, std::path::Path::new(&saved.path));
+        }
+        return Ok(saved);
+    }
+
     let mut dialog = app.dialog().file();
 
     if let Some(t) = title {
         dialog = dialog.set_title(&t);
     }
 
-    if let Some(path) = default_path {
-        dialog = dialog.set_file_name(&path);
+    if let Some(folder) = folder {
+        dialog = dialog.set_directory(folder);
+    }
+
+    if let Some(name) = file_name {
+        dialog = dialog.set_file_name(&name);
+    }
+
+    for filter in &filters {
+        dialog = dialog.add_filter(&filter.name, &filter.extensions());
     }
 
     let result = dialog.save_file();
     match result {
-        Some(path) => Ok(Some(path.to_string_lossy().to_string())),
+        Some(path) => {
+            let saved = dialogs::SavedFile::new(path.to_string_lossy().to_string(), &filters, true);
+            fs_scope::allow_file(&app, std::path::Path::new(&saved.path));
+            Ok(Some(saved))
+        }
         None => Ok(None),
     }
 }
@@ -150,17 +282,30 @@ async fn save_file_picker(
 /// Check for updates
 #[tauri::command]
 async fn check_update(app: AppHandle) -> Result<UpdateInfo, String> {
+    // Flatpak and Snap installs are updated by their store
+    if sandbox::kind().is_some() {
+        return Ok(UpdateInfo {
+            update_available: false,
+            version: None,
+            held_back: false,
+        });
+    }
     // Using tauri-plugin-updater for update checks
     match app.updater() {
         Ok(updater) => {
             match updater.check().await {
-                Ok(Some(update)) => Ok(UpdateInfo {
-                    update_available: true,
-                    version: Some(update.version.clone()),
-                }),
+                Ok(Some(update)) => {
+                    let admitted = rollout::admits(&app, &update);
+                    Ok(UpdateInfo {
+                        update_available: admitted,
+                        version: Some(update.version.clone()),
+                        held_back: !admitted,
+                    })
+                }
                 Ok(None) => Ok(UpdateInfo {
                     update_available: false,
                     version: None,
+                    held_back: false,
                 }),
                 Err(e) => Err(e.to_string()),
             }
@@ -172,11 +317,34 @@ async fn check_update(app: AppHandle) -> Result<UpdateInfo, String> {
 /// Install update
 #[tauri::command]
 async fn install_update(app: AppHandle) -> Result<(), String> {
+    install(&app, false).await
+}
+
+/// Install the newest update even if its staged rollout hasn't reached this machine
+#[tauri::command]
+async fn install_update_now(app: AppHandle) -> Result<(), String> {
+    install(&app, true).await
+}
+
+async fn install(app: &AppHandle, ignore_rollout: bool) -> Result<(), String> {
+    if sandbox::kind().is_some() {
+        return Err("This install is updated by Flatpak or Snap, not by Apollo".to_string());
+    }
+    if let Some(installed) = updates::install_downloaded(app) {
+        return installed;
+    }
     match app.updater() {
         Ok(updater) => {
             match updater.check().await {
+                Ok(Some(update)) if !ignore_rollout && !rollout::admits(app, &update) => {
+                    Err(format!(
+                        "Apollo {} isn't rolled out to this machine yet",
+                        update.version
+                    ))
+                }
                 Ok(Some(update)) => {
-                    update.download_and_install(|_, _| {}, || {}).await.map_err(|e| e.to_string())
+                    let bytes = updates::fetch(app, &update).await?;
+                    update.install(&bytes).map_err(|e| e.to_string())
                 }
                 Ok(None) => Err("No update available".to_string()),
                 Err(e) => Err(e.to_string()),
@@ -188,8 +356,8 @@ async fn install_update(app: AppHandle) -> Result<(), String> {
 
 /// Get the default server URL from settings
 #[tauri::command]
-async fn get_default_server_url(app: AppHandle) -> Result<Option<String>, String> {
-    let store = app.store(STORE_NAME).map_err(|e| e.to_string())?;
+async fn get_default_server_url(app: AppHandle, window: Window) -> Result<Option<String>, String> {
+    let store = profiles::window_store(&app, window.label()).map_err(|e| e.to_string())?;
 
     match store.get(DEFAULT_SERVER_KEY) {
         Some(value) => {
@@ -205,8 +373,12 @@ async fn get_default_server_url(app: AppHandle) -> Result<Option<String>, String
 
 /// Set the default server URL in settings
 #[tauri::command]
-async fn set_default_server_url(app: AppHandle, url: Option<String>) -> Result<(), String> {
-    let store = app.store(STORE_NAME).map_err(|e| e.to_string())?;
+async fn set_default_server_url(
+    app: AppHandle,
+    window: Window,
+    url: Option<String>,
+) -> Result<(), String> {
+    let store = profiles::window_store(&app, window.label()).map_err(|e| e.to_string())?;
 
     match url {
         Some(u) => {
@@ -229,21 +401,250 @@ fn main() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_store::Builder::new().build())
-        .invoke_handler(tauri::generate_handler![
+        .plugin(tauri_plugin_log::Builder::new().build())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(shortcuts::handle_global)
+                .build(),
+        )
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .menu(zoom::menu)
+        .manage(audit::Audit::default())
+        .manage(cache::MediaCache::default())
+        .manage(capabilities::Capabilities::default())
+        .manage(events::EventBus::default())
+        .manage(fs_scope::FsScope::default())
+        .manage(profiles::ActiveProfile::default())
+        .manage(profiles::ProfileWindows::default())
+        .manage(usage::Usage::default())
+        .manage(shortcuts::Shortcuts::default())
+        .manage(sidecar::Sidecars::default())
+        .manage(ssh_tunnel::Tunnels::default())
+        .manage(transcode::Transcodes::default())
+        .manage(archive::Archives::default())
+        .manage(transfers::Transfers::default())
+        .manage(schedule::Schedule::default())
+        .manage(bandwidth::Bandwidth::default())
+        .manage(coalesce::Coalescer::default())
+        .manage(sync::SyncEngine::default())
+        .manage(screenshots::ScreenshotWatcher::default())
+        .manage(updates::Updates::default())
+        .invoke_handler(capabilities::guard(tauri::generate_handler![
             get_os,
             get_version,
-            open_link,
+            capabilities::get_window_capabilities,
+            audit::get_audit_log,
+            usage::get_usage_stats,
+            displays::get_displays,
+            displays::move_window_to_display,
+            displays::get_display_color_info,
+            accessibility::get_accessibility_preferences,
+            accessibility::announce,
+            audio::list_audio_devices,
+            audio::set_audio_output,
+            player::player_enqueue,
+            player::player_control,
+            player::get_player_state,
+            video_player::open_video_player,
+            video_player::video_control,
+            video_player::get_video_tracks,
+            window::set_content_protection,
+            zoom::get_window_zoom,
+            zoom::set_window_zoom,
+            zoom::get_ui_scale,
+            zoom::set_ui_scale,
+            snap_layouts::set_snap_layout_area,
+            slideshow::start_external_slideshow,
+            slideshow::control_slideshow,
+            slideshow::stop_external_slideshow,
+            presentation::enable_presentation_mode,
+            presentation::disable_presentation_mode,
+            presentation::get_presentation_mode,
+            window::open_private_window,
+            window::open_guest_window,
+            export::export_items,
+            pdf::export_album_pdf,
+            archive::create_archive,
+            archive::extract_archive,
+            archive::cancel_archive,
+            wallpaper::set_wallpaper,
+            wallpaper::set_lock_screen_image,
+            virtual_drive::mount_virtual_drive,
+            virtual_drive::unmount_virtual_drive,
+            virtual_drive::get_virtual_drive,
+            quick_look::quick_look,
+            quick_look::close_quick_look,
+            transfers::enqueue_transfers,
+            transfers::list_transfers,
+            transfers::cancel_transfer,
+            transfers::retry_transfer,
+            transfers::clear_finished_transfers,
+            transfers::pause_transfers,
+            transfers::resume_transfers,
+            transfers::get_transfers_paused,
+            schedule::get_transfer_schedule,
+            schedule::set_transfer_schedule,
+            schedule::get_transfer_policy,
+            bandwidth::get_bandwidth_limits,
+            bandwidth::set_bandwidth_limits,
+            http_cache::cached_fetch,
+            http_cache::clear_http_cache,
+            coalesce::coalesced_fetch,
+            coalesce::batched_fetch,
+            server::check_server_compatibility,
+            server::get_server_update_settings,
+            vpn::get_vpn_status,
+            vpn::get_vpn_settings,
+            vpn::set_vpn_settings,
+            ssh_tunnel::get_ssh_tunnel_settings,
+            ssh_tunnel::set_ssh_tunnel_settings,
+            ssh_tunnel::open_ssh_tunnel,
+            ssh_tunnel::close_ssh_tunnel,
+            ssh_tunnel::get_ssh_tunnel_status,
+            wake::wake_server,
+            server::set_server_update_settings,
+            sync::get_sync_selections,
+            sync::estimate_sync_size,
+            sync::enable_sync,
+            sync::unsync_album,
+            sync::sync_now,
+            offline::pin_album_offline,
+            offline::unpin_album_offline,
+            offline::get_offline_albums,
+            folder_sync::get_folder_pairs,
+            folder_sync::set_folder_pair,
+            folder_sync::remove_folder_pair,
+            folder_sync::preview_folder_sync,
+            folder_sync::sync_folder_pair,
+            verify::verify_backup,
+            import::scan_import_folder,
+            volumes::list_removable_volumes,
+            devices::list_mtp_devices,
+            devices::import_from_device,
+            takeout::import_takeout,
+            geo::reverse_geocode,
+            ml::analyze_local_images,
+            screenshots::get_screenshot_settings,
+            screenshots::set_screenshot_settings,
+            screenshots::get_screenshot_folder,
+            capture::get_quick_capture_settings,
+            capture::set_quick_capture_settings,
+            capture::quick_capture,
+            duplicates::find_duplicates,
+            raw::extract_raw_preview,
+            transcode::transcode_video,
+            transcode::cancel_transcode,
+            sidecar::list_sidecars,
+            sidecar::start_sidecar,
+            sidecar::stop_sidecar,
+            links::open_link,
+            links::confirm_open_link,
+            links::get_link_allowlist,
+            links::set_link_allowlist,
             restart,
-            notify,
+            notifications::notify,
+            notifications::get_notification_preferences,
+            notifications::set_notification_preferences,
+            notifications::get_notification_history,
+            notifications::mark_notifications_read,
+            notifications::clear_notification_history,
+            activity::get_activity_settings,
+            activity::set_activity_settings,
+            realtime::get_realtime_settings,
+            realtime::set_realtime_settings,
+            notifications::get_notification_permission,
+            notifications::request_notification_permission,
+            events::subscribe_events,
+            events::unsubscribe_events,
+            shortcuts::declare_shortcuts,
+            shortcuts::get_shortcuts,
+            shortcuts::set_shortcut,
+            shortcuts::reset_shortcuts,
+            shortcuts::get_shortcut_support,
+            sandbox::get_sandbox_info,
+            platform::get_platform_capabilities,
+            quick_search::toggle_quick_search,
+            quick_search::hide_quick_search,
+            menubar::get_menubar_settings,
+            menubar::set_menubar_settings,
+            search_index::get_search_index_settings,
+            search_index::set_search_index_settings,
+            search_index::index_album,
+            search_index::remove_indexed_album,
+            search_index::take_opened_search_result,
+            share::create_share_shortcut,
             open_directory_picker,
             open_file_picker,
             save_file_picker,
+            fs_scope::get_fs_scope,
+            fs_scope::get_fs_scope_settings,
+            fs_scope::set_fs_scope_settings,
+            data_dir::get_data_dir,
+            data_dir::migrate_data_dir,
+            cache::get_media_cache_dir,
+            cache::set_media_cache_dir,
+            profiles::list_profiles,
+            profiles::get_window_profile,
+            profiles::open_profile_window,
+            profiles::clear_server_data,
+            user_agent::get_user_agent,
+            user_agent::set_user_agent,
+            integrated_auth::get_integrated_auth,
+            integrated_auth::set_integrated_auth,
+            pinning::get_certificate_pins,
+            pinning::set_certificate_pins,
+            doh::get_doh_settings,
+            doh::set_doh_settings,
+            hosts::get_host_overrides,
+            hosts::set_host_overrides,
+            downloads::get_downloads_dir,
+            downloads::set_downloads_dir,
+            downloads::pick_downloads_dir,
+            downloads::open_downloads_folder,
+            recent::add_recent_item,
+            recent::list_recent_items,
+            recent::clear_recent_items,
+            drag_out::start_drag,
+            clipboard::copy_image_from_url,
+            context_menu::show_context_menu,
+            server_headers::get_custom_headers,
+            server_headers::set_custom_headers,
+            server_auth::list_server_credentials,
+            server_auth::set_server_credentials,
+            server_auth::remove_server_credentials,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::delete_profile,
             check_update,
             install_update,
+            install_update_now,
+            updates::get_update_settings,
+            updates::set_update_settings,
+            updates::get_update_status,
             get_default_server_url,
             set_default_server_url,
-        ])
+        ]))
+        .on_page_load(zoom::handle_page_load)
+        .on_window_event(|window, event| {
+            displays::handle_window_event(window, event);
+            capabilities::handle_window_event(window, event);
+            events::handle_window_event(window, event);
+            window::handle_window_event(window, event);
+            slideshow::handle_window_event(window, event);
+            presentation::handle_window_event(window, event);
+            quick_search::handle_window_event(window, event);
+            menubar::handle_window_event(window, event);
+            profiles::handle_window_event(window, event);
+        })
         .setup(|app| {
+            app.manage(db::Database::open(app.handle())?);
+            data_dir::cleanup(app.handle());
+            cache::init(app.handle());
+            http_cache::prune(app.handle());
+            audit::prune(app.handle());
+            window::wipe_guest_data(app.handle());
+            profiles::create_main_window(app.handle())?;
+
             // Set up window decorations for macOS
             #[cfg(target_os = "macos")]
             {
@@ -254,8 +655,63 @@ fn main() {
                 }
             }
 
+            displays::restore_windows(app.handle());
+            displays::watch_displays(app.handle().clone());
+            volumes::watch_volumes(app.handle().clone());
+            accessibility::watch_accessibility(app.handle().clone());
+            audio::watch_audio_devices(app.handle().clone());
+            bandwidth::load(app.handle());
+            user_agent::load(app.handle());
+            integrated_auth::load(app.handle());
+            pinning::load(app.handle());
+            doh::load(app.handle());
+            hosts::load(app.handle());
+            server_headers::load(app.handle());
+            server_auth::load(app.handle().clone());
+            transfers::resume(app.handle());
+            drag_out::cleanup(app.handle());
+            tray::setup(app.handle())?;
+            app.on_menu_event(context_menu::handle_menu_event);
+            app.on_menu_event(zoom::handle_menu_event);
+            if let Err(e) = menubar::apply(app.handle()) {
+                log::warn!("Menu bar mode disabled: {}", e);
+            }
+            search_index::init(app.handle());
+            schedule::watch_schedule(app.handle().clone());
+            sync::watch_sync(app.handle().clone());
+            usage::start(app.handle().clone());
+            updates::start(app.handle().clone());
+            activity::watch_activity(app.handle().clone());
+            realtime::watch_realtime(app.handle().clone());
+            server::start(app.handle().clone());
+            vpn::start(app.handle().clone());
+
+            sidecar::register(app.handle(), sidecar::SidecarSpec::tool(transcode::FFMPEG));
+            ml::register(app.handle());
+
+            if let Err(e) = screenshots::apply(app.handle()) {
+                log::warn!("Screenshot auto-upload disabled: {}", e);
+            }
+            quick_search::register(app.handle());
+            if let Err(e) = capture::apply(app.handle()) {
+                log::warn!("Quick capture hotkey disabled: {}", e);
+            }
+            sidecar::watch_health(app.handle().clone());
+
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Give sidecar services a chance to exit cleanly
+            if let tauri::RunEvent::Exit = event {
+                usage::flush(app);
+                sidecar::shutdown(app);
+                ssh_tunnel::shutdown(app);
+                presentation::shutdown(app);
+                virtual_drive::shutdown(app);
+                quick_look::shutdown();
+                video_player::shutdown();
+            }
+        });
 }
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconEvent};
+use tauri::{
+    AppHandle, Manager, PhysicalPosition, PhysicalSize, Rect, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder, Window, WindowEvent,
+};
+
+use crate::capabilities::{self, Capability};
+use crate::profiles;
+use crate::tray;
+
+const MENUBAR_KEY: &str = "menubar";
+/// Label of the popover window shown under the menu bar icon
+pub const POPOVER_LABEL: &str = "tray-popover";
+/// Frontend route with transfer status and a quick upload button
+const POPOVER_ROUTE: &str = "index.html#/tray-popover";
+const POPOVER_WIDTH: f64 = 360.0;
+const POPOVER_HEIGHT: f64 = 480.0;
+/// Gap between the menu bar icon and the popover
+const POPOVER_MARGIN: i32 = 4;
+
+/// Whether menu bar mode is in effect, checked on every window close
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Run as a menu bar app with no dock icon
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MenubarSettings {
+    /// Hide the dock icon, open the popover on click, and keep running when
+    /// the main window is closed. macOS only.
+    pub enabled: bool,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<MenubarSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(MENUBAR_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(MenubarSettings::default()),
+    }
+}
+
+/// Switch the activation policy and tray behavior to match the saved settings
+pub fn apply(app: &AppHandle) -> Result<(), String> {
+    let enabled = cfg!(target_os = "macos") && read_settings(app)?.enabled;
+    let was_enabled = ENABLED.swap(enabled, Ordering::Relaxed);
+
+    #[cfg(target_os = "macos")]
+    {
+        let policy = match enabled {
+            true => tauri::ActivationPolicy::Accessory,
+            false => tauri::ActivationPolicy::Regular,
+        };
+        app.set_activation_policy(policy)
+            .map_err(|e| e.to_string())?;
+    }
+
+    // The popover takes the left click; the menu stays on the right click
+    tray::set_menu_on_left_click(app, !enabled)?;
+    if was_enabled && !enabled {
+        if let Some(popover) = app.get_webview_window(POPOVER_LABEL) {
+            let _ = popover.hide();
+        }
+        // Leaving the mode with the main window closed would strand the app
+        tray::show_main_window(app);
+    }
+    Ok(())
+}
+
+fn build_popover(app: &AppHandle) -> Result<WebviewWindow, String> {
+    let builder =
+        WebviewWindowBuilder::new(app, POPOVER_LABEL, WebviewUrl::App(POPOVER_ROUTE.into()))
+            .title("Apollo")
+            .inner_size(POPOVER_WIDTH, POPOVER_HEIGHT)
+            .decorations(false)
+            .resizable(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .visible(false);
+
+    #[cfg(target_os = "macos")]
+    let builder = builder.visible_on_all_workspaces(true);
+    let builder = profiles::with_session(app, builder, &profiles::active(app))?;
+
+    // Shows transfer status and uploads picked files, nothing more
+    capabilities::grant(app, POPOVER_LABEL, &[Capability::Fs, Capability::Network]);
+    builder.build().map_err(|e| {
+        capabilities::revoke(app, POPOVER_LABEL);
+        e.to_string()
+    })
+}
+
+/// Center the popover under the tray icon, kept inside the icon's monitor
+fn position_popover(window: &WebviewWindow, icon: &Rect) -> Result<(), String> {
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let origin: PhysicalPosition<i32> = icon.position.to_physical(scale);
+    let icon_size: PhysicalSize<u32> = icon.size.to_physical(scale);
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+
+    let mut x = origin.x + icon_size.width as i32 / 2 - size.width as i32 / 2;
+    let mut y = origin.y + icon_size.height as i32 + POPOVER_MARGIN;
+    if let Ok(Some(monitor)) = window.monitor_from_point(origin.x as f64, origin.y as f64) {
+        let area = monitor.work_area();
+        let right = area.position.x + area.size.width.saturating_sub(size.width) as i32;
+        let bottom = area.position.y + area.size.height as i32;
+        x = x.clamp(area.position.x, right.max(area.position.x));
+        // A taskbar at the bottom of the screen puts the popover above the icon
+        if y + size.height as i32 > bottom {
+            y = origin.y - size.height as i32 - POPOVER_MARGIN;
+        }
+    }
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())
+}
+
+fn toggle_popover(app: &AppHandle, icon: &Rect) -> Result<(), String> {
+    let window = match app.get_webview_window(POPOVER_LABEL) {
+        Some(window) => window,
+        None => build_popover(app)?,
+    };
+    if window.is_visible().unwrap_or(false) {
+        return window.hide().map_err(|e| e.to_string());
+    }
+    position_popover(&window, icon)?;
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+/// Open or close the popover when the menu bar icon is clicked
+pub fn handle_tray_event(app: &AppHandle, event: TrayIconEvent) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let TrayIconEvent::Click {
+        rect,
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+    } = event
+    {
+        if let Err(e) = toggle_popover(app, &rect) {
+            log::warn!("Failed to toggle tray popover: {}", e);
+        }
+    }
+}
+
+/// Hide the popover on blur, and keep the app running when the main window closes
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    match event {
+        WindowEvent::Focused(false) if window.label() == POPOVER_LABEL => {
+            let _ = window.hide();
+        }
+        WindowEvent::CloseRequested { api, .. }
+            if window.label() == "main" && ENABLED.load(Ordering::Relaxed) =>
+        {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+        _ => {}
+    }
+}
+
+/// Get the menu bar mode settings
+#[tauri::command]
+pub async fn get_menubar_settings(app: AppHandle) -> Result<MenubarSettings, String> {
+    read_settings(&app)
+}
+
+/// Turn menu bar mode on or off, taking effect immediately
+///
+/// While enabled there is no dock icon, clicking the menu bar icon opens a
+/// popover with transfer status and quick upload, and closing the main window
+/// hides it instead of quitting. Only supported on macOS.
+#[tauri::command]
+pub async fn set_menubar_settings(app: AppHandle, settings: MenubarSettings) -> Result<(), String> {
+    if settings.enabled && !cfg!(target_os = "macos") {
+        return Err("Menu bar mode is only available on macOS".to_string());
+    }
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(MENUBAR_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+    apply(&app)
+}
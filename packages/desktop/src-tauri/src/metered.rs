@@ -0,0 +1,120 @@
+//! Whether the current internet connection is metered
+//!
+//! Used to hold back large optional downloads, such as updates, on mobile
+//! hotspots and capped plans. Unknown counts as unmetered.
+
+/// Start following the network state where the OS only reports it by callback
+pub fn watch() {
+    #[cfg(target_os = "macos")]
+    mac::watch();
+}
+
+/// Whether the connection is metered, roaming, or over its data limit
+pub async fn is_metered() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        mac::is_metered()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        tauri::async_runtime::spawn_blocking(win::is_metered)
+            .await
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::is_metered().await
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        false
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod mac {
+    use block::ConcreteBlock;
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Once;
+
+    #[link(name = "Network", kind = "framework")]
+    extern "C" {
+        fn nw_path_monitor_create() -> *mut c_void;
+        fn nw_path_monitor_set_update_handler(monitor: *mut c_void, handler: *const c_void);
+        fn nw_path_monitor_set_queue(monitor: *mut c_void, queue: *mut c_void);
+        fn nw_path_monitor_start(monitor: *mut c_void);
+        fn nw_path_is_expensive(path: *mut c_void) -> bool;
+        fn nw_path_is_constrained(path: *mut c_void) -> bool;
+    }
+
+    extern "C" {
+        fn dispatch_get_global_queue(identifier: isize, flags: usize) -> *mut c_void;
+    }
+
+    /// Last reported state: cellular or personal hotspot, or Low Data Mode
+    static EXPENSIVE: AtomicBool = AtomicBool::new(false);
+    static STARTED: Once = Once::new();
+
+    /// Keep an `NWPathMonitor` running for the life of the app
+    pub fn watch() {
+        STARTED.call_once(|| unsafe {
+            let monitor = nw_path_monitor_create();
+            let handler = ConcreteBlock::new(|path: *mut c_void| {
+                let expensive = nw_path_is_expensive(path) || nw_path_is_constrained(path);
+                EXPENSIVE.store(expensive, Ordering::Relaxed);
+            })
+            .copy();
+            nw_path_monitor_set_update_handler(monitor, &*handler as *const _ as *const c_void);
+            // The monitor keeps calling the handler, so neither is ever released
+            std::mem::forget(handler);
+            nw_path_monitor_set_queue(monitor, dispatch_get_global_queue(0, 0));
+            nw_path_monitor_start(monitor);
+        });
+    }
+
+    pub fn is_metered() -> bool {
+        watch();
+        EXPENSIVE.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+    pub fn is_metered() -> bool {
+        let initialized = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+        let metered = NetworkInformation::GetInternetConnectionProfile()
+            .and_then(|profile| profile.GetConnectionCost())
+            .map(|cost| {
+                matches!(
+                    cost.NetworkCostType(),
+                    Ok(NetworkCostType::Fixed | NetworkCostType::Variable)
+                ) || cost.Roaming().unwrap_or(false)
+                    || cost.OverDataLimit().unwrap_or(false)
+            })
+            .unwrap_or(false);
+        if initialized {
+            unsafe { CoUninitialize() };
+        }
+        metered
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ashpd::desktop::network_monitor::NetworkMonitor;
+
+    /// Ask the NetworkMonitor portal, which works inside and outside Flatpak
+    pub async fn is_metered() -> bool {
+        match NetworkMonitor::new().await {
+            Ok(monitor) => monitor.is_metered().await.unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+}
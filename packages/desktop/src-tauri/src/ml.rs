@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::data_dir;
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::http;
+use crate::sidecar::{self, SidecarKind, SidecarSpec};
+
+/// ONNX Runtime inference service, bundled as `binaries/apollo-ml`
+pub const ML_SIDECAR: &str = "apollo-ml";
+/// Time allowed for the service to load its models and answer health checks
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Loopback address the service listens on, fixed for the app's lifetime
+static BASE_URL: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnalyzeOptions {
+    pub tags: bool,
+    pub faces: bool,
+    /// Drop tags scoring below this
+    pub min_score: f32,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            tags: true,
+            faces: false,
+            min_score: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageTag {
+    pub label: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Face {
+    /// `[x, y, width, height]` relative to the image size (0-1)
+    pub bbox: [f32; 4],
+    pub score: f32,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImageAnalysis {
+    pub path: String,
+    pub tags: Option<Vec<ImageTag>>,
+    pub faces: Option<Vec<Face>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalyzeProgress {
+    pub processed: usize,
+    pub total: usize,
+}
+
+#[derive(Deserialize)]
+struct TagResponse {
+    tags: Vec<ImageTag>,
+}
+
+#[derive(Deserialize)]
+struct FaceResponse {
+    faces: Vec<Face>,
+}
+
+/// Reserve a loopback port and register the inference service with the
+/// sidecar manager; it is only started when first needed
+pub fn register(app: &AppHandle) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .unwrap_or(47_820);
+    let base = BASE_URL.get_or_init(|| format!("http://127.0.0.1:{}", port));
+    let models = data_dir::data_dir(app)
+        .map(|dir| dir.join("models").to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    sidecar::register(
+        app,
+        SidecarSpec {
+            name: ML_SIDECAR.to_string(),
+            kind: SidecarKind::Service,
+            args: vec![
+                "--port".to_string(),
+                port.to_string(),
+                "--models".to_string(),
+                models,
+            ],
+            health_url: Some(format!("{}/health", base)),
+            restart: true,
+        },
+    );
+}
+
+fn base_url() -> Result<&'static str, String> {
+    BASE_URL
+        .get()
+        .map(|s| s.as_str())
+        .ok_or_else(|| "Local inference is not available".to_string())
+}
+
+/// Start the service if needed and wait until it answers health checks
+async fn ensure_running(app: &AppHandle) -> Result<&'static str, String> {
+    let base = base_url()?;
+    if !sidecar::is_available(ML_SIDECAR) {
+        return Err(format!("'{}' is not installed", ML_SIDECAR));
+    }
+    sidecar::start(app, ML_SIDECAR)?;
+
+    let client = http::client()?;
+    let health = format!("{}/health", base);
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        let ready = client
+            .get(&health)
+            .send()
+            .await
+            .is_ok_and(|r| r.status().is_success());
+        if ready {
+            return Ok(base);
+        }
+        if Instant::now() >= deadline {
+            return Err("Local inference service did not start in time".to_string());
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+async fn infer<T: for<'de> Deserialize<'de>>(
+    base: &str,
+    endpoint: &str,
+    path: &str,
+) -> Result<T, String> {
+    http::client()?
+        .post(format!("{}/v1/{}", base, endpoint))
+        .json(&serde_json::json!({ "path": path }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn analyze(base: &str, path: String, options: &AnalyzeOptions) -> ImageAnalysis {
+    let mut analysis = ImageAnalysis {
+        path,
+        ..Default::default()
+    };
+
+    if options.tags {
+        match infer::<TagResponse>(base, "tag", &analysis.path).await {
+            Ok(response) => {
+                let mut tags: Vec<ImageTag> = response
+                    .tags
+                    .into_iter()
+                    .filter(|t| t.score >= options.min_score)
+                    .collect();
+                tags.sort_by(|a, b| b.score.total_cmp(&a.score));
+                analysis.tags = Some(tags);
+            }
+            Err(e) => analysis.error = Some(e),
+        }
+    }
+    if options.faces && analysis.error.is_none() {
+        match infer::<FaceResponse>(base, "faces", &analysis.path).await {
+            Ok(response) => analysis.faces = Some(response.faces),
+            Err(e) => analysis.error = Some(e),
+        }
+    }
+
+    analysis
+}
+
+/// Tag images and compute face embeddings on this device
+///
+/// Starts the local inference sidecar on first use. Files are read by the
+/// sidecar directly and nothing leaves the machine. Emits `ml-progress`
+/// after each file.
+#[tauri::command]
+pub async fn analyze_local_images(
+    app: AppHandle,
+    paths: Vec<String>,
+    options: Option<AnalyzeOptions>,
+) -> Result<Vec<ImageAnalysis>, String> {
+    fs_scope::check_all(&app, &paths)?;
+    let options = options.unwrap_or_default();
+    let base = ensure_running(&app).await?;
+    let total = paths.len();
+    let mut results = Vec::with_capacity(total);
+
+    for (index, path) in paths.into_iter().enumerate() {
+        results.push(analyze(base, path, &options).await);
+        events::publish(
+            &app,
+            Event::MlProgress(AnalyzeProgress {
+                processed: index + 1,
+                total,
+            }),
+        );
+    }
+
+    Ok(results)
+}
@@ -0,0 +1,487 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::plugin::PermissionState;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::db::{now, Database};
+use crate::events::{self, Event};
+use crate::presentation;
+use crate::profiles;
+
+const NOTIFICATION_PREFERENCES_KEY: &str = "notificationPreferences";
+/// Only the newest notifications are kept in the history
+const MAX_HISTORY: i64 = 500;
+const DEFAULT_HISTORY_LIMIT: u32 = 100;
+/// How often to check whether fullscreen playback has ended
+const FULLSCREEN_CHECK: Duration = Duration::from_secs(2);
+/// Titles listed in the digest before it just counts the rest
+const DIGEST_TITLES: usize = 5;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationCategory {
+    Sync,
+    /// Albums and photos shared with the user
+    Shares,
+    Comments,
+    Memories,
+    Errors,
+    /// Cameras, cards, and phones being connected
+    Devices,
+}
+
+/// Which notification categories the user wants to see
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationPreferences {
+    pub sync: bool,
+    pub shares: bool,
+    pub comments: bool,
+    pub memories: bool,
+    pub errors: bool,
+    pub devices: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            sync: true,
+            shares: true,
+            comments: true,
+            memories: true,
+            errors: true,
+            devices: true,
+        }
+    }
+}
+
+impl NotificationCategory {
+    fn as_str(self) -> &'static str {
+        match self {
+            NotificationCategory::Sync => "sync",
+            NotificationCategory::Shares => "shares",
+            NotificationCategory::Comments => "comments",
+            NotificationCategory::Memories => "memories",
+            NotificationCategory::Errors => "errors",
+            NotificationCategory::Devices => "devices",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sync" => Some(NotificationCategory::Sync),
+            "shares" => Some(NotificationCategory::Shares),
+            "comments" => Some(NotificationCategory::Comments),
+            "memories" => Some(NotificationCategory::Memories),
+            "errors" => Some(NotificationCategory::Errors),
+            "devices" => Some(NotificationCategory::Devices),
+            _ => None,
+        }
+    }
+}
+
+/// Notifications held back while a window is fullscreen, oldest first
+static HELD: Mutex<Vec<(String, Option<String>)>> = Mutex::new(Vec::new());
+
+/// A notification the app has shown
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// Unix time in seconds
+    pub at: i64,
+    pub title: String,
+    pub body: Option<String>,
+    pub href: Option<String>,
+    pub category: Option<NotificationCategory>,
+    pub read: bool,
+}
+
+impl NotificationPreferences {
+    pub fn allows(&self, category: NotificationCategory) -> bool {
+        match category {
+            NotificationCategory::Sync => self.sync,
+            NotificationCategory::Shares => self.shares,
+            NotificationCategory::Comments => self.comments,
+            NotificationCategory::Memories => self.memories,
+            NotificationCategory::Errors => self.errors,
+            NotificationCategory::Devices => self.devices,
+        }
+    }
+}
+
+pub fn read_preferences(app: &AppHandle) -> Result<NotificationPreferences, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(NOTIFICATION_PREFERENCES_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(NotificationPreferences::default()),
+    }
+}
+
+fn record(
+    app: &AppHandle,
+    title: &str,
+    body: Option<&str>,
+    href: Option<&str>,
+    category: Option<NotificationCategory>,
+) {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    let result = conn
+        .execute(
+            "INSERT INTO notification_history (at, title, body, href, category)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![now(), title, body, href, category.map(|c| c.as_str())],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "DELETE FROM notification_history WHERE id <= ?1",
+                params![conn.last_insert_rowid() - MAX_HISTORY],
+            )
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to record notification '{}': {}", title, e);
+    }
+}
+
+/// Whether a window is playing a video or slideshow fullscreen
+fn fullscreen_playback(app: &AppHandle) -> bool {
+    app.webview_windows().values().any(|window| {
+        window.is_fullscreen().unwrap_or(false) && window.is_visible().unwrap_or(false)
+    })
+}
+
+fn show(app: &AppHandle, title: &str, body: Option<&str>) -> Result<(), String> {
+    let mut notification = app.notification().builder();
+    notification = notification.title(title);
+
+    if let Some(b) = body {
+        notification = notification.body(b);
+    }
+
+    notification.show().map_err(|e| e.to_string())
+}
+
+/// Show what was held back, as a single digest when there's more than one
+fn release_held(app: &AppHandle) {
+    let held = std::mem::take(&mut *HELD.lock().unwrap_or_else(|e| e.into_inner()));
+    let result = match held.as_slice() {
+        [] => return,
+        [(title, body)] => show(app, title, body.as_deref()),
+        _ => {
+            let mut body: Vec<String> = held
+                .iter()
+                .take(DIGEST_TITLES)
+                .map(|(title, _)| title.clone())
+                .collect();
+            if held.len() > DIGEST_TITLES {
+                body.push(format!("and {} more", held.len() - DIGEST_TITLES));
+            }
+            show(
+                app,
+                &format!("{} notifications while you were watching", held.len()),
+                Some(&body.join("\n")),
+            )
+        }
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to show held notifications: {}", e);
+    }
+}
+
+/// Hold a notification until fullscreen playback ends
+fn hold(app: &AppHandle, title: String, body: Option<String>) {
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    held.push((title, body));
+    if held.len() > 1 {
+        return;
+    }
+    drop(held);
+
+    // The first one held starts the wait for playback to end
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(FULLSCREEN_CHECK);
+        loop {
+            interval.tick().await;
+            if !fullscreen_playback(&app) {
+                release_held(&app);
+                return;
+            }
+        }
+    });
+}
+
+/// Send a system notification
+///
+/// Notifications in a category the user has turned off are dropped here,
+/// regardless of what the frontend decided. In presentation mode only the
+/// native notification is held back; the event is still published. Either
+/// way it's kept in the notification history.
+///
+/// While a window is fullscreen, such as a video or slideshow playing, only
+/// errors are shown. The rest are held until no window is fullscreen, then
+/// shown together as a digest.
+#[tauri::command]
+pub async fn notify(
+    app: AppHandle,
+    title: String,
+    body: Option<String>,
+    href: Option<String>,
+    category: Option<NotificationCategory>,
+) -> Result<(), String> {
+    if let Some(c) = category {
+        if !read_preferences(&app)?.allows(c) {
+            return Ok(());
+        }
+    }
+
+    if !presentation::suppresses_notifications() {
+        let critical = matches!(category, Some(NotificationCategory::Errors));
+        match !critical && fullscreen_playback(&app) {
+            true => hold(&app, title.clone(), body.clone()),
+            false => show(&app, &title, body.as_deref())?,
+        }
+    }
+    record(&app, &title, body.as_deref(), href.as_deref(), category);
+    events::publish(
+        &app,
+        Event::Notification {
+            title,
+            body,
+            href,
+            category,
+        },
+    );
+    Ok(())
+}
+
+/// Get the notification category preferences
+#[tauri::command]
+pub async fn get_notification_preferences(
+    app: AppHandle,
+) -> Result<NotificationPreferences, String> {
+    read_preferences(&app)
+}
+
+/// Set the notification category preferences
+#[tauri::command]
+pub async fn set_notification_preferences(
+    app: AppHandle,
+    preferences: NotificationPreferences,
+) -> Result<(), String> {
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(NOTIFICATION_PREFERENCES_KEY, serde_json::json!(preferences));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// List the notifications the app has shown, newest first
+///
+/// The newest 500 are kept. `unread` limits the list to notifications not
+/// yet marked read.
+#[tauri::command]
+pub async fn get_notification_history(
+    app: AppHandle,
+    unread: Option<bool>,
+    limit: Option<u32>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, at, title, body, href, category, read FROM notification_history
+             WHERE (?1 = 0 OR read = 0)
+             ORDER BY id DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            params![
+                unread.unwrap_or(false),
+                limit.unwrap_or(DEFAULT_HISTORY_LIMIT)
+            ],
+            |row| {
+                Ok(HistoryEntry {
+                    id: row.get(0)?,
+                    at: row.get(1)?,
+                    title: row.get(2)?,
+                    body: row.get(3)?,
+                    href: row.get(4)?,
+                    category: row
+                        .get::<_, Option<String>>(5)?
+                        .and_then(|c| NotificationCategory::parse(&c)),
+                    read: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Mark notifications in the history as read, or all of them without `ids`
+#[tauri::command]
+pub async fn mark_notifications_read(app: AppHandle, ids: Option<Vec<i64>>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    match ids {
+        Some(ids) => {
+            let mut stmt = conn
+                .prepare("UPDATE notification_history SET read = 1 WHERE id = ?1")
+                .map_err(|e| e.to_string())?;
+            for id in ids {
+                stmt.execute(params![id]).map_err(|e| e.to_string())?;
+            }
+        }
+        None => {
+            conn.execute("UPDATE notification_history SET read = 1", [])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Forget every notification in the history
+#[tauri::command]
+pub async fn clear_notification_history(app: AppHandle) -> Result<(), String> {
+    app.state::<Database>()
+        .conn()
+        .execute("DELETE FROM notification_history", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Get the OS-level notification permission for the app
+#[tauri::command]
+pub async fn get_notification_permission(app: AppHandle) -> Result<PermissionState, String> {
+    #[cfg(target_os = "macos")]
+    if let Some(state) = macos::permission_state() {
+        return Ok(state);
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(state) = win::permission_state(&app.config().identifier) {
+        return Ok(state);
+    }
+
+    app.notification()
+        .permission_state()
+        .map_err(|e| e.to_string())
+}
+
+/// Ask the OS for notification permission, prompting the user where supported
+///
+/// Windows has no runtime prompt, so this only reports the current state there.
+#[tauri::command]
+pub async fn request_notification_permission(app: AppHandle) -> Result<PermissionState, String> {
+    #[cfg(target_os = "macos")]
+    if let Some(state) = macos::request_permission() {
+        return Ok(state);
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(state) = win::permission_state(&app.config().identifier) {
+        return Ok(state);
+    }
+
+    app.notification()
+        .request_permission()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use block::ConcreteBlock;
+    use cocoa::base::{id, nil, BOOL, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tauri::plugin::PermissionState;
+
+    #[link(name = "UserNotifications", kind = "framework")]
+    extern "C" {}
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // UNAuthorizationStatus values
+    const NOT_DETERMINED: i64 = 0;
+    const DENIED: i64 = 1;
+
+    // UNAuthorizationOptionBadge | UNAuthorizationOptionSound | UNAuthorizationOptionAlert
+    const AUTHORIZATION_OPTIONS: u64 = 1 | 2 | 4;
+
+    /// UNUserNotificationCenter raises an exception when the process has no
+    /// bundle (e.g. `tauri dev`), so only use it from a bundled app.
+    fn is_bundled() -> bool {
+        unsafe {
+            let bundle: id = msg_send![class!(NSBundle), mainBundle];
+            let identifier: id = msg_send![bundle, bundleIdentifier];
+            identifier != nil
+        }
+    }
+
+    pub fn permission_state() -> Option<PermissionState> {
+        if !is_bundled() {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        unsafe {
+            let center: id = msg_send![class!(UNUserNotificationCenter), currentNotificationCenter];
+            let handler = ConcreteBlock::new(move |settings: id| {
+                let status: i64 = msg_send![settings, authorizationStatus];
+                let _ = tx.send(status);
+            })
+            .copy();
+            let _: () = msg_send![center, getNotificationSettingsWithCompletionHandler: &*handler];
+        }
+
+        match rx.recv_timeout(TIMEOUT).ok()? {
+            NOT_DETERMINED => Some(PermissionState::Prompt),
+            DENIED => Some(PermissionState::Denied),
+            _ => Some(PermissionState::Granted),
+        }
+    }
+
+    pub fn request_permission() -> Option<PermissionState> {
+        if !is_bundled() {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        unsafe {
+            let center: id = msg_send![class!(UNUserNotificationCenter), currentNotificationCenter];
+            let handler = ConcreteBlock::new(move |granted: BOOL, _error: id| {
+                let _ = tx.send(granted == YES);
+            })
+            .copy();
+            let _: () = msg_send![center, requestAuthorizationWithOptions: AUTHORIZATION_OPTIONS completionHandler: &*handler];
+        }
+
+        match rx.recv_timeout(TIMEOUT).ok()? {
+            true => Some(PermissionState::Granted),
+            false => Some(PermissionState::Denied),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    use tauri::plugin::PermissionState;
+    use windows::core::HSTRING;
+    use windows::UI::Notifications::{NotificationSetting, ToastNotificationManager};
+
+    /// Read the toast setting for the app's AUMID, which reflects both the
+    /// per-app toggle and system-wide/group-policy switches.
+    pub fn permission_state(identifier: &str) -> Option<PermissionState> {
+        let notifier =
+            ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(identifier)).ok()?;
+
+        match notifier.Setting().ok()? {
+            NotificationSetting::Enabled => Some(PermissionState::Granted),
+            _ => Some(PermissionState::Denied),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager};
+
+use crate::sync::{self, SyncEngine, SyncPass, SyncSelection};
+use crate::transfers;
+
+/// Where to fetch a pinned album's contents from
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PinOptions {
+    pub server: String,
+    pub name: Option<String>,
+    /// Endpoint answering with a JSON list of `SyncItem` for the album
+    pub list_url: String,
+    /// Change feed for delta syncs; see `SyncSelection::changes_url`
+    pub changes_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// Rendition to keep offline; originals when unset
+    pub quality: Option<String>,
+}
+
+/// Offline availability of a pinned album
+#[derive(Debug, Clone, Serialize)]
+pub struct OfflineAlbum {
+    pub server: String,
+    pub album_id: String,
+    pub name: Option<String>,
+    pub quality: Option<String>,
+    pub items: usize,
+    /// Items available offline
+    pub downloaded: usize,
+    /// Items still queued or downloading
+    pub pending: usize,
+    /// Size of the offline copies on disk
+    pub bytes: u64,
+}
+
+fn status(app: &AppHandle, selection: &SyncSelection) -> OfflineAlbum {
+    let mut album = OfflineAlbum {
+        server: selection.server.clone(),
+        album_id: selection.album_id.clone(),
+        name: selection.name.clone(),
+        quality: selection.quality.clone(),
+        items: 0,
+        downloaded: 0,
+        pending: 0,
+        bytes: 0,
+    };
+
+    for path in sync::synced_paths(app, &selection.server, &selection.album_id).into_values() {
+        album.items += 1;
+        if transfers::is_pending(app, &path) {
+            album.pending += 1;
+        } else if let Ok(metadata) = std::fs::metadata(&path) {
+            album.downloaded += 1;
+            album.bytes += metadata.len();
+        }
+    }
+    album
+}
+
+/// Keep an album available offline in the managed cache
+///
+/// The album is kept up to date by the sync engine. Pinning again with a
+/// different quality replaces the existing offline copies.
+#[tauri::command]
+pub async fn pin_album_offline(
+    app: AppHandle,
+    id: String,
+    options: PinOptions,
+) -> Result<SyncPass, String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let existing = sync::read_selections(&app)?
+        .into_iter()
+        .find(|s| s.server == options.server && s.album_id == id);
+    if let Some(existing) = existing {
+        if existing.quality != options.quality || !existing.pinned {
+            sync::remove_selection(&app, &options.server, &id, existing.pinned)?;
+        }
+    }
+
+    let selection = SyncSelection {
+        server: options.server,
+        album_id: id,
+        name: options.name,
+        folder: None,
+        list_url: options.list_url,
+        changes_url: options.changes_url,
+        headers: options.headers,
+        quality: options.quality,
+        pinned: true,
+    };
+    sync::save_selection(&app, &selection)?;
+    sync::sync_selection(&app, &selection).await
+}
+
+/// Remove an album's offline copies and stop keeping it up to date
+///
+/// Returns the number of files deleted.
+#[tauri::command]
+pub async fn unpin_album_offline(
+    app: AppHandle,
+    server: String,
+    id: String,
+) -> Result<usize, String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let pinned = sync::read_selections(&app)?
+        .iter()
+        .any(|s| s.server == server && s.album_id == id && s.pinned);
+    if !pinned {
+        return Err(format!("Album '{}' is not pinned", id));
+    }
+    sync::remove_selection(&app, &server, &id, true)
+}
+
+/// Offline status and size of every pinned album
+#[tauri::command]
+pub async fn get_offline_albums(app: AppHandle) -> Result<Vec<OfflineAlbum>, String> {
+    let selections: Vec<SyncSelection> = sync::read_selections(&app)?
+        .into_iter()
+        .filter(|s| s.pinned)
+        .collect();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        selections
+            .iter()
+            .map(|selection| status(&app, selection))
+            .collect()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
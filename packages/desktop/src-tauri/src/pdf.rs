@@ -0,0 +1,683 @@
+//! Albums as PDFs, for printing and archiving
+//!
+//! The PDF is written by hand rather than with a PDF library. Every photo is
+//! re-encoded as a baseline JPEG, which PDF embeds as it is, and pages are
+//! streamed to disk as they fill, so a large album never sits in memory.
+//! Captions use Helvetica, one of the fonts every PDF reader has built in.
+
+use futures_util::{stream, StreamExt};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+use crate::export::{self, ExportFailure, ExportItem, ExportProgress};
+use crate::fs_scope;
+use crate::http;
+
+const CONCURRENCY: usize = 4;
+const JPEG_QUALITY: u8 = 85;
+/// Space around the edge of every page, in points
+const MARGIN: f64 = 36.0;
+/// Space between contact sheet cells, in points
+const GUTTER: f64 = 8.0;
+const CAPTION_SIZE: f64 = 7.0;
+/// Longest side of a photo on its own page, about 300 dpi on A4
+const PAGE_PIXELS: u32 = 3000;
+/// Longest side of a contact sheet thumbnail
+const THUMBNAIL_PIXELS: u32 = 800;
+
+/// Objects with fixed numbers, written before the photos
+const CATALOG: usize = 1;
+/// Written last, once every page is known
+const PAGES: usize = 2;
+const FONT: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    /// Portrait width and height in points
+    fn points(self) -> (f64, f64) {
+        match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+fn default_columns() -> u32 {
+    4
+}
+
+fn default_rows() -> u32 {
+    5
+}
+
+fn default_captions() -> bool {
+    true
+}
+
+/// How photos are placed on pages
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PdfLayout {
+    /// Each photo fills a page, turned sideways for landscape photos
+    OnePerPage,
+    /// A grid of thumbnails, captioned with their file names
+    ContactSheet {
+        #[serde(default = "default_columns")]
+        columns: u32,
+        #[serde(default = "default_rows")]
+        rows: u32,
+        #[serde(default = "default_captions")]
+        captions: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    /// Shown by PDF readers in place of the file name
+    pub title: Option<String>,
+    /// Extra headers (e.g. auth) sent with every download
+    pub headers: HashMap<String, String>,
+}
+
+/// Summary returned once the PDF is written
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PdfReport {
+    pub pages: usize,
+    pub added: usize,
+    pub failed: Vec<ExportFailure>,
+}
+
+/// A photo ready to embed
+struct Photo {
+    jpeg: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Decode, turn upright, shrink to at most `max` pixels, and re-encode
+fn prepare(bytes: &[u8], max: u32) -> Result<Photo, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| format!("Not an image that can be added: {}", e))?;
+    let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    image.apply_orientation(orientation);
+    if image.width().max(image.height()) > max {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    let image = image.into_rgb8();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&image)
+        .map_err(|e| e.to_string())?;
+    Ok(Photo {
+        jpeg,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    item: &ExportItem,
+    headers: &HashMap<String, String>,
+    max: u32,
+) -> Result<Photo, String> {
+    let request = http::with_headers(client.get(&item.url), &item.url, headers)?;
+    let bytes = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(move || prepare(&bytes, max))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// A PDF string in Helvetica's encoding; characters it lacks become `?`
+fn text(value: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in value.chars() {
+        let byte = match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        };
+        if matches!(byte, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(byte);
+    }
+    out.push(b')');
+    out
+}
+
+/// Streams numbered objects to a file, remembering where each one starts
+struct PdfWriter {
+    out: BufWriter<File>,
+    written: u64,
+    /// Offset of each object, by its number minus one
+    offsets: Vec<u64>,
+    pages: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = Self {
+            out: BufWriter::new(File::create(path)?),
+            written: 0,
+            offsets: vec![0; FONT],
+            pages: Vec::new(),
+        };
+        // The binary comment tells transfer tools the file isn't text
+        writer.write(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n")?;
+        writer.object(
+            CATALOG,
+            format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES).as_bytes(),
+        )?;
+        writer.object(
+            FONT,
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        )?;
+        Ok(writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn object(&mut self, number: usize, body: &[u8]) -> io::Result<()> {
+        self.offsets[number - 1] = self.written;
+        self.write(format!("{} 0 obj\n", number).as_bytes())?;
+        self.write(body)?;
+        self.write(b"\nendobj\n")
+    }
+
+    fn stream(&mut self, number: usize, dict: &str, data: &[u8]) -> io::Result<()> {
+        self.offsets[number - 1] = self.written;
+        self.write(
+            format!(
+                "{} 0 obj\n<< {} /Length {} >>\nstream\n",
+                number,
+                dict,
+                data.len()
+            )
+            .as_bytes(),
+        )?;
+        self.write(data)?;
+        self.write(b"\nendstream\nendobj\n")
+    }
+
+    fn image(&mut self, photo: &Photo) -> io::Result<usize> {
+        let number = self.reserve();
+        let dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8 /Filter /DCTDecode",
+            photo.width, photo.height
+        );
+        self.stream(number, &dict, &photo.jpeg)?;
+        Ok(number)
+    }
+
+    /// Add a page drawing `content`, which paints each of `images` as `/Im<number>`
+    fn page(
+        &mut self,
+        (width, height): (f64, f64),
+        content: &[u8],
+        images: &[usize],
+    ) -> io::Result<()> {
+        let contents = self.reserve();
+        self.stream(contents, "", content)?;
+        let page = self.reserve();
+        let xobjects: String = images
+            .iter()
+            .map(|n| format!("/Im{} {} 0 R ", n, n))
+            .collect();
+        let dict = format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 {} 0 R >> /XObject << {}>> >> /Contents {} 0 R >>",
+            PAGES, width, height, FONT, xobjects, contents
+        );
+        self.object(page, dict.as_bytes())?;
+        self.pages.push(page);
+        Ok(())
+    }
+
+    fn finish(mut self, title: Option<&str>) -> io::Result<usize> {
+        let kids: Vec<String> = self.pages.iter().map(|n| format!("{} 0 R", n)).collect();
+        let pages = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            self.pages.len()
+        );
+        self.object(PAGES, pages.as_bytes())?;
+
+        let info = self.reserve();
+        let mut dict = b"<< /Producer (Apollo)".to_vec();
+        if let Some(title) = title {
+            dict.extend_from_slice(b" /Title ");
+            dict.extend(text(title));
+        }
+        dict.extend_from_slice(b" >>");
+        self.object(info, &dict)?;
+
+        // Every cross-reference entry is exactly 20 bytes
+        let xref = self.written;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            CATALOG,
+            info,
+            xref
+        ));
+        self.write(table.as_bytes())?;
+        self.out.flush()?;
+        Ok(self.pages.len())
+    }
+}
+
+/// Paint an image as large as fits in a box, centered
+fn draw(image: usize, photo: &Photo, x: f64, y: f64, width: f64, height: f64) -> Vec<u8> {
+    let scale = (width / photo.width as f64).min(height / photo.height as f64);
+    let (w, h) = (photo.width as f64 * scale, photo.height as f64 * scale);
+    format!(
+        "q {:.2} 0 0 {:.2} {:.2} {:.2} cm /Im{} Do Q\n",
+        w,
+        h,
+        x + (width - w) / 2.0,
+        y + (height - h) / 2.0,
+        image
+    )
+    .into_bytes()
+}
+
+/// A caption cut short to fit `width`, going by Helvetica's average glyph
+fn caption(x: f64, y: f64, width: f64, value: &str) -> Vec<u8> {
+    let fits = (width / (CAPTION_SIZE * 0.5)) as usize;
+    let value = match value.chars().count() > fits {
+        true => format!(
+            "{}...",
+            value
+                .chars()
+                .take(fits.saturating_sub(3))
+                .collect::<String>()
+        ),
+        false => value.to_string(),
+    };
+    let mut out = format!("BT /F1 {} Tf {:.2} {:.2} Td ", CAPTION_SIZE, x, y).into_bytes();
+    out.extend(text(&value));
+    out.extend_from_slice(b" Tj ET\n");
+    out
+}
+
+/// Places photos on pages as they arrive, writing each page once it's full
+struct Composer {
+    writer: PdfWriter,
+    layout: PdfLayout,
+    size: (f64, f64),
+    content: Vec<u8>,
+    images: Vec<usize>,
+    placed: usize,
+}
+
+impl Composer {
+    fn add(&mut self, photo: &Photo, filename: &str) -> io::Result<()> {
+        let image = self.writer.image(photo)?;
+        let (width, height) = self.size;
+        match self.layout {
+            PdfLayout::OnePerPage => {
+                let size = match photo.width > photo.height {
+                    true => (height, width),
+                    false => (width, height),
+                };
+                let content = draw(
+                    image,
+                    photo,
+                    MARGIN,
+                    MARGIN,
+                    size.0 - 2.0 * MARGIN,
+                    size.1 - 2.0 * MARGIN,
+                );
+                self.writer.page(size, &content, &[image])
+            }
+            PdfLayout::ContactSheet {
+                columns,
+                rows,
+                captions,
+            } => {
+                let (columns, rows) = (columns.max(1) as usize, rows.max(1) as usize);
+                let cell = self.placed % (columns * rows);
+                let (column, row) = ((cell % columns) as f64, (cell / columns) as f64);
+                let cell_width =
+                    (width - 2.0 * MARGIN - GUTTER * (columns - 1) as f64) / columns as f64;
+                let cell_height =
+                    (height - 2.0 * MARGIN - GUTTER * (rows - 1) as f64) / rows as f64;
+                let x = MARGIN + column * (cell_width + GUTTER);
+                let y = height - MARGIN - row * (cell_height + GUTTER) - cell_height;
+                let caption_height = match captions {
+                    true => CAPTION_SIZE + 4.0,
+                    false => 0.0,
+                };
+
+                self.content.extend(draw(
+                    image,
+                    photo,
+                    x,
+                    y + caption_height,
+                    cell_width,
+                    cell_height - caption_height,
+                ));
+                if captions {
+                    self.content
+                        .extend(caption(x, y + 1.0, cell_width, filename));
+                }
+                self.images.push(image);
+                self.placed += 1;
+                match self.placed % (columns * rows) {
+                    0 => self.flush(),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.images.is_empty() {
+            return Ok(());
+        }
+        let content = std::mem::take(&mut self.content);
+        let images = std::mem::take(&mut self.images);
+        self.writer.page(self.size, &content, &images)
+    }
+
+    fn finish(mut self, title: Option<&str>) -> io::Result<usize> {
+        self.flush()?;
+        self.writer.finish(title)
+    }
+}
+
+/// Compose photos into a PDF, one per page or as a contact sheet
+///
+/// Photos keep the order they're given in. Emits `pdf-export-progress` after
+/// each one; photos that fail to download or decode are left out and listed
+/// in the report. Fails if none could be added.
+#[tauri::command]
+pub async fn export_album_pdf(
+    app: AppHandle,
+    items: Vec<ExportItem>,
+    layout: PdfLayout,
+    path: String,
+    options: Option<PdfOptions>,
+) -> Result<PdfReport, String> {
+    let options = options.unwrap_or_default();
+    let target = fs_scope::check(&app, &path)?;
+    let partial = export::partial_path(&target);
+    let client = http::client()?;
+    let total = items.len();
+    let max = match layout {
+        PdfLayout::OnePerPage => PAGE_PIXELS,
+        PdfLayout::ContactSheet { .. } => THUMBNAIL_PIXELS,
+    };
+
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let writer = PdfWriter::create(&partial).map_err(|e| e.to_string())?;
+
+    // Pages are written on a blocking thread, fed photos in order
+    let (tx, rx) = mpsc::channel::<(Photo, String)>();
+    let title = options.title.clone();
+    let composer = tauri::async_runtime::spawn_blocking(move || {
+        let mut composer = Composer {
+            writer,
+            layout,
+            size: options.page_size.points(),
+            content: Vec::new(),
+            images: Vec::new(),
+            placed: 0,
+        };
+        for (photo, filename) in rx {
+            composer.add(&photo, &filename)?;
+        }
+        composer.finish(title.as_deref())
+    });
+
+    let mut photos = stream::iter(items)
+        .map(|item| {
+            let client = &client;
+            let headers = &options.headers;
+            async move {
+                let result = fetch(client, &item, headers, max).await;
+                (item, result)
+            }
+        })
+        .buffered(CONCURRENCY);
+
+    let mut report = PdfReport::default();
+    let mut completed = 0;
+
+    while let Some((item, result)) = photos.next().await {
+        completed += 1;
+
+        match result {
+            Ok(photo) => {
+                // Only fails once the composer has stopped on an error
+                if tx.send((photo, item.filename.clone())).is_err() {
+                    break;
+                }
+                report.added += 1;
+            }
+            Err(error) => report.failed.push(ExportFailure {
+                filename: item.filename.clone(),
+                error,
+            }),
+        }
+
+        events::publish(
+            &app,
+            Event::PdfExportProgress(ExportProgress {
+                completed,
+                total,
+                filename: item.filename,
+            }),
+        );
+    }
+    drop(photos);
+    drop(tx);
+
+    let written = composer
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|pages| pages.map_err(|e| e.to_string()));
+    let result = match written {
+        Ok(_) if report.added == 0 => Err("None of the photos could be added".to_string()),
+        Ok(pages) => tokio::fs::rename(&partial, &target)
+            .await
+            .map(|_| pages)
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(pages) => {
+            report.pages = pages;
+            Ok(report)
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    fn photo(width: u32, height: u32) -> Photo {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 128]));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        prepare(&png, PAGE_PIXELS).unwrap()
+    }
+
+    /// Write a PDF of `count` photos and read it back
+    fn compose(layout: PdfLayout, count: usize, title: Option<&str>) -> (Vec<u8>, usize) {
+        let path = std::env::temp_dir().join(format!(
+            "apollo-pdf-test-{}-{}.pdf",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut composer = Composer {
+            writer: PdfWriter::create(&path).unwrap(),
+            layout,
+            size: PageSize::A4.points(),
+            content: Vec::new(),
+            images: Vec::new(),
+            placed: 0,
+        };
+        for i in 0..count {
+            let (width, height) = match i % 2 {
+                0 => (40, 30),
+                _ => (30, 40),
+            };
+            composer
+                .add(&photo(width, height), &format!("photo ({}).jpg", i))
+                .unwrap();
+        }
+        let pages = composer.finish(title).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        (bytes, pages)
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).rposition(|w| w == needle)
+    }
+
+    /// Check that `startxref` points at the table and every entry at its object
+    fn check_xref(pdf: &[u8]) -> usize {
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let marker = rfind(pdf, b"startxref\n").expect("no startxref");
+        let tail = std::str::from_utf8(&pdf[marker + b"startxref\n".len()..]).unwrap();
+        let xref: usize = tail.lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n"), "startxref is off");
+
+        let table = std::str::from_utf8(&pdf[xref..marker]).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let header: Vec<usize> = lines
+            .next()
+            .unwrap()
+            .split(' ')
+            .map(|n| n.parse().unwrap())
+            .collect();
+        assert_eq!(header[0], 0);
+        let size = header[1];
+
+        // Entries are 20 bytes each, including their two-byte line ending
+        let entries = &pdf[xref + "xref\n".len() + format!("0 {}\n", size).len()..];
+        assert_eq!(&entries[..20], b"0000000000 65535 f \n");
+        for number in 1..size {
+            let entry = std::str::from_utf8(&entries[number * 20..(number + 1) * 20]).unwrap();
+            assert!(
+                entry.ends_with(" 00000 n \n"),
+                "entry {} is malformed",
+                number
+            );
+            let offset: usize = entry[..10].parse().unwrap();
+            let object = format!("{} 0 obj\n", number);
+            assert!(
+                pdf[offset..].starts_with(object.as_bytes()),
+                "entry {} points at the wrong offset",
+                number
+            );
+        }
+        assert!(std::str::from_utf8(&entries[size * 20..])
+            .unwrap()
+            .starts_with(&format!("trailer\n<< /Size {} ", size)));
+
+        // No object is left out of the table
+        let mut number = size;
+        while find(pdf, format!("\n{} 0 obj\n", number).as_bytes()).is_some() {
+            number += 1;
+        }
+        assert_eq!(number, size, "objects are missing from the table");
+        size
+    }
+
+    #[test]
+    fn one_per_page_offsets() {
+        let (pdf, pages) = compose(PdfLayout::OnePerPage, 3, Some("Trip (2024) \\ day one"));
+        assert_eq!(pages, 3);
+        // Catalog, pages, font, info, and an image, content, and page per photo
+        assert_eq!(check_xref(&pdf), 1 + 4 + 3 * 3);
+        assert!(find(&pdf, b"/Count 3").is_some());
+        assert!(find(&pdf, b"/Title (Trip \\(2024\\) \\\\ day one)").is_some());
+    }
+
+    #[test]
+    fn contact_sheet_offsets() {
+        let layout = PdfLayout::ContactSheet {
+            columns: 2,
+            rows: 2,
+            captions: true,
+        };
+        let (pdf, pages) = compose(layout, 5, None);
+        assert_eq!(pages, 2);
+        // Catalog, pages, font, info, five images, and two pages with contents
+        assert_eq!(check_xref(&pdf), 1 + 4 + 5 + 2 * 2);
+        assert!(find(&pdf, b"/Count 2").is_some());
+    }
+
+    #[test]
+    fn empty_document_offsets() {
+        let (pdf, pages) = compose(PdfLayout::OnePerPage, 0, None);
+        assert_eq!(pages, 0);
+        assert_eq!(check_xref(&pdf), 1 + 4);
+    }
+}
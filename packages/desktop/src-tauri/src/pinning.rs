@@ -0,0 +1,287 @@
+//! Per-server certificate pinning
+//!
+//! A profile can pin the certificate, or just the public key, its servers
+//! present. Native requests to a pinned host fail when neither matches, even
+//! if the system trusts the new certificate, so a TLS-inspecting proxy or a
+//! swapped certificate is caught. Pin the public key to survive renewals that
+//! keep the key, as certbot does with `--reuse-key`.
+//!
+//! Webviews use the system's certificate checks and aren't pinned.
+
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Window};
+
+use crate::events::{self, Event};
+use crate::http;
+use crate::profiles;
+
+const CERTIFICATE_PINS_KEY: &str = "certificatePins";
+
+/// Pins of the active profile, checked on every TLS handshake
+static PINS: Mutex<Vec<CertificatePin>> = Mutex::new(Vec::new());
+/// For sending `certificate-changed` events from inside the TLS handshake
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertificatePin {
+    /// Host name the pin applies to, like `photos.example.com`
+    pub host: String,
+    /// SHA-256 fingerprints of the certificate or of its public key, in hex or
+    /// base64; a connection is accepted when any of them matches
+    pub sha256: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertificatePins {
+    pub pins: Vec<CertificatePin>,
+}
+
+/// A pinned server presented a certificate matching none of its pins
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateMismatch {
+    pub host: String,
+    /// Hex SHA-256 of the presented certificate
+    pub certificate_sha256: String,
+    /// Base64 SHA-256 of the presented public key, for pinning it instead
+    pub public_key_sha256: Option<String>,
+}
+
+/// Decode a pin from hex, with or without colons, or from base64
+///
+/// Accepts curl's `sha256//` prefix on base64 pins.
+fn decode_pin(pin: &str) -> Option<[u8; 32]> {
+    let pin = pin.trim();
+    let pin = pin
+        .strip_prefix("sha256//")
+        .or_else(|| pin.strip_prefix("sha256/"))
+        .unwrap_or(pin);
+    let hex: String = pin.chars().filter(|c| *c != ':').collect();
+    let bytes = if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        (0..32)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?
+    } else {
+        base64::engine::general_purpose::STANDARD.decode(pin).ok()?
+    };
+    bytes.try_into().ok()
+}
+
+/// One DER element split off the front of some input
+struct DerElement<'a> {
+    tag: u8,
+    /// The element with its tag and length
+    whole: &'a [u8],
+    contents: &'a [u8],
+    /// Whatever follows it
+    rest: &'a [u8],
+}
+
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n]
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+        (len, &rest[n..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let header = input.len() - rest.len();
+    Some(DerElement {
+        tag,
+        whole: &input[..header + len],
+        contents: &rest[..len],
+        rest: &rest[len..],
+    })
+}
+
+/// The `subjectPublicKeyInfo` of a DER certificate, which public-key pins hash
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let certificate = der_element(certificate)?.contents;
+    let tbs = der_element(certificate)?.contents;
+    let first = der_element(tbs)?;
+    // The version is an optional explicit [0] tag
+    let mut rest = if first.tag == 0xa0 { first.rest } else { tbs };
+    // Skip the serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        rest = der_element(rest)?.rest;
+    }
+    Some(der_element(rest)?.whole)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check a certificate against the pins for its host, if it has any
+fn check(host: &str, certificate: &[u8]) -> Result<(), CertificateMismatch> {
+    let pins: Vec<[u8; 32]> = PINS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|pin| pin.host.eq_ignore_ascii_case(host))
+        .flat_map(|pin| pin.sha256.iter().filter_map(|pin| decode_pin(pin)))
+        .collect();
+    if pins.is_empty() {
+        return Ok(());
+    }
+    let certificate_hash: [u8; 32] = Sha256::digest(certificate).into();
+    let public_key_hash: Option<[u8; 32]> =
+        subject_public_key_info(certificate).map(|spki| Sha256::digest(spki).into());
+    let matches = pins
+        .iter()
+        .any(|pin| *pin == certificate_hash || Some(*pin) == public_key_hash);
+    if matches {
+        return Ok(());
+    }
+    Err(CertificateMismatch {
+        host: host.to_string(),
+        certificate_sha256: hex(&certificate_hash),
+        public_key_sha256: public_key_hash
+            .map(|hash| base64::engine::general_purpose::STANDARD.encode(hash)),
+    })
+}
+
+/// The system's certificate checks, plus the profile's pins
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: rustls_platform_verifier::Verifier,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        // Pins first, so a proxy with a certificate the system trusts is still reported
+        let host = server_name.to_str();
+        if let Err(mismatch) = check(&host, end_entity) {
+            log::warn!("Certificate of {} doesn't match its pins", mismatch.host);
+            if let Some(app) = APP.get() {
+                events::publish(app, Event::CertificateChanged(mismatch));
+            }
+            return Err(rustls::Error::General(format!(
+                "The certificate of {} changed and no longer matches its pin",
+                host
+            )));
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// TLS settings for the shared client: reqwest's defaults with pinning added
+pub fn tls_config() -> Result<rustls::ClientConfig, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let inner =
+        rustls_platform_verifier::Verifier::new(provider.clone()).map_err(|e| e.to_string())?;
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier { inner }))
+        .with_no_client_auth();
+    // reqwest leaves ALPN to preconfigured TLS, and HTTP/2 depends on it
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// A profile's certificate pins
+pub fn read(app: &AppHandle, id: &str) -> Result<CertificatePins, String> {
+    let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+    match store.get(CERTIFICATE_PINS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(CertificatePins::default()),
+    }
+}
+
+/// Load the active profile's pins so handshakes don't read the store
+pub fn load(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    match read(app, &profiles::active(app)) {
+        Ok(settings) => *PINS.lock().unwrap_or_else(|e| e.into_inner()) = settings.pins,
+        Err(e) => log::warn!("Failed to load certificate pins: {}", e),
+    }
+}
+
+/// Get the calling window's profile's certificate pins
+#[tauri::command]
+pub async fn get_certificate_pins(
+    app: AppHandle,
+    window: Window,
+) -> Result<CertificatePins, String> {
+    read(&app, &profiles::for_window(&app, window.label()))
+}
+
+/// Save the calling window's profile's certificate pins
+///
+/// Connections already open keep going; the client is rebuilt so the next
+/// request makes a new handshake.
+#[tauri::command]
+pub async fn set_certificate_pins(
+    app: AppHandle,
+    window: Window,
+    settings: CertificatePins,
+) -> Result<(), String> {
+    for pin in &settings.pins {
+        if pin.host.trim().is_empty() {
+            return Err("Every certificate pin needs a host".to_string());
+        }
+        if let Some(bad) = pin.sha256.iter().find(|hash| decode_pin(hash).is_none()) {
+            return Err(format!("'{}' is not a SHA-256 fingerprint", bad));
+        }
+    }
+
+    let id = profiles::for_window(&app, window.label());
+    let store = profiles::profile_store(&app, &id).map_err(|e| e.to_string())?;
+    store.set(CERTIFICATE_PINS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    if id == profiles::active(&app) {
+        load(&app);
+        http::reset();
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_updater::UpdaterExt;
+
+use crate::accessibility;
+use crate::sandbox::{self, SandboxKind};
+use crate::shortcuts::{self, ShortcutBackend};
+use crate::tray;
+use crate::updates;
+
+/// Which optional native features work in this session
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformFeatures {
+    /// The tray (menu bar) icon was created
+    pub tray: bool,
+    /// Shortcuts can be registered system-wide, natively or through a portal
+    pub global_shortcuts: bool,
+    /// Apollo can update itself: the updater is configured with a signing key
+    /// and the install isn't managed by Flatpak or Snap
+    pub updater: bool,
+    /// The OS offers a credential store: always on macOS and Windows, and on
+    /// Linux when a Secret Service provider such as GNOME Keyring is running
+    pub keychain: bool,
+    /// This build can mount the library as a drive; mounting still needs
+    /// libfuse or macFUSE installed
+    pub virtual_drive: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformCapabilities {
+    /// `macos`, `windows`, or `linux`
+    pub os: String,
+    /// e.g. `14.5` on macOS or `11 (22631)` on Windows
+    pub os_version: Option<String>,
+    /// CPU architecture, e.g. `aarch64` or `x86_64`
+    pub arch: String,
+    /// WebView2, WKWebView, or WebKitGTK version
+    pub webview_version: Option<String>,
+    pub sandbox: Option<SandboxKind>,
+    /// A high contrast theme is on, so the UI should use a compliant
+    /// palette; changes are sent as `accessibility-changed`
+    pub high_contrast: bool,
+    pub features: PlatformFeatures,
+}
+
+fn updater_available(app: &AppHandle) -> bool {
+    updates::pubkey(app).is_some() && sandbox::kind().is_none() && app.updater().is_ok()
+}
+
+async fn keychain_available() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use ashpd::zbus;
+        let Ok(connection) = zbus::Connection::session().await else {
+            return false;
+        };
+        let Ok(dbus) = zbus::fdo::DBusProxy::new(&connection).await else {
+            return false;
+        };
+        let Ok(name) = zbus::names::BusName::try_from("org.freedesktop.secrets") else {
+            return false;
+        };
+        dbus.name_has_owner(name).await.unwrap_or(false)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        cfg!(any(target_os = "macos", target_os = "windows"))
+    }
+}
+
+/// Describe the platform and which optional native features actually work
+///
+/// Lets the frontend show or hide features from facts rather than guessing
+/// from the user agent.
+#[tauri::command]
+pub async fn get_platform_capabilities(app: AppHandle) -> Result<PlatformCapabilities, String> {
+    let shortcuts = shortcuts::support(&app);
+    Ok(PlatformCapabilities {
+        os: crate::get_os(),
+        os_version: sysinfo::System::os_version(),
+        arch: std::env::consts::ARCH.to_string(),
+        webview_version: tauri::webview_version().ok(),
+        sandbox: sandbox::kind(),
+        high_contrast: tauri::async_runtime::spawn_blocking(accessibility::high_contrast)
+            .await
+            .unwrap_or(false),
+        features: PlatformFeatures {
+            tray: tray::is_available(&app),
+            global_shortcuts: shortcuts.backend != ShortcutBackend::Unavailable,
+            updater: updater_available(&app),
+            keychain: keychain_available().await,
+            virtual_drive: cfg!(all(feature = "virtual-drive", unix)),
+        },
+    })
+}
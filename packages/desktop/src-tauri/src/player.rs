@@ -0,0 +1,611 @@
+//! Native music playback
+//!
+//! Webview audio leaves a gap between tracks and can't crossfade, which
+//! breaks live albums and DJ mixes. With the `native-audio` feature, off by
+//! default, tracks are decoded with symphonia and played through rodio
+//! instead: the next track is fetched ahead of time and either queued right
+//! behind the current one, for a gapless transition, or faded in over its
+//! end. Progress arrives as `player-state` events, about once a second while
+//! playing and whenever the track changes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+use crate::fs_scope;
+
+/// Longest crossfade, in seconds
+const MAX_CROSSFADE: f64 = 12.0;
+
+/// A track to play, from a server or a local file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlayerTrack {
+    /// The frontend's identifier, sent back in `player-state` events
+    pub id: String,
+    /// An http(s) URL or a local path
+    pub source: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum PlayerCommand {
+    Play,
+    Pause,
+    Next,
+    /// Stop and clear the queue
+    Stop,
+    /// Seconds into the current track
+    Seek {
+        position: f64,
+    },
+    /// `0` for gapless transitions
+    SetCrossfade {
+        seconds: f64,
+    },
+    /// From `0.0` to `1.0`
+    SetVolume {
+        volume: f32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerState {
+    /// `id` of the track playing, if any
+    pub track: Option<String>,
+    /// Seconds into the track
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub paused: bool,
+    /// Tracks waiting after the current one
+    pub queued: usize,
+    pub crossfade: f64,
+    pub volume: f32,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            track: None,
+            position: 0.0,
+            duration: None,
+            paused: false,
+            queued: 0,
+            crossfade: 0.0,
+            volume: 1.0,
+        }
+    }
+}
+
+/// Add tracks to the play queue, starting playback if nothing is playing
+///
+/// With `next` they go right after the current track instead of at the end.
+/// Local paths must be inside the file access scope.
+#[tauri::command]
+pub async fn player_enqueue(
+    app: AppHandle,
+    tracks: Vec<PlayerTrack>,
+    next: Option<bool>,
+) -> Result<PlayerState, String> {
+    let tracks = tracks
+        .into_iter()
+        .map(|mut track| {
+            if !track.source.starts_with("http://") && !track.source.starts_with("https://") {
+                track.source = fs_scope::check(&app, &track.source)?
+                    .to_string_lossy()
+                    .to_string();
+            }
+            Ok(track)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    engine::enqueue(&app, tracks, next.unwrap_or(false))
+}
+
+/// Play, pause, skip, seek, or change the crossfade or volume
+#[tauri::command]
+pub async fn player_control(command: PlayerCommand) -> Result<PlayerState, String> {
+    let command = match command {
+        PlayerCommand::SetCrossfade { seconds } if !seconds.is_finite() => {
+            return Err(format!("'{}' is not a crossfade", seconds));
+        }
+        PlayerCommand::SetCrossfade { seconds } => PlayerCommand::SetCrossfade {
+            seconds: seconds.clamp(0.0, MAX_CROSSFADE),
+        },
+        PlayerCommand::SetVolume { volume } if !volume.is_finite() => {
+            return Err(format!("'{}' is not a volume", volume));
+        }
+        PlayerCommand::SetVolume { volume } => PlayerCommand::SetVolume {
+            volume: volume.clamp(0.0, 1.0),
+        },
+        command => command,
+    };
+    engine::control(command)
+}
+
+/// What the native player is playing
+#[tauri::command]
+pub async fn get_player_state() -> Result<PlayerState, String> {
+    Ok(engine::state())
+}
+
+#[cfg(feature = "native-audio")]
+mod engine {
+    use super::{PlayerCommand, PlayerState, PlayerTrack};
+    use crate::events::{self, Event};
+    use crate::http;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+    use std::collections::{HashMap, HashSet};
+    use std::io::Cursor;
+    use std::sync::{mpsc, Mutex};
+    use std::time::{Duration, Instant};
+    use tauri::AppHandle;
+
+    /// How often the queue moves on and fades are stepped
+    const TICK: Duration = Duration::from_millis(100);
+    /// How often the position is reported while playing
+    const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+    /// Fetch the next track this long before the current one ends
+    const PRELOAD: Duration = Duration::from_secs(20);
+
+    type Audio = Decoder<Cursor<Vec<u8>>>;
+
+    /// The audio output stream, which isn't `Send`, so it lives on its own
+    /// thread until this is dropped
+    struct Output {
+        handle: OutputStreamHandle,
+        _close: mpsc::Sender<()>,
+    }
+
+    impl Output {
+        fn open() -> Result<Self, String> {
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let (close_tx, close_rx) = mpsc::channel::<()>();
+            std::thread::spawn(move || match OutputStream::try_default() {
+                Ok((stream, handle)) => {
+                    let _ = ready_tx.send(Ok(handle));
+                    // Returns once the sender is dropped
+                    let _ = close_rx.recv();
+                    drop(stream);
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e.to_string()));
+                }
+            });
+            let handle = ready_rx.recv().map_err(|e| e.to_string())??;
+            Ok(Self {
+                handle,
+                _close: close_tx,
+            })
+        }
+    }
+
+    struct Engine {
+        output: Output,
+        queue: Vec<PlayerTrack>,
+        /// The track playing, or to start once it's fetched
+        index: usize,
+        /// Playing `index`, and maybe the next track queued behind it
+        sink: Option<Sink>,
+        duration: Option<Duration>,
+        /// The duration of the next track when it's queued behind this one
+        appended: Option<Option<Duration>>,
+        /// The previous track fading out, and when it started to
+        fading: Option<(Sink, Instant)>,
+        /// Fetched tracks by queue index
+        fetched: HashMap<usize, Vec<u8>>,
+        fetching: Option<usize>,
+        /// Tracks that couldn't be fetched or decoded, which are skipped
+        failed: HashSet<usize>,
+        /// Bumped whenever queue indexes change, to drop stale fetches
+        generation: u64,
+        paused: bool,
+        crossfade: Duration,
+        volume: f32,
+        reported: Option<(PlayerState, Instant)>,
+    }
+
+    static ENGINE: Mutex<Option<Engine>> = Mutex::new(None);
+
+    fn lock() -> std::sync::MutexGuard<'static, Option<Engine>> {
+        ENGINE.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Audio, String> {
+        Decoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())
+    }
+
+    async fn fetch(track: &PlayerTrack) -> Result<Vec<u8>, String> {
+        if !track.source.starts_with("http://") && !track.source.starts_with("https://") {
+            return tokio::fs::read(&track.source)
+                .await
+                .map_err(|e| e.to_string());
+        }
+        let request = http::with_headers(
+            http::client()?.get(&track.source),
+            &track.source,
+            &track.headers,
+        )?;
+        let bytes = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?
+            .bytes()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(bytes.to_vec())
+    }
+
+    impl Engine {
+        fn state(&self) -> PlayerState {
+            let playing = self.sink.as_ref().and(self.queue.get(self.index));
+            PlayerState {
+                track: playing.map(|track| track.id.clone()),
+                position: match &self.sink {
+                    Some(sink) => sink.get_pos().as_secs_f64(),
+                    None => 0.0,
+                },
+                duration: self.duration.map(|d| d.as_secs_f64()),
+                paused: self.paused,
+                queued: self.queue.len().saturating_sub(self.index + 1),
+                crossfade: self.crossfade.as_secs_f64(),
+                volume: self.volume,
+            }
+        }
+
+        fn remaining(&self) -> Option<Duration> {
+            let sink = self.sink.as_ref()?;
+            Some(self.duration?.saturating_sub(sink.get_pos()))
+        }
+
+        /// Throw away what was fetched past the current track
+        fn invalidate(&mut self) {
+            let index = self.index;
+            self.fetched.retain(|i, _| *i <= index);
+            self.failed.retain(|i| *i <= index);
+            self.fetching = None;
+            self.generation += 1;
+        }
+
+        fn sink(&self, audio: Audio, fade_in: Duration) -> Result<Sink, String> {
+            let sink = Sink::try_new(&self.output.handle).map_err(|e| e.to_string())?;
+            sink.set_volume(self.volume);
+            match fade_in.is_zero() {
+                true => sink.append(audio),
+                false => sink.append(audio.fade_in(fade_in)),
+            }
+            Ok(sink)
+        }
+
+        /// Start `index` once it's fetched, fading it in over the last track
+        /// when crossfading; returns whether it started
+        fn start(&mut self, fade_in: Duration) -> bool {
+            let Some(bytes) = self.fetched.remove(&self.index) else {
+                return false;
+            };
+            let started = decode(bytes).and_then(|audio| {
+                let duration = audio.total_duration();
+                Ok((self.sink(audio, fade_in)?, duration))
+            });
+            match started {
+                Ok((sink, duration)) => {
+                    if self.paused {
+                        sink.pause();
+                    }
+                    if let Some(previous) = self.sink.replace(sink) {
+                        self.fading = Some((previous, Instant::now()));
+                    }
+                    self.duration = duration;
+                    self.appended = None;
+                    true
+                }
+                Err(e) => {
+                    log::warn!("Failed to play '{}': {}", self.queue[self.index].id, e);
+                    self.failed.insert(self.index);
+                    false
+                }
+            }
+        }
+
+        /// Move the queue along; returns the track to fetch next, if any
+        fn tick(&mut self) -> Option<(u64, usize, PlayerTrack)> {
+            if let Some((sink, started)) = &self.fading {
+                let elapsed = started.elapsed();
+                match elapsed >= self.crossfade {
+                    true => self.fading = None,
+                    false => sink.set_volume(
+                        self.volume * (1.0 - elapsed.as_secs_f32() / self.crossfade.as_secs_f32()),
+                    ),
+                }
+            }
+
+            // The track queued behind the current one took over
+            if let (Some(sink), Some(duration)) = (&self.sink, self.appended) {
+                if sink.len() <= 1 {
+                    self.index += 1;
+                    self.duration = duration;
+                    self.appended = None;
+                }
+            }
+            if self.sink.as_ref().is_some_and(|sink| sink.empty()) {
+                self.sink = None;
+                self.duration = None;
+                self.index += 1;
+            }
+            while self.failed.contains(&self.index) {
+                self.index += 1;
+            }
+
+            let next = self.index + 1;
+            match &self.sink {
+                None if !self.paused => {
+                    self.start(Duration::ZERO);
+                }
+                Some(sink) if self.appended.is_none() && self.fetched.contains_key(&next) => {
+                    if self.crossfade.is_zero() {
+                        // Queued right behind, so there's no gap between them
+                        let bytes = self.fetched.remove(&next).unwrap_or_default();
+                        match decode(bytes) {
+                            Ok(audio) => {
+                                self.appended = Some(audio.total_duration());
+                                sink.append(audio);
+                            }
+                            Err(e) => {
+                                log::warn!("Failed to play '{}': {}", self.queue[next].id, e);
+                                self.failed.insert(next);
+                            }
+                        }
+                    } else if !self.paused
+                        && self.remaining().is_some_and(|left| left <= self.crossfade)
+                    {
+                        self.index = next;
+                        if !self.start(self.crossfade) {
+                            self.index -= 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            let wanted = match &self.sink {
+                None => self.index,
+                Some(_)
+                    if self.appended.is_none()
+                        && self.remaining().is_some_and(|left| left <= PRELOAD) =>
+                {
+                    self.index + 1
+                }
+                Some(_) => return None,
+            };
+            if self.fetching.is_some()
+                || self.fetched.contains_key(&wanted)
+                || self.failed.contains(&wanted)
+            {
+                return None;
+            }
+            let track = self.queue.get(wanted)?.clone();
+            self.fetching = Some(wanted);
+            Some((self.generation, wanted, track))
+        }
+    }
+
+    /// Step the queue until the player is stopped
+    fn spawn_ticker(app: AppHandle) {
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(TICK);
+            loop {
+                interval.tick().await;
+
+                let (fetch_next, report) = {
+                    let mut engine = lock();
+                    let Some(engine) = engine.as_mut() else {
+                        return;
+                    };
+                    let fetch_next = engine.tick();
+
+                    let state = engine.state();
+                    let due = match &engine.reported {
+                        Some((last, at)) => {
+                            last.track != state.track
+                                || last.paused != state.paused
+                                || (!state.paused
+                                    && state.track.is_some()
+                                    && at.elapsed() >= REPORT_INTERVAL)
+                        }
+                        None => true,
+                    };
+                    if due {
+                        engine.reported = Some((state.clone(), Instant::now()));
+                    }
+                    (fetch_next, due.then_some(state))
+                };
+                if let Some(state) = report {
+                    events::publish(&app, Event::PlayerState(state));
+                }
+
+                if let Some((generation, index, track)) = fetch_next {
+                    tauri::async_runtime::spawn(async move {
+                        let result = fetch(&track).await;
+                        let mut engine = lock();
+                        let Some(engine) = engine.as_mut() else {
+                            return;
+                        };
+                        if engine.generation != generation {
+                            return;
+                        }
+                        engine.fetching = None;
+                        match result {
+                            Ok(bytes) => {
+                                engine.fetched.insert(index, bytes);
+                            }
+                            Err(e) => {
+                                log::warn!("Failed to fetch '{}': {}", track.id, e);
+                                engine.failed.insert(index);
+                            }
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    pub fn enqueue(
+        app: &AppHandle,
+        tracks: Vec<PlayerTrack>,
+        next: bool,
+    ) -> Result<PlayerState, String> {
+        let mut engine = lock();
+        if engine.is_none() {
+            *engine = Some(Engine {
+                output: Output::open()?,
+                queue: Vec::new(),
+                index: 0,
+                sink: None,
+                duration: None,
+                appended: None,
+                fading: None,
+                fetched: HashMap::new(),
+                fetching: None,
+                failed: HashSet::new(),
+                generation: 0,
+                paused: false,
+                crossfade: Duration::ZERO,
+                volume: 1.0,
+                reported: None,
+            });
+            spawn_ticker(app.clone());
+        }
+        let Some(engine) = engine.as_mut() else {
+            return Err("The player couldn't start".to_string());
+        };
+
+        match next {
+            true => {
+                // After the track already queued behind the current one
+                let at = match engine.sink {
+                    Some(_) => engine.index + 1 + engine.appended.is_some() as usize,
+                    None => engine.index,
+                }
+                .min(engine.queue.len());
+                engine.queue.splice(at..at, tracks);
+                engine.invalidate();
+            }
+            false => engine.queue.extend(tracks),
+        }
+        Ok(engine.state())
+    }
+
+    pub fn control(command: PlayerCommand) -> Result<PlayerState, String> {
+        let mut engine = lock();
+        let Some(engine) = engine.as_mut() else {
+            return Ok(PlayerState::default());
+        };
+
+        match command {
+            PlayerCommand::Play => {
+                engine.paused = false;
+                if let Some(sink) = &engine.sink {
+                    sink.play();
+                }
+                if let Some((sink, _)) = &engine.fading {
+                    sink.play();
+                }
+            }
+            PlayerCommand::Pause => {
+                engine.paused = true;
+                if let Some(sink) = &engine.sink {
+                    sink.pause();
+                }
+                if let Some((sink, _)) = &engine.fading {
+                    sink.pause();
+                }
+            }
+            PlayerCommand::Next => match (&engine.sink, engine.appended) {
+                (Some(sink), Some(duration)) => {
+                    sink.skip_one();
+                    engine.index += 1;
+                    engine.duration = duration;
+                    engine.appended = None;
+                }
+                (Some(_), None) => {
+                    if let Some(sink) = engine.sink.take() {
+                        sink.stop();
+                    }
+                    engine.duration = None;
+                    engine.index += 1;
+                }
+                // Already past the last track, where the next one enqueued starts
+                (None, _) if engine.index >= engine.queue.len() => {
+                    return Err("There's no next track".to_string());
+                }
+                (None, _) => engine.index += 1,
+            },
+            PlayerCommand::Stop => {
+                if let Some(sink) = engine.sink.take() {
+                    sink.stop();
+                }
+                engine.fading = None;
+                engine.queue.clear();
+                engine.index = 0;
+                engine.duration = None;
+                engine.appended = None;
+                engine.fetched.clear();
+                engine.failed.clear();
+                engine.fetching = None;
+                engine.generation += 1;
+            }
+            PlayerCommand::Seek { position } => {
+                let Some(sink) = &engine.sink else {
+                    return Err("Nothing is playing".to_string());
+                };
+                if !position.is_finite() || position < 0.0 {
+                    return Err(format!("'{}' is not a position", position));
+                }
+                sink.try_seek(Duration::from_secs_f64(position))
+                    .map_err(|e| e.to_string())?;
+            }
+            PlayerCommand::SetCrossfade { seconds } => {
+                engine.crossfade = Duration::from_secs_f64(seconds);
+            }
+            PlayerCommand::SetVolume { volume } => {
+                engine.volume = volume;
+                if let Some(sink) = &engine.sink {
+                    sink.set_volume(volume);
+                }
+            }
+        }
+        Ok(engine.state())
+    }
+
+    pub fn state() -> PlayerState {
+        lock()
+            .as_ref()
+            .map(|engine| engine.state())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(not(feature = "native-audio"))]
+mod engine {
+    use super::{PlayerCommand, PlayerState, PlayerTrack};
+    use tauri::AppHandle;
+
+    const UNAVAILABLE: &str = "This build of Apollo doesn't include the native audio player";
+
+    pub fn enqueue(
+        _app: &AppHandle,
+        _tracks: Vec<PlayerTrack>,
+        _next: bool,
+    ) -> Result<PlayerState, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn control(_command: PlayerCommand) -> Result<PlayerState, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn state() -> PlayerState {
+        PlayerState::default()
+    }
+}
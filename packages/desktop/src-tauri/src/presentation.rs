@@ -0,0 +1,227 @@
+//! Presentation mode, for showing photos on a projector or TV
+//!
+//! Turning it on makes a window fullscreen, keeps the display from sleeping
+//! or starting the screensaver, and holds back native notifications so
+//! nothing pops up over the photos. Turning it off puts everything back as it
+//! was. Closing the window or quitting the app ends it too; the system drops
+//! the sleep inhibition along with the process.
+
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Window, WindowEvent};
+
+struct Presentation {
+    window: String,
+    was_fullscreen: bool,
+    /// `None` when the system refused, which doesn't stop the presentation
+    inhibitor: Option<inhibit::Inhibitor>,
+}
+
+static ACTIVE: Mutex<Option<Presentation>> = Mutex::new(None);
+
+/// Whether native notifications are being held back
+pub fn suppresses_notifications() -> bool {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+fn take() -> Option<Presentation> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+/// Undo presentation mode, leaving a closed window alone
+fn restore(app: &AppHandle, presentation: Presentation) -> Result<(), String> {
+    if let Some(inhibitor) = presentation.inhibitor {
+        inhibitor.release(app);
+    }
+    match app.get_webview_window(&presentation.window) {
+        Some(window) if !presentation.was_fullscreen => {
+            window.set_fullscreen(false).map_err(|e| e.to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// End presentation mode when its window closes
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return;
+    }
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    if active
+        .as_ref()
+        .is_some_and(|presentation| presentation.window == window.label())
+    {
+        if let Some(presentation) = active.take() {
+            drop(active);
+            let _ = restore(window.app_handle(), presentation);
+        }
+    }
+}
+
+/// Release the sleep inhibition before the app exits
+pub fn shutdown(app: &AppHandle) {
+    if let Some(presentation) = take() {
+        if let Some(inhibitor) = presentation.inhibitor {
+            inhibitor.release(app);
+        }
+    }
+}
+
+/// Make the calling window fullscreen, keep the display awake, and hold back
+/// native notifications until `disable_presentation_mode`
+///
+/// Notifications still reach the frontend as `notification` events. Only one
+/// window presents at a time; enabling it in another window ends it in the
+/// first. Returns whether the display is kept awake, which fails where the
+/// system offers no way to ask, such as Linux without the desktop portal.
+#[tauri::command]
+pub async fn enable_presentation_mode(app: AppHandle, window: Window) -> Result<bool, String> {
+    let current = take();
+    if let Some(presentation) = current {
+        if presentation.window == window.label() {
+            let inhibited = presentation.inhibitor.is_some();
+            *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = Some(presentation);
+            return Ok(inhibited);
+        }
+        restore(&app, presentation)?;
+    }
+
+    let was_fullscreen = window.is_fullscreen().map_err(|e| e.to_string())?;
+    window.set_fullscreen(true).map_err(|e| e.to_string())?;
+    let inhibitor = match inhibit::Inhibitor::acquire(&app).await {
+        Ok(inhibitor) => Some(inhibitor),
+        Err(e) => {
+            log::warn!("Failed to keep the display awake: {}", e);
+            None
+        }
+    };
+    let inhibited = inhibitor.is_some();
+    *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Presentation {
+        window: window.label().to_string(),
+        was_fullscreen,
+        inhibitor,
+    });
+    Ok(inhibited)
+}
+
+/// Leave presentation mode, restoring the window and letting the display sleep
+#[tauri::command]
+pub async fn disable_presentation_mode(app: AppHandle) -> Result<(), String> {
+    match take() {
+        Some(presentation) => restore(&app, presentation),
+        None => Ok(()),
+    }
+}
+
+/// Whether a window is presenting
+#[tauri::command]
+pub async fn get_presentation_mode() -> Result<bool, String> {
+    Ok(suppresses_notifications())
+}
+
+#[cfg(target_os = "macos")]
+mod inhibit {
+    use std::process::{Child, Command};
+    use tauri::AppHandle;
+
+    /// `caffeinate` holds the assertions, and exits by itself if the app dies
+    pub struct Inhibitor(Child);
+
+    impl Inhibitor {
+        pub async fn acquire(_app: &AppHandle) -> Result<Self, String> {
+            Command::new("/usr/bin/caffeinate")
+                .args(["-d", "-i", "-w", &std::process::id().to_string()])
+                .spawn()
+                .map(Self)
+                .map_err(|e| e.to_string())
+        }
+
+        pub fn release(mut self, _app: &AppHandle) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod inhibit {
+    use tauri::AppHandle;
+    use windows::Win32::System::Power::{
+        SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+        EXECUTION_STATE,
+    };
+
+    /// The execution state belongs to the thread that set it, so it's always
+    /// set on the main thread, which lives as long as the app
+    pub struct Inhibitor;
+
+    fn set(app: &AppHandle, state: EXECUTION_STATE) -> Result<(), String> {
+        app.run_on_main_thread(move || {
+            if unsafe { SetThreadExecutionState(state) }.0 == 0 {
+                log::warn!("Failed to set the thread execution state");
+            }
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    impl Inhibitor {
+        pub async fn acquire(app: &AppHandle) -> Result<Self, String> {
+            set(
+                app,
+                ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED,
+            )?;
+            Ok(Self)
+        }
+
+        pub fn release(self, app: &AppHandle) {
+            let _ = set(app, ES_CONTINUOUS);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inhibit {
+    use ashpd::desktop::inhibit::{InhibitFlags, InhibitOptions, InhibitProxy};
+    use ashpd::desktop::Request;
+    use tauri::AppHandle;
+
+    /// Held through the XDG Inhibit portal, which covers the screensaver too
+    pub struct Inhibitor(Request<()>);
+
+    impl Inhibitor {
+        pub async fn acquire(_app: &AppHandle) -> Result<Self, String> {
+            let proxy = InhibitProxy::new().await.map_err(|e| e.to_string())?;
+            proxy
+                .inhibit(
+                    None,
+                    InhibitFlags::Idle | InhibitFlags::Suspend,
+                    InhibitOptions::default().set_reason("Presenting photos"),
+                )
+                .await
+                .map(Self)
+                .map_err(|e| e.to_string())
+        }
+
+        pub fn release(self, _app: &AppHandle) {
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = self.0.close().await {
+                    log::warn!("Failed to release the sleep inhibition: {}", e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod inhibit {
+    use tauri::AppHandle;
+
+    pub struct Inhibitor;
+
+    impl Inhibitor {
+        pub async fn acquire(_app: &AppHandle) -> Result<Self, String> {
+            Err("Not supported on this platform".to_string())
+        }
+
+        pub fn release(self, _app: &AppHandle) {}
+    }
+}
@@ -0,0 +1,499 @@
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, WebviewWindow, WebviewWindowBuilder, Window, WindowEvent, Wry};
+use tauri_plugin_store::{Store, StoreExt};
+
+use crate::capabilities;
+use crate::data_dir;
+use crate::db::now;
+use crate::integrated_auth;
+use crate::menubar;
+use crate::quick_search;
+use crate::server_auth;
+use crate::ssh_tunnel;
+use crate::tray;
+use crate::user_agent;
+use crate::zoom;
+use crate::STORE_NAME;
+
+/// Profiles and which one to start as, kept in the shared settings file
+const PROFILES_KEY: &str = "profiles";
+/// The profile from before profiles existed; its data keeps its old location
+pub const DEFAULT_PROFILE: &str = "default";
+/// Subdirectory of the data and cache directories holding the other profiles
+pub const PROFILES_SUBDIR: &str = "profiles";
+/// Webview data stays in the default location, like the default profile's
+const WEBVIEW_SUBDIR: &str = "profile-webviews";
+/// Label prefix of windows opened for a profile other than the session's
+const WINDOW_PREFIX: &str = "profile-";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    /// Unix time in seconds
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ProfileSettings {
+    profiles: Vec<Profile>,
+    active: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileList {
+    /// Every profile, the default one first
+    pub profiles: Vec<Profile>,
+    pub active: String,
+}
+
+/// The profile this session runs as
+///
+/// Decided on first use and fixed until the app restarts, so nothing reads one
+/// profile's settings and writes another's.
+#[derive(Default)]
+pub struct ActiveProfile(OnceLock<String>);
+
+/// Profile of each window opened with `open_profile_window`, keyed by label
+///
+/// Every other window runs as the active profile.
+#[derive(Default)]
+pub struct ProfileWindows(Mutex<HashMap<String, String>>);
+
+impl ProfileWindows {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn read_settings(app: &AppHandle) -> Result<ProfileSettings, String> {
+    let store = app.store(STORE_NAME).map_err(|e| e.to_string())?;
+    match store.get(PROFILES_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(ProfileSettings::default()),
+    }
+}
+
+fn write_settings(app: &AppHandle, settings: &ProfileSettings) -> Result<(), String> {
+    let store = app.store(STORE_NAME).map_err(|e| e.to_string())?;
+    store.set(PROFILES_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Id of the profile this session runs as
+pub fn active(app: &AppHandle) -> String {
+    app.state::<ActiveProfile>()
+        .0
+        .get_or_init(|| {
+            read_settings(app)
+                .ok()
+                .and_then(|s| {
+                    let active = s.active?;
+                    s.profiles.iter().any(|p| p.id == active).then_some(active)
+                })
+                .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+        })
+        .clone()
+}
+
+/// Where a profile's files live relative to the data and cache directories
+fn relative_dir(id: &str) -> PathBuf {
+    match id {
+        DEFAULT_PROFILE => PathBuf::new(),
+        id => PathBuf::from(PROFILES_SUBDIR).join(id),
+    }
+}
+
+/// Where the active profile's files live relative to the data and cache directories
+pub fn relative(app: &AppHandle) -> PathBuf {
+    relative_dir(&active(app))
+}
+
+fn store_name(id: &str) -> String {
+    match id {
+        DEFAULT_PROFILE => STORE_NAME.to_string(),
+        id => format!("profile-{}.json", id),
+    }
+}
+
+/// Settings of the active profile
+///
+/// Use this for anything tied to an account or person. The shared
+/// `STORE_NAME` file only holds where data lives and the profile list.
+pub fn store(app: &AppHandle) -> tauri_plugin_store::Result<Arc<Store<Wry>>> {
+    app.store(store_name(&active(app)))
+}
+
+/// Id of the profile a window runs as
+pub fn for_window(app: &AppHandle, label: &str) -> String {
+    app.state::<ProfileWindows>()
+        .lock()
+        .get(label)
+        .cloned()
+        .unwrap_or_else(|| active(app))
+}
+
+/// Run a window other than a profile window as a profile, such as a
+/// slideshow opened from one
+pub fn assign_window(app: &AppHandle, label: &str, id: &str) {
+    app.state::<ProfileWindows>()
+        .lock()
+        .insert(label.to_string(), id.to_string());
+}
+
+/// Forget the profile given to a window with `assign_window`
+pub fn release_window(app: &AppHandle, label: &str) {
+    app.state::<ProfileWindows>().lock().remove(label);
+}
+
+/// Settings of any profile, active or not
+pub fn profile_store(app: &AppHandle, id: &str) -> tauri_plugin_store::Result<Arc<Store<Wry>>> {
+    app.store(store_name(id))
+}
+
+/// Settings of the profile a window runs as
+///
+/// For the few settings a profile window reads from the frontend, such as its
+/// server. Background work still runs as the active profile.
+pub fn window_store(app: &AppHandle, label: &str) -> tauri_plugin_store::Result<Arc<Store<Wry>>> {
+    profile_store(app, &for_window(app, label))
+}
+
+/// Directory holding the active profile's database
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(data_dir::data_dir(app)?.join(relative(app)))
+}
+
+/// Directory holding the active profile's caches
+pub fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(data_dir::cache_dir(app)?.join(relative(app)))
+}
+
+fn webview_dir(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    let dir = app.path().app_local_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(WEBVIEW_SUBDIR).join(id))
+}
+
+/// Stable webview data store id for a profile, used on macOS
+fn data_store_identifier(id: &str) -> [u8; 16] {
+    let digest = Sha1::digest(id.as_bytes());
+    let mut identifier = [0u8; 16];
+    identifier.copy_from_slice(&digest[..16]);
+    identifier
+}
+
+/// Give a window the web session and user agent of a profile
+///
+/// Cookies, caches, web storage, and service workers live in the profile's own
+/// webview data directory (a data store on macOS), created here before the
+/// webview starts, so signing in to one server never touches another's
+/// session. The default profile keeps the webview's usual location, so its
+/// session survives.
+pub fn with_session<'a>(
+    app: &AppHandle,
+    builder: WebviewWindowBuilder<'a, Wry, AppHandle>,
+    id: &str,
+) -> Result<WebviewWindowBuilder<'a, Wry, AppHandle>, String> {
+    let builder = match user_agent::read(app, id) {
+        Some(user_agent) => builder.user_agent(&user_agent),
+        None => builder,
+    };
+    let builder = integrated_auth::with_browser_args(app, builder, id);
+    if id == DEFAULT_PROFILE {
+        return Ok(builder);
+    }
+    let dir = webview_dir(app, id)?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(builder
+        .data_directory(dir)
+        .data_store_identifier(data_store_identifier(id)))
+}
+
+/// Build the main window with the active profile's cookies and web storage
+///
+/// The window is declared with `create: false` so it can be given its own
+/// session before it opens.
+pub fn create_main_window(app: &AppHandle) -> Result<(), String> {
+    let mut config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == "main")
+        .ok_or("No main window in the app config")?
+        .clone();
+    zoom::scale_min_size(app, &mut config);
+    let mut builder = WebviewWindowBuilder::from_config(app, &config).map_err(|e| e.to_string())?;
+
+    let id = active(app);
+    if id != DEFAULT_PROFILE {
+        let name = read_settings(app)?
+            .profiles
+            .into_iter()
+            .find(|p| p.id == id)
+            .map(|p| p.name)
+            .unwrap_or_default();
+        builder = builder.title(format!("{} — {}", config.title, name));
+    }
+    with_session(app, builder, &id)?
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Forget a profile window once it closes
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) && window.label().starts_with(WINDOW_PREFIX) {
+        window
+            .app_handle()
+            .state::<ProfileWindows>()
+            .lock()
+            .remove(window.label());
+    }
+}
+
+/// Every profile, the default one first
+fn all_profiles(app: &AppHandle) -> Result<Vec<Profile>, String> {
+    let mut profiles = vec![Profile {
+        id: DEFAULT_PROFILE.to_string(),
+        name: "Default".to_string(),
+        created_at: 0,
+    }];
+    profiles.extend(read_settings(app)?.profiles);
+    Ok(profiles)
+}
+
+/// List profiles and which one is active
+#[tauri::command]
+pub async fn list_profiles(app: AppHandle) -> Result<ProfileList, String> {
+    Ok(ProfileList {
+        profiles: all_profiles(&app)?,
+        active: active(&app),
+    })
+}
+
+/// The profile the calling window runs as
+#[tauri::command]
+pub async fn get_window_profile(app: AppHandle, window: Window) -> Result<Profile, String> {
+    let id = for_window(&app, window.label());
+    all_profiles(&app)?
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("Profile '{}' not found", id))
+}
+
+/// Open another profile in its own window, alongside the current one
+///
+/// The window gets that profile's web session, so it stays signed in to its
+/// own server, and its server setting. Sync, transfers, and other background
+/// work keep running as the active profile. Opening the active profile, or
+/// one that already has a window, focuses the existing window. Returns the
+/// window's label.
+#[tauri::command]
+pub async fn open_profile_window(app: AppHandle, id: String) -> Result<String, String> {
+    let profile = all_profiles(&app)?
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("Profile '{}' not found", id))?;
+    if id == active(&app) {
+        tray::show_main_window(&app);
+        return Ok("main".to_string());
+    }
+
+    let label = format!("{}{}", WINDOW_PREFIX, id);
+    if let Some(window) = app.get_webview_window(&label) {
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(label);
+    }
+
+    let mut config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == "main")
+        .ok_or("No main window in the app config")?
+        .clone();
+    config.label = label.clone();
+    zoom::scale_min_size(&app, &mut config);
+    let builder = WebviewWindowBuilder::from_config(&app, &config)
+        .map_err(|e| e.to_string())?
+        .title(format!("{} — {}", config.title, profile.name));
+    let builder = with_session(&app, builder, &id)?;
+
+    app.state::<ProfileWindows>()
+        .lock()
+        .insert(label.clone(), id);
+    // It is the profile's own main window, so it is as trusted as this one
+    capabilities::grant(&app, &label, &capabilities::ALL);
+    if let Err(e) = builder.build() {
+        capabilities::revoke(&app, &label);
+        app.state::<ProfileWindows>().lock().remove(&label);
+        return Err(e.to_string());
+    }
+    Ok(label)
+}
+
+/// Add a profile with its own settings, database, caches, and web session
+///
+/// Sign-ins live in the webview's storage, which is separate for each
+/// profile, and proxy passwords in the OS keychain under the profile's id.
+#[tauri::command]
+pub async fn create_profile(app: AppHandle, name: String) -> Result<Profile, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name can't be empty".to_string());
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let profile = Profile {
+        id: format!("{:x}", nanos),
+        name,
+        created_at: now(),
+    };
+
+    let mut settings = read_settings(&app)?;
+    settings.profiles.push(profile.clone());
+    write_settings(&app, &settings)?;
+    Ok(profile)
+}
+
+/// Restart the app as another profile
+#[tauri::command]
+pub async fn switch_profile(app: AppHandle, id: String) -> Result<(), String> {
+    let mut settings = read_settings(&app)?;
+    if id != DEFAULT_PROFILE && !settings.profiles.iter().any(|p| p.id == id) {
+        return Err(format!("Profile '{}' not found", id));
+    }
+    if id == active(&app) {
+        return Ok(());
+    }
+    settings.active = Some(id);
+    write_settings(&app, &settings)?;
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        handle.restart();
+    });
+    Ok(())
+}
+
+/// Open windows using a profile's web session
+fn session_windows(app: &AppHandle, id: &str) -> Vec<WebviewWindow> {
+    let labels = match id == active(app) {
+        true => vec![
+            "main".to_string(),
+            quick_search::LABEL.to_string(),
+            menubar::POPOVER_LABEL.to_string(),
+        ],
+        false => vec![format!("{}{}", WINDOW_PREFIX, id)],
+    };
+    labels
+        .iter()
+        .filter_map(|label| app.get_webview_window(label))
+        .collect()
+}
+
+/// Wipe one profile's cookies, web caches, and local storage
+///
+/// A targeted fix for a stuck sign-in: the profile's settings, database, and
+/// other profiles' sessions are left alone. Open windows of the profile are
+/// reloaded so they start signed out.
+#[tauri::command]
+pub async fn clear_server_data(app: AppHandle, profile: String) -> Result<(), String> {
+    if !all_profiles(&app)?.iter().any(|p| p.id == profile) {
+        return Err(format!("Profile '{}' not found", profile));
+    }
+
+    let windows = session_windows(&app, &profile);
+    if let Some(window) = windows.first() {
+        window
+            .clear_all_browsing_data()
+            .map_err(|e| e.to_string())?;
+        for window in &windows {
+            let _ = window.reload();
+        }
+        return Ok(());
+    }
+    if profile == DEFAULT_PROFILE {
+        // Its data is in the webview's shared location, only reachable through a webview
+        return Err("Open the default profile's window to clear its data".to_string());
+    }
+
+    #[cfg(target_os = "macos")]
+    app.remove_data_store(data_store_identifier(&profile))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let dir = webview_dir(&app, &profile)?;
+    tauri::async_runtime::spawn_blocking(move || match std::fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete a profile along with its settings, data, caches, and web session
+///
+/// The default and active profiles can't be deleted.
+#[tauri::command]
+pub async fn delete_profile(app: AppHandle, id: String) -> Result<(), String> {
+    if id == DEFAULT_PROFILE {
+        return Err("The default profile can't be deleted".to_string());
+    }
+    if id == active(&app) {
+        return Err("Switch to another profile before deleting this one".to_string());
+    }
+    if app
+        .get_webview_window(&format!("{}{}", WINDOW_PREFIX, id))
+        .is_some()
+    {
+        return Err("Close this profile's window before deleting it".to_string());
+    }
+    let mut settings = read_settings(&app)?;
+    let before = settings.profiles.len();
+    settings.profiles.retain(|p| p.id != id);
+    if settings.profiles.len() == before {
+        return Err(format!("Profile '{}' not found", id));
+    }
+
+    let store = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(store_name(&id));
+    let dirs = [
+        data_dir::data_dir(&app)?.join(relative_dir(&id)),
+        data_dir::cache_dir(&app)?.join(relative_dir(&id)),
+        webview_dir(&app, &id)?,
+    ];
+    write_settings(&app, &settings)?;
+
+    ssh_tunnel::close(&app, &id);
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        // Before the store file goes, since it lists the keychain items
+        server_auth::forget_profile(&handle, &id);
+        let _ = std::fs::remove_file(store);
+        for dir in dirs {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to remove profile data {}: {}", dir.display(), e);
+                }
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
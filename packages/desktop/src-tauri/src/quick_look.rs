@@ -0,0 +1,183 @@
+//! Previewing files with macOS Quick Look
+//!
+//! Downloaded files are tagged with the server they came from, as browsers
+//! do, so Finder's Get Info and Quick Look show where a photo came from and
+//! Spotlight can find it by server. The `quick_look` command opens the
+//! system preview panel for a file, for pressing space in the app like in
+//! Finder.
+
+use std::path::Path;
+use std::process::Child;
+use std::sync::Mutex;
+use tauri::{AppHandle, Url};
+
+use crate::fs_scope;
+
+/// The running preview, closed before another one opens
+static PREVIEW: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Where a download came from, without credentials or query parameters
+fn origin(url: &str) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+/// Record the URL a file was downloaded from, on macOS
+///
+/// Failures are only logged, since the file itself is fine without it.
+pub fn tag(path: &Path, url: &str) {
+    let Some(origin) = origin(url) else {
+        return;
+    };
+    if let Err(e) = os::set_where_from(path, &origin) {
+        log::debug!("Failed to tag '{}': {}", path.display(), e);
+    }
+}
+
+/// Close the preview before the app exits, which would leave it open
+pub fn shutdown() {
+    close_preview();
+}
+
+fn close_preview() {
+    if let Some(mut child) = PREVIEW.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Preview a file in the Quick Look panel, replacing any open preview
+///
+/// Only available on macOS; elsewhere the frontend can open the file in its
+/// default app instead.
+#[tauri::command]
+pub async fn quick_look(app: AppHandle, path: String) -> Result<(), String> {
+    let path = fs_scope::check(&app, &path)?;
+    if !path.exists() {
+        return Err(format!("'{}' does not exist", path.display()));
+    }
+    close_preview();
+    let child = os::preview(&path)?;
+    *PREVIEW.lock().unwrap_or_else(|e| e.into_inner()) = Some(child);
+    Ok(())
+}
+
+/// Close the Quick Look panel, if it's open
+#[tauri::command]
+pub async fn close_quick_look() -> Result<(), String> {
+    close_preview();
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::process::{Child, Command, Stdio};
+
+    const WHERE_FROMS: &str = "com.apple.metadata:kMDItemWhereFroms";
+
+    /// Marker byte for a plist object, with its length inline when it fits
+    fn header(marker: u8, len: usize, out: &mut Vec<u8>) {
+        if len < 15 {
+            out.push(marker | len as u8);
+            return;
+        }
+        out.push(marker | 0x0f);
+        match len {
+            len if len <= 0xff => out.extend([0x10, len as u8]),
+            len if len <= 0xffff => {
+                out.push(0x11);
+                out.extend((len as u16).to_be_bytes());
+            }
+            len => {
+                out.push(0x12);
+                out.extend((len as u32).to_be_bytes());
+            }
+        }
+    }
+
+    /// A binary plist holding an array of one string, the form Spotlight
+    /// expects in `kMDItemWhereFroms`
+    fn where_froms(url: &str) -> Vec<u8> {
+        let mut out = b"bplist00".to_vec();
+        let array = out.len();
+        header(0xa0, 1, &mut out);
+        out.push(1);
+
+        let string = out.len();
+        match url.is_ascii() {
+            true => {
+                header(0x50, url.len(), &mut out);
+                out.extend(url.as_bytes());
+            }
+            false => {
+                let units: Vec<u16> = url.encode_utf16().collect();
+                header(0x60, units.len(), &mut out);
+                out.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+            }
+        }
+
+        let offsets = out.len();
+        out.extend((array as u64).to_be_bytes());
+        out.extend((string as u64).to_be_bytes());
+        // Trailer: sizes of offsets and references, object count, the top
+        // object, and where the offset table starts
+        out.extend([0; 6]);
+        out.extend([8, 1]);
+        out.extend(2u64.to_be_bytes());
+        out.extend(0u64.to_be_bytes());
+        out.extend((offsets as u64).to_be_bytes());
+        out
+    }
+
+    pub fn set_where_from(path: &Path, url: &str) -> Result<(), String> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        let name = CString::new(WHERE_FROMS).map_err(|e| e.to_string())?;
+        let value = where_froms(url);
+        let result = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            )
+        };
+        match result {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error().to_string()),
+        }
+    }
+
+    /// `qlmanage` shows the same panel as Finder, and closes with it when killed
+    pub fn preview(path: &Path) -> Result<Child, String> {
+        Command::new("/usr/bin/qlmanage")
+            .arg("-p")
+            .arg(path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod os {
+    use std::path::Path;
+    use std::process::Child;
+
+    pub fn set_where_from(_path: &Path, _url: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn preview(_path: &Path) -> Result<Child, String> {
+        Err("Quick Look is only available on macOS".to_string())
+    }
+}
@@ -0,0 +1,137 @@
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+    Window, WindowEvent,
+};
+
+use crate::capabilities::{self, Capability};
+use crate::profiles;
+use crate::shortcuts::{self, ShortcutAction};
+use crate::tray;
+
+/// Label of the popup window, which is created once and then hidden and shown
+pub const LABEL: &str = "quick-search";
+/// Frontend route rendering the search box and its results
+const ROUTE: &str = "index.html#/quick-search";
+const SHORTCUT_ID: &str = "quick-search";
+const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+Space";
+const WIDTH: f64 = 680.0;
+const HEIGHT: f64 = 420.0;
+
+/// Declare the hotkey that toggles the popup
+pub fn register(app: &AppHandle) {
+    shortcuts::declare_native(
+        app,
+        ShortcutAction {
+            id: SHORTCUT_ID.to_string(),
+            label: "Quick search".to_string(),
+            default: Some(DEFAULT_SHORTCUT.to_string()),
+            global: true,
+        },
+        on_shortcut,
+    );
+    shortcuts::refresh(app);
+}
+
+fn on_shortcut(app: &AppHandle) {
+    if let Err(e) = toggle(app) {
+        log::warn!("Failed to toggle quick search: {}", e);
+    }
+}
+
+fn build(app: &AppHandle) -> Result<WebviewWindow, String> {
+    let builder = WebviewWindowBuilder::new(app, LABEL, WebviewUrl::App(ROUTE.into()))
+        .title("Quick search")
+        .inner_size(WIDTH, HEIGHT)
+        .decorations(false)
+        .resizable(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false);
+
+    #[cfg(target_os = "macos")]
+    let builder = builder.visible_on_all_workspaces(true);
+    let builder = profiles::with_session(app, builder, &profiles::active(app))?;
+
+    // Searching reaches the server, but nothing else is needed
+    capabilities::grant(app, LABEL, &[Capability::Network]);
+    builder.build().map_err(|e| {
+        capabilities::revoke(app, LABEL);
+        e.to_string()
+    })
+}
+
+/// The monitor under the mouse, where the user is working
+fn active_monitor(app: &AppHandle, window: &WebviewWindow) -> Option<Monitor> {
+    app.cursor_position()
+        .ok()
+        .and_then(|cursor| app.monitor_from_point(cursor.x, cursor.y).ok().flatten())
+        .or_else(|| window.primary_monitor().ok().flatten())
+}
+
+/// Center the popup horizontally, a fifth of the way down the work area
+fn position(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let Some(monitor) = active_monitor(app, window) else {
+        return Ok(());
+    };
+    let area = monitor.work_area();
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let x = area.position.x + area.size.width.saturating_sub(size.width) as i32 / 2;
+    let y = area.position.y + area.size.height.saturating_sub(size.height) as i32 / 5;
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())
+}
+
+/// Show the popup on the active monitor, creating it the first time
+pub fn show(app: &AppHandle) -> Result<(), String> {
+    let window = match app.get_webview_window(LABEL) {
+        Some(window) => window,
+        None => build(app)?,
+    };
+    position(app, &window)?;
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+pub fn hide(app: &AppHandle) -> Result<(), String> {
+    match app.get_webview_window(LABEL) {
+        Some(window) => window.hide().map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+pub fn toggle(app: &AppHandle) -> Result<(), String> {
+    let visible = app
+        .get_webview_window(LABEL)
+        .is_some_and(|w| w.is_visible().unwrap_or(false));
+    match visible {
+        true => hide(app),
+        false => show(app),
+    }
+}
+
+/// Dismiss the popup as soon as it loses focus
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Focused(false)) && window.label() == LABEL {
+        let _ = window.hide();
+    }
+}
+
+/// Show the quick-search popup, or hide it if it is open
+#[tauri::command]
+pub async fn toggle_quick_search(app: AppHandle) -> Result<(), String> {
+    toggle(&app)
+}
+
+/// Hide the quick-search popup, e.g. on Escape or after picking a result
+///
+/// With `focus_main`, the main window is brought to the front so it can show
+/// the result.
+#[tauri::command]
+pub async fn hide_quick_search(app: AppHandle, focus_main: Option<bool>) -> Result<(), String> {
+    hide(&app)?;
+    if focus_main.unwrap_or(false) {
+        tray::show_main_window(&app);
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::AppHandle;
+
+use crate::cache;
+use crate::fs_scope;
+
+/// RAW formats with an embedded JPEG preview we know how to locate
+pub const RAW_EXTENSIONS: &[&str] = &["cr2", "cr3", "nef", "arw", "dng"];
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014a;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const MAX_IFDS: usize = 64;
+
+/// Canon's CR3 preview lives in a `uuid` box with this identifier
+const CR3_PREVIEW_UUID: [u8; 16] = [
+    0xea, 0xf4, 0x2b, 0x5e, 0x1c, 0x98, 0x4b, 0x88, 0xb9, 0xfb, 0xb7, 0xdc, 0x40, 0x6e, 0x4d, 0x16,
+];
+
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| RAW_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    /// Read all values of an entry as u32, following the offset when they don't fit inline
+    fn values(&self, entry: usize) -> Vec<u32> {
+        let (Some(kind), Some(count)) = (self.u16(entry + 2), self.u32(entry + 4)) else {
+            return Vec::new();
+        };
+        let size = match kind {
+            3 => 2,
+            4 | 13 => 4,
+            _ => return Vec::new(),
+        };
+        let count = count as usize;
+        let base = match count * size {
+            0..=4 => entry + 8,
+            _ => match self.u32(entry + 8) {
+                Some(offset) => offset as usize,
+                None => return Vec::new(),
+            },
+        };
+
+        (0..count)
+            .map_while(|i| match size {
+                2 => self.u16(base + i * 2).map(u32::from),
+                _ => self.u32(base + i * 4),
+            })
+            .collect()
+    }
+}
+
+/// Collect `(offset, length)` of JPEG streams referenced from a TIFF-based RAW
+fn tiff_candidates(data: &[u8]) -> Vec<(usize, usize)> {
+    let little = match data.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Vec::new(),
+    };
+    let tiff = Tiff { data, little };
+    let mut pending: Vec<usize> = tiff.u32(4).map(|o| vec![o as usize]).unwrap_or_default();
+    let mut visited = Vec::new();
+    let mut candidates = Vec::new();
+
+    while let Some(ifd) = pending.pop() {
+        if ifd == 0 || visited.contains(&ifd) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(ifd);
+
+        let Some(count) = tiff.u16(ifd) else {
+            continue;
+        };
+        let (mut jpeg_offset, mut jpeg_length) = (None, None);
+        let (mut strips, mut strip_counts, mut compression) = (Vec::new(), Vec::new(), None);
+
+        for i in 0..count as usize {
+            let entry = ifd + 2 + i * 12;
+            match tiff.u16(entry) {
+                Some(TAG_JPEG_OFFSET) => jpeg_offset = tiff.values(entry).first().copied(),
+                Some(TAG_JPEG_LENGTH) => jpeg_length = tiff.values(entry).first().copied(),
+                Some(TAG_STRIP_OFFSETS) => strips = tiff.values(entry),
+                Some(TAG_STRIP_BYTE_COUNTS) => strip_counts = tiff.values(entry),
+                Some(TAG_COMPRESSION) => compression = tiff.values(entry).first().copied(),
+                Some(TAG_SUB_IFDS) | Some(TAG_EXIF_IFD) => {
+                    pending.extend(tiff.values(entry).into_iter().map(|o| o as usize))
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(offset), Some(length)) = (jpeg_offset, jpeg_length) {
+            candidates.push((offset as usize, length as usize));
+        }
+        // Single-strip JPEG-compressed images (DNG previews, NEF/ARW full-size previews)
+        if matches!(compression, Some(6) | Some(7)) && strips.len() == 1 && strip_counts.len() == 1
+        {
+            candidates.push((strips[0] as usize, strip_counts[0] as usize));
+        }
+
+        if let Some(next) = tiff.u32(ifd + 2 + count as usize * 12) {
+            pending.push(next as usize);
+        }
+    }
+
+    candidates
+}
+
+/// Locate the PRVW JPEG inside a CR3 (ISO base media) file
+fn cr3_candidates(data: &[u8]) -> Vec<(usize, usize)> {
+    let Some(start) = data.windows(16).position(|w| w == CR3_PREVIEW_UUID) else {
+        return Vec::new();
+    };
+    let Some(prvw) = data[start..].windows(4).position(|w| w == b"PRVW") else {
+        return Vec::new();
+    };
+    // PRVW box: size(4) "PRVW" unknown(4) unknown(2) width(2) height(2) unknown(2) length(4) jpeg
+    let header = start + prvw + 4;
+    let length = data
+        .get(header + 12..header + 16)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_be_bytes);
+
+    match length {
+        Some(length) => vec![(header + 16, length as usize)],
+        None => Vec::new(),
+    }
+}
+
+/// A displayable JPEG: starts with SOI and is not lossless (SOF3), which
+/// some RAWs use for the sensor data itself
+fn is_preview_jpeg(jpeg: &[u8]) -> bool {
+    if !jpeg.starts_with(&[0xff, 0xd8]) {
+        return false;
+    }
+
+    let mut at = 2;
+    while let Some(&[0xff, marker]) = jpeg.get(at..at + 2) {
+        match marker {
+            0xc3 => return false,
+            0xc0..=0xc2 => return true,
+            _ => {}
+        }
+        let Some(len) = jpeg.get(at + 2..at + 4) else {
+            return false;
+        };
+        at += 2 + u16::from_be_bytes([len[0], len[1]]) as usize;
+    }
+    false
+}
+
+/// Return the largest embedded JPEG preview in a RAW file
+pub fn extract_preview(data: &[u8]) -> Option<&[u8]> {
+    let candidates = match data.get(4..8) {
+        Some(b"ftyp") => cr3_candidates(data),
+        _ => tiff_candidates(data),
+    };
+
+    candidates
+        .into_iter()
+        .filter_map(|(offset, length)| data.get(offset..offset.checked_add(length)?))
+        .filter(|jpeg| is_preview_jpeg(jpeg))
+        .max_by_key(|jpeg| jpeg.len())
+}
+
+/// Cached preview location, keyed by path, size, and mtime
+fn preview_path(app: &AppHandle, path: &Path) -> Result<PathBuf, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let key = format!("{}:{}:{}", path.to_string_lossy(), metadata.len(), modified);
+    let name: String = Sha1::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    Ok(cache::previews_dir(app)?.join(format!("{}.jpg", name)))
+}
+
+/// Extract the embedded JPEG preview of a RAW file into the preview cache
+///
+/// Returns the path of the cached JPEG, or `None` when the file has no
+/// usable preview.
+#[tauri::command]
+pub async fn extract_raw_preview(app: AppHandle, path: String) -> Result<Option<String>, String> {
+    let source = fs_scope::check(&app, &path)?;
+    if !is_raw(&source) {
+        return Err(format!("'{}' is not a supported RAW file", path));
+    }
+
+    let target = preview_path(&app, &source)?;
+    if target.exists() {
+        return Ok(Some(target.to_string_lossy().to_string()));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = std::fs::read(&source).map_err(|e| e.to_string())?;
+        let Some(jpeg) = extract_preview(&data) else {
+            return Ok(None);
+        };
+        std::fs::write(&target, jpeg).map_err(|e| e.to_string())?;
+        Ok(Some(target.to_string_lossy().to_string()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: u16 = 4;
+
+    /// A JPEG of `size` bytes whose first frame marker is `sof`
+    fn jpeg(size: usize, sof: u8) -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, sof, 0x00, 0x02];
+        jpeg.resize(size, 0);
+        jpeg
+    }
+
+    /// A little-endian IFD with LONG entries
+    fn ifd(entries: &[(u16, u32)], next: u32) -> Vec<u8> {
+        let mut ifd = (entries.len() as u16).to_le_bytes().to_vec();
+        for (tag, value) in entries {
+            ifd.extend(tag.to_le_bytes());
+            ifd.extend(LONG.to_le_bytes());
+            ifd.extend(1u32.to_le_bytes());
+            ifd.extend(value.to_le_bytes());
+        }
+        ifd.extend(next.to_le_bytes());
+        ifd
+    }
+
+    /// A TIFF with a JPEG-tag thumbnail, a strip preview in the next IFD,
+    /// and lossless sensor data in a sub-IFD
+    fn tiff(thumbnail: &[u8], preview: &[u8], sensor: &[u8]) -> Vec<u8> {
+        // IFD0 and IFD1 have 3 entries and the sub-IFD 2, then the image data
+        let ifd0 = 8;
+        let ifd1 = ifd0 + 2 + 3 * 12 + 4;
+        let sub = ifd1 + 2 + 3 * 12 + 4;
+        let thumbnail_at = sub + 2 + 2 * 12 + 4;
+        let preview_at = thumbnail_at + thumbnail.len() as u32;
+        let sensor_at = preview_at + preview.len() as u32;
+
+        let mut data = b"II*\0".to_vec();
+        data.extend(ifd0.to_le_bytes());
+        data.extend(ifd(
+            &[
+                (TAG_SUB_IFDS, sub),
+                (TAG_JPEG_OFFSET, thumbnail_at),
+                (TAG_JPEG_LENGTH, thumbnail.len() as u32),
+            ],
+            ifd1,
+        ));
+        data.extend(ifd(
+            &[
+                (TAG_COMPRESSION, 7),
+                (TAG_STRIP_OFFSETS, preview_at),
+                (TAG_STRIP_BYTE_COUNTS, preview.len() as u32),
+            ],
+            0,
+        ));
+        data.extend(ifd(
+            &[
+                (TAG_JPEG_OFFSET, sensor_at),
+                (TAG_JPEG_LENGTH, sensor.len() as u32),
+            ],
+            0,
+        ));
+        data.extend(thumbnail);
+        data.extend(preview);
+        data.extend(sensor);
+        data
+    }
+
+    #[test]
+    fn largest_displayable_tiff_preview_wins() {
+        let preview = jpeg(40, 0xc0);
+        let data = tiff(&jpeg(20, 0xc0), &preview, &jpeg(60, 0xc3));
+        assert_eq!(extract_preview(&data), Some(preview.as_slice()));
+    }
+
+    #[test]
+    fn thumbnail_is_used_without_a_larger_preview() {
+        let thumbnail = jpeg(20, 0xc2);
+        let data = tiff(&thumbnail, &[0u8; 40], &jpeg(60, 0xc3));
+        assert_eq!(extract_preview(&data), Some(thumbnail.as_slice()));
+    }
+
+    #[test]
+    fn cr3_preview_is_found_after_its_uuid() {
+        let preview = jpeg(30, 0xc0);
+        let mut data = vec![0, 0, 0, 24];
+        data.extend(b"ftypcrx ");
+        data.extend([0; 12]);
+        data.extend(CR3_PREVIEW_UUID);
+        data.extend([0, 0, 0, 0]);
+        data.extend(b"PRVW");
+        data.extend([0; 12]);
+        data.extend((preview.len() as u32).to_be_bytes());
+        data.extend(&preview);
+        assert_eq!(extract_preview(&data), Some(preview.as_slice()));
+    }
+
+    #[test]
+    fn broken_files_have_no_preview() {
+        assert_eq!(extract_preview(b""), None);
+        assert_eq!(extract_preview(b"not a raw file"), None);
+
+        // Previews running past the end of the file are skipped
+        let thumbnail = jpeg(20, 0xc0);
+        let mut data = tiff(&thumbnail, &jpeg(40, 0xc0), &jpeg(60, 0xc3));
+        data.truncate(data.len() - 80);
+        assert_eq!(extract_preview(&data), Some(thumbnail.as_slice()));
+        data.truncate(data.len() - 21);
+        assert_eq!(extract_preview(&data), None);
+
+        // An IFD that links back to itself
+        let mut looped = b"II*\0".to_vec();
+        looped.extend(8u32.to_le_bytes());
+        looped.extend(ifd(&[], 8));
+        assert_eq!(extract_preview(&looped), None);
+    }
+}
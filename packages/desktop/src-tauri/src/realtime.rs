@@ -0,0 +1,216 @@
+//! Realtime server notifications while Apollo is in the tray
+//!
+//! The frontend's WebSocket closes with its windows hidden, so while Apollo
+//! is only in the tray a native WebSocket connects to the server instead.
+//! Each message is an event in the shape the activity endpoint lists, and
+//! shares, comments and memories are raised as native notifications as soon
+//! as they arrive, filtered by the notification category preferences. Events
+//! are deduplicated against those activity polling already announced.
+//!
+//! The connection is dropped as soon as a window is shown again, and retried
+//! with a growing delay when the server can't be reached.
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Url};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+
+use crate::activity::{self, ServerActivity};
+use crate::http;
+use crate::pinning;
+use crate::profiles;
+
+const REALTIME_SETTINGS_KEY: &str = "realtimeNotifications";
+/// How often to check whether the connection should be open
+const REALTIME_TICK: Duration = Duration::from_secs(5);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RealtimeSettings {
+    pub enabled: bool,
+    /// The server's WebSocket endpoint, `ws://` or `wss://`
+    pub url: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// JSON pointer to the event in each message, e.g. `/data`; the message
+    /// itself when unset
+    pub event_pointer: Option<String>,
+}
+
+fn read_settings(app: &AppHandle) -> Result<RealtimeSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(REALTIME_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(RealtimeSettings::default()),
+    }
+}
+
+/// The endpoint, checked to be a WebSocket URL
+fn endpoint(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    match url.scheme() {
+        "ws" | "wss" => Ok(url),
+        scheme => Err(format!("'{}' is not a WebSocket URL", scheme)),
+    }
+}
+
+/// The endpoint as the HTTP URL the handshake is sent to, which is what
+/// configured headers and saved sign-ins are matched against
+fn handshake_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let scheme = match url.scheme() {
+        "wss" => "https",
+        _ => "http",
+    };
+    let _ = url.set_scheme(scheme);
+    url.to_string()
+}
+
+/// Whether the connection should be open with these settings
+fn wanted(app: &AppHandle, settings: &RealtimeSettings) -> bool {
+    settings.enabled && settings.url.is_some() && activity::in_background(app)
+}
+
+/// Connect and raise notifications until a window is shown or the settings
+/// change; the server closing the connection is an error, retried later
+async fn listen(app: &AppHandle, settings: &RealtimeSettings) -> Result<(), String> {
+    let Some(url) = &settings.url else {
+        return Ok(());
+    };
+    let url = endpoint(url)?;
+
+    // The same headers and sign-in native requests to the server get
+    let http_url = handshake_url(&url);
+    let auth = http::with_headers(http::client()?.get(&http_url), &http_url, &settings.headers)?
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| e.to_string())?;
+    for (name, value) in auth.headers() {
+        request.headers_mut().insert(name, value.clone());
+    }
+
+    // Pinned certificates apply here too; WebSockets only run over HTTP/1.1
+    let mut tls = pinning::tls_config()?;
+    tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let (mut socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+        request,
+        None,
+        false,
+        Some(Connector::Rustls(Arc::new(tls))),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    log::info!("Connected to realtime notifications at {}", url);
+
+    let mut check = tokio::time::interval(REALTIME_TICK);
+    loop {
+        tokio::select! {
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err("The server closed the connection".to_string());
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.to_string()),
+                };
+                if let Err(e) = handle(app, settings, &text).await {
+                    log::debug!("Ignored a realtime message: {}", e);
+                }
+            }
+            _ = check.tick() => {
+                let current = read_settings(app).unwrap_or_default();
+                if !wanted(app, &current)
+                    || current.url != settings.url
+                    || current.headers != settings.headers
+                    || current.event_pointer != settings.event_pointer
+                {
+                    let _ = socket.close(None).await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+async fn handle(app: &AppHandle, settings: &RealtimeSettings, text: &str) -> Result<(), String> {
+    let message: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let event = match &settings.event_pointer {
+        Some(pointer) => message
+            .pointer(pointer)
+            .ok_or_else(|| format!("The message has nothing at '{}'", pointer))?,
+        None => &message,
+    };
+    let item: ServerActivity = serde_json::from_value(event.clone()).map_err(|e| e.to_string())?;
+    activity::push(app, item).await
+}
+
+/// Keep the realtime connection open while Apollo is only in the tray
+///
+/// Settings are read on every tick, so changes apply without a restart.
+pub fn watch_realtime(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REALTIME_TICK);
+        let mut delay = MIN_RECONNECT_DELAY;
+        let mut retry_at: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(settings) = read_settings(&app) else {
+                continue;
+            };
+            if !wanted(&app, &settings) {
+                delay = MIN_RECONNECT_DELAY;
+                retry_at = None;
+                continue;
+            }
+            if retry_at.is_some_and(|at| Instant::now() < at) {
+                continue;
+            }
+
+            let started = Instant::now();
+            match listen(&app, &settings).await {
+                Ok(()) => retry_at = None,
+                Err(e) => {
+                    log::warn!("Realtime notifications disconnected: {}", e);
+                    // A connection that held for a while starts the backoff over
+                    if started.elapsed() >= MAX_RECONNECT_DELAY {
+                        delay = MIN_RECONNECT_DELAY;
+                    }
+                    retry_at = Some(Instant::now() + delay);
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+}
+
+/// Get the realtime notification settings
+#[tauri::command]
+pub async fn get_realtime_settings(app: AppHandle) -> Result<RealtimeSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the realtime notification settings, taking effect within seconds
+#[tauri::command]
+pub async fn set_realtime_settings(
+    app: AppHandle,
+    settings: RealtimeSettings,
+) -> Result<(), String> {
+    if let Some(url) = &settings.url {
+        endpoint(url)?;
+    }
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(REALTIME_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
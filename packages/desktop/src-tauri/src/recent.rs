@@ -0,0 +1,196 @@
+//! Most recently used servers, albums and exports
+//!
+//! Each kind keeps its own short list in the active profile's store, newest
+//! first. Exports are on disk, so they're also handed to the OS: the Recent
+//! category of the taskbar jump list on Windows and the Dock's recent
+//! documents on macOS. Servers and albums only live in the app's list, as the
+//! OS lists can only point at files and folders.
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::profiles;
+
+const RECENT_ITEMS_KEY: &str = "recentItems";
+const MAX_PER_KIND: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecentKind {
+    Server,
+    Album,
+    Export,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentItem {
+    pub kind: RecentKind,
+    /// Server URL, album id, or path of the exported file or folder
+    pub target: String,
+    pub label: String,
+    /// Unix milliseconds
+    pub used_at: i64,
+}
+
+fn read(app: &AppHandle) -> Result<Vec<RecentItem>, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(RECENT_ITEMS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn write(app: &AppHandle, items: &[RecentItem]) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(RECENT_ITEMS_KEY, serde_json::json!(items));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Move an item to the front of its list, dropping the oldest past the limit
+pub fn add(app: &AppHandle, kind: RecentKind, target: &str, label: &str) -> Result<(), String> {
+    let target = target.trim();
+    if target.is_empty() {
+        return Err("A recent item needs a target".to_string());
+    }
+
+    let mut items = read(app)?;
+    items.retain(|item| !(item.kind == kind && item.target == target));
+    items.insert(
+        0,
+        RecentItem {
+            kind,
+            target: target.to_string(),
+            label: label.to_string(),
+            used_at: chrono::Utc::now().timestamp_millis(),
+        },
+    );
+    let mut kept = 0;
+    items.retain(|item| {
+        if item.kind != kind {
+            return true;
+        }
+        kept += 1;
+        kept <= MAX_PER_KIND
+    });
+    write(app, &items)?;
+
+    if kind == RecentKind::Export {
+        os::add(app, target);
+    }
+    Ok(())
+}
+
+/// Remember a server, album or export as recently used
+#[tauri::command]
+pub async fn add_recent_item(
+    app: AppHandle,
+    kind: RecentKind,
+    target: String,
+    label: Option<String>,
+) -> Result<(), String> {
+    let label = label.unwrap_or_else(|| target.clone());
+    add(&app, kind, &target, &label)
+}
+
+/// List recently used items, newest first, optionally of one kind
+#[tauri::command]
+pub async fn list_recent_items(
+    app: AppHandle,
+    kind: Option<RecentKind>,
+) -> Result<Vec<RecentItem>, String> {
+    let mut items = read(&app)?;
+    if let Some(kind) = kind {
+        items.retain(|item| item.kind == kind);
+    }
+    Ok(items)
+}
+
+/// Forget recently used items, of one kind or all of them
+///
+/// Clearing exports also clears the app's recent documents in the OS.
+#[tauri::command]
+pub async fn clear_recent_items(app: AppHandle, kind: Option<RecentKind>) -> Result<(), String> {
+    let mut items = read(&app)?;
+    match kind {
+        Some(kind) => items.retain(|item| item.kind != kind),
+        None => items.clear(),
+    }
+    write(&app, &items)?;
+
+    if kind.is_none() || kind == Some(RecentKind::Export) {
+        os::clear(&app);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::AppHandle;
+
+    /// NSDocumentController belongs to the main thread
+    pub fn add(app: &AppHandle, path: &str) {
+        let path = path.to_string();
+        let _ = app.run_on_main_thread(move || unsafe {
+            let path = NSString::alloc(nil).init_str(&path);
+            let url: id = msg_send![class!(NSURL), fileURLWithPath: path];
+            let controller: id = msg_send![class!(NSDocumentController), sharedDocumentController];
+            let _: () = msg_send![controller, noteNewRecentDocumentURL: url];
+        });
+    }
+
+    pub fn clear(app: &AppHandle) {
+        let _ = app.run_on_main_thread(|| unsafe {
+            let controller: id = msg_send![class!(NSDocumentController), sharedDocumentController];
+            let _: () = msg_send![controller, clearRecentDocuments: nil];
+        });
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use tauri::AppHandle;
+    use windows::core::HSTRING;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+        COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::{
+        ApplicationDestinations, IApplicationDestinations, SHAddToRecentDocs, SHARD_PATHW,
+    };
+
+    /// Files added this way show up in the jump list's Recent category
+    pub fn add(_app: &AppHandle, path: &str) {
+        let path = HSTRING::from(path);
+        unsafe { SHAddToRecentDocs(SHARD_PATHW.0 as u32, Some(path.as_ptr() as _)) };
+    }
+
+    /// Only the app's own jump list entries; the system's recent files are kept
+    pub fn clear(_app: &AppHandle) {
+        std::thread::spawn(|| unsafe {
+            let initialized = CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok();
+            let destinations: windows::core::Result<IApplicationDestinations> =
+                CoCreateInstance(&ApplicationDestinations, None, CLSCTX_INPROC_SERVER);
+            if let Err(e) =
+                destinations.and_then(|destinations| destinations.RemoveAllDestinations())
+            {
+                log::warn!("Failed to clear the jump list: {}", e);
+            }
+            if initialized {
+                CoUninitialize();
+            }
+        });
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod os {
+    use tauri::AppHandle;
+
+    pub fn add(_app: &AppHandle, _path: &str) {}
+
+    pub fn clear(_app: &AppHandle) {}
+}
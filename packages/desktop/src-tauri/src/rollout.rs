@@ -0,0 +1,130 @@
+//! Staged rollouts
+//!
+//! A release can reach a fraction of desktops first by setting `rollout` to a
+//! percentage in the update manifest, for one platform or the whole release:
+//!
+//! ```json
+//! "darwin-aarch64": { "url": "…", "signature": "…", "rollout": 10 }
+//! ```
+//!
+//! Each machine lands in a bucket from 0 to 99 derived from its machine ID and
+//! the release version, so raising the percentage only adds machines, and the
+//! first machines differ from release to release.
+
+use sha1::{Digest, Sha1};
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_updater::Update;
+
+const MACHINE_ID_FILE: &str = "machine-id";
+
+fn machine_id_path(app: &AppHandle) -> Result<PathBuf, String> {
+    // Not the data directory, which can be moved or shared between machines
+    Ok(app
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(MACHINE_ID_FILE))
+}
+
+/// Random ID for this install, created on first use
+pub fn machine_id(app: &AppHandle) -> Result<String, String> {
+    let path = machine_id_path(app)?;
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+    // RandomState is seeded from the OS random source
+    let random = || {
+        std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish()
+    };
+    let id = format!("{:016x}{:016x}", random(), random());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, &id).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// The rollout percentage the manifest sets for this update, if any
+///
+/// Accepts `"rollout": 25` or `"rollout": { "percentage": 25 }`, on the
+/// platform entry or at the top level.
+pub fn percentage(update: &Update) -> Option<f64> {
+    let read = |value: &serde_json::Value| {
+        let rollout = value.get("rollout")?;
+        rollout
+            .as_f64()
+            .or_else(|| rollout.get("percentage")?.as_f64())
+    };
+    update
+        .raw_json
+        .get("platforms")
+        .and_then(|platforms| platforms.get(&update.target))
+        .and_then(read)
+        .or_else(|| read(&update.raw_json))
+}
+
+/// This machine's bucket for a release, from 0 to 99
+fn bucket(machine_id: &str, version: &str) -> u32 {
+    let digest = Sha1::digest(format!(
+        "{}:{}",
+        machine_id,
+        version.trim_start_matches('v')
+    ));
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100
+}
+
+/// Whether the rollout has reached this machine yet
+///
+/// Updates without a rollout, or when the machine ID can't be stored, are
+/// offered to everyone.
+pub fn admits(app: &AppHandle, update: &Update) -> bool {
+    let Some(percentage) = percentage(update) else {
+        return true;
+    };
+    match machine_id(app) {
+        Ok(id) => (bucket(&id, &update.version) as f64) < percentage,
+        Err(e) => {
+            log::warn!("Failed to read machine ID for staged rollout: {}", e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_stable_per_machine_and_release() {
+        let id = "0123456789abcdef0123456789abcdef";
+        assert_eq!(bucket(id, "1.4.0"), bucket(id, "1.4.0"));
+        assert_eq!(bucket(id, "v1.4.0"), bucket(id, "1.4.0"));
+        assert!(bucket(id, "1.4.0") < 100);
+    }
+
+    #[test]
+    fn buckets_spread_evenly() {
+        let mut counts = [0u32; 100];
+        for machine in 0..10_000 {
+            counts[bucket(&format!("{:032x}", machine), "1.4.0") as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| (50..=150).contains(&count)));
+    }
+
+    #[test]
+    fn early_machines_differ_between_releases() {
+        let early = |version: &str| -> Vec<u32> {
+            (0..1_000)
+                .filter(|&machine| bucket(&format!("{:032x}", machine), version) < 10)
+                .collect()
+        };
+        assert_ne!(early("1.4.0"), early("1.5.0"));
+    }
+}
@@ -0,0 +1,116 @@
+use serde::Serialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Packaging sandbox Apollo is running in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxKind {
+    Flatpak,
+    Snap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SandboxInfo {
+    /// `None` when Apollo runs unconfined
+    pub kind: Option<SandboxKind>,
+    /// Flatpak app id or snap name
+    pub app_id: Option<String>,
+    /// Apollo can download and install its own updates; sandboxed installs
+    /// are updated by Flatpak or snapd instead
+    pub self_update: bool,
+    /// The data directory can be moved anywhere; in a sandbox only to folders
+    /// the sandbox already grants, not ones picked through the portal
+    pub data_dir_anywhere: bool,
+    /// Changes in watched folders are noticed right away; under the document
+    /// portal they are polled instead
+    pub folder_events: bool,
+}
+
+fn detect() -> Option<(SandboxKind, Option<String>)> {
+    if cfg!(not(target_os = "linux")) {
+        return None;
+    }
+    if std::env::var_os("FLATPAK_ID").is_some() || Path::new("/.flatpak-info").exists() {
+        return Some((SandboxKind::Flatpak, std::env::var("FLATPAK_ID").ok()));
+    }
+    if std::env::var_os("SNAP").is_some() {
+        return Some((SandboxKind::Snap, std::env::var("SNAP_NAME").ok()));
+    }
+    None
+}
+
+fn cached() -> &'static Option<(SandboxKind, Option<String>)> {
+    static SANDBOX: OnceLock<Option<(SandboxKind, Option<String>)>> = OnceLock::new();
+    SANDBOX.get_or_init(detect)
+}
+
+/// The sandbox Apollo runs in, if any
+pub fn kind() -> Option<SandboxKind> {
+    cached().as_ref().map(|(kind, _)| *kind)
+}
+
+pub fn info() -> SandboxInfo {
+    let sandboxed = kind().is_some();
+    SandboxInfo {
+        kind: kind(),
+        app_id: cached().as_ref().and_then(|(_, id)| id.clone()),
+        self_update: !sandboxed,
+        data_dir_anywhere: !sandboxed,
+        folder_events: !sandboxed,
+    }
+}
+
+/// Whether a path was exported through the document portal
+///
+/// Those paths live on a FUSE mount at `/run/user/<uid>/doc` (or
+/// `/run/flatpak/doc` inside Flatpak), which delivers no inotify events.
+pub fn is_portal_path(path: &Path) -> bool {
+    if !path.has_root() {
+        return false;
+    }
+    let parts: Vec<String> = path
+        .components()
+        .skip(1)
+        .take(4)
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let parts: Vec<&str> = parts.iter().map(|p| p.as_str()).collect();
+    matches!(
+        parts.as_slice(),
+        ["run", "flatpak", "doc", ..] | ["run", "user", _, "doc"]
+    )
+}
+
+/// Refuse data directory targets the sandboxed app can't keep a database in
+///
+/// Portal paths are only granted until the document store forgets them and
+/// don't support the locking SQLite needs, so the folder must be shared with
+/// the sandbox directly.
+pub fn check_data_dir(target: &Path) -> Result<(), String> {
+    let Some(kind) = kind() else {
+        return Ok(());
+    };
+    if !is_portal_path(target) {
+        return Ok(());
+    }
+    let hint = match (kind, cached().as_ref().and_then(|(_, id)| id.as_deref())) {
+        (SandboxKind::Flatpak, Some(id)) => format!(
+            "run `flatpak override --user --filesystem=<folder> {}` and choose the folder by its real path",
+            id
+        ),
+        (SandboxKind::Flatpak, None) => "grant the folder with `flatpak override`".to_string(),
+        (SandboxKind::Snap, _) => "choose a folder in your home directory or on removable media".to_string(),
+    };
+    Err(format!(
+        "'{}' was shared through the document portal and can't hold Apollo's data; {}",
+        target.display(),
+        hint
+    ))
+}
+
+/// Describe the sandbox so the UI can hide features it doesn't support
+#[tauri::command]
+pub async fn get_sandbox_info() -> Result<SandboxInfo, String> {
+    Ok(info())
+}
@@ -0,0 +1,317 @@
+use chrono::{Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::events::{self, Event};
+use crate::profiles;
+use crate::transfers;
+
+const SCHEDULE_SETTINGS_KEY: &str = "transferSchedule";
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietMode {
+    /// Stop transfers and background sync entirely
+    #[default]
+    Pause,
+    /// Cap transfer speed at the window's limit
+    Throttle,
+}
+
+/// A recurring time window during which heavy transfer work is held back
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietWindow {
+    /// Days the window starts on, 0 = Sunday; every day when empty
+    pub days: Vec<u8>,
+    /// Local start time as `HH:MM`
+    pub start: String,
+    /// Local end time as `HH:MM`; earlier than `start` for overnight windows
+    pub end: String,
+    pub mode: QuietMode,
+    /// Bytes per second allowed in `throttle` mode
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    pub enabled: bool,
+    pub windows: Vec<QuietWindow>,
+}
+
+/// What the scheduler currently allows transfers to do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum TransferPolicy {
+    #[default]
+    Normal,
+    Throttle {
+        /// Bytes per second
+        limit: u64,
+    },
+    Pause,
+}
+
+/// The policy in effect, re-evaluated by `watch_schedule`
+#[derive(Default)]
+pub struct Schedule(Mutex<TransferPolicy>);
+
+pub fn read_settings(app: &AppHandle) -> Result<ScheduleSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(SCHEDULE_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(ScheduleSettings::default()),
+    }
+}
+
+pub fn policy(app: &AppHandle) -> TransferPolicy {
+    *app.state::<Schedule>()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn parse_minutes(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl QuietWindow {
+    /// Whether the window covers a moment, given as minutes since midnight and weekday
+    fn contains(&self, minutes: u32, weekday: u8) -> bool {
+        let (Some(start), Some(end)) = (parse_minutes(&self.start), parse_minutes(&self.end))
+        else {
+            return false;
+        };
+        let on = |day: u8| self.days.is_empty() || self.days.contains(&day);
+
+        if start <= end {
+            on(weekday) && (start..end).contains(&minutes)
+        } else if minutes >= start {
+            on(weekday)
+        } else {
+            // The part after midnight belongs to the previous day's window
+            minutes < end && on((weekday + 6) % 7)
+        }
+    }
+}
+
+fn evaluate(settings: &ScheduleSettings) -> TransferPolicy {
+    let now = Local::now();
+    let minutes = now.hour() * 60 + now.minute();
+    let weekday = now.weekday().num_days_from_sunday() as u8;
+    evaluate_at(settings, minutes, weekday)
+}
+
+/// Pause wins over throttling; overlapping throttles use the lowest limit
+fn evaluate_at(settings: &ScheduleSettings, minutes: u32, weekday: u8) -> TransferPolicy {
+    if !settings.enabled {
+        return TransferPolicy::Normal;
+    }
+
+    settings
+        .windows
+        .iter()
+        .filter(|w| w.contains(minutes, weekday))
+        .fold(TransferPolicy::Normal, |policy, window| {
+            match (policy, window.mode, window.limit) {
+                (TransferPolicy::Pause, _, _) | (_, QuietMode::Pause, _) => TransferPolicy::Pause,
+                (_, QuietMode::Throttle, None) => policy,
+                (TransferPolicy::Throttle { limit }, QuietMode::Throttle, Some(next)) => {
+                    TransferPolicy::Throttle {
+                        limit: limit.min(next),
+                    }
+                }
+                (TransferPolicy::Normal, QuietMode::Throttle, Some(limit)) => {
+                    TransferPolicy::Throttle { limit }
+                }
+            }
+        })
+}
+
+/// Re-evaluate the schedule and enforce a change of policy
+///
+/// Entering a pause suspends running transfers back into the queue, where
+/// downloads later continue from their partial files.
+fn update(app: &AppHandle) {
+    let next = read_settings(app)
+        .map(|settings| evaluate(&settings))
+        .unwrap_or_default();
+    let previous = {
+        let state = app.state::<Schedule>();
+        let mut policy = state.0.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *policy, next)
+    };
+    if previous == next {
+        return;
+    }
+
+    log::info!("Transfer policy changed to {:?}", next);
+    match next {
+        TransferPolicy::Pause => transfers::suspend(app),
+        _ => transfers::wake(app),
+    }
+    events::publish(app, Event::TransferPolicy(next));
+}
+
+/// Apply the quiet hours schedule now and re-check it periodically
+pub fn watch_schedule(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            update(&app);
+        }
+    });
+}
+
+/// Get the quiet hours schedule
+#[tauri::command]
+pub async fn get_transfer_schedule(app: AppHandle) -> Result<ScheduleSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the quiet hours schedule and apply it immediately
+#[tauri::command]
+pub async fn set_transfer_schedule(
+    app: AppHandle,
+    settings: ScheduleSettings,
+) -> Result<TransferPolicy, String> {
+    if let Some(window) = settings
+        .windows
+        .iter()
+        .find(|w| parse_minutes(&w.start).is_none() || parse_minutes(&w.end).is_none())
+    {
+        return Err(format!(
+            "Invalid quiet hours '{}'-'{}', expected HH:MM",
+            window.start, window.end
+        ));
+    }
+
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(SCHEDULE_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    update(&app);
+    Ok(policy(&app))
+}
+
+/// Get the transfer policy currently in effect
+#[tauri::command]
+pub async fn get_transfer_policy(app: AppHandle) -> Result<TransferPolicy, String> {
+    Ok(policy(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUNDAY: u8 = 0;
+    const MONDAY: u8 = 1;
+    const SATURDAY: u8 = 6;
+
+    fn window(
+        days: &[u8],
+        start: &str,
+        end: &str,
+        mode: QuietMode,
+        limit: Option<u64>,
+    ) -> QuietWindow {
+        QuietWindow {
+            days: days.to_vec(),
+            start: start.to_string(),
+            end: end.to_string(),
+            mode,
+            limit,
+        }
+    }
+
+    fn at(time: &str) -> u32 {
+        parse_minutes(time).unwrap()
+    }
+
+    #[test]
+    fn daytime_windows_cover_start_to_end() {
+        let work = window(&[MONDAY], "09:00", "17:30", QuietMode::Pause, None);
+        assert!(!work.contains(at("08:59"), MONDAY));
+        assert!(work.contains(at("09:00"), MONDAY));
+        assert!(work.contains(at("17:29"), MONDAY));
+        assert!(!work.contains(at("17:30"), MONDAY));
+        assert!(!work.contains(at("12:00"), SUNDAY));
+    }
+
+    #[test]
+    fn overnight_windows_wrap_past_midnight() {
+        let night = window(&[SATURDAY], "22:00", "06:00", QuietMode::Pause, None);
+        assert!(night.contains(at("23:00"), SATURDAY));
+        // After midnight it is Sunday, but the window started on Saturday
+        assert!(night.contains(at("01:00"), SUNDAY));
+        assert!(!night.contains(at("06:00"), SUNDAY));
+        assert!(!night.contains(at("01:00"), SATURDAY));
+        assert!(!night.contains(at("23:00"), SUNDAY));
+    }
+
+    #[test]
+    fn windows_without_days_run_every_day() {
+        let night = window(&[], "23:30", "00:30", QuietMode::Pause, None);
+        for day in 0..7 {
+            assert!(night.contains(at("23:45"), day));
+            assert!(night.contains(at("00:15"), day));
+            assert!(!night.contains(at("12:00"), day));
+        }
+    }
+
+    #[test]
+    fn unreadable_times_never_match() {
+        assert!(!window(&[], "9am", "17:00", QuietMode::Pause, None).contains(at("12:00"), MONDAY));
+        assert!(
+            !window(&[], "09:00", "24:00", QuietMode::Pause, None).contains(at("12:00"), MONDAY)
+        );
+        assert!(
+            !window(&[], "09:60", "17:00", QuietMode::Pause, None).contains(at("12:00"), MONDAY)
+        );
+    }
+
+    #[test]
+    fn pause_wins_and_throttles_take_the_lowest_limit() {
+        let mut settings = ScheduleSettings {
+            enabled: true,
+            windows: vec![
+                window(&[], "00:00", "12:00", QuietMode::Throttle, Some(5_000)),
+                window(&[], "06:00", "18:00", QuietMode::Throttle, Some(2_000)),
+                window(&[], "06:00", "18:00", QuietMode::Throttle, None),
+                window(&[], "10:00", "11:00", QuietMode::Pause, None),
+            ],
+        };
+        assert_eq!(
+            evaluate_at(&settings, at("05:00"), MONDAY),
+            TransferPolicy::Throttle { limit: 5_000 }
+        );
+        assert_eq!(
+            evaluate_at(&settings, at("07:00"), MONDAY),
+            TransferPolicy::Throttle { limit: 2_000 }
+        );
+        assert_eq!(
+            evaluate_at(&settings, at("10:30"), MONDAY),
+            TransferPolicy::Pause
+        );
+        assert_eq!(
+            evaluate_at(&settings, at("20:00"), MONDAY),
+            TransferPolicy::Normal
+        );
+
+        settings.enabled = false;
+        assert_eq!(
+            evaluate_at(&settings, at("10:30"), MONDAY),
+            TransferPolicy::Normal
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use notify::event::ModifyKind;
+use notify::{EventKind, PollWatcher, RecursiveMode, Watcher};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc;
+
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::import::{cached_hash, is_media};
+use crate::profiles;
+use crate::sandbox;
+use crate::upload;
+
+const SCREENSHOT_SETTINGS_KEY: &str = "screenshotUpload";
+/// How long a new file's size must stay unchanged before it is uploaded
+const SETTLE_INTERVAL: Duration = Duration::from_millis(750);
+const SETTLE_ATTEMPTS: usize = 20;
+/// How often folders without change events are rescanned
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Auto-upload preset for the OS screenshots folder
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotSettings {
+    pub enabled: bool,
+    /// Overrides the detected screenshots folder
+    pub folder: Option<String>,
+    pub server: Option<String>,
+    pub album: Option<String>,
+    /// Endpoint accepting a multipart upload with `file` and `album` parts
+    pub upload_url: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+/// Active watcher on the screenshots folder, if the preset is enabled
+#[derive(Default)]
+pub struct ScreenshotWatcher(Mutex<Option<Box<dyn Watcher + Send>>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenshotUpload {
+    pub path: String,
+    pub album: Option<String>,
+    /// Set when the upload failed
+    pub error: Option<String>,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<ScreenshotSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(SCREENSHOT_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(ScreenshotSettings::default()),
+    }
+}
+
+/// Where the OS saves screenshots by default
+pub fn default_folder(app: &AppHandle) -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        // Set via the Screenshot app's Options menu; defaults to the Desktop
+        let configured = std::process::Command::new("defaults")
+            .args(["read", "com.apple.screencapture", "location"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.strip_prefix("~/") {
+                Some(rest) => app
+                    .path()
+                    .home_dir()
+                    .map(|h| h.join(rest))
+                    .unwrap_or_default(),
+                None => PathBuf::from(s),
+            });
+        configured
+            .filter(|p| p.is_dir())
+            .or_else(|| app.path().desktop_dir().ok())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Snipping Tool and Win+PrtScn save under Pictures\Screenshots
+        app.path()
+            .picture_dir()
+            .ok()
+            .map(|dir| dir.join("Screenshots"))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        // GNOME uses Pictures/Screenshots; KDE Spectacle and others use Pictures
+        let pictures = app.path().picture_dir().ok()?;
+        let screenshots = pictures.join("Screenshots");
+        Some(if screenshots.is_dir() {
+            screenshots
+        } else {
+            pictures
+        })
+    }
+}
+
+pub fn folder(app: &AppHandle, settings: &ScreenshotSettings) -> Option<PathBuf> {
+    match &settings.folder {
+        Some(folder) => Some(PathBuf::from(folder)),
+        None => default_folder(app),
+    }
+}
+
+fn is_candidate(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+    !hidden && is_media(path)
+}
+
+/// Wait until a file stops growing so half-written screenshots aren't uploaded
+async fn settle(path: &Path) -> bool {
+    let mut last = None;
+    for _ in 0..SETTLE_ATTEMPTS {
+        tokio::time::sleep(SETTLE_INTERVAL).await;
+        let Ok(size) = std::fs::metadata(path).map(|m| m.len()) else {
+            return false;
+        };
+        if size > 0 && last == Some(size) {
+            return true;
+        }
+        last = Some(size);
+    }
+    false
+}
+
+async fn upload_screenshot(
+    app: &AppHandle,
+    settings: &ScreenshotSettings,
+    path: &Path,
+) -> Result<(), String> {
+    let url = settings
+        .upload_url
+        .as_deref()
+        .ok_or_else(|| "No upload URL configured".to_string())?;
+    let server = settings.server.clone().unwrap_or_default();
+
+    let (checksum, _, _) =
+        cached_hash(&app.state::<Database>(), path).map_err(|e| e.to_string())?;
+    let uploaded = app
+        .state::<Database>()
+        .conn()
+        .prepare("SELECT 1 FROM remote_checksums WHERE server = ?1 AND checksum = ?2")
+        .and_then(|mut stmt| stmt.exists(params![server, checksum]))
+        .unwrap_or(false);
+    if uploaded {
+        return Ok(());
+    }
+
+    let mut fields = Vec::new();
+    if let Some(album) = &settings.album {
+        fields.push(("album", album.clone()));
+    }
+    upload::upload_file(url, &settings.headers, path, &fields).await?;
+
+    let _ = app.state::<Database>().conn().execute(
+        "INSERT OR IGNORE INTO remote_checksums (server, checksum) VALUES (?1, ?2)",
+        params![server, checksum],
+    );
+    Ok(())
+}
+
+/// Upload files reported by the watcher one at a time
+async fn process(
+    app: AppHandle,
+    settings: ScreenshotSettings,
+    mut paths: mpsc::UnboundedReceiver<PathBuf>,
+) {
+    while let Some(path) = paths.recv().await {
+        if !settle(&path).await {
+            continue;
+        }
+
+        let error = upload_screenshot(&app, &settings, &path).await.err();
+        if let Some(e) = &error {
+            log::warn!("Screenshot upload failed for {}: {}", path.display(), e);
+        }
+        events::publish(
+            &app,
+            Event::ScreenshotUpload(ScreenshotUpload {
+                path: path.to_string_lossy().to_string(),
+                album: settings.album.clone(),
+                error,
+            }),
+        );
+    }
+}
+
+/// Start or stop watching the screenshots folder to match the saved settings
+pub fn apply(app: &AppHandle) -> Result<(), String> {
+    let settings = read_settings(app)?;
+    let state = app.state::<ScreenshotWatcher>();
+    let mut watcher = state.0.lock().unwrap_or_else(|e| e.into_inner());
+    // Dropping the old watcher closes its channel and ends its upload task
+    *watcher = None;
+
+    if !settings.enabled {
+        return Ok(());
+    }
+    let folder = folder(app, &settings)
+        .filter(|f| f.is_dir())
+        .ok_or_else(|| "Screenshots folder not found".to_string())?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let handler = move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        // macOS writes to a hidden temp file and renames it into place
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+        ) {
+            return;
+        }
+        for path in event.paths.into_iter().filter(|p| is_candidate(p)) {
+            let _ = tx.send(path);
+        }
+    };
+    // The document portal's FUSE mount sends no change events, so poll it
+    let mut next: Box<dyn Watcher + Send> = match sandbox::is_portal_path(&folder) {
+        true => Box::new(
+            PollWatcher::new(
+                handler,
+                notify::Config::default().with_poll_interval(POLL_INTERVAL),
+            )
+            .map_err(|e| e.to_string())?,
+        ),
+        false => Box::new(notify::recommended_watcher(handler).map_err(|e| e.to_string())?),
+    };
+    next.watch(&folder, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+
+    *watcher = Some(next);
+    tauri::async_runtime::spawn(process(app.clone(), settings, rx));
+    Ok(())
+}
+
+/// Get the screenshots auto-upload settings
+#[tauri::command]
+pub async fn get_screenshot_settings(app: AppHandle) -> Result<ScreenshotSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the screenshots auto-upload settings and restart the watcher
+#[tauri::command]
+pub async fn set_screenshot_settings(
+    app: AppHandle,
+    settings: ScreenshotSettings,
+) -> Result<(), String> {
+    if let Some(folder) = &settings.folder {
+        fs_scope::check(&app, folder)?;
+    }
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(SCREENSHOT_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+    apply(&app)
+}
+
+/// Get the folder that would be watched with the current settings
+#[tauri::command]
+pub async fn get_screenshot_folder(app: AppHandle) -> Result<Option<String>, String> {
+    let settings = read_settings(&app)?;
+    Ok(folder(&app, &settings).map(|f| f.to_string_lossy().to_string()))
+}
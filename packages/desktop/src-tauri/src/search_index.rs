@@ -0,0 +1,424 @@
+//! Synced photos in Spotlight
+//!
+//! Off by default. While on, the frontend hands over each synced album's
+//! metadata and every photo becomes a Spotlight entry with its title, album,
+//! date, and tags, using the synced file as the thumbnail. Choosing one
+//! brings Apollo forward with a `search-result-opened` event naming the
+//! photo. Windows Search only takes entries like these from packaged apps,
+//! so it can't be turned on there.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use crate::profiles;
+use crate::sync;
+
+const SEARCH_INDEX_KEY: &str = "searchIndex";
+
+/// A result opened before the frontend was listening, from a cold launch
+static OPENED: Mutex<Option<SearchResult>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchIndexSettings {
+    pub enabled: bool,
+}
+
+/// A photo's metadata, as the server has it
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub struct IndexedItem {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// RFC 3339 or `YYYY-MM-DD`
+    pub taken_at: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// The photo behind an opened search result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub server: String,
+    pub album_id: String,
+    pub item_id: String,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<SearchIndexSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(SEARCH_INDEX_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(SearchIndexSettings::default()),
+    }
+}
+
+/// Entries are grouped by album, so an album's can be replaced together
+fn domain(server: &str, album_id: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(album_id.as_bytes());
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("album-{}", hash)
+}
+
+/// Start handling chosen search results; call once at startup
+pub fn init(app: &AppHandle) {
+    os::init(app);
+}
+
+/// Drop an album's entries, e.g. when it stops syncing
+pub async fn remove_album(server: &str, album_id: &str) -> Result<(), String> {
+    let domain = domain(server, album_id);
+    tauri::async_runtime::spawn_blocking(move || os::remove(vec![domain]))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_search_index_settings(app: AppHandle) -> Result<SearchIndexSettings, String> {
+    read_settings(&app)
+}
+
+/// Turn Spotlight entries on or off; turning them off removes them all
+#[tauri::command]
+pub async fn set_search_index_settings(
+    app: AppHandle,
+    settings: SearchIndexSettings,
+) -> Result<(), String> {
+    if settings.enabled && !os::SUPPORTED {
+        return Err("Search indexing is only available on macOS".to_string());
+    }
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(SEARCH_INDEX_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+    if !settings.enabled && os::SUPPORTED {
+        tauri::async_runtime::spawn_blocking(os::clear)
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+/// Replace an album's Spotlight entries with `items`
+///
+/// Does nothing while indexing is off. Returns the number of entries.
+#[tauri::command]
+pub async fn index_album(
+    app: AppHandle,
+    server: String,
+    album_id: String,
+    album_name: Option<String>,
+    items: Vec<IndexedItem>,
+) -> Result<usize, String> {
+    if !read_settings(&app)?.enabled {
+        return Ok(0);
+    }
+    let synced = sync::synced_paths(&app, &server, &album_id);
+    let entries: Vec<(String, IndexedItem, Option<PathBuf>)> = items
+        .into_iter()
+        .map(|item| {
+            let identifier = serde_json::json!([server, album_id, item.id]).to_string();
+            let thumbnail = synced.get(&item.id).filter(|path| path.is_file()).cloned();
+            (identifier, item, thumbnail)
+        })
+        .collect();
+    let count = entries.len();
+    let domain = domain(&server, &album_id);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        os::remove(vec![domain.clone()])?;
+        os::index(&domain, album_name.as_deref(), entries)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(count)
+}
+
+/// Remove an album's Spotlight entries
+#[tauri::command]
+pub async fn remove_indexed_album(server: String, album_id: String) -> Result<(), String> {
+    remove_album(&server, &album_id).await
+}
+
+/// The result that launched the app, if the frontend hasn't taken it yet
+#[tauri::command]
+pub async fn take_opened_search_result() -> Result<Option<SearchResult>, String> {
+    Ok(OPENED.lock().unwrap_or_else(|e| e.into_inner()).take())
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use super::{IndexedItem, SearchResult, OPENED};
+    use block::{Block, ConcreteBlock};
+    use cocoa::base::{id, nil, BOOL, NO, YES};
+    use cocoa::foundation::{NSAutoreleasePool, NSString};
+    use objc::runtime::{
+        class_getInstanceMethod, method_setImplementation, object_getClass, Imp, Method, Object,
+        Sel,
+    };
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+    use std::path::PathBuf;
+    use std::sync::{mpsc, OnceLock};
+    use std::time::Duration;
+    use tauri::AppHandle;
+
+    use crate::events::{self, Event};
+    use crate::tray;
+
+    #[link(name = "CoreSpotlight", kind = "framework")]
+    extern "C" {}
+
+    pub const SUPPORTED: bool = true;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+    const CONTENT_TYPE: &str = "public.image";
+    // CSSearchableItemActionType and CSSearchableItemActivityIdentifier
+    const ACTION_TYPE: &str = "com.apple.corespotlightitem";
+    const ACTIVITY_IDENTIFIER: &str = "kCSSearchableItemActivityIdentifier";
+
+    type ContinueActivity = extern "C" fn(&Object, Sel, id, id, id) -> BOOL;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+    /// tao's handler, which opens web links and gets everything else
+    static ORIGINAL: OnceLock<ContinueActivity> = OnceLock::new();
+
+    unsafe fn ns(value: &str) -> id {
+        NSString::alloc(nil).init_str(value)
+    }
+
+    unsafe fn to_string(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let ptr = NSString::UTF8String(value);
+        if ptr.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
+    }
+
+    /// Tell the frontend a search result was chosen
+    fn opened(app: &AppHandle, identifier: &str) {
+        let Ok((server, album_id, item_id)) = serde_json::from_str(identifier) else {
+            return;
+        };
+        let result = SearchResult {
+            server,
+            album_id,
+            item_id,
+        };
+        *OPENED.lock().unwrap_or_else(|e| e.into_inner()) = Some(result.clone());
+        tray::show_main_window(app);
+        events::publish(app, Event::SearchResultOpened(result));
+    }
+
+    extern "C" fn continue_activity(
+        this: &Object,
+        cmd: Sel,
+        application: id,
+        activity: id,
+        handler: id,
+    ) -> BOOL {
+        unsafe {
+            let kind: id = msg_send![activity, activityType];
+            if to_string(kind).as_deref() == Some(ACTION_TYPE) {
+                let info: id = msg_send![activity, userInfo];
+                let identifier: id = msg_send![info, objectForKey: ns(ACTIVITY_IDENTIFIER)];
+                if let (Some(identifier), Some(app)) = (to_string(identifier), APP.get()) {
+                    opened(app, &identifier);
+                    return YES;
+                }
+            }
+        }
+        match ORIGINAL.get() {
+            Some(original) => original(this, cmd, application, activity, handler),
+            None => NO,
+        }
+    }
+
+    /// Spotlight results reach the app delegate as user activities, which
+    /// tao only handles for web links, so its handler is wrapped
+    pub fn init(app: &AppHandle) {
+        if APP.set(app.clone()).is_err() {
+            return;
+        }
+        unsafe {
+            let application: id = msg_send![class!(NSApplication), sharedApplication];
+            let delegate: id = msg_send![application, delegate];
+            if delegate == nil {
+                return;
+            }
+            let method = class_getInstanceMethod(
+                object_getClass(delegate),
+                sel!(application:continueUserActivity:restorationHandler:),
+            );
+            if method.is_null() {
+                return;
+            }
+            let replacement = std::mem::transmute::<ContinueActivity, Imp>(continue_activity);
+            let original = method_setImplementation(method as *mut Method, replacement);
+            let _ = ORIGINAL.set(std::mem::transmute::<Imp, ContinueActivity>(original));
+        }
+    }
+
+    /// Hand CoreSpotlight a completion handler and wait for it
+    fn complete(send: impl FnOnce(&Block<(id,), ()>)) -> Result<(), String> {
+        let (tx, rx) = mpsc::channel();
+        let handler = ConcreteBlock::new(move |error: id| {
+            let result = match error {
+                e if e == nil => Ok(()),
+                e => Err(unsafe { to_string(msg_send![e, localizedDescription]) }
+                    .unwrap_or_else(|| "Spotlight refused the change".to_string())),
+            };
+            let _ = tx.send(result);
+        })
+        .copy();
+        send(&handler);
+        rx.recv_timeout(TIMEOUT)
+            .map_err(|_| "Spotlight did not respond".to_string())?
+    }
+
+    /// Seconds since the epoch for `YYYY-MM-DD` or RFC 3339 dates
+    fn timestamp(date: &str) -> Option<f64> {
+        if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+            return Some(date.timestamp() as f64);
+        }
+        let date = chrono::NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() as f64)
+    }
+
+    unsafe fn searchable_item(
+        domain: &str,
+        album: Option<&str>,
+        identifier: &str,
+        item: &IndexedItem,
+        thumbnail: Option<&PathBuf>,
+    ) -> id {
+        let attributes: id = msg_send![class!(CSSearchableItemAttributeSet), alloc];
+        let attributes: id = msg_send![attributes, initWithItemContentType: ns(CONTENT_TYPE)];
+        let _: () = msg_send![attributes, setTitle: ns(&item.title)];
+        let description = item.description.as_deref().or(album);
+        if let Some(description) = description {
+            let _: () = msg_send![attributes, setContentDescription: ns(description)];
+        }
+        let keywords: id = msg_send![class!(NSMutableArray), array];
+        for keyword in item.tags.iter().map(String::as_str).chain(album) {
+            let _: () = msg_send![keywords, addObject: ns(keyword)];
+        }
+        let _: () = msg_send![attributes, setKeywords: keywords];
+        if let Some(seconds) = item.taken_at.as_deref().and_then(timestamp) {
+            let date: id = msg_send![class!(NSDate), dateWithTimeIntervalSince1970: seconds];
+            let _: () = msg_send![attributes, setContentCreationDate: date];
+        }
+        if let Some(path) = thumbnail {
+            let url: id = msg_send![
+                class!(NSURL),
+                fileURLWithPath: ns(&path.to_string_lossy())
+            ];
+            let _: () = msg_send![attributes, setThumbnailURL: url];
+        }
+
+        let searchable: id = msg_send![class!(CSSearchableItem), alloc];
+        let searchable: id = msg_send![
+            searchable,
+            initWithUniqueIdentifier: ns(identifier)
+            domainIdentifier: ns(domain)
+            attributeSet: attributes
+        ];
+        let _: () = msg_send![attributes, release];
+        searchable
+    }
+
+    pub fn index(
+        domain: &str,
+        album: Option<&str>,
+        entries: Vec<(String, IndexedItem, Option<PathBuf>)>,
+    ) -> Result<(), String> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let items: id = msg_send![class!(NSMutableArray), arrayWithCapacity: entries.len()];
+            for (identifier, item, thumbnail) in &entries {
+                let searchable =
+                    searchable_item(domain, album, identifier, item, thumbnail.as_ref());
+                let _: () = msg_send![items, addObject: searchable];
+                let _: () = msg_send![searchable, release];
+            }
+            let index: id = msg_send![class!(CSSearchableIndex), defaultSearchableIndex];
+            let result = complete(|handler| {
+                let _: () =
+                    msg_send![index, indexSearchableItems: items completionHandler: handler];
+            });
+            pool.drain();
+            result
+        }
+    }
+
+    pub fn remove(domains: Vec<String>) -> Result<(), String> {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let identifiers: id = msg_send![class!(NSMutableArray), array];
+            for domain in &domains {
+                let _: () = msg_send![identifiers, addObject: ns(domain)];
+            }
+            let index: id = msg_send![class!(CSSearchableIndex), defaultSearchableIndex];
+            let result = complete(|handler| {
+                let _: () = msg_send![
+                    index,
+                    deleteSearchableItemsWithDomainIdentifiers: identifiers
+                    completionHandler: handler
+                ];
+            });
+            pool.drain();
+            result
+        }
+    }
+
+    pub fn clear() -> Result<(), String> {
+        unsafe {
+            let index: id = msg_send![class!(CSSearchableIndex), defaultSearchableIndex];
+            complete(|handler| {
+                let _: () =
+                    msg_send![index, deleteAllSearchableItemsWithCompletionHandler: handler];
+            })
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod os {
+    use super::IndexedItem;
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+
+    pub const SUPPORTED: bool = false;
+
+    pub fn init(_app: &AppHandle) {}
+
+    pub fn index(
+        _domain: &str,
+        _album: Option<&str>,
+        _entries: Vec<(String, IndexedItem, Option<PathBuf>)>,
+    ) -> Result<(), String> {
+        Err("Search indexing is only available on macOS".to_string())
+    }
+
+    pub fn remove(_domains: Vec<String>) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn clear() -> Result<(), String> {
+        Ok(())
+    }
+}
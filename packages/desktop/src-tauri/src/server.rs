@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::http;
+use crate::notifications;
+use crate::profiles;
+use crate::server_auth;
+use crate::DEFAULT_SERVER_KEY;
+
+/// Oldest server the desktop app works with at all
+const MIN_SERVER_VERSION: &str = "1.0.0";
+
+/// Server versions that native features rely on, raised when a feature
+/// starts calling newer endpoints
+const FEATURE_VERSIONS: &[(&str, &str)] = &[
+    ("sync", "1.0.4"),
+    ("offline", "1.0.8"),
+    ("quick-capture", "1.0.10"),
+];
+
+const SERVER_UPDATE_SETTINGS_KEY: &str = "serverUpdates";
+/// Latest server release already notified about, so it's only raised once
+const SERVER_UPDATE_NOTIFIED_KEY: &str = "serverUpdateNotified";
+const RELEASE_FEED: &str = "https://api.github.com/repos/i-luv-pho/apollov2/releases?per_page=100";
+const RELEASE_CHECK_DELAY: Duration = Duration::from_secs(5 * 60);
+const RELEASE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerUpdateSettings {
+    /// Compare the default server against the release feed once a day and
+    /// notify when it falls behind
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compatibility {
+    Compatible,
+    /// Works, but some features need a newer server
+    Degraded,
+    /// Older than `MIN_SERVER_VERSION`
+    Unsupported,
+    /// The server didn't report a version this app can read
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnavailableFeature {
+    pub feature: String,
+    pub min_version: String,
+}
+
+/// How the connected server measures up against what this app needs
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerCompatibility {
+    pub verdict: Compatibility,
+    pub server_version: Option<String>,
+    pub min_version: String,
+    /// Features that need a newer server than the connected one
+    pub unavailable_features: Vec<UnavailableFeature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Health {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+/// Parse `1.2.3`, `v1.2.3` or `1.2.3-beta.1` into comparable parts
+///
+/// Pre-release and build suffixes are ignored.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// The version the server reports from `/global/health`
+pub async fn fetch_version(
+    app: &AppHandle,
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<String, String> {
+    let health_url = format!("{}/global/health", url.trim_end_matches('/'));
+    let request = http::with_headers(http::client()?.get(&health_url), &health_url, headers)?;
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    server_auth::handle_challenge(app, &health_url, &response);
+    let health: Health = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(health.version)
+}
+
+fn assess(server_version: Option<String>) -> ServerCompatibility {
+    let parsed = server_version.as_deref().and_then(parse_version);
+    let min = parse_version(MIN_SERVER_VERSION);
+    let unavailable_features: Vec<UnavailableFeature> = match parsed {
+        Some(version) => FEATURE_VERSIONS
+            .iter()
+            .filter(|(_, needed)| parse_version(needed).is_some_and(|needed| version < needed))
+            .map(|(feature, needed)| UnavailableFeature {
+                feature: feature.to_string(),
+                min_version: needed.to_string(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let verdict = match parsed {
+        None => Compatibility::Unknown,
+        Some(version) if min.is_some_and(|min| version < min) => Compatibility::Unsupported,
+        Some(_) if !unavailable_features.is_empty() => Compatibility::Degraded,
+        Some(_) => Compatibility::Compatible,
+    };
+    ServerCompatibility {
+        verdict,
+        server_version,
+        min_version: MIN_SERVER_VERSION.to_string(),
+        unavailable_features,
+    }
+}
+
+/// Compare the server's version against what this app needs
+///
+/// Lets the app warn about an old server up front instead of features
+/// failing one by one.
+#[tauri::command]
+pub async fn check_server_compatibility(
+    app: AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<ServerCompatibility, String> {
+    let version = fetch_version(&app, &url, &headers.unwrap_or_default()).await?;
+    Ok(assess(Some(version)))
+}
+
+pub fn read_update_settings(app: &AppHandle) -> Result<ServerUpdateSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(SERVER_UPDATE_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(ServerUpdateSettings::default()),
+    }
+}
+
+/// Stable releases newer than `version`, newest first
+async fn newer_releases(version: (u64, u64, u64)) -> Result<Vec<Release>, String> {
+    let releases: Vec<Release> = http::client()?
+        .get(RELEASE_FEED)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut newer: Vec<(Release, (u64, u64, u64))> = releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .filter_map(|release| {
+            let parsed = parse_version(&release.tag_name)?;
+            Some((release, parsed))
+        })
+        .filter(|(_, parsed)| *parsed > version)
+        .collect();
+    newer.sort_by_key(|(_, parsed)| std::cmp::Reverse(*parsed));
+    Ok(newer.into_iter().map(|(release, _)| release).collect())
+}
+
+/// Notify once per release when the default server is behind the feed
+async fn check_releases(app: &AppHandle) -> Result<(), String> {
+    if !read_update_settings(app)?.enabled {
+        return Ok(());
+    }
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    let Some(url) = store
+        .get(DEFAULT_SERVER_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+    else {
+        return Ok(());
+    };
+    let current = fetch_version(app, &url, &HashMap::new()).await?;
+    let Some(parsed) = parse_version(&current) else {
+        return Ok(());
+    };
+    let newer = newer_releases(parsed).await?;
+    let Some(latest) = newer.first() else {
+        return Ok(());
+    };
+
+    let notified = store
+        .get(SERVER_UPDATE_NOTIFIED_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    if notified.as_deref() == Some(latest.tag_name.as_str()) {
+        return Ok(());
+    }
+    let title = match newer.len() {
+        1 => "Your server is 1 version behind".to_string(),
+        n => format!("Your server is {} versions behind", n),
+    };
+    notifications::notify(
+        app.clone(),
+        title,
+        Some(format!(
+            "{} runs {}; the latest release is {}.",
+            url,
+            current,
+            latest.tag_name.trim_start_matches('v')
+        )),
+        Some(latest.html_url.clone()),
+        None,
+    )
+    .await?;
+    store.set(
+        SERVER_UPDATE_NOTIFIED_KEY,
+        serde_json::json!(latest.tag_name),
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Check the default server against the release feed once a day, when enabled
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(RELEASE_CHECK_DELAY).await;
+        let mut interval = tokio::time::interval(RELEASE_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_releases(&app).await {
+                log::warn!("Server release check failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Get whether the server release check is enabled
+#[tauri::command]
+pub async fn get_server_update_settings(app: AppHandle) -> Result<ServerUpdateSettings, String> {
+    read_update_settings(&app)
+}
+
+/// Turn the server release check on or off
+#[tauri::command]
+pub async fn set_server_update_settings(
+    app: AppHandle,
+    settings: ServerUpdateSettings,
+) -> Result<(), String> {
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(SERVER_UPDATE_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_parse_with_prefixes_and_suffixes() {
+        assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version(" 1.2.3\n"), Some((1, 2, 3)));
+        assert_eq!(parse_version("1.2.3-beta.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("1.2.3+build.7"), Some((1, 2, 3)));
+    }
+
+    #[test]
+    fn missing_components_are_zero() {
+        assert_eq!(parse_version("2"), Some((2, 0, 0)));
+        assert_eq!(parse_version("v2.5"), Some((2, 5, 0)));
+    }
+
+    #[test]
+    fn unreadable_versions_are_rejected() {
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("v"), None);
+        assert_eq!(parse_version("latest"), None);
+        assert_eq!(parse_version("1.x.3"), None);
+    }
+
+    #[test]
+    fn new_servers_are_compatible() {
+        let compatibility = assess(Some("v1.0.10".to_string()));
+        assert_eq!(compatibility.verdict, Compatibility::Compatible);
+        assert!(compatibility.unavailable_features.is_empty());
+    }
+
+    #[test]
+    fn servers_missing_features_are_degraded() {
+        let compatibility = assess(Some("1.0.8-rc.2".to_string()));
+        assert_eq!(compatibility.verdict, Compatibility::Degraded);
+        let features: Vec<&str> = compatibility
+            .unavailable_features
+            .iter()
+            .map(|f| f.feature.as_str())
+            .collect();
+        assert_eq!(features, ["quick-capture"]);
+    }
+
+    #[test]
+    fn old_servers_are_unsupported() {
+        let compatibility = assess(Some("0.9".to_string()));
+        assert_eq!(compatibility.verdict, Compatibility::Unsupported);
+        assert_eq!(
+            compatibility.unavailable_features.len(),
+            FEATURE_VERSIONS.len()
+        );
+    }
+
+    #[test]
+    fn unreadable_versions_are_unknown() {
+        for version in [None, Some("dev".to_string())] {
+            let compatibility = assess(version);
+            assert_eq!(compatibility.verdict, Compatibility::Unknown);
+            assert!(compatibility.unavailable_features.is_empty());
+        }
+    }
+}
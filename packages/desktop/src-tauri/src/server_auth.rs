@@ -0,0 +1,558 @@
+//! Sign-in for reverse proxies that answer with a Basic or Digest challenge
+//!
+//! When a server replies `401` with `WWW-Authenticate`, an `auth-required`
+//! event asks the frontend for credentials. `set_server_credentials` keeps the
+//! password in the OS keychain and every later native request to that server
+//! carries an `Authorization` header. Webviews can't have headers added, so
+//! page loads rely on the engine's own prompt (WebView2 and WebKitGTK show
+//! one; WKWebView accepts credentials embedded in the server URL).
+
+use md5::Md5;
+use reqwest::header::{HeaderValue, WWW_AUTHENTICATE};
+use reqwest::{Method, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+use crate::profiles;
+
+const SAVED_LOGINS_KEY: &str = "serverLogins";
+const KEYCHAIN_SERVICE: &str = "Apollo server sign-in";
+
+/// Signed-in servers of the active profile and their latest challenge, keyed by origin
+static LOGINS: Mutex<Option<HashMap<String, Login>>> = Mutex::new(None);
+/// Challenges waiting for credentials, keyed by origin
+static PENDING: Mutex<Option<HashMap<String, Challenge>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    Basic,
+    Digest,
+}
+
+#[derive(Debug, Clone)]
+struct Challenge {
+    scheme: AuthScheme,
+    realm: Option<String>,
+    nonce: String,
+    opaque: Option<String>,
+    /// Only `auth` is supported; `auth-int` would need the request body
+    qop_auth: bool,
+    sha256: bool,
+}
+
+impl Challenge {
+    fn basic(realm: Option<String>) -> Self {
+        Challenge {
+            scheme: AuthScheme::Basic,
+            realm,
+            nonce: String::new(),
+            opaque: None,
+            qop_auth: false,
+            sha256: false,
+        }
+    }
+
+    /// What a saved sign-in starts with: Basic is sent preemptively, Digest
+    /// waits for the server's first challenge to get a nonce
+    fn saved(scheme: AuthScheme) -> Self {
+        Challenge {
+            scheme,
+            ..Challenge::basic(None)
+        }
+    }
+}
+
+struct Login {
+    username: String,
+    password: String,
+    challenge: Challenge,
+    /// Digest nonce count, sent as `nc`
+    count: u32,
+}
+
+/// A saved sign-in; the password is only in the keychain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedLogin {
+    pub origin: String,
+    pub username: String,
+    pub scheme: AuthScheme,
+}
+
+/// Sent when a server asks for credentials Apollo doesn't have
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthRequest {
+    pub origin: String,
+    pub scheme: AuthScheme,
+    pub realm: Option<String>,
+}
+
+fn origin(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    Some(url.origin().ascii_serialization())
+}
+
+/// Keychain item for a profile's sign-in to a server
+fn keychain_entry(profile: &str, origin: &str) -> Result<keyring::Entry, String> {
+    let account = format!("{}:{}", profile, origin);
+    keyring::Entry::new(KEYCHAIN_SERVICE, &account).map_err(|e| e.to_string())
+}
+
+/// Split `key=value, key="quoted, value"` challenge parameters
+fn parse_params(params: &str) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+        out.insert(key, value.trim().to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    out
+}
+
+/// Read a Basic or Digest challenge, preferring Digest when both are offered
+fn parse_challenge(response: &Response) -> Option<Challenge> {
+    let mut basic = None;
+    for value in response.headers().get_all(WWW_AUTHENTICATE) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        let (scheme, params) = value.split_once(' ').unwrap_or((value, ""));
+        let params = parse_params(params);
+        let realm = params.get("realm").cloned();
+        if scheme.eq_ignore_ascii_case("digest") {
+            let algorithm = params.get("algorithm").map(|a| a.to_uppercase());
+            let sha256 = match algorithm.as_deref() {
+                None | Some("MD5") => false,
+                Some("SHA-256") => true,
+                // Session variants and SHA-512-256 aren't supported
+                Some(_) => continue,
+            };
+            return Some(Challenge {
+                scheme: AuthScheme::Digest,
+                realm,
+                nonce: params.get("nonce").cloned()?,
+                opaque: params.get("opaque").cloned(),
+                qop_auth: params
+                    .get("qop")
+                    .is_some_and(|qop| qop.split(',').any(|q| q.trim() == "auth")),
+                sha256,
+            });
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            basic = Some(Challenge::basic(realm));
+        }
+    }
+    basic
+}
+
+fn hash(sha256: bool, input: String) -> String {
+    let digest: Vec<u8> = match sha256 {
+        true => Sha256::digest(input.as_bytes()).to_vec(),
+        false => Md5::digest(input.as_bytes()).to_vec(),
+    };
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn header_value(login: &mut Login, method: &Method, url: &Url) -> Option<HeaderValue> {
+    if login.challenge.scheme == AuthScheme::Digest && login.challenge.nonce.is_empty() {
+        return None;
+    }
+    let value = match login.challenge.scheme {
+        AuthScheme::Basic => {
+            use base64::Engine;
+            let pair = format!("{}:{}", login.username, login.password);
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(pair)
+            )
+        }
+        AuthScheme::Digest => {
+            let challenge = &login.challenge;
+            let realm = challenge.realm.clone().unwrap_or_default();
+            let uri = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            let ha1 = hash(
+                challenge.sha256,
+                format!("{}:{}:{}", login.username, realm, login.password),
+            );
+            let ha2 = hash(challenge.sha256, format!("{}:{}", method.as_str(), uri));
+            let mut value = format!(
+                "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}",
+                login.username,
+                realm,
+                challenge.nonce,
+                uri,
+                if challenge.sha256 { "SHA-256" } else { "MD5" }
+            );
+            let response = if challenge.qop_auth {
+                login.count += 1;
+                let count = format!("{:08x}", login.count);
+                let cnonce = format!(
+                    "{:016x}",
+                    std::collections::hash_map::RandomState::new()
+                        .build_hasher()
+                        .finish()
+                );
+                value.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", count, cnonce));
+                hash(
+                    challenge.sha256,
+                    format!(
+                        "{}:{}:{}:{}:auth:{}",
+                        ha1, challenge.nonce, count, cnonce, ha2
+                    ),
+                )
+            } else {
+                hash(
+                    challenge.sha256,
+                    format!("{}:{}:{}", ha1, challenge.nonce, ha2),
+                )
+            };
+            value.push_str(&format!(", response=\"{}\"", response));
+            if let Some(opaque) = &challenge.opaque {
+                value.push_str(&format!(", opaque=\"{}\"", opaque));
+            }
+            value
+        }
+    };
+    HeaderValue::from_str(&value).ok()
+}
+
+/// The `Authorization` value for a request, if its server was signed in to
+pub fn authorization(method: &Method, url: &Url) -> Option<HeaderValue> {
+    let origin = url.origin().ascii_serialization();
+    let mut logins = LOGINS.lock().unwrap_or_else(|e| e.into_inner());
+    let login = logins.as_mut()?.get_mut(&origin)?;
+    header_value(login, method, url)
+}
+
+/// Note a `401` challenge from a server
+///
+/// A new Digest nonce for a signed-in server is used from the next request on.
+/// Otherwise the frontend is asked for credentials. Returns whether the
+/// response was a challenge.
+pub fn handle_challenge(app: &AppHandle, url: &str, response: &Response) -> bool {
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return false;
+    }
+    let (Some(origin), Some(challenge)) = (origin(url), parse_challenge(response)) else {
+        return false;
+    };
+
+    let mut logins = LOGINS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(login) = logins.as_mut().and_then(|l| l.get_mut(&origin)) {
+        let renewed =
+            challenge.scheme == AuthScheme::Digest && challenge.nonce != login.challenge.nonce;
+        if renewed {
+            login.challenge = challenge;
+            login.count = 0;
+            return true;
+        }
+    }
+    drop(logins);
+
+    let request = AuthRequest {
+        origin: origin.clone(),
+        scheme: challenge.scheme,
+        realm: challenge.realm.clone(),
+    };
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(origin, challenge);
+    events::publish(app, Event::AuthRequired(request));
+    true
+}
+
+fn read_saved(app: &AppHandle) -> Result<Vec<SavedLogin>, String> {
+    read_saved_for(app, &profiles::active(app))
+}
+
+fn read_saved_for(app: &AppHandle, profile: &str) -> Result<Vec<SavedLogin>, String> {
+    let store = profiles::profile_store(app, profile).map_err(|e| e.to_string())?;
+    match store.get(SAVED_LOGINS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn write_saved(app: &AppHandle, saved: &[SavedLogin]) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(SAVED_LOGINS_KEY, serde_json::json!(saved));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Read the active profile's saved passwords from the keychain
+///
+/// Runs once in the background at startup; until then requests go without
+/// credentials and a challenge is answered once they're loaded.
+pub fn load(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let saved = match read_saved(&app) {
+            Ok(saved) => saved,
+            Err(e) => {
+                log::warn!("Failed to read saved server sign-ins: {}", e);
+                return;
+            }
+        };
+        let mut loaded = HashMap::new();
+        for login in saved {
+            let password = keychain_entry(&profiles::active(&app), &login.origin)
+                .and_then(|entry| entry.get_password().map_err(|e| e.to_string()));
+            match password {
+                Ok(password) => {
+                    loaded.insert(
+                        login.origin,
+                        Login {
+                            username: login.username,
+                            password,
+                            challenge: Challenge::saved(login.scheme),
+                            count: 0,
+                        },
+                    );
+                }
+                Err(e) => log::warn!("No keychain password for {}: {}", login.origin, e),
+            }
+        }
+        *LOGINS.lock().unwrap_or_else(|e| e.into_inner()) = Some(loaded);
+    });
+}
+
+/// Remove a deleted profile's passwords from the keychain
+pub fn forget_profile(app: &AppHandle, profile: &str) {
+    let Ok(saved) = read_saved_for(app, profile) else {
+        return;
+    };
+    for login in saved {
+        let removed = keychain_entry(profile, &login.origin)
+            .and_then(|entry| entry.delete_credential().map_err(|e| e.to_string()));
+        if let Err(e) = removed {
+            log::warn!(
+                "Failed to remove keychain password for {}: {}",
+                login.origin,
+                e
+            );
+        }
+    }
+}
+
+/// Servers the active profile has signed in to
+#[tauri::command]
+pub async fn list_server_credentials(app: AppHandle) -> Result<Vec<SavedLogin>, String> {
+    read_saved(&app)
+}
+
+/// Save credentials for a server that sent an `auth-required` challenge
+///
+/// The password goes to the OS keychain; only the server and username are
+/// kept in the profile's settings.
+#[tauri::command]
+pub async fn set_server_credentials(
+    app: AppHandle,
+    url: String,
+    username: String,
+    password: String,
+) -> Result<(), String> {
+    let origin = origin(&url).ok_or_else(|| format!("'{}' is not a valid URL", url))?;
+    let entry = keychain_entry(&profiles::active(&app), &origin)?;
+    let secret = password.clone();
+    tauri::async_runtime::spawn_blocking(move || entry.set_password(&secret))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let challenge = PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .and_then(|pending| pending.remove(&origin))
+        .unwrap_or_else(|| Challenge::basic(None));
+    let mut saved = read_saved(&app)?;
+    saved.retain(|login| login.origin != origin);
+    saved.push(SavedLogin {
+        origin: origin.clone(),
+        username: username.clone(),
+        scheme: challenge.scheme,
+    });
+    write_saved(&app, &saved)?;
+
+    LOGINS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            origin,
+            Login {
+                username,
+                password,
+                challenge,
+                count: 0,
+            },
+        );
+    Ok(())
+}
+
+/// Forget a server's credentials and remove them from the keychain
+#[tauri::command]
+pub async fn remove_server_credentials(app: AppHandle, url: String) -> Result<(), String> {
+    let origin = origin(&url).ok_or_else(|| format!("'{}' is not a valid URL", url))?;
+    let mut saved = read_saved(&app)?;
+    saved.retain(|login| login.origin != origin);
+    write_saved(&app, &saved)?;
+    if let Some(logins) = LOGINS.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        logins.remove(&origin);
+    }
+
+    let entry = keychain_entry(&profiles::active(&app), &origin)?;
+    tauri::async_runtime::spawn_blocking(move || match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Digest example from RFC 2617
+    fn digest_login(qop_auth: bool, opaque: Option<&str>) -> Login {
+        Login {
+            username: "Mufasa".to_string(),
+            password: "Circle Of Life".to_string(),
+            challenge: Challenge {
+                scheme: AuthScheme::Digest,
+                realm: Some("testrealm@host.com".to_string()),
+                nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093".to_string(),
+                opaque: opaque.map(String::from),
+                qop_auth,
+                sha256: false,
+            },
+            count: 0,
+        }
+    }
+
+    fn digest_params(login: &mut Login, url: &str) -> HashMap<String, String> {
+        let url = Url::parse(url).unwrap();
+        let value = header_value(login, &Method::GET, &url).unwrap();
+        let value = value.to_str().unwrap();
+        parse_params(value.strip_prefix("Digest ").unwrap())
+    }
+
+    #[test]
+    fn basic_sends_the_encoded_pair() {
+        let mut login = Login {
+            username: "Aladdin".to_string(),
+            password: "open sesame".to_string(),
+            challenge: Challenge::basic(None),
+            count: 0,
+        };
+        let url = Url::parse("https://photos.example.com/api").unwrap();
+        assert_eq!(
+            header_value(&mut login, &Method::GET, &url).unwrap(),
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+    }
+
+    #[test]
+    fn digest_answers_the_rfc_example() {
+        let mut login = digest_login(true, Some("5ccc069c403ebaf9f0171e9517f40e41"));
+        let params = digest_params(&mut login, "http://www.nowhere.org/dir/index.html");
+        assert_eq!(params["username"], "Mufasa");
+        assert_eq!(params["realm"], "testrealm@host.com");
+        assert_eq!(params["uri"], "/dir/index.html");
+        assert_eq!(params["algorithm"], "MD5");
+        assert_eq!(params["qop"], "auth");
+        assert_eq!(params["nc"], "00000001");
+        assert_eq!(params["opaque"], "5ccc069c403ebaf9f0171e9517f40e41");
+
+        // HA1 and HA2 as given in the RFC, which answer its own cnonce with
+        // its response; the cnonce sent here is random
+        let answer = |cnonce: &str| {
+            hash(
+                false,
+                format!(
+                    "939e7578ed9e3c518a452acee763bce9:dcd98b7102dd2f0e8b11d0f600bfb0c093:00000001:{}:auth:39aff3a2bab6126f332b942af96d3366",
+                    cnonce
+                ),
+            )
+        };
+        assert_eq!(answer("0a4f113b"), "6629fae49393a05397450978507c4ef1");
+        assert_eq!(params["response"], answer(&params["cnonce"]));
+    }
+
+    #[test]
+    fn digest_counts_requests_and_keeps_the_query() {
+        let mut login = digest_login(true, None);
+        digest_params(&mut login, "http://www.nowhere.org/dir/index.html");
+        let params = digest_params(&mut login, "http://www.nowhere.org/dir/index.html?a=1");
+        assert_eq!(params["nc"], "00000002");
+        assert_eq!(params["uri"], "/dir/index.html?a=1");
+        assert!(!params.contains_key("opaque"));
+    }
+
+    #[test]
+    fn digest_without_qop_uses_the_legacy_response() {
+        let mut login = digest_login(false, None);
+        let params = digest_params(&mut login, "http://www.nowhere.org/dir/index.html");
+        assert!(!params.contains_key("qop"));
+        assert!(!params.contains_key("cnonce"));
+        assert_eq!(
+            params["response"],
+            hash(
+                false,
+                "939e7578ed9e3c518a452acee763bce9:dcd98b7102dd2f0e8b11d0f600bfb0c093:39aff3a2bab6126f332b942af96d3366"
+                    .to_string()
+            )
+        );
+        assert_eq!(login.count, 0);
+    }
+
+    #[test]
+    fn digest_sha256_is_named_and_used() {
+        let mut login = digest_login(false, None);
+        login.challenge.sha256 = true;
+        let params = digest_params(&mut login, "http://www.nowhere.org/dir/index.html");
+        assert_eq!(params["algorithm"], "SHA-256");
+        assert_eq!(params["response"].len(), 64);
+    }
+
+    #[test]
+    fn digest_waits_for_a_nonce() {
+        let mut login = digest_login(true, None);
+        login.challenge = Challenge::saved(AuthScheme::Digest);
+        let url = Url::parse("http://www.nowhere.org/dir/index.html").unwrap();
+        assert!(header_value(&mut login, &Method::GET, &url).is_none());
+    }
+
+    #[test]
+    fn challenge_params_allow_quoted_commas() {
+        let params = parse_params(r#"realm="Apollo, at home", qop="auth,auth-int", stale=FALSE"#);
+        assert_eq!(params["realm"], "Apollo, at home");
+        assert_eq!(params["qop"], "auth,auth-int");
+        assert_eq!(params["stale"], "FALSE");
+    }
+}
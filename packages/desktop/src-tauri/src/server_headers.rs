@@ -0,0 +1,152 @@
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Url};
+
+use crate::profiles;
+
+const CUSTOM_HEADERS_KEY: &str = "customHeaders";
+
+/// Rules of the active profile, read on every native request
+static RULES: Mutex<Vec<HeaderRule>> = Mutex::new(Vec::new());
+
+/// Headers sent with every native request to one server
+///
+/// For access proxies in front of the server, such as a Cloudflare Access
+/// service token or the `X-Forwarded-User` an Authelia setup trusts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderRule {
+    /// Server address; requests to the same origin under its path match
+    pub url: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomHeaders {
+    pub rules: Vec<HeaderRule>,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<CustomHeaders, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(CUSTOM_HEADERS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(CustomHeaders::default()),
+    }
+}
+
+/// Load the saved rules so requests don't read the store
+pub fn load(app: &AppHandle) {
+    match read_settings(app) {
+        Ok(settings) => *RULES.lock().unwrap_or_else(|e| e.into_inner()) = settings.rules,
+        Err(e) => log::warn!("Failed to load custom headers: {}", e),
+    }
+}
+
+/// Whether a URL points at a server: same scheme, host, and port, and a path
+/// at or below the server's, so lookalike hosts and other ports don't match
+pub fn is_on_server(server: &str, url: &str) -> bool {
+    let (Ok(server), Ok(url)) = (Url::parse(server), Url::parse(url)) else {
+        return false;
+    };
+    if server.origin() != url.origin() {
+        return false;
+    }
+    let base = server.path().trim_end_matches('/');
+    url.path()
+        .strip_prefix(base)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The entry for the server a URL points at, given each entry's server
+/// address; the longest address wins, and entries without one never match
+pub fn best_match<'a, T>(
+    entries: &'a [T],
+    url: &str,
+    address: impl Fn(&T) -> &str,
+) -> Option<&'a T> {
+    entries
+        .iter()
+        .filter(|e| !address(e).is_empty() && is_on_server(address(e), url))
+        .max_by_key(|e| address(e).len())
+}
+
+/// Headers configured for the server a URL points at
+///
+/// Never sent to other hosts, so tokens don't leak to update or release feeds.
+pub fn for_url(url: &str) -> Vec<(String, String)> {
+    let rules = RULES.lock().unwrap_or_else(|e| e.into_inner());
+    best_match(&rules, url, |r| r.url.as_str())
+        .map(|r| r.headers.clone().into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Get the custom headers sent to servers
+#[tauri::command]
+pub async fn get_custom_headers(app: AppHandle) -> Result<CustomHeaders, String> {
+    read_settings(&app)
+}
+
+/// Save the custom headers sent to servers, taking effect on the next request
+#[tauri::command]
+pub async fn set_custom_headers(app: AppHandle, settings: CustomHeaders) -> Result<(), String> {
+    for rule in &settings.rules {
+        for (name, value) in &rule.headers {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("'{}' is not a valid header name", name))?;
+            HeaderValue::from_str(value)
+                .map_err(|_| format!("The value of '{}' is not a valid header value", name))?;
+        }
+    }
+
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(CUSTOM_HEADERS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())?;
+
+    load(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookalike_hosts_are_other_servers() {
+        assert!(is_on_server("https://a.com", "https://a.com/photos"));
+        assert!(!is_on_server("https://a.com", "https://a.com.evil/photos"));
+        assert!(!is_on_server("https://a.com", "https://evil-a.com/photos"));
+        assert!(!is_on_server("https://a.com", "http://a.com/photos"));
+    }
+
+    #[test]
+    fn paths_match_whole_segments() {
+        assert!(is_on_server("https://a.com/api", "https://a.com/api"));
+        assert!(is_on_server(
+            "https://a.com/api",
+            "https://a.com/api/albums"
+        ));
+        assert!(is_on_server(
+            "https://a.com/api/",
+            "https://a.com/api/albums"
+        ));
+        assert!(!is_on_server("https://a.com/api", "https://a.com/apix"));
+        assert!(!is_on_server("https://a.com/api", "https://a.com/other"));
+    }
+
+    #[test]
+    fn default_ports_match_explicit_ones() {
+        assert!(is_on_server("https://a.com", "https://a.com:443/photos"));
+        assert!(is_on_server("http://a.com:80", "http://a.com/photos"));
+        assert!(!is_on_server("https://a.com", "https://a.com:8443/photos"));
+        assert!(!is_on_server("https://a.com:8443", "https://a.com/photos"));
+    }
+
+    #[test]
+    fn unparsable_addresses_never_match() {
+        assert!(!is_on_server("", "https://a.com/photos"));
+        assert!(!is_on_server("a.com", "https://a.com/photos"));
+    }
+}
@@ -0,0 +1,130 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{ImageFormat, Luma};
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::events::{self, Event};
+use crate::http;
+use crate::notifications::{self, NotificationCategory};
+
+/// Where the link is in the server's response unless the item says otherwise
+const DEFAULT_LINK_POINTER: &str = "/url";
+/// Pixels per QR module, big enough to scan off a laptop screen
+const QR_SCALE: u32 = 8;
+/// Medium error correction, which still scans from a phone across the room
+/// and fits any share link
+const QR_EC_LEVEL: EcLevel = EcLevel::M;
+
+/// An item to share and how to ask its server for a link
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareItem {
+    /// Endpoint that creates a share link when POSTed to
+    pub share_url: String,
+    pub headers: HashMap<String, String>,
+    /// JSON body for the request, e.g. an expiry or a password
+    pub body: Option<serde_json::Value>,
+    /// JSON pointer to the link in the response, e.g. `/url`
+    pub link_pointer: Option<String>,
+    /// Shown in the notification, e.g. the photo or album name
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub link: String,
+    /// The link as a QR code, a `data:image/png;base64` URL
+    pub qr: String,
+}
+
+async fn request_link(item: &ShareItem) -> Result<String, String> {
+    let client = http::client()?;
+    let mut request =
+        http::with_headers(client.post(&item.share_url), &item.share_url, &item.headers)?;
+    if let Some(body) = &item.body {
+        request = request.json(body);
+    }
+    let response: serde_json::Value = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    let pointer = item.link_pointer.as_deref().unwrap_or(DEFAULT_LINK_POINTER);
+    response
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+        .ok_or_else(|| format!("The server's response has no link at '{}'", pointer))
+}
+
+/// Draw a link as a QR code PNG, with the quiet zone the spec asks for
+fn qr_png(link: &str) -> Result<Vec<u8>, String> {
+    let image = QrCode::with_error_correction_level(link, QR_EC_LEVEL)
+        .map_err(|e| e.to_string())?
+        .render::<Luma<u8>>()
+        .module_dimensions(QR_SCALE, QR_SCALE)
+        .quiet_zone(true)
+        .build();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+async fn share(app: &AppHandle, item: &ShareItem) -> Result<ShareLink, String> {
+    let link = request_link(item).await?;
+    app.clipboard()
+        .write_text(link.clone())
+        .map_err(|e| e.to_string())?;
+
+    let text = link.clone();
+    let png = tauri::async_runtime::spawn_blocking(move || qr_png(&text))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(ShareLink {
+        link,
+        qr: format!("data:image/png;base64,{}", STANDARD.encode(png)),
+    })
+}
+
+/// Create a share link, copy it, and notify, all in one step
+///
+/// Meant for the tray and global shortcuts, which have no window to show
+/// progress in: failures are reported with a notification as well as
+/// returned. The link and its QR code are also sent as a
+/// `share-link-created` event, so a window can show the code.
+#[tauri::command]
+pub async fn create_share_shortcut(app: AppHandle, item: ShareItem) -> Result<ShareLink, String> {
+    let result = share(&app, &item).await;
+    let (title, body, category) = match &result {
+        Ok(shared) => (
+            match &item.title {
+                Some(title) => format!("Link to {} copied", title),
+                None => "Share link copied".to_string(),
+            },
+            Some(shared.link.clone()),
+            None,
+        ),
+        Err(e) => (
+            "Couldn't create a share link".to_string(),
+            Some(e.clone()),
+            Some(NotificationCategory::Errors),
+        ),
+    };
+    let href = result.as_ref().ok().map(|shared| shared.link.clone());
+    let _ = notifications::notify(app.clone(), title, body, href, category).await;
+
+    if let Ok(shared) = &result {
+        events::publish(&app, Event::ShareLinkCreated(shared.clone()));
+    }
+    result
+}
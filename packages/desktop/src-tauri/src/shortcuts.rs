@@ -0,0 +1,681 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use crate::events::{self, Event};
+use crate::profiles;
+
+/// User remappings, keyed by action id; `null` means the user unbound it
+const SHORTCUTS_KEY: &str = "shortcuts";
+
+/// Something a keyboard shortcut can trigger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShortcutAction {
+    pub id: String,
+    pub label: String,
+    /// Accelerator used until the user remaps it, e.g. `CmdOrCtrl+Shift+F`
+    pub default: Option<String>,
+    /// Registered with the OS so it works while Apollo is in the background
+    #[serde(default)]
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutBinding {
+    pub action: ShortcutAction,
+    /// Accelerator in effect, normalized, e.g. `shift+control+KeyF`
+    pub shortcut: Option<String>,
+    /// The user changed it from the default
+    pub customized: bool,
+    /// Why the shortcut isn't active, e.g. another action or app already uses it
+    pub conflict: Option<String>,
+    /// The shortcut works right now; for global actions, the OS or desktop
+    /// accepted it
+    pub active: bool,
+    /// Key combination the desktop portal reports, which the user may have
+    /// changed in the system settings
+    pub trigger: Option<String>,
+}
+
+/// How global shortcuts reach the OS on this system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortcutBackend {
+    /// Registered directly, on Windows, macOS, and Linux under X11
+    Native,
+    /// Bound through the XDG GlobalShortcuts portal, under Wayland
+    Portal,
+    /// No way to register them, so only in-window shortcuts work
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutSupport {
+    pub backend: ShortcutBackend,
+    /// The shortcut chosen in Apollo is the one used. Portals only take it as
+    /// a suggestion and let the user pick the trigger in the system settings.
+    pub remappable: bool,
+    /// Why global shortcuts are unavailable
+    pub reason: Option<String>,
+}
+
+/// Declared actions and the global shortcuts currently registered for them
+#[derive(Default)]
+pub struct Shortcuts(Mutex<Registry>);
+
+#[derive(Default)]
+struct Registry {
+    /// In declaration order, native actions first
+    actions: Vec<ShortcutAction>,
+    /// Actions handled in Rust instead of being sent to the frontend
+    native: HashMap<String, fn(&AppHandle)>,
+    /// Registered global shortcut id to action id
+    registered: HashMap<u32, String>,
+    /// Global shortcuts the OS refused, keyed by action id
+    refused: HashMap<String, String>,
+    /// Triggers the portal bound, keyed by action id
+    triggers: HashMap<String, String>,
+    /// Why the portal could not be used
+    portal_error: Option<String>,
+}
+
+impl Shortcuts {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    Shortcut::from_str(accelerator)
+        .map_err(|e| format!("'{}' is not a valid shortcut: {}", accelerator, e))
+}
+
+fn read_overrides(app: &AppHandle) -> Result<HashMap<String, Option<String>>, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(SHORTCUTS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn write_overrides(
+    app: &AppHandle,
+    overrides: &HashMap<String, Option<String>>,
+) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(SHORTCUTS_KEY, serde_json::json!(overrides));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Resolve every action's shortcut, leaving later duplicates unbound
+///
+/// The first action to claim a key combination keeps it, so native and
+/// earlier-declared actions win over later ones.
+fn resolve(
+    actions: &[ShortcutAction],
+    overrides: &HashMap<String, Option<String>>,
+    refused: &HashMap<String, String>,
+) -> Vec<(ShortcutBinding, Option<Shortcut>)> {
+    let mut claimed: HashMap<Shortcut, String> = HashMap::new();
+    actions
+        .iter()
+        .map(|action| {
+            let customized = overrides.contains_key(&action.id);
+            let accelerator = match overrides.get(&action.id) {
+                Some(mapped) => mapped.clone(),
+                None => action.default.clone(),
+            };
+            let mut conflict = None;
+            let shortcut = accelerator.and_then(|a| match parse(&a) {
+                Ok(shortcut) => Some(shortcut),
+                Err(e) => {
+                    conflict = Some(e);
+                    None
+                }
+            });
+            let shortcut = shortcut.filter(|shortcut| match claimed.get(shortcut) {
+                Some(owner) => {
+                    conflict = Some(format!("Already used by '{}'", owner));
+                    false
+                }
+                None => {
+                    claimed.insert(*shortcut, action.label.clone());
+                    true
+                }
+            });
+            if shortcut.is_some() {
+                if let Some(reason) = refused.get(&action.id) {
+                    conflict = Some(reason.clone());
+                }
+            }
+            let binding = ShortcutBinding {
+                action: action.clone(),
+                shortcut: shortcut.map(|s| s.into_string()),
+                customized,
+                conflict,
+                active: false,
+                trigger: None,
+            };
+            (binding, shortcut)
+        })
+        .collect()
+}
+
+fn bindings(app: &AppHandle) -> Result<Vec<ShortcutBinding>, String> {
+    let overrides = read_overrides(app)?;
+    let registry = app.state::<Shortcuts>();
+    let registry = registry.lock();
+    Ok(resolve(&registry.actions, &overrides, &registry.refused)
+        .into_iter()
+        .map(|(mut binding, shortcut)| {
+            let id = &binding.action.id;
+            binding.trigger = registry.triggers.get(id).cloned();
+            binding.active = match binding.action.global {
+                true => binding.trigger.is_some() || registry.registered.values().any(|a| a == id),
+                false => shortcut.is_some(),
+            };
+            binding
+        })
+        .collect())
+}
+
+/// Whether this session is Wayland, where apps can't grab keys themselves
+pub fn is_wayland() -> bool {
+    cfg!(target_os = "linux")
+        && (std::env::var("XDG_SESSION_TYPE").is_ok_and(|t| t == "wayland")
+            || std::env::var_os("WAYLAND_DISPLAY").is_some())
+}
+
+pub fn support(app: &AppHandle) -> ShortcutSupport {
+    if !is_wayland() {
+        return ShortcutSupport {
+            backend: ShortcutBackend::Native,
+            remappable: true,
+            reason: None,
+        };
+    }
+    match app.state::<Shortcuts>().lock().portal_error.clone() {
+        Some(reason) => ShortcutSupport {
+            backend: ShortcutBackend::Unavailable,
+            remappable: false,
+            reason: Some(reason),
+        },
+        None => ShortcutSupport {
+            backend: ShortcutBackend::Portal,
+            remappable: false,
+            reason: None,
+        },
+    }
+}
+
+/// Register the current global shortcuts with the OS, replacing the previous ones
+///
+/// Must not be called from the main thread, since the plugin waits on it.
+fn apply(app: &AppHandle) -> Result<(), String> {
+    let overrides = read_overrides(app)?;
+    let wanted: Vec<(ShortcutAction, Shortcut)> = {
+        let registry = app.state::<Shortcuts>();
+        let registry = registry.lock();
+        resolve(&registry.actions, &overrides, &HashMap::new())
+            .into_iter()
+            .filter(|(binding, _)| binding.action.global)
+            .filter_map(|(binding, shortcut)| Some((binding.action, shortcut?)))
+            .collect()
+    };
+
+    #[cfg(target_os = "linux")]
+    if is_wayland() {
+        let (triggers, portal_error) = match portal::bind(app, &wanted) {
+            Ok(triggers) => (triggers, None),
+            Err(e) => {
+                log::warn!("Global shortcuts portal unavailable: {}", e);
+                (HashMap::new(), Some(e))
+            }
+        };
+        let refused = wanted
+            .iter()
+            .filter(|(action, _)| !triggers.contains_key(&action.id))
+            .map(|(action, _)| {
+                let reason = match &portal_error {
+                    Some(_) => "Global shortcuts aren't supported by this desktop",
+                    None => "Not bound by the desktop",
+                };
+                (action.id.clone(), reason.to_string())
+            })
+            .collect();
+        let registry = app.state::<Shortcuts>();
+        let mut registry = registry.lock();
+        registry.registered.clear();
+        registry.refused = refused;
+        registry.triggers = triggers;
+        registry.portal_error = portal_error;
+        return Ok(());
+    }
+
+    // Every global shortcut goes through the registry, so start from scratch
+    let global = app.global_shortcut();
+    let _ = global.unregister_all();
+
+    let mut registered = HashMap::new();
+    let mut refused = HashMap::new();
+    for (action, shortcut) in wanted {
+        match global.register(shortcut) {
+            Ok(()) => {
+                registered.insert(shortcut.id(), action.id);
+            }
+            Err(e) => {
+                log::warn!("Failed to register global shortcut {}: {}", shortcut, e);
+                refused.insert(action.id, "Already used by another application".to_string());
+            }
+        }
+    }
+
+    let registry = app.state::<Shortcuts>();
+    let mut registry = registry.lock();
+    registry.registered = registered;
+    registry.refused = refused;
+    Ok(())
+}
+
+/// Declare an action handled in Rust, such as a system-wide hotkey
+///
+/// Native actions take precedence over frontend ones with the same shortcut.
+/// Call `refresh` afterwards, off the main thread, to register globals.
+pub fn declare_native(app: &AppHandle, action: ShortcutAction, handler: fn(&AppHandle)) {
+    let registry = app.state::<Shortcuts>();
+    let mut registry = registry.lock();
+    let Registry {
+        actions, native, ..
+    } = &mut *registry;
+    actions.retain(|a| a.id != action.id);
+    let at = actions
+        .iter()
+        .filter(|a| native.contains_key(&a.id))
+        .count();
+    native.insert(action.id.clone(), handler);
+    actions.insert(at, action);
+}
+
+/// Remove an action declared with `declare_native`
+pub fn remove_native(app: &AppHandle, id: &str) {
+    let registry = app.state::<Shortcuts>();
+    let mut registry = registry.lock();
+    registry.native.remove(id);
+    registry.actions.retain(|a| a.id != id);
+}
+
+/// Register global shortcuts in the background after the registry changed
+pub fn refresh(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = apply(&app) {
+            log::warn!("Failed to apply shortcuts: {}", e);
+        }
+    });
+}
+
+/// Run the action bound to a global shortcut that was pressed
+pub fn handle_global(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let action = app
+        .state::<Shortcuts>()
+        .lock()
+        .registered
+        .get(&shortcut.id())
+        .cloned();
+    if let Some(action) = action {
+        run_action(app, action);
+    }
+}
+
+/// Run a native action's handler, or tell the frontend about the others
+fn run_action(app: &AppHandle, action: String) {
+    let handler = app.state::<Shortcuts>().lock().native.get(&action).copied();
+    match handler {
+        Some(handler) => handler(app),
+        None => events::publish(app, Event::ShortcutTriggered { action }),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod portal {
+    use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+    use ashpd::desktop::Session;
+    use futures_util::StreamExt;
+    use std::collections::HashMap;
+    use tauri::async_runtime::JoinHandle;
+    use tauri::AppHandle;
+    use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
+
+    use super::ShortcutAction;
+
+    /// The portal session holding our shortcuts, and the task listening for them
+    struct Bound {
+        session: Session<GlobalShortcuts>,
+        listener: JoinHandle<()>,
+    }
+
+    static BOUND: tokio::sync::Mutex<Option<Bound>> = tokio::sync::Mutex::const_new(None);
+
+    /// Accelerator in the XDG shortcuts format, e.g. `CTRL+SHIFT+space`
+    fn xdg_trigger(shortcut: &Shortcut) -> String {
+        let mut parts = Vec::new();
+        for (modifier, name) in [
+            (Modifiers::CONTROL, "CTRL"),
+            (Modifiers::ALT, "ALT"),
+            (Modifiers::SHIFT, "SHIFT"),
+            (Modifiers::SUPER, "LOGO"),
+        ] {
+            if shortcut.mods.contains(modifier) {
+                parts.push(name.to_string());
+            }
+        }
+        let code = shortcut.key.to_string();
+        let key = match shortcut.key {
+            Code::Space => "space".to_string(),
+            Code::Enter => "Return".to_string(),
+            Code::Backspace => "BackSpace".to_string(),
+            Code::PageUp => "Page_Up".to_string(),
+            Code::PageDown => "Page_Down".to_string(),
+            Code::Minus => "minus".to_string(),
+            Code::Equal => "equal".to_string(),
+            Code::Comma => "comma".to_string(),
+            Code::Period => "period".to_string(),
+            Code::Slash => "slash".to_string(),
+            Code::Backslash => "backslash".to_string(),
+            Code::Semicolon => "semicolon".to_string(),
+            Code::Quote => "apostrophe".to_string(),
+            Code::Backquote => "grave".to_string(),
+            Code::BracketLeft => "bracketleft".to_string(),
+            Code::BracketRight => "bracketright".to_string(),
+            _ => match (code.strip_prefix("Key"), code.strip_prefix("Digit")) {
+                (Some(letter), _) => letter.to_lowercase(),
+                (_, Some(digit)) => digit.to_string(),
+                _ => code.strip_prefix("Arrow").unwrap_or(&code).to_string(),
+            },
+        };
+        parts.push(key);
+        parts.join("+")
+    }
+
+    /// Replace the bound shortcuts, returning the trigger the desktop chose for each
+    ///
+    /// Desktops only let a session bind once, and may ask the user to confirm,
+    /// so every change starts a new session. Blocks until the desktop answers.
+    pub fn bind(
+        app: &AppHandle,
+        wanted: &[(ShortcutAction, Shortcut)],
+    ) -> Result<HashMap<String, String>, String> {
+        tauri::async_runtime::block_on(bind_session(app.clone(), wanted)).map_err(|e| e.to_string())
+    }
+
+    async fn bind_session(
+        app: AppHandle,
+        wanted: &[(ShortcutAction, Shortcut)],
+    ) -> ashpd::Result<HashMap<String, String>> {
+        let mut bound = BOUND.lock().await;
+        if let Some(previous) = bound.take() {
+            previous.listener.abort();
+            let _ = previous.session.close().await;
+        }
+        if wanted.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let portal = GlobalShortcuts::new().await?;
+        let session = portal.create_session(Default::default()).await?;
+        let shortcuts: Vec<NewShortcut> = wanted
+            .iter()
+            .map(|(action, shortcut)| {
+                NewShortcut::new(&action.id, &action.label)
+                    .preferred_trigger(xdg_trigger(shortcut).as_str())
+            })
+            .collect();
+        let response = portal
+            .bind_shortcuts(&session, &shortcuts, None, Default::default())
+            .await?
+            .response()?;
+        let triggers = response
+            .shortcuts()
+            .iter()
+            .map(|s| (s.id().to_string(), s.trigger_description().to_string()))
+            .collect();
+
+        let mut activated = portal.receive_activated().await?;
+        let listener = tauri::async_runtime::spawn(async move {
+            while let Some(event) = activated.next().await {
+                super::run_action(&app, event.shortcut_id().to_string());
+            }
+        });
+        *bound = Some(Bound { session, listener });
+        Ok(triggers)
+    }
+}
+
+/// Declare the frontend's actions, replacing those it declared before
+///
+/// Global actions are registered with the OS and reported as
+/// `shortcut-triggered` events; the frontend handles the others itself.
+/// Returns every action with the shortcut in effect.
+#[tauri::command]
+pub async fn declare_shortcuts(
+    app: AppHandle,
+    actions: Vec<ShortcutAction>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    {
+        let registry = app.state::<Shortcuts>();
+        let mut registry = registry.lock();
+        let Registry {
+            actions: declared,
+            native,
+            ..
+        } = &mut *registry;
+        declared.retain(|a| native.contains_key(&a.id));
+        declared.extend(actions.into_iter().filter(|a| !native.contains_key(&a.id)));
+    }
+    tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || apply(&app)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    bindings(&app)
+}
+
+/// How global shortcuts are registered here, so the UI can explain missing ones
+///
+/// Under Wayland they go through the desktop's GlobalShortcuts portal, which
+/// decides the actual key combination; check each binding's `active` and
+/// `trigger` to see what took effect.
+#[tauri::command]
+pub async fn get_shortcut_support(app: AppHandle) -> Result<ShortcutSupport, String> {
+    Ok(support(&app))
+}
+
+/// Every declared action with the shortcut in effect
+#[tauri::command]
+pub async fn get_shortcuts(app: AppHandle) -> Result<Vec<ShortcutBinding>, String> {
+    bindings(&app)
+}
+
+/// Remap an action, or unbind it with no shortcut
+///
+/// Rejected if another action already uses the combination. For global
+/// actions the OS registration is checked too, and the previous mapping is
+/// restored if another application owns the shortcut.
+#[tauri::command]
+pub async fn set_shortcut(
+    app: AppHandle,
+    action: String,
+    shortcut: Option<String>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let parsed = shortcut.as_deref().map(parse).transpose()?;
+    let global = {
+        let registry = app.state::<Shortcuts>();
+        let registry = registry.lock();
+        let declared = registry
+            .actions
+            .iter()
+            .find(|a| a.id == action)
+            .ok_or_else(|| format!("Unknown shortcut action '{}'", action))?;
+        declared.global
+    };
+
+    let previous = read_overrides(&app)?;
+    let mut overrides = previous.clone();
+    overrides.insert(action.clone(), parsed.map(|s| s.into_string()));
+    if let Some(parsed) = parsed {
+        let registry = app.state::<Shortcuts>();
+        let registry = registry.lock();
+        let taken = resolve(&registry.actions, &overrides, &HashMap::new())
+            .into_iter()
+            .find(|(binding, s)| binding.action.id != action && *s == Some(parsed));
+        if let Some((other, _)) = taken {
+            return Err(format!(
+                "{} is already used by '{}'",
+                parsed, other.action.label
+            ));
+        }
+    }
+    write_overrides(&app, &overrides)?;
+
+    if global {
+        tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            move || apply(&app)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        let refused = app
+            .state::<Shortcuts>()
+            .lock()
+            .refused
+            .get(&action)
+            .cloned();
+        if let Some(reason) = refused {
+            write_overrides(&app, &previous)?;
+            refresh(&app);
+            return Err(reason);
+        }
+    }
+    bindings(&app)
+}
+
+/// Restore the default shortcut of one action, or of every action
+#[tauri::command]
+pub async fn reset_shortcuts(
+    app: AppHandle,
+    action: Option<String>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let mut overrides = read_overrides(&app)?;
+    match &action {
+        Some(action) => {
+            overrides.remove(action);
+        }
+        None => overrides.clear(),
+    }
+    write_overrides(&app, &overrides)?;
+    tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || apply(&app)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    bindings(&app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(id: &str, default: Option<&str>) -> ShortcutAction {
+        ShortcutAction {
+            id: id.to_string(),
+            label: id.to_uppercase(),
+            default: default.map(String::from),
+            global: false,
+        }
+    }
+
+    fn shortcuts(
+        actions: &[ShortcutAction],
+        overrides: &HashMap<String, Option<String>>,
+        refused: &HashMap<String, String>,
+    ) -> Vec<(Option<String>, Option<String>, bool)> {
+        resolve(actions, overrides, refused)
+            .into_iter()
+            .map(|(b, _)| (b.shortcut, b.conflict, b.customized))
+            .collect()
+    }
+
+    #[test]
+    fn defaults_are_bound_normalized() {
+        let resolved = resolve(
+            &[action("search", Some("CmdOrCtrl+Shift+F"))],
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+        let (binding, shortcut) = &resolved[0];
+        assert_eq!(
+            binding.shortcut,
+            Some(parse("CmdOrCtrl+Shift+F").unwrap().into_string())
+        );
+        assert_eq!(*shortcut, Some(parse("shift+CmdOrCtrl+KeyF").unwrap()));
+        assert!(!binding.customized);
+        assert!(binding.conflict.is_none());
+    }
+
+    #[test]
+    fn first_action_keeps_a_shared_combination() {
+        let actions = [
+            action("search", Some("CmdOrCtrl+K")),
+            action("capture", Some("CmdOrCtrl+K")),
+        ];
+        let resolved = shortcuts(&actions, &HashMap::new(), &HashMap::new());
+        assert!(resolved[0].0.is_some());
+        assert_eq!(resolved[1].0, None);
+        assert_eq!(resolved[1].1.as_deref(), Some("Already used by 'SEARCH'"));
+    }
+
+    #[test]
+    fn overrides_remap_and_unbind() {
+        let actions = [
+            action("search", Some("CmdOrCtrl+K")),
+            action("capture", Some("CmdOrCtrl+Shift+4")),
+        ];
+        let overrides = HashMap::from([
+            ("search".to_string(), None),
+            ("capture".to_string(), Some("CmdOrCtrl+K".to_string())),
+        ]);
+        let resolved = shortcuts(&actions, &overrides, &HashMap::new());
+        // Unbinding one action frees its combination for another
+        assert_eq!(resolved[0], (None, None, true));
+        assert_eq!(
+            resolved[1],
+            (
+                Some(parse("CmdOrCtrl+K").unwrap().into_string()),
+                None,
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_and_refused_shortcuts_report_why() {
+        let actions = [
+            action("search", Some("CmdOrCtrl+Nope")),
+            action("capture", Some("CmdOrCtrl+Shift+4")),
+        ];
+        let refused = HashMap::from([("capture".to_string(), "Another app uses it".to_string())]);
+        let resolved = shortcuts(&actions, &HashMap::new(), &refused);
+        assert_eq!(resolved[0].0, None);
+        assert!(resolved[0].1.as_deref().unwrap().contains("CmdOrCtrl+Nope"));
+        assert!(resolved[1].0.is_some());
+        assert_eq!(resolved[1].1.as_deref(), Some("Another app uses it"));
+    }
+}
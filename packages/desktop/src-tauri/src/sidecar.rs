@@ -0,0 +1,435 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::async_runtime::Receiver;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_shell::process::{Command, CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::events::{self, Event};
+use crate::http;
+
+const MAX_RESTARTS: u32 = 5;
+const HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Consecutive failed health checks before a service is restarted
+const HEALTH_FAILURES: u32 = 3;
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarKind {
+    /// Long-running process supervised by the manager
+    Service,
+    /// Binary invoked per job (e.g. ffmpeg); not started directly
+    Tool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarStatus {
+    Stopped,
+    Running,
+    Restarting,
+    Crashed,
+}
+
+/// How a sidecar binary is launched and supervised
+#[derive(Debug, Clone)]
+pub struct SidecarSpec {
+    pub name: String,
+    pub kind: SidecarKind,
+    pub args: Vec<String>,
+    /// URL polled while running; repeated failures restart the process
+    pub health_url: Option<String>,
+    pub restart: bool,
+}
+
+impl SidecarSpec {
+    pub fn tool(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: SidecarKind::Tool,
+            args: Vec::new(),
+            health_url: None,
+            restart: false,
+        }
+    }
+}
+
+struct Entry {
+    spec: SidecarSpec,
+    status: SidecarStatus,
+    child: Option<CommandChild>,
+    pid: Option<u32>,
+    started_at: Option<u64>,
+    restarts: u32,
+    healthy: Option<bool>,
+    health_failures: u32,
+    stopping: bool,
+    /// Incremented on every spawn so a stale supervisor can tell it was replaced
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct Sidecars(Mutex<HashMap<String, Entry>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SidecarInfo {
+    pub name: String,
+    pub kind: SidecarKind,
+    pub status: SidecarStatus,
+    /// Whether the binary is bundled or found on PATH
+    pub available: bool,
+    pub pid: Option<u32>,
+    /// Seconds since the Unix epoch
+    pub started_at: Option<u64>,
+    pub restarts: u32,
+    pub healthy: Option<bool>,
+}
+
+impl Sidecars {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn bundled_path(name: &str) -> Option<PathBuf> {
+    let exe = tauri::utils::platform::current_exe().ok()?;
+    let path = exe
+        .parent()?
+        .join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    path.exists().then_some(path)
+}
+
+fn on_path(name: &str) -> bool {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(&file).exists()))
+        .unwrap_or(false)
+}
+
+pub fn is_available(name: &str) -> bool {
+    bundled_path(name).is_some() || on_path(name)
+}
+
+/// Build a command for a sidecar, preferring the binary bundled next to the
+/// executable and falling back to the one on PATH
+pub fn command(app: &AppHandle, name: &str) -> Result<Command, String> {
+    match bundled_path(name) {
+        Some(_) => app.shell().sidecar(name).map_err(|e| e.to_string()),
+        None => Ok(app.shell().command(name)),
+    }
+}
+
+/// Forward a process output line to the app log
+pub fn log_output(name: &str, event: &CommandEvent) {
+    match event {
+        CommandEvent::Stdout(line) => {
+            log::info!(target: "sidecar", "[{}] {}", name, String::from_utf8_lossy(line).trim_end())
+        }
+        CommandEvent::Stderr(line) => {
+            log::warn!(target: "sidecar", "[{}] {}", name, String::from_utf8_lossy(line).trim_end())
+        }
+        CommandEvent::Error(e) => log::error!(target: "sidecar", "[{}] {}", name, e),
+        CommandEvent::Terminated(payload) => {
+            log::info!(target: "sidecar", "[{}] exited with {:?}", name, payload.code)
+        }
+        _ => {}
+    }
+}
+
+/// Make a sidecar known to the manager
+pub fn register(app: &AppHandle, spec: SidecarSpec) {
+    let sidecars = app.state::<Sidecars>();
+    sidecars.lock().insert(
+        spec.name.clone(),
+        Entry {
+            spec,
+            status: SidecarStatus::Stopped,
+            child: None,
+            pid: None,
+            started_at: None,
+            restarts: 0,
+            healthy: None,
+            health_failures: 0,
+            stopping: false,
+            generation: 0,
+        },
+    );
+}
+
+fn info(entry: &Entry) -> SidecarInfo {
+    SidecarInfo {
+        name: entry.spec.name.clone(),
+        kind: entry.spec.kind,
+        status: entry.status,
+        available: is_available(&entry.spec.name),
+        pid: entry.pid,
+        started_at: entry.started_at,
+        restarts: entry.restarts,
+        healthy: entry.healthy,
+    }
+}
+
+fn emit_status(app: &AppHandle, name: &str) {
+    let sidecars = app.state::<Sidecars>();
+    let payload = sidecars.lock().get(name).map(info);
+    if let Some(payload) = payload {
+        events::publish(app, Event::SidecarStatus(payload));
+    }
+}
+
+fn spawn(app: &AppHandle, name: &str) -> Result<(), String> {
+    let sidecars = app.state::<Sidecars>();
+    let mut map = sidecars.lock();
+    let entry = map
+        .get_mut(name)
+        .ok_or_else(|| format!("Sidecar '{}' not found", name))?;
+
+    if entry.spec.kind != SidecarKind::Service {
+        return Err(format!("Sidecar '{}' is not a long-running service", name));
+    }
+    if entry.child.is_some() {
+        return Ok(());
+    }
+
+    let (events, child) = command(app, name)?
+        .args(&entry.spec.args)
+        .spawn()
+        .map_err(|e| format!("Failed to start '{}': {}", name, e))?;
+
+    entry.generation += 1;
+    entry.pid = Some(child.pid());
+    entry.child = Some(child);
+    entry.status = SidecarStatus::Running;
+    entry.started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs());
+    entry.healthy = None;
+    entry.health_failures = 0;
+    entry.stopping = false;
+    let generation = entry.generation;
+    drop(map);
+
+    log::info!(target: "sidecar", "[{}] started", name);
+    emit_status(app, name);
+    tauri::async_runtime::spawn(supervise(app.clone(), name.to_string(), generation, events));
+    Ok(())
+}
+
+/// Capture output until the process exits, then restart it if it crashed
+async fn supervise(
+    app: AppHandle,
+    name: String,
+    generation: u64,
+    mut events: Receiver<CommandEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        log_output(&name, &event);
+    }
+
+    let restart = {
+        let sidecars = app.state::<Sidecars>();
+        let mut map = sidecars.lock();
+        let Some(entry) = map.get_mut(&name) else {
+            return;
+        };
+        if entry.generation != generation {
+            return;
+        }
+
+        entry.child = None;
+        entry.pid = None;
+        if entry.stopping {
+            entry.stopping = false;
+            entry.status = SidecarStatus::Stopped;
+            None
+        } else if entry.spec.restart && entry.restarts < MAX_RESTARTS {
+            entry.restarts += 1;
+            entry.status = SidecarStatus::Restarting;
+            Some(entry.restarts)
+        } else {
+            entry.status = SidecarStatus::Crashed;
+            None
+        }
+    };
+    emit_status(&app, &name);
+
+    let Some(attempt) = restart else {
+        return;
+    };
+    let backoff = Duration::from_secs((1u64 << attempt.min(5)).min(30));
+    tokio::time::sleep(backoff).await;
+
+    let still_wanted = app
+        .state::<Sidecars>()
+        .lock()
+        .get(&name)
+        .is_some_and(|e| e.status == SidecarStatus::Restarting);
+    if still_wanted {
+        if let Err(e) = spawn(&app, &name) {
+            log::error!(target: "sidecar", "[{}] restart failed: {}", name, e);
+            if let Some(entry) = app.state::<Sidecars>().lock().get_mut(&name) {
+                entry.status = SidecarStatus::Crashed;
+            }
+            emit_status(&app, &name);
+        }
+    }
+}
+
+/// Ask a process to exit: SIGTERM on Unix so it can clean up, a hard kill elsewhere
+fn request_exit(entry: &mut Entry) {
+    entry.stopping = true;
+
+    #[cfg(unix)]
+    if let Some(pid) = entry.pid {
+        unsafe {
+            libc::kill(pid as i32, libc::SIGTERM);
+        }
+        return;
+    }
+
+    if let Some(child) = entry.child.take() {
+        let _ = child.kill();
+    }
+}
+
+/// Kill any process that didn't exit within the grace period
+fn force_kill(sidecars: &Sidecars, names: &[String]) {
+    let mut map = sidecars.lock();
+    for name in names {
+        if let Some(child) = map.get_mut(name).and_then(|e| e.child.take()) {
+            let _ = child.kill();
+        }
+    }
+}
+
+fn running(sidecars: &Sidecars, names: &[String]) -> bool {
+    let map = sidecars.lock();
+    names
+        .iter()
+        .any(|name| map.get(name).is_some_and(|e| e.child.is_some()))
+}
+
+/// Stop every running service, waiting briefly for each to exit gracefully
+pub fn shutdown(app: &AppHandle) {
+    let sidecars = app.state::<Sidecars>();
+    let names: Vec<String> = {
+        let mut map = sidecars.lock();
+        map.values_mut()
+            .filter(|e| e.child.is_some())
+            .map(|e| {
+                request_exit(e);
+                e.spec.name.clone()
+            })
+            .collect()
+    };
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while running(&sidecars, &names) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    force_kill(&sidecars, &names);
+}
+
+/// Poll health URLs of running services and restart ones that stop responding
+pub fn watch_health(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let checks: Vec<(String, String)> = app
+                .state::<Sidecars>()
+                .lock()
+                .values()
+                .filter(|e| e.status == SidecarStatus::Running)
+                .filter_map(|e| Some((e.spec.name.clone(), e.spec.health_url.clone()?)))
+                .collect();
+            let Ok(client) = http::client() else {
+                continue;
+            };
+
+            for (name, url) in checks {
+                let healthy = client
+                    .get(&url)
+                    .timeout(HEALTH_TIMEOUT)
+                    .send()
+                    .await
+                    .is_ok_and(|r| r.status().is_success());
+
+                let sidecars = app.state::<Sidecars>();
+                let mut map = sidecars.lock();
+                let Some(entry) = map.get_mut(&name) else {
+                    continue;
+                };
+                entry.healthy = Some(healthy);
+                entry.health_failures = if healthy {
+                    0
+                } else {
+                    entry.health_failures + 1
+                };
+
+                // Killing without `stopping` makes the supervisor restart it
+                if entry.health_failures >= HEALTH_FAILURES {
+                    log::warn!(target: "sidecar", "[{}] failed health checks, restarting", name);
+                    entry.health_failures = 0;
+                    if let Some(child) = entry.child.take() {
+                        let _ = child.kill();
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// List registered sidecars with their status
+#[tauri::command]
+pub async fn list_sidecars(sidecars: State<'_, Sidecars>) -> Result<Vec<SidecarInfo>, String> {
+    let mut list: Vec<SidecarInfo> = sidecars.lock().values().map(info).collect();
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(list)
+}
+
+/// Start a service if it isn't already running, resetting its restart budget
+pub fn start(app: &AppHandle, name: &str) -> Result<(), String> {
+    if let Some(entry) = app.state::<Sidecars>().lock().get_mut(name) {
+        entry.restarts = 0;
+    }
+    spawn(app, name)
+}
+
+/// Start a sidecar service
+#[tauri::command]
+pub async fn start_sidecar(app: AppHandle, name: String) -> Result<(), String> {
+    start(&app, &name)
+}
+
+/// Stop a sidecar service, killing it if it doesn't exit within a few seconds
+#[tauri::command]
+pub async fn stop_sidecar(sidecars: State<'_, Sidecars>, name: String) -> Result<(), String> {
+    {
+        let mut map = sidecars.lock();
+        let entry = map
+            .get_mut(&name)
+            .ok_or_else(|| format!("Sidecar '{}' not found", name))?;
+        if entry.child.is_none() {
+            entry.status = SidecarStatus::Stopped;
+            return Ok(());
+        }
+        request_exit(entry);
+    }
+
+    let names = [name];
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while running(&sidecars, &names) && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    force_kill(&sidecars, &names);
+    Ok(())
+}
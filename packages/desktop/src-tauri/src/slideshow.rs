@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Manager, PhysicalPosition, WebviewUrl, WebviewWindowBuilder, Window, WindowEvent,
+};
+
+use crate::capabilities::{self, Capability};
+use crate::displays;
+use crate::events::{self, Event};
+use crate::profiles;
+
+const SLIDESHOW_WINDOW: &str = "slideshow";
+const DEFAULT_ROUTE: &str = "index.html#/slideshow";
+
+/// Sent from the main window to the slideshow window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum SlideshowCommand {
+    Play,
+    Pause,
+    Next,
+    Previous,
+    GoTo { index: usize },
+}
+
+/// Tell windows the slideshow ended, however it was closed
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) && window.label() == SLIDESHOW_WINDOW {
+        profiles::release_window(window.app_handle(), SLIDESHOW_WINDOW);
+        events::publish(window.app_handle(), Event::SlideshowClosed);
+    }
+}
+
+/// Show a slideshow fullscreen on another display, like a projector or TV
+///
+/// The window has no frame and runs `path`, the slideshow route by default,
+/// with the calling window's profile and session. It's driven from the
+/// calling window with `control_slideshow`, reaching it as
+/// `slideshow-control` events. Starting it again moves it to the given
+/// display. Returns the window's label.
+#[tauri::command]
+pub async fn start_external_slideshow(
+    app: AppHandle,
+    window: Window,
+    display_id: String,
+    path: Option<String>,
+) -> Result<String, String> {
+    let displays = displays::list_displays(&app)?;
+    let (display, _) = displays
+        .iter()
+        .find(|(d, _)| d.id == display_id)
+        .ok_or_else(|| format!("Display '{}' not found", display_id))?;
+    let position = PhysicalPosition::new(display.bounds.x, display.bounds.y);
+
+    let slideshow = match app.get_webview_window(SLIDESHOW_WINDOW) {
+        Some(slideshow) => {
+            // Fullscreen windows can't be moved
+            slideshow.set_fullscreen(false).map_err(|e| e.to_string())?;
+            slideshow
+        }
+        None => {
+            let profile = profiles::for_window(&app, window.label());
+            let url = WebviewUrl::App(path.unwrap_or_else(|| DEFAULT_ROUTE.to_string()).into());
+            let builder = WebviewWindowBuilder::new(&app, SLIDESHOW_WINDOW, url)
+                .title("Apollo Slideshow")
+                .decorations(false)
+                .visible(false)
+                .focused(false);
+            let builder = profiles::with_session(&app, builder, &profile)?;
+
+            profiles::assign_window(&app, SLIDESHOW_WINDOW, &profile);
+            // It only shows photos, so it only needs to reach the server
+            capabilities::grant(&app, SLIDESHOW_WINDOW, &[Capability::Network]);
+            match builder.build() {
+                Ok(slideshow) => slideshow,
+                Err(e) => {
+                    capabilities::revoke(&app, SLIDESHOW_WINDOW);
+                    profiles::release_window(&app, SLIDESHOW_WINDOW);
+                    return Err(e.to_string());
+                }
+            }
+        }
+    };
+
+    slideshow
+        .set_position(position)
+        .map_err(|e| e.to_string())?;
+    slideshow.set_fullscreen(true).map_err(|e| e.to_string())?;
+    slideshow.show().map_err(|e| e.to_string())?;
+    Ok(SLIDESHOW_WINDOW.to_string())
+}
+
+/// Send a command to the slideshow window
+#[tauri::command]
+pub async fn control_slideshow(app: AppHandle, command: SlideshowCommand) -> Result<(), String> {
+    if app.get_webview_window(SLIDESHOW_WINDOW).is_none() {
+        return Err("No slideshow is running".to_string());
+    }
+    events::publish(&app, Event::SlideshowControl(command));
+    Ok(())
+}
+
+/// Close the slideshow window, if one is open
+#[tauri::command]
+pub async fn stop_external_slideshow(app: AppHandle) -> Result<(), String> {
+    match app.get_webview_window(SLIDESHOW_WINDOW) {
+        Some(slideshow) => slideshow.close().map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,311 @@
+//! Windows 11 snap layouts for a titlebar drawn by the frontend
+//!
+//! Windows shows the snap layout flyout when the pointer rests on whatever a
+//! window's `WM_NCHITTEST` reports as its maximize button. Over a webview the
+//! top-level window never sees that message, since the webview's own windows
+//! take the pointer. So an invisible window is laid over the frontend's
+//! maximize button: it and the webview's host window pass the hit test on,
+//! and the top-level window answers `HTMAXBUTTON` for that area. Clicks there
+//! maximize or restore the window, as on the native button.
+
+use serde::Deserialize;
+use tauri::Window;
+
+/// Where the frontend draws its maximize button, in logical pixels
+///
+/// Measured from the window's top right corner so it stays in place when the
+/// window is resized.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapArea {
+    /// Gap between the button's right edge and the window's
+    pub right: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Route snap layouts to the frontend's maximize button, or stop with `None`
+///
+/// Only has an effect on Windows.
+#[tauri::command]
+pub async fn set_snap_layout_area(window: Window, area: Option<SnapArea>) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let hwnd = window.hwnd().map_err(|e| e.to_string())?.0 as isize;
+        window
+            .run_on_main_thread(move || win::set_area(hwnd, area))
+            .map_err(|e| e.to_string())?;
+    }
+    let _ = (window, area);
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    use super::SnapArea;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use windows::core::w;
+    use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
+    use windows::Win32::Graphics::Gdi::ScreenToClient;
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::HiDpi::GetDpiForWindow;
+    use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, EnumChildWindows, GetClientRect,
+        GetWindowThreadProcessId, IsZoomed, RegisterClassW, SetLayeredWindowAttributes,
+        SetWindowPos, ShowWindow, HTMAXBUTTON, HTTRANSPARENT, HWND_TOP, LWA_ALPHA, SWP_NOACTIVATE,
+        SW_MAXIMIZE, SW_RESTORE, WM_NCDESTROY, WM_NCHITTEST, WM_NCLBUTTONDBLCLK, WM_NCLBUTTONDOWN,
+        WM_NCLBUTTONUP, WM_SIZE, WNDCLASSW, WS_CHILD, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_VISIBLE,
+    };
+
+    const WINDOW_SUBCLASS: usize = 1;
+    const HOST_SUBCLASS: usize = 2;
+
+    /// A window routing snap layouts, by its handle
+    struct Routed {
+        area: SnapArea,
+        overlay: isize,
+        hosts: Vec<isize>,
+    }
+
+    static ROUTED: Mutex<Option<HashMap<isize, Routed>>> = Mutex::new(None);
+
+    fn with_routed<T>(f: impl FnOnce(&mut HashMap<isize, Routed>) -> T) -> T {
+        f(ROUTED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashMap::new))
+    }
+
+    fn hwnd(value: isize) -> HWND {
+        HWND(value as _)
+    }
+
+    /// The area in physical client coordinates of the top-level window
+    fn client_rect(window: HWND, area: &SnapArea) -> Option<RECT> {
+        let mut client = RECT::default();
+        unsafe { GetClientRect(window, &mut client) }.ok()?;
+        let scale = unsafe { GetDpiForWindow(window) } as f64 / 96.0;
+        let right = client.right - (area.right * scale).round() as i32;
+        Some(RECT {
+            left: right - (area.width * scale).round() as i32,
+            top: (area.top * scale).round() as i32,
+            right,
+            bottom: ((area.top + area.height) * scale).round() as i32,
+        })
+    }
+
+    fn contains(window: HWND, lparam: LPARAM) -> bool {
+        let Some(area) = with_routed(|routed| routed.get(&(window.0 as isize)).map(|r| r.area))
+        else {
+            return false;
+        };
+        let Some(rect) = client_rect(window, &area) else {
+            return false;
+        };
+        let mut point = POINT {
+            x: (lparam.0 & 0xffff) as i16 as i32,
+            y: ((lparam.0 >> 16) & 0xffff) as i16 as i32,
+        };
+        if !unsafe { ScreenToClient(window, &mut point) }.as_bool() {
+            return false;
+        }
+        point.x >= rect.left && point.x < rect.right && point.y >= rect.top && point.y < rect.bottom
+    }
+
+    fn place_overlay(window: HWND) {
+        let Some((area, overlay)) = with_routed(|routed| {
+            routed
+                .get(&(window.0 as isize))
+                .map(|r| (r.area, r.overlay))
+        }) else {
+            return;
+        };
+        if let Some(rect) = client_rect(window, &area) {
+            let _ = unsafe {
+                SetWindowPos(
+                    hwnd(overlay),
+                    Some(HWND_TOP),
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    SWP_NOACTIVATE,
+                )
+            };
+        }
+    }
+
+    unsafe extern "system" fn window_proc(
+        window: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        _data: usize,
+    ) -> LRESULT {
+        match message {
+            WM_NCHITTEST if contains(window, lparam) => LRESULT(HTMAXBUTTON as isize),
+            // Handled here so Windows doesn't draw its own caption buttons
+            WM_NCLBUTTONDOWN | WM_NCLBUTTONDBLCLK if wparam.0 == HTMAXBUTTON as usize => LRESULT(0),
+            WM_NCLBUTTONUP if wparam.0 == HTMAXBUTTON as usize => {
+                let command = match unsafe { IsZoomed(window) }.as_bool() {
+                    true => SW_RESTORE,
+                    false => SW_MAXIMIZE,
+                };
+                let _ = unsafe { ShowWindow(window, command) };
+                LRESULT(0)
+            }
+            WM_SIZE => {
+                place_overlay(window);
+                unsafe { DefSubclassProc(window, message, wparam, lparam) }
+            }
+            WM_NCDESTROY => {
+                remove(window.0 as isize);
+                unsafe { DefSubclassProc(window, message, wparam, lparam) }
+            }
+            _ => unsafe { DefSubclassProc(window, message, wparam, lparam) },
+        }
+    }
+
+    /// Lets hit tests over the area fall through a webview host window
+    unsafe extern "system" fn host_proc(
+        host: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        window: usize,
+    ) -> LRESULT {
+        if message == WM_NCHITTEST && contains(hwnd(window as isize), lparam) {
+            return LRESULT(HTTRANSPARENT as isize);
+        }
+        unsafe { DefSubclassProc(host, message, wparam, lparam) }
+    }
+
+    unsafe extern "system" fn overlay_proc(
+        overlay: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if message == WM_NCHITTEST {
+            return LRESULT(HTTRANSPARENT as isize);
+        }
+        unsafe { DefWindowProcW(overlay, message, wparam, lparam) }
+    }
+
+    fn create_overlay(window: HWND) -> Option<HWND> {
+        let module = unsafe { GetModuleHandleW(None) }.ok()?;
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(overlay_proc),
+            hInstance: module.into(),
+            lpszClassName: w!("ApolloSnapLayoutOverlay"),
+            ..Default::default()
+        };
+        // Fails harmlessly when the class is already registered
+        unsafe { RegisterClassW(&class) };
+        let overlay = unsafe {
+            CreateWindowExW(
+                WS_EX_LAYERED | WS_EX_NOACTIVATE,
+                w!("ApolloSnapLayoutOverlay"),
+                None,
+                WS_CHILD | WS_VISIBLE,
+                0,
+                0,
+                0,
+                0,
+                Some(window),
+                None,
+                Some(module.into()),
+                None,
+            )
+        }
+        .ok()?;
+        // Fully transparent windows don't take the pointer at all
+        let _ = unsafe { SetLayeredWindowAttributes(overlay, COLORREF(0), 1, LWA_ALPHA) };
+        Some(overlay)
+    }
+
+    /// The webview's windows that live in this process
+    fn hosts(window: HWND, overlay: HWND) -> Vec<isize> {
+        unsafe extern "system" fn collect(child: HWND, found: LPARAM) -> windows::core::BOOL {
+            let found = unsafe { &mut *(found.0 as *mut Vec<isize>) };
+            let mut process = 0;
+            unsafe { GetWindowThreadProcessId(child, Some(&mut process)) };
+            if process == std::process::id() {
+                found.push(child.0 as isize);
+            }
+            true.into()
+        }
+
+        let mut found: Vec<isize> = Vec::new();
+        let _ = unsafe {
+            EnumChildWindows(
+                Some(window),
+                Some(collect),
+                LPARAM(&mut found as *mut Vec<isize> as isize),
+            )
+        };
+        found.retain(|child| *child != overlay.0 as isize);
+        found
+    }
+
+    fn remove(window: isize) {
+        let Some(routed) = with_routed(|routed| routed.remove(&window)) else {
+            return;
+        };
+        unsafe {
+            let _ = RemoveWindowSubclass(hwnd(window), Some(window_proc), WINDOW_SUBCLASS);
+            for host in routed.hosts {
+                let _ = RemoveWindowSubclass(hwnd(host), Some(host_proc), HOST_SUBCLASS);
+            }
+            let _ = DestroyWindow(hwnd(routed.overlay));
+        }
+    }
+
+    pub fn set_area(window: isize, area: Option<SnapArea>) {
+        let Some(area) = area else {
+            remove(window);
+            return;
+        };
+        let updated = with_routed(|routed| match routed.get_mut(&window) {
+            Some(existing) => {
+                existing.area = area;
+                true
+            }
+            None => false,
+        });
+        if !updated {
+            let Some(overlay) = create_overlay(hwnd(window)) else {
+                log::warn!("Failed to create the snap layout overlay");
+                return;
+            };
+            let hosts = hosts(hwnd(window), overlay);
+            unsafe {
+                let _ = SetWindowSubclass(hwnd(window), Some(window_proc), WINDOW_SUBCLASS, 0);
+                for host in &hosts {
+                    let _ = SetWindowSubclass(
+                        hwnd(*host),
+                        Some(host_proc),
+                        HOST_SUBCLASS,
+                        window as usize,
+                    );
+                }
+            }
+            with_routed(|routed| {
+                routed.insert(
+                    window,
+                    Routed {
+                        area,
+                        overlay: overlay.0 as isize,
+                        hosts,
+                    },
+                )
+            });
+        }
+        place_overlay(hwnd(window));
+    }
+}
@@ -0,0 +1,414 @@
+//! SSH tunnels to servers only reachable through a jump host
+//!
+//! Each profile can have one tunnel, run by the system `ssh` client as a local
+//! port forward. Authentication is key-based only: keys come from the SSH
+//! agent or the profile's identity file, whose passphrase macOS keeps in the
+//! login keychain. Password prompts are disabled, so a missing key fails
+//! instead of hanging.
+//!
+//! While the tunnel is open the profile's server URL points at the forwarded
+//! port; closing it puts the previous URL back.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::Receiver;
+use tauri::{AppHandle, Manager, Window};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::events::{self, Event};
+use crate::profiles;
+use crate::sidecar;
+use crate::DEFAULT_SERVER_KEY;
+
+const SSH_TUNNEL_KEY: &str = "sshTunnel";
+/// Time allowed for connecting, authenticating and opening the forward
+const READY_TIMEOUT: Duration = Duration::from_secs(20);
+const READY_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunnelSettings {
+    /// Jump host as `host` or `user@host`; aliases from `~/.ssh/config` work too
+    pub host: String,
+    pub port: u16,
+    /// Private key to offer instead of the agent's
+    pub identity_file: Option<String>,
+    /// The server's address as seen from the jump host
+    pub remote_host: String,
+    pub remote_port: u16,
+    /// Local port to forward; a free one is picked when unset
+    pub local_port: Option<u16>,
+    /// Whether the server behind the tunnel speaks HTTPS
+    pub https: bool,
+}
+
+impl Default for TunnelSettings {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 22,
+            identity_file: None,
+            remote_host: "localhost".to_string(),
+            remote_port: 0,
+            local_port: None,
+            https: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelState {
+    Closed,
+    Connecting,
+    Open,
+    /// `ssh` exited on its own; `error` has its last message
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelStatus {
+    pub profile: String,
+    pub state: TunnelState,
+    pub local_port: Option<u16>,
+    /// The server URL through the tunnel, once it's open
+    pub url: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TunnelStatus {
+    fn closed(profile: &str) -> Self {
+        Self {
+            profile: profile.to_string(),
+            state: TunnelState::Closed,
+            local_port: None,
+            url: None,
+            error: None,
+        }
+    }
+}
+
+struct Tunnel {
+    status: TunnelStatus,
+    child: Option<CommandChild>,
+    /// Server URL the profile had before the tunnel replaced it
+    previous_url: Option<String>,
+    /// Incremented on every spawn so a stale supervisor can tell it was replaced
+    generation: u64,
+}
+
+/// Tunnels by profile ID
+#[derive(Default)]
+pub struct Tunnels(Mutex<HashMap<String, Tunnel>>);
+
+impl Tunnels {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Tunnel>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub fn read_settings(app: &AppHandle, id: &str) -> Result<Option<TunnelSettings>, String> {
+    let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+    match store.get(SSH_TUNNEL_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+fn status(app: &AppHandle, id: &str) -> TunnelStatus {
+    app.state::<Tunnels>()
+        .lock()
+        .get(id)
+        .map(|tunnel| tunnel.status.clone())
+        .unwrap_or_else(|| TunnelStatus::closed(id))
+}
+
+fn emit_status(app: &AppHandle, id: &str) {
+    events::publish(app, Event::SshTunnelStatus(status(app, id)));
+}
+
+fn free_port() -> Result<u16, String> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).map_err(|e| e.to_string())?;
+    Ok(listener.local_addr().map_err(|e| e.to_string())?.port())
+}
+
+fn ssh_args(settings: &TunnelSettings, local_port: u16) -> Vec<String> {
+    let mut args = vec![
+        "-N".to_string(),
+        "-L".to_string(),
+        format!(
+            "127.0.0.1:{}:{}:{}",
+            local_port, settings.remote_host, settings.remote_port
+        ),
+        "-p".to_string(),
+        settings.port.to_string(),
+    ];
+    let options = [
+        // Never prompt for a password or passphrase; there's no terminal to answer it
+        "BatchMode=yes",
+        "ExitOnForwardFailure=yes",
+        // Trust a jump host on first use, but refuse one whose key changed
+        "StrictHostKeyChecking=accept-new",
+        "ServerAliveInterval=30",
+        "ServerAliveCountMax=3",
+        // Apple's ssh reads key passphrases from the keychain; others don't know the option
+        "IgnoreUnknown=UseKeychain",
+        "UseKeychain=yes",
+    ];
+    for option in options {
+        args.push("-o".to_string());
+        args.push(option.to_string());
+    }
+    if let Some(identity) = settings.identity_file.as_ref().filter(|i| !i.is_empty()) {
+        args.extend([
+            "-i".to_string(),
+            identity.clone(),
+            "-o".to_string(),
+            "IdentitiesOnly=yes".to_string(),
+        ]);
+    }
+    args.push(settings.host.clone());
+    args
+}
+
+/// Point the profile's server URL back where it was, unless it was changed since
+fn restore_url(app: &AppHandle, id: &str, tunnel_url: Option<&str>, previous: Option<String>) {
+    let result = (|| -> Result<(), String> {
+        let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+        let current = store
+            .get(DEFAULT_SERVER_KEY)
+            .and_then(|v| v.as_str().map(|s| s.to_string()));
+        if tunnel_url.is_none() || current.as_deref() != tunnel_url {
+            return Ok(());
+        }
+        match previous {
+            Some(url) => store.set(DEFAULT_SERVER_KEY, serde_json::json!(url)),
+            None => {
+                store.delete(DEFAULT_SERVER_KEY);
+            }
+        }
+        store.save().map_err(|e| e.to_string())
+    })();
+    if let Err(e) = result {
+        log::warn!(
+            "Failed to restore the server URL after the SSH tunnel: {}",
+            e
+        );
+    }
+}
+
+/// Log `ssh`'s output until it exits, then mark the tunnel failed
+async fn supervise(
+    app: AppHandle,
+    id: String,
+    generation: u64,
+    mut events: Receiver<CommandEvent>,
+) {
+    let mut last_error = None;
+    while let Some(event) = events.recv().await {
+        sidecar::log_output("ssh", &event);
+        if let CommandEvent::Stderr(line) = &event {
+            let line = String::from_utf8_lossy(line).trim().to_string();
+            if !line.is_empty() {
+                last_error = Some(line);
+            }
+        }
+    }
+
+    let restore = {
+        let tunnels = app.state::<Tunnels>();
+        let mut map = tunnels.lock();
+        let Some(tunnel) = map.get_mut(&id) else {
+            return;
+        };
+        if tunnel.generation != generation || tunnel.status.state == TunnelState::Closed {
+            return;
+        }
+        tunnel.child = None;
+        let url = tunnel.status.url.take();
+        tunnel.status.state = TunnelState::Failed;
+        tunnel.status.error =
+            Some(last_error.unwrap_or_else(|| "ssh exited unexpectedly".to_string()));
+        (url, tunnel.previous_url.take())
+    };
+    restore_url(&app, &id, restore.0.as_deref(), restore.1);
+    emit_status(&app, &id);
+}
+
+async fn open(app: &AppHandle, id: &str) -> Result<TunnelStatus, String> {
+    let settings = read_settings(app, id)?.ok_or("This profile has no SSH tunnel set up")?;
+    if settings.host.trim().is_empty() || settings.remote_port == 0 {
+        return Err("The SSH tunnel needs a jump host and a server port".to_string());
+    }
+    if !sidecar::is_available("ssh") {
+        return Err("No ssh client found on PATH".to_string());
+    }
+    let local_port = match settings.local_port {
+        Some(port) => port,
+        None => free_port()?,
+    };
+
+    let generation = {
+        let tunnels = app.state::<Tunnels>();
+        let mut map = tunnels.lock();
+        if let Some(tunnel) = map.get(id).filter(|t| t.child.is_some()) {
+            return Ok(tunnel.status.clone());
+        }
+        let (events, child) = app
+            .shell()
+            .command("ssh")
+            .args(ssh_args(&settings, local_port))
+            .spawn()
+            .map_err(|e| format!("Failed to start ssh: {}", e))?;
+        let generation = map.get(id).map_or(0, |t| t.generation) + 1;
+        map.insert(
+            id.to_string(),
+            Tunnel {
+                status: TunnelStatus {
+                    profile: id.to_string(),
+                    state: TunnelState::Connecting,
+                    local_port: Some(local_port),
+                    url: None,
+                    error: None,
+                },
+                child: Some(child),
+                previous_url: None,
+                generation,
+            },
+        );
+        tauri::async_runtime::spawn(supervise(app.clone(), id.to_string(), generation, events));
+        generation
+    };
+    emit_status(app, id);
+
+    // ssh only listens on the local port once it's signed in
+    let deadline = Instant::now() + READY_TIMEOUT;
+    loop {
+        let current = status(app, id);
+        if current.state != TunnelState::Connecting {
+            return match current.error {
+                Some(error) => Err(error),
+                None => Ok(current),
+            };
+        }
+        if tokio::net::TcpStream::connect(("127.0.0.1", local_port))
+            .await
+            .is_ok()
+        {
+            break;
+        }
+        if Instant::now() >= deadline {
+            close(app, id);
+            return Err("Timed out waiting for the SSH tunnel".to_string());
+        }
+        tokio::time::sleep(READY_POLL).await;
+    }
+
+    let scheme = if settings.https { "https" } else { "http" };
+    let url = format!("{}://127.0.0.1:{}", scheme, local_port);
+    let store = profiles::profile_store(app, id).map_err(|e| e.to_string())?;
+    let previous_url = store
+        .get(DEFAULT_SERVER_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    {
+        let tunnels = app.state::<Tunnels>();
+        let mut map = tunnels.lock();
+        let Some(tunnel) = map.get_mut(id).filter(|t| t.generation == generation) else {
+            return Ok(TunnelStatus::closed(id));
+        };
+        tunnel.status.state = TunnelState::Open;
+        tunnel.status.url = Some(url.clone());
+        tunnel.previous_url = previous_url;
+    }
+    store.set(DEFAULT_SERVER_KEY, serde_json::json!(url));
+    store.save().map_err(|e| e.to_string())?;
+    emit_status(app, id);
+    Ok(status(app, id))
+}
+
+/// Close a profile's tunnel, if open, and put its server URL back
+pub fn close(app: &AppHandle, id: &str) {
+    let closed = {
+        let tunnels = app.state::<Tunnels>();
+        let mut map = tunnels.lock();
+        let Some(tunnel) = map.get_mut(id) else {
+            return;
+        };
+        if let Some(child) = tunnel.child.take() {
+            let _ = child.kill();
+        }
+        let url = tunnel.status.url.take();
+        tunnel.status = TunnelStatus::closed(id);
+        (url, tunnel.previous_url.take())
+    };
+    restore_url(app, id, closed.0.as_deref(), closed.1);
+    emit_status(app, id);
+}
+
+/// Close every tunnel so no profile is left pointing at a dead port
+pub fn shutdown(app: &AppHandle) {
+    let ids: Vec<String> = app.state::<Tunnels>().lock().keys().cloned().collect();
+    for id in ids {
+        close(app, &id);
+    }
+}
+
+/// Get the SSH tunnel set up for the calling window's profile
+#[tauri::command]
+pub async fn get_ssh_tunnel_settings(
+    app: AppHandle,
+    window: Window,
+) -> Result<Option<TunnelSettings>, String> {
+    read_settings(&app, &profiles::for_window(&app, window.label()))
+}
+
+/// Set up or remove the calling window's profile's SSH tunnel
+///
+/// Takes effect the next time the tunnel is opened.
+#[tauri::command]
+pub async fn set_ssh_tunnel_settings(
+    app: AppHandle,
+    window: Window,
+    settings: Option<TunnelSettings>,
+) -> Result<(), String> {
+    if let Some(settings) = &settings {
+        // Anything starting with `-` would be read as another ssh option
+        if settings.host.starts_with('-') || settings.remote_host.starts_with('-') {
+            return Err("Host names can't start with '-'".to_string());
+        }
+    }
+    let id = profiles::for_window(&app, window.label());
+    let store = profiles::profile_store(&app, &id).map_err(|e| e.to_string())?;
+    match settings {
+        Some(settings) => store.set(SSH_TUNNEL_KEY, serde_json::json!(settings)),
+        None => {
+            store.delete(SSH_TUNNEL_KEY);
+        }
+    }
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Open the calling window's profile's SSH tunnel and point its server URL at it
+///
+/// Resolves once the forward accepts connections. Status changes are sent as
+/// `ssh-tunnel-status` events.
+#[tauri::command]
+pub async fn open_ssh_tunnel(app: AppHandle, window: Window) -> Result<TunnelStatus, String> {
+    open(&app, &profiles::for_window(&app, window.label())).await
+}
+
+/// Close the calling window's profile's SSH tunnel, restoring its server URL
+#[tauri::command]
+pub async fn close_ssh_tunnel(app: AppHandle, window: Window) -> Result<(), String> {
+    close(&app, &profiles::for_window(&app, window.label()));
+    Ok(())
+}
+
+/// Get the state of the calling window's profile's SSH tunnel
+#[tauri::command]
+pub async fn get_ssh_tunnel_status(app: AppHandle, window: Window) -> Result<TunnelStatus, String> {
+    Ok(status(&app, &profiles::for_window(&app, window.label())))
+}
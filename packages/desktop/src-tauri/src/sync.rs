@@ -0,0 +1,591 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::Disks;
+use tauri::{AppHandle, Manager};
+
+use crate::cache;
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::export::{partial_path, sanitize_component, unique_path};
+use crate::folder_sync;
+use crate::fs_scope;
+use crate::http;
+use crate::http_cache;
+use crate::profiles;
+use crate::schedule::{self, TransferPolicy};
+use crate::search_index;
+use crate::transfers::{self, TransferDirection, TransferRequest};
+
+const SYNC_SETTINGS_KEY: &str = "selectiveSync";
+const SYNC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// A remote asset in a synced album, as listed by the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncItem {
+    pub id: String,
+    /// URL of the original file
+    pub url: String,
+    pub filename: String,
+    pub size: Option<u64>,
+    /// SHA-1, hex or base64 encoded
+    pub checksum: Option<String>,
+    /// Other renditions by quality name, e.g. `preview` or `fullsize`
+    #[serde(default)]
+    pub variants: HashMap<String, String>,
+}
+
+impl SyncItem {
+    /// URL for the chosen quality, falling back to the original
+    pub fn url_for(&self, quality: Option<&str>) -> &str {
+        quality
+            .and_then(|q| self.variants.get(q))
+            .unwrap_or(&self.url)
+    }
+}
+
+/// An album mirrored locally from a server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSelection {
+    pub server: String,
+    pub album_id: String,
+    pub name: Option<String>,
+    /// Local folder to mirror into; defaults to a folder in the media cache
+    pub folder: Option<String>,
+    /// Endpoint answering with a JSON list of `SyncItem` for the album
+    pub list_url: String,
+    /// Change feed answering with a `ChangeSet` since the `cursor` query
+    /// parameter; without one, only the current cursor. Full listings are
+    /// used when unset.
+    pub changes_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// Rendition to download from `SyncItem::variants`; originals when unset
+    pub quality: Option<String>,
+    /// Pinned for offline use, stored in the managed cache
+    pub pinned: bool,
+}
+
+/// Changes to an album since a cursor, from its change feed
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ChangeSet {
+    /// Position to ask for changes from next time
+    pub cursor: String,
+    /// Items added to the album or modified
+    pub upserted: Vec<SyncItem>,
+    /// Ids of items removed from the album
+    pub deleted: Vec<String>,
+    /// The cursor has expired and a full listing is needed
+    pub reset: bool,
+}
+
+/// What enabling sync for an album would download
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncEstimate {
+    pub items: usize,
+    /// Combined size of items the server reported a size for
+    pub total_bytes: u64,
+    /// Items without a reported size, not counted in `total_bytes`
+    pub unknown_sizes: usize,
+    /// Already present in the local folder
+    pub local_bytes: u64,
+    pub download_bytes: u64,
+    /// Free space on the volume holding the local folder
+    pub available_bytes: Option<u64>,
+}
+
+/// Result of one sync pass over an album
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncPass {
+    pub server: String,
+    pub album_id: String,
+    pub items: usize,
+    /// Downloads added to the transfer queue
+    pub queued: usize,
+    /// Local copies removed because the item left the album
+    pub removed: usize,
+    pub error: Option<String>,
+}
+
+/// Serializes sync passes so the periodic and on-demand runs don't overlap
+#[derive(Default)]
+pub struct SyncEngine(tokio::sync::Mutex<()>);
+
+impl SyncEngine {
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
+
+pub fn read_selections(app: &AppHandle) -> Result<Vec<SyncSelection>, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+
+    match store.get(SYNC_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn write_selections(app: &AppHandle, selections: &[SyncSelection]) -> Result<(), String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    store.set(SYNC_SETTINGS_KEY, serde_json::json!(selections));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Add or replace the selection for an album
+pub fn save_selection(app: &AppHandle, selection: &SyncSelection) -> Result<(), String> {
+    let mut selections = read_selections(app)?;
+    selections.retain(|s| !(s.server == selection.server && s.album_id == selection.album_id));
+    selections.push(selection.clone());
+    write_selections(app, &selections)
+}
+
+/// Drop an album's selection, cancelling its pending downloads
+///
+/// With `delete_files`, the local copies written by sync are removed too.
+/// Returns the number of files deleted. Callers hold the `SyncEngine` lock.
+pub fn remove_selection(
+    app: &AppHandle,
+    server: &str,
+    album_id: &str,
+    delete_files: bool,
+) -> Result<usize, String> {
+    let mut selections = read_selections(app)?;
+    let removed: Vec<SyncSelection> = selections
+        .iter()
+        .filter(|s| s.server == server && s.album_id == album_id)
+        .cloned()
+        .collect();
+    selections.retain(|s| !(s.server == server && s.album_id == album_id));
+    write_selections(app, &selections)?;
+
+    let paths: Vec<PathBuf> = synced_paths(app, server, album_id).into_values().collect();
+    transfers::cancel_paths(app, &paths)?;
+    app.state::<Database>()
+        .conn()
+        .execute(
+            "DELETE FROM synced_files WHERE server = ?1 AND album_id = ?2",
+            params![server, album_id],
+        )
+        .map_err(|e| e.to_string())?;
+    let _ = app.state::<Database>().conn().execute(
+        "DELETE FROM sync_cursors WHERE server = ?1 AND album_id = ?2",
+        params![server, album_id],
+    );
+
+    if !delete_files {
+        return Ok(0);
+    }
+    let deleted = paths.iter().filter(|path| remove_local(path)).count();
+    // Only removes folders that are now empty
+    for selection in &removed {
+        if let Ok(folder) = folder(app, selection) {
+            let _ = std::fs::remove_dir(folder);
+        }
+    }
+    Ok(deleted)
+}
+
+pub fn folder(app: &AppHandle, selection: &SyncSelection) -> Result<PathBuf, String> {
+    if let Some(folder) = &selection.folder {
+        return Ok(PathBuf::from(folder));
+    }
+    let name = selection.name.as_deref().unwrap_or(&selection.album_id);
+    Ok(cache::media_dir(app)?
+        .join(sanitize_component(&selection.server))
+        .join(sanitize_component(name)))
+}
+
+/// List an album's items, revalidating the previous listing when the server supports it
+pub async fn fetch_items(
+    app: &AppHandle,
+    selection: &SyncSelection,
+) -> Result<Vec<SyncItem>, String> {
+    http_cache::get_json(app, &selection.list_url, &selection.headers).await
+}
+
+/// Assign each item a local path, stable across passes while the album is unchanged
+fn plan(folder: &Path, mut items: Vec<SyncItem>) -> Vec<(SyncItem, PathBuf)> {
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut used = HashSet::new();
+    items
+        .into_iter()
+        .map(|item| {
+            let path = unique_path(folder.join(sanitize_component(&item.filename)), &mut used);
+            (item, path)
+        })
+        .collect()
+}
+
+fn available_space(path: &Path) -> Option<u64> {
+    Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+pub fn remove_local(path: &Path) -> bool {
+    let _ = std::fs::remove_file(partial_path(path));
+    std::fs::remove_file(path).is_ok()
+}
+
+pub fn synced_paths(app: &AppHandle, server: &str, album_id: &str) -> HashMap<String, PathBuf> {
+    app.state::<Database>()
+        .conn()
+        .prepare("SELECT remote_id, path FROM synced_files WHERE server = ?1 AND album_id = ?2")
+        .and_then(|mut stmt| {
+            stmt.query_map(params![server, album_id], |row| {
+                Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+            })?
+            .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reconcile the local mirror against a full listing of the album
+async fn full_sync(app: &AppHandle, selection: &SyncSelection) -> Result<SyncPass, String> {
+    let folder = folder(app, selection)?;
+    let items = fetch_items(app, selection).await?;
+    let planned = plan(&folder, items);
+    let previous = synced_paths(app, &selection.server, &selection.album_id);
+
+    let mut pass = SyncPass {
+        server: selection.server.clone(),
+        album_id: selection.album_id.clone(),
+        items: planned.len(),
+        ..Default::default()
+    };
+
+    let current: HashMap<&str, &PathBuf> = planned
+        .iter()
+        .map(|(item, path)| (item.id.as_str(), path))
+        .collect();
+    let planned_paths: HashSet<&PathBuf> = current.values().copied().collect();
+    let stale: Vec<(String, PathBuf)> = previous
+        .iter()
+        .filter(|(id, path)| current.get(id.as_str()) != Some(path))
+        .map(|(id, path)| (id.clone(), path.clone()))
+        .collect();
+    let stale_paths: Vec<PathBuf> = stale
+        .iter()
+        .map(|(_, path)| path.clone())
+        .filter(|path| !planned_paths.contains(path))
+        .collect();
+    transfers::cancel_paths(app, &stale_paths)?;
+    for (id, path) in &stale {
+        // A renamed item may have handed its path to another one
+        if !planned_paths.contains(path) && remove_local(path) {
+            pass.removed += 1;
+        }
+        untrack(app, selection, id);
+    }
+
+    let downloads: Vec<TransferRequest> = planned
+        .iter()
+        .filter_map(|(item, path)| track(app, selection, item, path))
+        .collect();
+    transfers::enqueue(app, &downloads)?;
+    pass.queued = downloads.len();
+
+    Ok(pass)
+}
+
+/// Record where sync keeps an item, returning a download if it isn't there yet
+fn track(
+    app: &AppHandle,
+    selection: &SyncSelection,
+    item: &SyncItem,
+    path: &Path,
+) -> Option<TransferRequest> {
+    let _ = app.state::<Database>().conn().execute(
+        "INSERT OR REPLACE INTO synced_files (server, album_id, remote_id, path)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            selection.server,
+            selection.album_id,
+            item.id,
+            path.to_string_lossy()
+        ],
+    );
+    if path.exists() || transfers::is_pending(app, path) {
+        return None;
+    }
+    Some(TransferRequest {
+        direction: TransferDirection::Download,
+        url: item.url_for(selection.quality.as_deref()).to_string(),
+        path: path.to_string_lossy().to_string(),
+        headers: selection.headers.clone(),
+        fields: HashMap::new(),
+        link_pointer: None,
+    })
+}
+
+fn untrack(app: &AppHandle, selection: &SyncSelection, remote_id: &str) {
+    let _ = app.state::<Database>().conn().execute(
+        "DELETE FROM synced_files WHERE server = ?1 AND album_id = ?2 AND remote_id = ?3",
+        params![selection.server, selection.album_id, remote_id],
+    );
+}
+
+fn load_cursor(app: &AppHandle, selection: &SyncSelection) -> Option<String> {
+    app.state::<Database>()
+        .conn()
+        .query_row(
+            "SELECT cursor FROM sync_cursors WHERE server = ?1 AND album_id = ?2",
+            params![selection.server, selection.album_id],
+            |row| row.get(0),
+        )
+        .ok()
+}
+
+fn save_cursor(app: &AppHandle, selection: &SyncSelection, cursor: &str) {
+    let _ = app.state::<Database>().conn().execute(
+        "INSERT OR REPLACE INTO sync_cursors (server, album_id, cursor) VALUES (?1, ?2, ?3)",
+        params![selection.server, selection.album_id, cursor],
+    );
+}
+
+async fn fetch_changes(
+    selection: &SyncSelection,
+    changes_url: &str,
+    cursor: Option<&str>,
+) -> Result<ChangeSet, String> {
+    let mut url = reqwest::Url::parse(changes_url).map_err(|e| e.to_string())?;
+    if let Some(cursor) = cursor {
+        url.query_pairs_mut().append_pair("cursor", cursor);
+    }
+
+    let request = http::client()?.get(url.clone());
+    http::with_headers(request, url.as_str(), &selection.headers)?
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Apply a change set to the local mirror without listing the whole album
+fn delta_sync(
+    app: &AppHandle,
+    selection: &SyncSelection,
+    changes: ChangeSet,
+) -> Result<SyncPass, String> {
+    let folder = folder(app, selection)?;
+    let mut previous = synced_paths(app, &selection.server, &selection.album_id);
+    let mut pass = SyncPass {
+        server: selection.server.clone(),
+        album_id: selection.album_id.clone(),
+        ..Default::default()
+    };
+
+    let removed: Vec<(String, PathBuf)> = changes
+        .deleted
+        .iter()
+        .filter_map(|id| previous.remove_entry(id))
+        .collect();
+    let removed_paths: Vec<PathBuf> = removed.iter().map(|(_, path)| path.clone()).collect();
+    transfers::cancel_paths(app, &removed_paths)?;
+    for (id, path) in &removed {
+        if remove_local(path) {
+            pass.removed += 1;
+        }
+        untrack(app, selection, id);
+    }
+
+    let mut used: HashSet<PathBuf> = previous.values().cloned().collect();
+    let mut upserted = changes.upserted;
+    upserted.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut downloads = Vec::new();
+    for item in &upserted {
+        let path = match previous.get(&item.id) {
+            Some(path) => path.clone(),
+            None => unique_path(folder.join(sanitize_component(&item.filename)), &mut used),
+        };
+        downloads.extend(track(app, selection, item, &path));
+    }
+    transfers::enqueue(app, &downloads)?;
+
+    pass.items = previous.len()
+        + upserted
+            .iter()
+            .filter(|i| !previous.contains_key(&i.id))
+            .count();
+    pass.queued = downloads.len();
+    Ok(pass)
+}
+
+/// Bring one album's local mirror up to date
+///
+/// Missing files are queued as downloads; copies of items that left the
+/// album are removed. Only files recorded as written by sync are touched.
+/// With a change feed, only changes since the saved cursor are fetched.
+pub async fn sync_selection(
+    app: &AppHandle,
+    selection: &SyncSelection,
+) -> Result<SyncPass, String> {
+    // Don't re-download a whole offline library onto the fallback cache
+    if selection.folder.is_none() && cache::is_fallback(app) {
+        return Err(
+            "The media cache drive is missing; reconnect it to sync this album".to_string(),
+        );
+    }
+    let Some(changes_url) = &selection.changes_url else {
+        return full_sync(app, selection).await;
+    };
+
+    if let Some(cursor) = load_cursor(app, selection) {
+        let changes = fetch_changes(selection, changes_url, Some(&cursor)).await?;
+        if !changes.reset {
+            let cursor = changes.cursor.clone();
+            let pass = delta_sync(app, selection, changes)?;
+            save_cursor(app, selection, &cursor);
+            return Ok(pass);
+        }
+    }
+
+    // Take the cursor before listing so changes made meanwhile are replayed next pass
+    let start = fetch_changes(selection, changes_url, None).await?;
+    let pass = full_sync(app, selection).await?;
+    save_cursor(app, selection, &start.cursor);
+    Ok(pass)
+}
+
+/// Run a sync pass over every selected album, emitting `sync-pass` for each
+pub async fn run_pass(app: &AppHandle) -> Result<Vec<SyncPass>, String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+
+    let mut passes = Vec::new();
+    for selection in read_selections(app)? {
+        let pass = match sync_selection(app, &selection).await {
+            Ok(pass) => pass,
+            Err(error) => SyncPass {
+                server: selection.server.clone(),
+                album_id: selection.album_id.clone(),
+                error: Some(error),
+                ..Default::default()
+            },
+        };
+        events::publish(app, Event::SyncPass(pass.clone()));
+        passes.push(pass);
+    }
+    Ok(passes)
+}
+
+/// Periodically re-sync selected albums and folder pairs in the background
+pub fn watch_sync(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            if schedule::policy(&app) == TransferPolicy::Pause {
+                continue;
+            }
+
+            if let Err(e) = run_pass(&app).await {
+                log::warn!("Sync pass failed: {}", e);
+            }
+            if let Err(e) = folder_sync::run_pairs(&app).await {
+                log::warn!("Folder sync pass failed: {}", e);
+            }
+        }
+    });
+}
+
+/// List selected albums, optionally only those for one server
+#[tauri::command]
+pub async fn get_sync_selections(
+    app: AppHandle,
+    server: Option<String>,
+) -> Result<Vec<SyncSelection>, String> {
+    let mut selections = read_selections(&app)?;
+    if let Some(server) = server {
+        selections.retain(|s| s.server == server);
+    }
+    Ok(selections)
+}
+
+/// Estimate how much enabling sync for an album would download
+#[tauri::command]
+pub async fn estimate_sync_size(
+    app: AppHandle,
+    selection: SyncSelection,
+) -> Result<SyncEstimate, String> {
+    if let Some(folder) = &selection.folder {
+        fs_scope::check(&app, folder)?;
+    }
+    let folder = folder(&app, &selection)?;
+    let items = fetch_items(&app, &selection).await?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let planned = plan(&folder, items);
+        let mut estimate = SyncEstimate {
+            items: planned.len(),
+            available_bytes: available_space(&folder),
+            ..Default::default()
+        };
+
+        for (item, path) in planned {
+            let local = std::fs::metadata(&path).ok().map(|m| m.len());
+            match (local, item.size) {
+                (Some(len), _) => estimate.local_bytes += len,
+                (None, Some(size)) => estimate.download_bytes += size,
+                (None, None) => {}
+            }
+            match item.size {
+                Some(size) => estimate.total_bytes += size,
+                None => estimate.unknown_sizes += 1,
+            }
+        }
+        estimate
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Mirror an album locally, replacing any existing selection for it, and run a first pass
+#[tauri::command]
+pub async fn enable_sync(app: AppHandle, selection: SyncSelection) -> Result<SyncPass, String> {
+    if let Some(folder) = &selection.folder {
+        fs_scope::check(&app, folder)?;
+    }
+    save_selection(&app, &selection)?;
+
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+    sync_selection(&app, &selection).await
+}
+
+/// Stop mirroring an album
+///
+/// Pending downloads are cancelled; with `delete_files`, the local copies
+/// written by sync are removed too. Returns the number of files deleted.
+#[tauri::command]
+pub async fn unsync_album(
+    app: AppHandle,
+    server: String,
+    album_id: String,
+    delete_files: bool,
+) -> Result<usize, String> {
+    let engine = app.state::<SyncEngine>();
+    let _running = engine.lock().await;
+    let removed = remove_selection(&app, &server, &album_id, delete_files)?;
+    if let Err(e) = search_index::remove_album(&server, &album_id).await {
+        log::warn!("Failed to remove the album from search: {}", e);
+    }
+    Ok(removed)
+}
+
+/// Sync every selected album now instead of waiting for the next pass
+#[tauri::command]
+pub async fn sync_now(app: AppHandle) -> Result<Vec<SyncPass>, String> {
+    run_pass(&app).await
+}
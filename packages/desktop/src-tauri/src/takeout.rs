@@ -0,0 +1,717 @@
+use exif::experimental::Writer;
+use exif::{Field, In, Rational, Reader, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::AppHandle;
+use zip::ZipArchive;
+
+use crate::events::{self, Event};
+use crate::export::{sanitize_component, ExportFailure, ExportProgress};
+use crate::fs_scope;
+use crate::geo::GpsPosition;
+use crate::import::is_media;
+
+/// Sidecars larger than this aren't per-photo metadata
+const MAX_SIDECAR_BYTES: u64 = 1024 * 1024;
+/// Album folders carry this file; "Photos from YYYY" folders don't
+const ALBUM_METADATA: &str = "metadata.json";
+/// Suffix Google adds to edited copies, which share the original's sidecar
+const EDITED_SUFFIX: &str = "-edited";
+
+#[derive(Debug, Clone, Deserialize)]
+struct Timestamp {
+    timestamp: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GeoData {
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    altitude: f64,
+}
+
+impl GeoData {
+    /// Takeout writes zeros when there is no location
+    fn position(&self) -> Option<GpsPosition> {
+        (self.latitude != 0.0 || self.longitude != 0.0).then_some(GpsPosition {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: (self.altitude != 0.0).then_some(self.altitude),
+        })
+    }
+}
+
+/// Per-photo JSON sidecar written by Google Takeout
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sidecar {
+    title: Option<String>,
+    description: Option<String>,
+    photo_taken_time: Option<Timestamp>,
+    geo_data: Option<GeoData>,
+    geo_data_exif: Option<GeoData>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TakeoutItem {
+    pub path: String,
+    /// Seconds since the Unix epoch, from the sidecar
+    pub taken_at: Option<i64>,
+    pub description: Option<String>,
+    pub gps: Option<GpsPosition>,
+    /// Albums the file appeared in
+    pub albums: Vec<String>,
+    /// Whether missing EXIF fields were written into the file
+    pub metadata_written: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TakeoutReport {
+    pub archives: Vec<String>,
+    pub items: Vec<TakeoutItem>,
+    /// Media with no matching sidecar
+    pub unmatched: Vec<String>,
+    pub failed: Vec<ExportFailure>,
+    pub bytes: u64,
+}
+
+/// A media entry inside one of the archive parts
+struct Entry {
+    archive: usize,
+    index: usize,
+    /// Path inside the archive
+    path: PathBuf,
+    size: u64,
+    crc: u32,
+}
+
+/// `takeout-20240101T000000Z-001.zip` -> every `-NNN.zip` part next to it
+fn discover_parts(path: &Path) -> Vec<PathBuf> {
+    let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+        return vec![path.to_path_buf()];
+    };
+    let Some((prefix, number)) = stem.rsplit_once('-') else {
+        return vec![path.to_path_buf()];
+    };
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return vec![path.to_path_buf()];
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut parts: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    let is_zip = p.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+                    let part = p
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .and_then(|s| s.strip_prefix(&format!("{}-", prefix)).map(String::from))
+                        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+                    is_zip && part
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    parts.sort();
+    if parts.is_empty() {
+        parts.push(path.to_path_buf());
+    }
+    parts
+}
+
+/// Media file name a sidecar describes, taking duplicate suffixes into account:
+/// `IMG_0001.jpg(1).json` belongs to `IMG_0001(1).jpg`
+fn sidecar_target(json_name: &str, title: &str) -> String {
+    let stem = json_name.trim_end_matches(".json");
+    let duplicate = stem
+        .rfind('(')
+        .filter(|_| stem.ends_with(')'))
+        .map(|at| &stem[at..])
+        .filter(|n| n[1..n.len() - 1].chars().all(|c| c.is_ascii_digit()));
+
+    let title = sanitize_component(title);
+    match duplicate {
+        Some(n) => match title.rsplit_once('.') {
+            Some((name, ext)) => format!("{}{}.{}", name, n, ext),
+            None => format!("{}{}", title, n),
+        },
+        None => title,
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Look a file up by name, then as the edited copy of an original
+///
+/// Sidecars are keyed by folder and lowercased file name.
+fn find_sidecar<'a>(
+    sidecars: &'a HashMap<(PathBuf, String), Sidecar>,
+    dir: &Path,
+    name: &str,
+) -> Option<&'a Sidecar> {
+    let name = name.to_lowercase();
+    let lookup = |name: &str| sidecars.get(&(dir.to_path_buf(), name.to_string()));
+
+    lookup(&name).or_else(|| {
+        let (stem, ext) = name.rsplit_once('.')?;
+        let original = stem.strip_suffix(EDITED_SUFFIX)?;
+        lookup(&format!("{}.{}", original, ext))
+    })
+}
+
+/// Unix seconds to EXIF `YYYY:MM:DD HH:MM:SS` in UTC
+fn exif_datetime(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let secs = seconds.rem_euclid(86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+fn ascii(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+fn dms(value: f64) -> Value {
+    let value = value.abs();
+    let degrees = value.trunc();
+    let minutes = ((value - degrees) * 60.0).trunc();
+    let seconds = ((value - degrees) * 60.0 - minutes) * 60.0;
+    Value::Rational(vec![
+        Rational::from((degrees as u32, 1)),
+        Rational::from((minutes as u32, 1)),
+        Rational::from(((seconds * 1000.0).round() as u32, 1000)),
+    ])
+}
+
+fn gps_fields(gps: &GpsPosition) -> Vec<Field> {
+    let field = |tag, value| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value,
+    };
+    let mut fields = vec![
+        field(Tag::GPSVersionID, Value::Byte(vec![2, 3, 0, 0])),
+        ascii(
+            Tag::GPSLatitudeRef,
+            if gps.latitude < 0.0 { "S" } else { "N" },
+        ),
+        field(Tag::GPSLatitude, dms(gps.latitude)),
+        ascii(
+            Tag::GPSLongitudeRef,
+            if gps.longitude < 0.0 { "W" } else { "E" },
+        ),
+        field(Tag::GPSLongitude, dms(gps.longitude)),
+    ];
+    if let Some(altitude) = gps.altitude {
+        fields.push(field(
+            Tag::GPSAltitudeRef,
+            Value::Byte(vec![u8::from(altitude < 0.0)]),
+        ));
+        fields.push(field(
+            Tag::GPSAltitude,
+            Value::Rational(vec![Rational::from((
+                (altitude.abs() * 100.0).round() as u32,
+                100,
+            ))]),
+        ));
+    }
+    fields
+}
+
+/// Byte ranges of the APP1 Exif segments in a JPEG
+fn exif_segments(jpeg: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let mut segments = Vec::new();
+    let mut at = 2;
+    while let Some(&[0xff, marker]) = jpeg.get(at..at + 2) {
+        // Start of scan: metadata segments all come before it
+        if marker == 0xda {
+            break;
+        }
+        let Some(len) = jpeg.get(at + 2..at + 4) else {
+            break;
+        };
+        let end = at + 2 + u16::from_be_bytes([len[0], len[1]]) as usize;
+        if marker == 0xe1 && jpeg.get(at + 4..at + 10) == Some(b"Exif\0\0") {
+            segments.push(at..end);
+        }
+        at = end;
+    }
+    segments
+}
+
+/// Fill in EXIF date, description, and GPS that a JPEG is missing
+///
+/// Fields already present are kept, so camera data always wins over the
+/// sidecar. Returns whether the file was changed.
+fn write_jpeg_metadata(
+    path: &Path,
+    taken_at: Option<i64>,
+    description: Option<&str>,
+    gps: Option<&GpsPosition>,
+) -> Result<bool, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Ok(false);
+    }
+
+    let existing = Reader::new()
+        .read_from_container(&mut Cursor::new(&data))
+        .ok();
+    let has = |tag| {
+        existing
+            .as_ref()
+            .is_some_and(|e| e.get_field(tag, In::PRIMARY).is_some())
+    };
+
+    let mut added = Vec::new();
+    if let Some(seconds) = taken_at.filter(|_| !has(Tag::DateTimeOriginal)) {
+        added.push(ascii(Tag::DateTimeOriginal, &exif_datetime(seconds)));
+        if !has(Tag::OffsetTimeOriginal) {
+            added.push(ascii(Tag::OffsetTimeOriginal, "+00:00"));
+        }
+    }
+    if let Some(text) = description.filter(|d| !d.trim().is_empty()) {
+        if !has(Tag::ImageDescription) {
+            added.push(ascii(Tag::ImageDescription, text));
+        }
+    }
+    if let Some(gps) = gps.filter(|_| !has(Tag::GPSLatitude)) {
+        added.extend(gps_fields(gps));
+    }
+    if added.is_empty() {
+        return Ok(false);
+    }
+
+    // Thumbnail IFDs are dropped since their image data isn't carried over
+    let mut writer = Writer::new();
+    if let Some(exif) = &existing {
+        for field in exif.fields().filter(|f| f.ifd_num == In::PRIMARY) {
+            writer.push_field(field);
+        }
+    }
+    for field in &added {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    let little_endian = existing.as_ref().is_some_and(|e| e.little_endian());
+    writer
+        .write(&mut tiff, little_endian)
+        .map_err(|e| e.to_string())?;
+    let tiff = tiff.into_inner();
+
+    let length = tiff.len() + 8;
+    if length > u16::MAX as usize {
+        return Ok(false);
+    }
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend((length as u16).to_be_bytes());
+    segment.extend(b"Exif\0\0");
+    segment.extend(tiff);
+
+    let old = exif_segments(&data);
+    let insert_at = old.first().map(|r| r.start).unwrap_or(2);
+    let mut output = Vec::with_capacity(data.len() + segment.len());
+    output.extend(&data[..insert_at]);
+    output.extend(segment);
+    let mut at = insert_at;
+    for range in old {
+        output.extend(&data[at..range.start]);
+        at = range.end;
+    }
+    output.extend(&data[at..]);
+
+    let temp = path.with_extension("part");
+    std::fs::write(&temp, output).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp, path).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+fn is_jpeg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "jpg" | "jpeg"))
+}
+
+/// Import a Google Takeout export into a local folder
+///
+/// Accepts one or more archive paths; other `-NNN.zip` parts of the same
+/// export are picked up automatically, and sidecars are matched to media
+/// across parts. Files shared between album and year folders are extracted
+/// once. Missing EXIF date, description, and GPS are written back into
+/// JPEGs, and every file's modification time is set to when it was taken.
+/// Emits `takeout-progress` after each file.
+#[tauri::command]
+pub async fn import_takeout(
+    app: AppHandle,
+    archives: Vec<String>,
+    target: String,
+) -> Result<TakeoutReport, String> {
+    let root = fs_scope::check(&app, &target)?;
+    fs_scope::check_all(&app, &archives)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let parts: Vec<PathBuf> = archives
+            .iter()
+            .flat_map(|a| discover_parts(Path::new(a)))
+            .collect();
+        // Parts found next to a checked archive may still link out of the scope
+        let mut paths = fs_scope::check_all(&app, &parts)?;
+        paths.sort();
+        paths.dedup();
+
+        let mut zips = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let zip = ZipArchive::new(BufReader::new(file))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            zips.push(zip);
+        }
+
+        let mut sidecars: HashMap<(PathBuf, String), Sidecar> = HashMap::new();
+        let mut album_dirs = HashSet::new();
+        let mut entries = Vec::new();
+
+        for (archive, zip) in zips.iter_mut().enumerate() {
+            for index in 0..zip.len() {
+                let Ok(mut file) = zip.by_index(index) else {
+                    continue;
+                };
+                let Some(path) = file.enclosed_name() else {
+                    continue;
+                };
+                if !file.is_file() {
+                    continue;
+                }
+                let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                if name.to_lowercase().ends_with(".json") {
+                    if name == ALBUM_METADATA {
+                        album_dirs.insert(dir);
+                        continue;
+                    }
+                    if file.size() > MAX_SIDECAR_BYTES {
+                        continue;
+                    }
+                    let mut json = Vec::new();
+                    if file.read_to_end(&mut json).is_err() {
+                        continue;
+                    }
+                    let Ok(sidecar) = serde_json::from_slice::<Sidecar>(&json) else {
+                        continue;
+                    };
+                    if let Some(title) = sidecar.title.clone() {
+                        sidecars
+                            .insert((dir, sidecar_target(&name, &title).to_lowercase()), sidecar);
+                    }
+                } else if is_media(&path) {
+                    entries.push(Entry {
+                        archive,
+                        index,
+                        size: file.size(),
+                        crc: file.crc32(),
+                        path,
+                    });
+                }
+            }
+        }
+
+        // The same photo appears in its year folder and every album it is in
+        let mut unique: Vec<(Entry, Vec<String>)> = Vec::new();
+        let mut seen: HashMap<(String, u64, u32), usize> = HashMap::new();
+        for entry in entries {
+            let dir = entry
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let album = album_dirs.contains(&dir).then(|| file_name(&dir));
+            let key = (file_name(&entry.path), entry.size, entry.crc);
+
+            match seen.get(&key) {
+                Some(&at) => unique[at].1.extend(album),
+                None => {
+                    seen.insert(key, unique.len());
+                    unique.push((entry, album.into_iter().collect()));
+                }
+            }
+        }
+
+        let mut report = TakeoutReport {
+            archives: paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect(),
+            ..Default::default()
+        };
+        let total = unique.len();
+
+        for (completed, (entry, albums)) in unique.into_iter().enumerate() {
+            let dir = entry
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let name = file_name(&entry.path);
+            // Drop the `Takeout/Google Photos` prefix
+            let relative: PathBuf = entry
+                .path
+                .components()
+                .skip_while(|c| {
+                    let c = c.as_os_str().to_string_lossy();
+                    c == "Takeout" || c == "Google Photos"
+                })
+                .collect();
+            let output = root.join(relative);
+
+            let result = (|| -> Result<(TakeoutItem, bool), String> {
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                let mut file = zips[entry.archive]
+                    .by_index(entry.index)
+                    .map_err(|e| e.to_string())?;
+                let mut out = File::create(&output).map_err(|e| e.to_string())?;
+                std::io::copy(&mut file, &mut out).map_err(|e| e.to_string())?;
+                drop(out);
+
+                let sidecar = find_sidecar(&sidecars, &dir, &name);
+                let taken_at = sidecar
+                    .and_then(|s| s.photo_taken_time.as_ref())
+                    .and_then(|t| t.timestamp.parse::<i64>().ok());
+                let description = sidecar
+                    .and_then(|s| s.description.clone())
+                    .filter(|d| !d.trim().is_empty());
+                let gps = sidecar.and_then(|s| {
+                    s.geo_data
+                        .as_ref()
+                        .and_then(GeoData::position)
+                        .or_else(|| s.geo_data_exif.as_ref().and_then(GeoData::position))
+                });
+
+                let metadata_written = is_jpeg(&output)
+                    && write_jpeg_metadata(&output, taken_at, description.as_deref(), gps.as_ref())
+                        .unwrap_or(false);
+                if let Some(seconds) = taken_at.filter(|s| *s > 0) {
+                    let time = UNIX_EPOCH + Duration::from_secs(seconds as u64);
+                    if let Ok(file) = File::options().write(true).open(&output) {
+                        let _ = file.set_modified(time);
+                    }
+                }
+
+                let item = TakeoutItem {
+                    path: output.to_string_lossy().to_string(),
+                    taken_at,
+                    description,
+                    gps,
+                    albums,
+                    metadata_written,
+                };
+                Ok((item, sidecar.is_some()))
+            })();
+
+            match result {
+                Ok((item, matched)) => {
+                    if !matched {
+                        report.unmatched.push(item.path.clone());
+                    }
+                    report.bytes += entry.size;
+                    report.items.push(item);
+                }
+                Err(error) => {
+                    let _ = std::fs::remove_file(&output);
+                    report.failed.push(ExportFailure {
+                        filename: entry.path.to_string_lossy().to_string(),
+                        error,
+                    });
+                }
+            }
+
+            events::publish(
+                &app,
+                Event::TakeoutProgress(ExportProgress {
+                    completed: completed + 1,
+                    total,
+                    filename: name,
+                }),
+            );
+        }
+
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest marker layout the writer walks: SOI, a JFIF header, a scan, EOI
+    const JPEG: &[u8] = &[
+        0xff, 0xd8, // SOI
+        0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00,
+        0x01, 0x00, 0x00, // APP0
+        0xff, 0xda, 0x00, 0x02, 0x12, 0x34, // SOS and scan data
+        0xff, 0xd9, // EOI
+    ];
+
+    fn temp_jpeg(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "apollo-takeout-test-{}-{}.jpg",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, JPEG).unwrap();
+        path
+    }
+
+    fn read_exif(path: &Path) -> exif::Exif {
+        let data = std::fs::read(path).unwrap();
+        Reader::new()
+            .read_from_container(&mut Cursor::new(&data))
+            .unwrap()
+    }
+
+    fn text(exif: &exif::Exif, tag: Tag) -> Option<String> {
+        exif.get_field(tag, In::PRIMARY)
+            .map(|f| f.display_value().to_string())
+    }
+
+    #[test]
+    fn datetimes_are_exif_formatted_in_utc() {
+        assert_eq!(exif_datetime(0), "1970:01:01 00:00:00");
+        assert_eq!(exif_datetime(951_782_400), "2000:02:29 00:00:00");
+        assert_eq!(exif_datetime(1_704_067_199), "2023:12:31 23:59:59");
+        assert_eq!(exif_datetime(-1), "1969:12:31 23:59:59");
+    }
+
+    #[test]
+    fn sidecars_match_their_media() {
+        assert_eq!(
+            sidecar_target("IMG_0001.jpg.json", "IMG_0001.jpg"),
+            "IMG_0001.jpg"
+        );
+        assert_eq!(
+            sidecar_target("IMG_0001.jpg(1).json", "IMG_0001.jpg"),
+            "IMG_0001(1).jpg"
+        );
+        assert_eq!(sidecar_target("notes(2).json", "notes"), "notes(2)");
+        // Not a duplicate counter
+        assert_eq!(
+            sidecar_target("Trip (Paris).json", "Trip (Paris).jpg"),
+            "Trip (Paris).jpg"
+        );
+        assert_eq!(sidecar_target("a.jpg.json", "a/b:c.jpg"), "a_b_c.jpg");
+    }
+
+    #[test]
+    fn missing_metadata_is_written() {
+        let path = temp_jpeg("write");
+        let gps = GpsPosition {
+            latitude: -33.8568,
+            longitude: 151.2153,
+            altitude: Some(12.5),
+        };
+        let written =
+            write_jpeg_metadata(&path, Some(1_704_067_199), Some("Opera House"), Some(&gps));
+        assert_eq!(written, Ok(true));
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(&[0xff, 0xd8]));
+        assert!(data.ends_with(&JPEG[20..]));
+        assert_eq!(exif_segments(&data).len(), 1);
+
+        let exif = read_exif(&path);
+        assert_eq!(
+            text(&exif, Tag::DateTimeOriginal).as_deref(),
+            Some("2023-12-31 23:59:59")
+        );
+        assert_eq!(
+            text(&exif, Tag::ImageDescription).as_deref(),
+            Some("\"Opera House\"")
+        );
+        assert_eq!(text(&exif, Tag::GPSLatitudeRef).as_deref(), Some("S"));
+        assert_eq!(text(&exif, Tag::GPSLongitudeRef).as_deref(), Some("E"));
+        assert!(exif.get_field(Tag::GPSAltitude, In::PRIMARY).is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn existing_metadata_is_kept() {
+        let path = temp_jpeg("keep");
+        assert_eq!(write_jpeg_metadata(&path, Some(0), None, None), Ok(true));
+        let before = std::fs::read(&path).unwrap();
+
+        assert_eq!(
+            write_jpeg_metadata(&path, Some(1_704_067_199), Some("  "), None),
+            Ok(false)
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        // New fields join the old ones in the one Exif segment
+        assert_eq!(
+            write_jpeg_metadata(&path, Some(1_704_067_199), Some("Later"), None),
+            Ok(true)
+        );
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(exif_segments(&data).len(), 1);
+        let exif = read_exif(&path);
+        assert_eq!(
+            text(&exif, Tag::DateTimeOriginal).as_deref(),
+            Some("1970-01-01 00:00:00")
+        );
+        assert_eq!(
+            text(&exif, Tag::ImageDescription).as_deref(),
+            Some("\"Later\"")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn non_jpegs_are_left_alone() {
+        let path = temp_jpeg("png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n").unwrap();
+        assert_eq!(write_jpeg_metadata(&path, Some(0), None, None), Ok(false));
+        assert_eq!(std::fs::read(&path).unwrap(), b"\x89PNG\r\n\x1a\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
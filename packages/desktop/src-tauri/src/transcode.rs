@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::sidecar;
+
+pub const FFMPEG: &str = "ffmpeg";
+
+/// Encoding presets offered for pre-upload transcoding
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum TranscodePreset {
+    #[serde(rename = "h264-1080p")]
+    H264_1080p,
+    #[serde(rename = "h264-720p")]
+    H264_720p,
+    #[serde(rename = "hevc-1080p")]
+    Hevc1080p,
+    /// H.264 at the source resolution
+    #[serde(rename = "h264-source")]
+    H264Source,
+}
+
+impl TranscodePreset {
+    fn args(self) -> Vec<&'static str> {
+        let (codec, crf, scale) = match self {
+            Self::H264_1080p => ("libx264", "23", Some("scale=-2:'min(1080,ih)'")),
+            Self::H264_720p => ("libx264", "23", Some("scale=-2:'min(720,ih)'")),
+            Self::Hevc1080p => ("libx265", "28", Some("scale=-2:'min(1080,ih)'")),
+            Self::H264Source => ("libx264", "20", None),
+        };
+
+        let mut args = vec!["-c:v", codec, "-preset", "medium", "-crf", crf];
+        if let Some(filter) = scale {
+            args.extend(["-vf", filter]);
+        }
+        if matches!(self, Self::Hevc1080p) {
+            // Tag HEVC so Apple players recognise it
+            args.extend(["-tag:v", "hvc1"]);
+        }
+        args.extend(["-c:a", "aac", "-b:a", "160k", "-movflags", "+faststart"]);
+        args
+    }
+}
+
+/// Running transcodes, keyed by the id the frontend chose
+#[derive(Default)]
+pub struct Transcodes(Mutex<HashMap<String, CommandChild>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscodeProgress {
+    pub id: String,
+    /// Output position in milliseconds
+    pub position_ms: u64,
+    pub duration_ms: Option<u64>,
+    /// 0-100, when the input duration is known
+    pub percent: Option<f64>,
+}
+
+/// Parse `HH:MM:SS.ss` into milliseconds
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let mut parts = value.trim().split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(((hours * 3600.0 + minutes * 60.0 + seconds) * 1000.0) as u64)
+}
+
+/// Transcode a video with ffmpeg using a preset
+///
+/// Emits `transcode-progress` while encoding and resolves once ffmpeg exits.
+/// Cancel with `cancel_transcode` using the same id.
+#[tauri::command]
+pub async fn transcode_video(
+    app: AppHandle,
+    transcodes: State<'_, Transcodes>,
+    id: String,
+    input: String,
+    output: String,
+    preset: TranscodePreset,
+) -> Result<String, String> {
+    fs_scope::check_all(&app, &[&input, &output])?;
+    let mut args = vec!["-y", "-hide_banner", "-i", input.as_str()];
+    args.extend(preset.args());
+    args.extend(["-progress", "pipe:1", "-nostats", output.as_str()]);
+
+    let (mut events, child) = sidecar::command(&app, FFMPEG)?
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    transcodes
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(id.clone(), child);
+
+    let mut duration_ms = None;
+    let mut last_error = String::new();
+    let mut status = None;
+
+    while let Some(event) = events.recv().await {
+        if !matches!(event, CommandEvent::Stdout(_)) {
+            sidecar::log_output(FFMPEG, &event);
+        }
+        match event {
+            CommandEvent::Stdout(line) => {
+                let line = String::from_utf8_lossy(&line);
+                let Some(value) = line.trim().strip_prefix("out_time_us=") else {
+                    continue;
+                };
+                let Ok(micros) = value.parse::<u64>() else {
+                    continue;
+                };
+                let position_ms = micros / 1000;
+                events::publish(
+                    &app,
+                    Event::TranscodeProgress(TranscodeProgress {
+                        id: id.clone(),
+                        position_ms,
+                        duration_ms,
+                        percent: duration_ms
+                            .filter(|d| *d > 0)
+                            .map(|d| (position_ms as f64 / d as f64 * 100.0).min(100.0)),
+                    }),
+                );
+            }
+            CommandEvent::Stderr(line) => {
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+                if duration_ms.is_none() {
+                    if let Some(rest) = line.strip_prefix("Duration:") {
+                        duration_ms = rest.split(',').next().and_then(parse_timestamp);
+                    }
+                }
+                if !line.is_empty() {
+                    last_error = line;
+                }
+            }
+            CommandEvent::Terminated(payload) => status = payload.code,
+            CommandEvent::Error(e) => last_error = e,
+            _ => {}
+        }
+    }
+
+    let cancelled = transcodes
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id)
+        .is_none();
+
+    if cancelled {
+        let _ = std::fs::remove_file(&output);
+        return Err("Transcode cancelled".to_string());
+    }
+    match status {
+        Some(0) => Ok(output),
+        _ => {
+            let _ = std::fs::remove_file(&output);
+            Err(format!("ffmpeg failed: {}", last_error))
+        }
+    }
+}
+
+/// Cancel a running transcode
+#[tauri::command]
+pub async fn cancel_transcode(transcodes: State<'_, Transcodes>, id: String) -> Result<(), String> {
+    let child = transcodes
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+
+    match child {
+        Some(child) => child.kill().map_err(|e| e.to_string()),
+        None => Err(format!("Transcode '{}' not found", id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_parse_to_milliseconds() {
+        assert_eq!(parse_timestamp("00:00:00.00"), Some(0));
+        assert_eq!(parse_timestamp("00:00:01.500000"), Some(1_500));
+        assert_eq!(parse_timestamp(" 01:02:03.25\n"), Some(3_723_250));
+        assert_eq!(parse_timestamp("10:00:00"), Some(36_000_000));
+    }
+
+    #[test]
+    fn unknown_timestamps_are_rejected() {
+        assert_eq!(parse_timestamp("N/A"), None);
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("01:02"), None);
+        assert_eq!(parse_timestamp("aa:bb:cc"), None);
+    }
+}
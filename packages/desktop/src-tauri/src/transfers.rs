@@ -0,0 +1,709 @@
+use futures_util::StreamExt;
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+
+use crate::bandwidth;
+use crate::db::{now, Database};
+use crate::downloads;
+use crate::events::{self, Event};
+use crate::export::partial_path;
+use crate::fs_scope;
+use crate::http;
+use crate::profiles;
+use crate::quick_look;
+use crate::schedule::{self, TransferPolicy};
+use crate::tray;
+use crate::upload;
+use crate::usage;
+
+const CONCURRENCY: usize = 4;
+const TRANSFERS_PAUSED_KEY: &str = "transfersPaused";
+/// How often download progress is written back to the queue
+const PROGRESS_STEP: u64 = 1024 * 1024;
+/// Where an upload's link is in the server's response unless the request says otherwise
+const DEFAULT_LINK_POINTER: &str = "/url";
+/// Finished uploads listed in the tray
+const RECENT_UPLOADS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferStatus {
+    Queued,
+    Active,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl TransferDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Download => "download",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "upload" => Self::Upload,
+            _ => Self::Download,
+        }
+    }
+}
+
+impl TransferStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Active => "active",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "active" => Self::Active,
+            "done" => Self::Done,
+            "failed" => Self::Failed,
+            "cancelled" => Self::Cancelled,
+            _ => Self::Queued,
+        }
+    }
+}
+
+/// A transfer to add to the queue
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransferRequest {
+    pub direction: TransferDirection,
+    /// Upload endpoint, or URL of the file to download
+    pub url: String,
+    /// Local file to upload, or destination of the download; a relative
+    /// download path is placed in the downloads folder
+    pub path: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Extra multipart text fields sent with uploads
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+    /// JSON pointer to the uploaded item's link in the server's response,
+    /// `/url` by default
+    #[serde(default)]
+    pub link_pointer: Option<String>,
+}
+
+/// A queued transfer and its progress
+#[derive(Debug, Clone, Serialize)]
+pub struct Transfer {
+    pub id: i64,
+    pub direction: TransferDirection,
+    pub url: String,
+    pub path: String,
+    pub status: TransferStatus,
+    pub bytes_done: u64,
+    pub bytes_total: Option<u64>,
+    pub attempts: u32,
+    pub error: Option<String>,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Link to the uploaded item, if the server's response had one
+    pub link: Option<String>,
+}
+
+/// Wakes queue workers and tracks running transfers so they can be cancelled
+#[derive(Default)]
+pub struct Transfers {
+    wake: Notify,
+    running: Mutex<HashMap<i64, AbortHandle>>,
+    /// Paused by the user, independent of the quiet hours schedule
+    paused: AtomicBool,
+}
+
+struct Job {
+    id: i64,
+    direction: TransferDirection,
+    url: String,
+    path: PathBuf,
+    headers: HashMap<String, String>,
+    fields: HashMap<String, String>,
+    link_pointer: Option<String>,
+}
+
+const TRANSFER_COLUMNS: &str =
+    "id, direction, url, path, status, bytes_done, bytes_total, attempts, error, created_at, link";
+
+fn transfer_from_row(row: &Row) -> rusqlite::Result<Transfer> {
+    Ok(Transfer {
+        id: row.get(0)?,
+        direction: TransferDirection::parse(&row.get::<_, String>(1)?),
+        url: row.get(2)?,
+        path: row.get(3)?,
+        status: TransferStatus::parse(&row.get::<_, String>(4)?),
+        bytes_done: row.get(5)?,
+        bytes_total: row.get(6)?,
+        attempts: row.get(7)?,
+        error: row.get(8)?,
+        created_at: row.get(9)?,
+        link: row.get(10)?,
+    })
+}
+
+fn load(app: &AppHandle, id: i64) -> Option<Transfer> {
+    app.state::<Database>()
+        .conn()
+        .query_row(
+            &format!("SELECT {} FROM transfers WHERE id = ?1", TRANSFER_COLUMNS),
+            params![id],
+            transfer_from_row,
+        )
+        .ok()
+}
+
+fn emit_update(app: &AppHandle, id: i64) {
+    if let Some(transfer) = load(app, id) {
+        events::publish(app, Event::TransferProgress(transfer.clone()));
+    }
+}
+
+/// Mark the next queued transfer active and return it
+fn claim(app: &AppHandle) -> Option<Job> {
+    app.state::<Database>()
+        .conn()
+        .query_row(
+            "UPDATE transfers SET status = 'active', attempts = attempts + 1, error = NULL
+             WHERE id = (SELECT id FROM transfers WHERE status = 'queued' ORDER BY id LIMIT 1)
+             RETURNING id, direction, url, path, headers, fields, link_pointer",
+            [],
+            |row| {
+                let headers: String = row.get(4)?;
+                let fields: String = row.get(5)?;
+                Ok(Job {
+                    id: row.get(0)?,
+                    direction: TransferDirection::parse(&row.get::<_, String>(1)?),
+                    url: row.get(2)?,
+                    path: PathBuf::from(row.get::<_, String>(3)?),
+                    headers: serde_json::from_str(&headers).unwrap_or_default(),
+                    fields: serde_json::from_str(&fields).unwrap_or_default(),
+                    link_pointer: row.get(6)?,
+                })
+            },
+        )
+        .optional()
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to read transfer queue: {}", e);
+            None
+        })
+}
+
+fn record_progress(app: &AppHandle, id: i64, done: u64, total: Option<u64>) {
+    let _ = app.state::<Database>().conn().execute(
+        "UPDATE transfers SET bytes_done = ?2, bytes_total = COALESCE(?3, bytes_total)
+         WHERE id = ?1",
+        params![id, done, total],
+    );
+    emit_update(app, id);
+}
+
+fn finish(app: &AppHandle, id: i64, result: Result<(), String>) {
+    let (status, error) = match result {
+        Ok(()) => (TransferStatus::Done, None),
+        Err(e) => (TransferStatus::Failed, Some(e)),
+    };
+    // A cancelled transfer keeps its status even if the attempt then errors out
+    let updated = app.state::<Database>().conn().execute(
+        "UPDATE transfers SET status = ?2, error = ?3 WHERE id = ?1 AND status = 'active'",
+        params![id, status.as_str(), error],
+    );
+    if status == TransferStatus::Done && updated == Ok(1) {
+        if let Some(transfer) = load(app, id) {
+            let bytes = transfer.bytes_total.unwrap_or(transfer.bytes_done);
+            usage::record_transfer(app, transfer.direction, bytes);
+            if transfer.direction == TransferDirection::Upload {
+                tray::refresh_recent_uploads(app);
+            }
+        }
+    }
+    emit_update(app, id);
+}
+
+/// Download into the partial file, continuing from its current length
+///
+/// Uses a `Range` request when part of the file is already on disk and
+/// starts over if the server ignores or rejects the range.
+async fn download(app: &AppHandle, job: &Job) -> Result<(), String> {
+    if let Some(parent) = job.path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let partial = partial_path(&job.path);
+    let client = http::client()?;
+    let mut offset = tokio::fs::metadata(&partial)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    let response = loop {
+        let mut request = http::with_headers(client.get(&job.url), &job.url, &job.headers)?;
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+            offset = 0;
+            continue;
+        }
+        let response = response.error_for_status().map_err(|e| e.to_string())?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            offset = 0;
+        }
+        break response;
+    };
+
+    let total = response.content_length().map(|len| len + offset);
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&partial)
+        .await
+        .map_err(|e| e.to_string())?;
+    record_progress(app, job.id, offset, total);
+
+    let mut done = offset;
+    let mut recorded = offset;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        done += chunk.len() as u64;
+        bandwidth::throttle(app, &job.url, chunk.len() as u64).await;
+
+        if done - recorded >= PROGRESS_STEP {
+            file.flush().await.map_err(|e| e.to_string())?;
+            record_progress(app, job.id, done, total);
+            recorded = done;
+        }
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    drop(file);
+
+    tokio::fs::rename(&partial, &job.path)
+        .await
+        .map_err(|e| e.to_string())?;
+    quick_look::tag(&job.path, &job.url);
+    record_progress(app, job.id, done, Some(done));
+    Ok(())
+}
+
+/// Upload the file; multipart uploads can't be continued part-way, so an
+/// interrupted upload is sent again from the start
+///
+/// The uploaded item's link is kept from the response, for the tray's
+/// recent uploads.
+async fn upload(app: &AppHandle, job: &Job) -> Result<(), String> {
+    let fields: Vec<(&str, String)> = job
+        .fields
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.clone()))
+        .collect();
+    let size = tokio::fs::metadata(&job.path)
+        .await
+        .map(|m| m.len())
+        .map_err(|e| e.to_string())?;
+    record_progress(app, job.id, 0, Some(size));
+
+    let pacer = (app.clone(), job.url.clone());
+    let (response, sent) =
+        upload::upload_file_paced_json(&job.url, &job.headers, &job.path, &fields, move |bytes| {
+            let (app, url) = pacer.clone();
+            async move { bandwidth::throttle(&app, &url, bytes).await }
+        })
+        .await?;
+    let pointer = job.link_pointer.as_deref().unwrap_or(DEFAULT_LINK_POINTER);
+    let link = response
+        .as_ref()
+        .and_then(|response| response.pointer(pointer))
+        .and_then(|link| link.as_str());
+    if let Some(link) = link {
+        let _ = app.state::<Database>().conn().execute(
+            "UPDATE transfers SET link = ?2 WHERE id = ?1",
+            params![job.id, link],
+        );
+    }
+    record_progress(app, job.id, sent, Some(sent));
+    Ok(())
+}
+
+async fn run(app: AppHandle, job: Job) -> Result<(), String> {
+    match job.direction {
+        TransferDirection::Download => download(&app, &job).await,
+        TransferDirection::Upload => upload(&app, &job).await,
+    }
+}
+
+async fn worker(app: AppHandle) {
+    let state = app.state::<Transfers>();
+
+    loop {
+        // Register for wake-ups before checking the queue so none are missed
+        let notified = state.wake.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        if is_paused(&app) || schedule::policy(&app) == TransferPolicy::Pause {
+            notified.await;
+            continue;
+        }
+        let Some(job) = claim(&app) else {
+            notified.await;
+            continue;
+        };
+        let id = job.id;
+        emit_update(&app, id);
+
+        let task = tokio::spawn(run(app.clone(), job));
+        state
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, task.abort_handle());
+        let result = task.await;
+        state
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+
+        match result {
+            Ok(result) => finish(&app, id, result),
+            Err(e) if e.is_cancelled() => emit_update(&app, id),
+            Err(e) => finish(&app, id, Err(e.to_string())),
+        }
+    }
+}
+
+/// Requeue transfers interrupted by the last quit or crash and start the workers
+///
+/// A pause set by the user before quitting stays in effect.
+pub fn resume(app: &AppHandle) {
+    let _ = app.state::<Database>().conn().execute(
+        "UPDATE transfers SET status = 'queued' WHERE status = 'active'",
+        [],
+    );
+    let paused = profiles::store(app)
+        .ok()
+        .and_then(|store| store.get(TRANSFERS_PAUSED_KEY))
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+    app.state::<Transfers>()
+        .paused
+        .store(paused, Ordering::Relaxed);
+    for _ in 0..CONCURRENCY {
+        tauri::async_runtime::spawn(worker(app.clone()));
+    }
+}
+
+/// Stop running transfers and put them back in the queue
+///
+/// Downloads continue from their partial files once the workers resume.
+pub fn suspend(app: &AppHandle) {
+    let _ = app.state::<Database>().conn().execute(
+        "UPDATE transfers SET status = 'queued' WHERE status = 'active'",
+        [],
+    );
+    let running: Vec<(i64, AbortHandle)> = app
+        .state::<Transfers>()
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain()
+        .collect();
+    for (id, task) in running {
+        task.abort();
+        emit_update(app, id);
+    }
+}
+
+/// Let idle workers check the queue again
+pub fn wake(app: &AppHandle) {
+    app.state::<Transfers>().wake.notify_waiters();
+}
+
+/// Whether the user has paused all transfers
+pub fn is_paused(app: &AppHandle) -> bool {
+    app.state::<Transfers>().paused.load(Ordering::Relaxed)
+}
+
+fn set_paused(app: &AppHandle, paused: bool) {
+    app.state::<Transfers>()
+        .paused
+        .store(paused, Ordering::Relaxed);
+    if let Ok(store) = profiles::store(app) {
+        store.set(TRANSFERS_PAUSED_KEY, serde_json::json!(paused));
+        let _ = store.save();
+    }
+    tray::refresh(app);
+    events::publish(app, Event::TransfersPaused { paused });
+}
+
+/// Freeze the workers, returning running transfers to the queue
+pub fn pause_all(app: &AppHandle) {
+    set_paused(app, true);
+    suspend(app);
+}
+
+/// Let the workers pick up queued transfers again
+///
+/// Quiet hours still apply; transfers stay held while a pause window is active.
+pub fn resume_all(app: &AppHandle) {
+    set_paused(app, false);
+    wake(app);
+}
+
+/// Add transfers to the persistent queue and wake the workers
+pub fn enqueue(app: &AppHandle, transfers: &[TransferRequest]) -> Result<Vec<i64>, String> {
+    let ids = {
+        let db = app.state::<Database>();
+        let mut conn = db.conn();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let mut ids = Vec::with_capacity(transfers.len());
+        for transfer in transfers {
+            if transfer.direction == TransferDirection::Upload
+                && !Path::new(&transfer.path).is_file()
+            {
+                return Err(format!("'{}' is not a file", transfer.path));
+            }
+            tx.execute(
+                "INSERT INTO transfers
+                 (direction, url, path, headers, fields, link_pointer, status, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'queued', ?7)",
+                params![
+                    transfer.direction.as_str(),
+                    transfer.url,
+                    transfer.path,
+                    serde_json::to_string(&transfer.headers).map_err(|e| e.to_string())?,
+                    serde_json::to_string(&transfer.fields).map_err(|e| e.to_string())?,
+                    transfer.link_pointer,
+                    now(),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            ids.push(tx.last_insert_rowid());
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        ids
+    };
+
+    wake(app);
+    Ok(ids)
+}
+
+/// The most recently finished uploads, newest first
+pub fn recent_uploads(app: &AppHandle) -> Vec<Transfer> {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    conn.prepare(&format!(
+        "SELECT {} FROM transfers WHERE direction = 'upload' AND status = 'done'
+         ORDER BY id DESC LIMIT ?1",
+        TRANSFER_COLUMNS
+    ))
+    .and_then(|mut stmt| {
+        stmt.query_map(params![RECENT_UPLOADS], transfer_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+    })
+    .unwrap_or_default()
+}
+
+/// Whether a transfer for this local path is queued or running
+pub fn is_pending(app: &AppHandle, path: &Path) -> bool {
+    app.state::<Database>()
+        .conn()
+        .prepare("SELECT 1 FROM transfers WHERE path = ?1 AND status IN ('queued', 'active')")
+        .and_then(|mut stmt| stmt.exists(params![path.to_string_lossy()]))
+        .unwrap_or(false)
+}
+
+fn cancel(app: &AppHandle, id: i64) -> Result<(), String> {
+    app.state::<Database>()
+        .conn()
+        .execute(
+            "UPDATE transfers SET status = 'cancelled'
+             WHERE id = ?1 AND status IN ('queued', 'active')",
+            params![id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    let running = app
+        .state::<Transfers>()
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    if let Some(task) = running {
+        task.abort();
+    }
+    emit_update(app, id);
+    Ok(())
+}
+
+/// Cancel any queued or running transfers for these local paths
+pub fn cancel_paths(app: &AppHandle, paths: &[PathBuf]) -> Result<(), String> {
+    let ids: Vec<i64> = {
+        let db = app.state::<Database>();
+        let conn = db.conn();
+        let mut stmt = conn
+            .prepare("SELECT id FROM transfers WHERE path = ?1 AND status IN ('queued', 'active')")
+            .map_err(|e| e.to_string())?;
+        let mut ids = Vec::new();
+        for path in paths {
+            let matched = stmt
+                .query_map(params![path.to_string_lossy()], |row| row.get(0))
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<i64>>>())
+                .map_err(|e| e.to_string())?;
+            ids.extend(matched);
+        }
+        ids
+    };
+
+    for id in ids {
+        cancel(app, id)?;
+    }
+    Ok(())
+}
+
+/// Add uploads and downloads to the persistent queue
+///
+/// Emits `transfer-progress` with the updated `Transfer` as each one
+/// progresses. Returns the ids of the new transfers.
+#[tauri::command]
+pub async fn enqueue_transfers(
+    app: AppHandle,
+    mut transfers: Vec<TransferRequest>,
+) -> Result<Vec<i64>, String> {
+    for transfer in &mut transfers {
+        if transfer.direction == TransferDirection::Download {
+            transfer.path = downloads::resolve(&app, &transfer.path)?;
+        }
+        fs_scope::check(&app, &transfer.path)?;
+    }
+    enqueue(&app, &transfers)
+}
+
+/// Pause every upload and download without losing queue state
+///
+/// Emits `transfers-paused`; the pause persists across restarts.
+#[tauri::command]
+pub async fn pause_transfers(app: AppHandle) -> Result<(), String> {
+    pause_all(&app);
+    Ok(())
+}
+
+/// Resume transfers paused with `pause_transfers`
+#[tauri::command]
+pub async fn resume_transfers(app: AppHandle) -> Result<(), String> {
+    resume_all(&app);
+    Ok(())
+}
+
+/// Whether transfers are paused by the user
+#[tauri::command]
+pub async fn get_transfers_paused(app: AppHandle) -> Result<bool, String> {
+    Ok(is_paused(&app))
+}
+
+/// List every transfer in the queue, oldest first
+#[tauri::command]
+pub async fn list_transfers(app: AppHandle) -> Result<Vec<Transfer>, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM transfers ORDER BY id",
+            TRANSFER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let transfers = stmt
+        .query_map([], transfer_from_row)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+    Ok(transfers)
+}
+
+/// Cancel a queued or running transfer
+#[tauri::command]
+pub async fn cancel_transfer(app: AppHandle, id: i64) -> Result<(), String> {
+    cancel(&app, id)
+}
+
+/// Put a failed or cancelled transfer back in the queue
+///
+/// Downloads continue from whatever was already written to disk.
+#[tauri::command]
+pub async fn retry_transfer(app: AppHandle, id: i64) -> Result<(), String> {
+    app.state::<Database>()
+        .conn()
+        .execute(
+            "UPDATE transfers SET status = 'queued', error = NULL
+             WHERE id = ?1 AND status IN ('failed', 'cancelled')",
+            params![id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    wake(&app);
+    emit_update(&app, id);
+    Ok(())
+}
+
+/// Remove finished, failed and cancelled transfers from the queue
+///
+/// Partial files left by failed and cancelled downloads are deleted.
+#[tauri::command]
+pub async fn clear_finished_transfers(app: AppHandle) -> Result<usize, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn();
+
+    let abandoned: Vec<String> = conn
+        .prepare(
+            "SELECT path FROM transfers WHERE status IN ('failed', 'cancelled')
+             AND direction = 'download'",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(|e| e.to_string())?;
+    for path in abandoned {
+        let _ = std::fs::remove_file(partial_path(Path::new(&path)));
+    }
+
+    let cleared = conn
+        .execute(
+            "DELETE FROM transfers WHERE status IN ('done', 'failed', 'cancelled')",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    drop(conn);
+    tray::refresh_recent_uploads(&app);
+    Ok(cleared)
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::links;
+use crate::menubar;
+use crate::transfers::{self, Transfer};
+
+const TRAY_ID: &str = "main";
+const SHOW_ID: &str = "show";
+const TOGGLE_TRANSFERS_ID: &str = "toggle-transfers";
+const QUIT_ID: &str = "quit";
+/// Prefixes of the recent upload items, followed by the transfer id
+const OPEN_UPLOAD_PREFIX: &str = "open-upload:";
+const COPY_UPLOAD_PREFIX: &str = "copy-upload:";
+
+/// Menu items whose text follows app state
+pub struct TrayMenu {
+    transfers: MenuItem<Wry>,
+    recent_uploads: Submenu<Wry>,
+}
+
+fn transfers_label(paused: bool) -> &'static str {
+    if paused {
+        "Resume Transfers"
+    } else {
+        "Pause Transfers"
+    }
+}
+
+/// Bring the main window to the front, restoring it if minimized or hidden
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn upload_link(app: &AppHandle, id: &str) -> Option<String> {
+    let id: i64 = id.parse().ok()?;
+    transfers::recent_uploads(app)
+        .into_iter()
+        .find(|upload| upload.id == id)?
+        .link
+}
+
+fn open_upload(app: &AppHandle, id: &str) {
+    let Some(link) = upload_link(app, id) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = links::open_link(app, link, None).await {
+            log::warn!("Failed to open upload: {}", e);
+        }
+    });
+}
+
+fn copy_upload_link(app: &AppHandle, id: &str) {
+    if let Some(link) = upload_link(app, id) {
+        if let Err(e) = app.clipboard().write_text(link) {
+            log::warn!("Failed to copy upload link: {}", e);
+        }
+    }
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id.as_ref();
+    if let Some(upload) = id.strip_prefix(OPEN_UPLOAD_PREFIX) {
+        return open_upload(app, upload);
+    }
+    if let Some(upload) = id.strip_prefix(COPY_UPLOAD_PREFIX) {
+        return copy_upload_link(app, upload);
+    }
+    match id {
+        SHOW_ID => show_main_window(app),
+        TOGGLE_TRANSFERS_ID => {
+            if transfers::is_paused(app) {
+                transfers::resume_all(app);
+            } else {
+                transfers::pause_all(app);
+            }
+        }
+        QUIT_ID => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Add the tray icon and its menu
+pub fn setup(app: &AppHandle) -> tauri::Result<()> {
+    let show = MenuItem::with_id(app, SHOW_ID, "Show Apollo", true, None::<&str>)?;
+    let toggle = MenuItem::with_id(
+        app,
+        TOGGLE_TRANSFERS_ID,
+        transfers_label(transfers::is_paused(app)),
+        true,
+        None::<&str>,
+    )?;
+    let recent_uploads = Submenu::new(app, "Recent Uploads", true)?;
+    fill_recent_uploads(app, &recent_uploads)?;
+    let quit = MenuItem::with_id(app, QUIT_ID, "Quit Apollo", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &show,
+            &PredefinedMenuItem::separator(app)?,
+            &toggle,
+            &recent_uploads,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Apollo")
+        .menu(&menu)
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| menubar::handle_tray_event(tray.app_handle(), event));
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayMenu {
+        transfers: toggle,
+        recent_uploads,
+    });
+    Ok(())
+}
+
+/// Whether the tray icon exists; some Linux desktops have no tray
+pub fn is_available(app: &AppHandle) -> bool {
+    app.tray_by_id(TRAY_ID).is_some()
+}
+
+/// Choose whether a left click opens the menu or is left to `menubar`
+pub fn set_menu_on_left_click(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    match app.tray_by_id(TRAY_ID) {
+        Some(tray) => tray
+            .set_show_menu_on_left_click(enabled)
+            .map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Update the pause/resume item after transfers are paused or resumed
+pub fn refresh(app: &AppHandle) {
+    if let Some(menu) = app.try_state::<TrayMenu>() {
+        let _ = menu
+            .transfers
+            .set_text(transfers_label(transfers::is_paused(app)));
+    }
+}
+
+/// A submenu for one upload, named after its file
+fn upload_submenu(app: &AppHandle, upload: &Transfer) -> tauri::Result<Submenu<Wry>> {
+    let name = Path::new(&upload.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| upload.path.clone());
+    let linked = upload.link.is_some();
+    let open = MenuItem::with_id(
+        app,
+        format!("{}{}", OPEN_UPLOAD_PREFIX, upload.id),
+        "Open",
+        linked,
+        None::<&str>,
+    )?;
+    let copy = MenuItem::with_id(
+        app,
+        format!("{}{}", COPY_UPLOAD_PREFIX, upload.id),
+        "Copy Link",
+        linked,
+        None::<&str>,
+    )?;
+    Submenu::with_items(app, name, true, &[&open, &copy])
+}
+
+fn fill_recent_uploads(app: &AppHandle, submenu: &Submenu<Wry>) -> tauri::Result<()> {
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+    let uploads = transfers::recent_uploads(app);
+    if uploads.is_empty() {
+        let empty = MenuItem::new(app, "No Recent Uploads", false, None::<&str>)?;
+        return submenu.append(&empty);
+    }
+    for upload in &uploads {
+        submenu.append(&upload_submenu(app, upload)?)?;
+    }
+    Ok(())
+}
+
+/// Rebuild the recent uploads submenu after an upload finishes or the
+/// queue is cleared
+pub fn refresh_recent_uploads(app: &AppHandle) {
+    if let Some(menu) = app.try_state::<TrayMenu>() {
+        if let Err(e) = fill_recent_uploads(app, &menu.recent_uploads) {
+            log::warn!("Failed to update recent uploads: {}", e);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::delta;
+use crate::events::{self, Event};
+use crate::metered;
+use crate::notifications;
+use crate::profiles;
+use crate::rollout;
+use crate::sandbox;
+
+const UPDATE_SETTINGS_KEY: &str = "updates";
+/// Wait after launch before the first background check
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// How often a deferred download looks for an unmetered connection
+const METERED_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    /// Check for updates in the background and download them when found
+    pub auto_check: bool,
+    /// Download on metered connections too, instead of waiting for another network
+    pub download_on_metered: bool,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            auto_check: true,
+            download_on_metered: false,
+        }
+    }
+}
+
+/// Where the background updater is
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum UpdateStatus {
+    #[default]
+    Idle,
+    /// Found while on a metered connection; downloads once it isn't
+    Deferred {
+        version: String,
+    },
+    Downloading {
+        version: String,
+    },
+    /// Downloaded and verified, installed by `install_update`
+    Ready {
+        version: String,
+    },
+}
+
+/// The background updater's status and the package it downloaded
+#[derive(Default)]
+pub struct Updates(Mutex<UpdatesState>);
+
+#[derive(Default)]
+struct UpdatesState {
+    status: UpdateStatus,
+    downloaded: Option<(Update, Vec<u8>)>,
+}
+
+impl Updates {
+    fn lock(&self) -> std::sync::MutexGuard<'_, UpdatesState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<UpdateSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(UPDATE_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(UpdateSettings::default()),
+    }
+}
+
+/// The updater's signing key from the app config, if one is set
+pub fn pubkey(app: &AppHandle) -> Option<String> {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .filter(|key| !key.is_empty())
+        .map(|key| key.to_string())
+}
+
+/// Download an update's package, from a delta when the manifest has one
+///
+/// Progress is sent as `update-progress` events. The package is kept as the
+/// base for the next delta.
+pub async fn fetch(app: &AppHandle, update: &Update) -> Result<Vec<u8>, String> {
+    let progress = |downloaded: u64, total: Option<u64>| {
+        events::publish(app, Event::UpdateProgress { downloaded, total });
+    };
+    let rebuilt = match pubkey(app) {
+        Some(pubkey) => {
+            let mut downloaded = 0u64;
+            delta::download(app, update, &pubkey, |chunk, total| {
+                downloaded += chunk as u64;
+                progress(downloaded, total);
+            })
+            .await
+        }
+        None => None,
+    };
+    let bytes = match rebuilt {
+        Some(bytes) => bytes,
+        None => {
+            let mut downloaded = 0u64;
+            update
+                .download(
+                    |chunk, total| {
+                        downloaded += chunk as u64;
+                        progress(downloaded, total);
+                    },
+                    || {},
+                )
+                .await
+                .map_err(|e| e.to_string())?
+        }
+    };
+    delta::keep(app, &update.version, &bytes);
+    Ok(bytes)
+}
+
+fn set_status(app: &AppHandle, status: UpdateStatus) {
+    app.state::<Updates>().lock().status = status.clone();
+    events::publish(app, Event::UpdateStatus(status));
+}
+
+/// Check for updates periodically, downloading them off metered connections
+pub fn start(app: AppHandle) {
+    metered::watch();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK_DELAY).await;
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = background_check(&app).await {
+                log::warn!("Background update check failed: {}", e);
+                set_status(&app, UpdateStatus::Idle);
+            }
+        }
+    });
+}
+
+async fn background_check(app: &AppHandle) -> Result<(), String> {
+    if !read_settings(app)?.auto_check || sandbox::kind().is_some() {
+        return Ok(());
+    }
+    let busy = matches!(
+        app.state::<Updates>().lock().status,
+        UpdateStatus::Downloading { .. } | UpdateStatus::Ready { .. }
+    );
+    if busy {
+        return Ok(());
+    }
+    let updater = app.updater().map_err(|e| e.to_string())?;
+    let Some(update) = updater.check().await.map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+    if !rollout::admits(app, &update) {
+        return Ok(());
+    }
+
+    let mut deferred = false;
+    while !read_settings(app)?.download_on_metered && metered::is_metered().await {
+        if !deferred {
+            deferred = true;
+            set_status(
+                app,
+                UpdateStatus::Deferred {
+                    version: update.version.clone(),
+                },
+            );
+            let _ = notifications::notify(
+                app.clone(),
+                format!("Apollo {} is available", update.version),
+                Some("It will download once you're on an unmetered network.".to_string()),
+                None,
+                None,
+            )
+            .await;
+        }
+        tokio::time::sleep(METERED_RETRY_INTERVAL).await;
+    }
+    download(app, update).await
+}
+
+async fn download(app: &AppHandle, update: Update) -> Result<(), String> {
+    let version = update.version.clone();
+    set_status(
+        app,
+        UpdateStatus::Downloading {
+            version: version.clone(),
+        },
+    );
+    let bytes = fetch(app, &update).await?;
+
+    app.state::<Updates>().lock().downloaded = Some((update, bytes));
+    set_status(
+        app,
+        UpdateStatus::Ready {
+            version: version.clone(),
+        },
+    );
+    let _ = notifications::notify(
+        app.clone(),
+        format!("Apollo {} is ready to install", version),
+        Some("Restart Apollo to finish updating.".to_string()),
+        None,
+        None,
+    )
+    .await;
+    Ok(())
+}
+
+/// Install the package the background updater already downloaded, if any
+pub fn install_downloaded(app: &AppHandle) -> Option<Result<(), String>> {
+    let (update, bytes) = app.state::<Updates>().lock().downloaded.take()?;
+    let result = update.install(&bytes).map_err(|e| e.to_string());
+    if result.is_err() {
+        // Keep it for another attempt
+        app.state::<Updates>().lock().downloaded = Some((update, bytes));
+    }
+    Some(result)
+}
+
+/// Get the background update settings
+#[tauri::command]
+pub async fn get_update_settings(app: AppHandle) -> Result<UpdateSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the background update settings
+///
+/// A download waiting for an unmetered connection starts at its next retry
+/// once `download_on_metered` is turned on.
+#[tauri::command]
+pub async fn set_update_settings(app: AppHandle, settings: UpdateSettings) -> Result<(), String> {
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(UPDATE_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// What the background updater is doing, also sent as `update-status` events
+#[tauri::command]
+pub async fn get_update_status(app: AppHandle) -> Result<UpdateStatus, String> {
+    Ok(app.state::<Updates>().lock().status.clone())
+}
@@ -0,0 +1,145 @@
+use futures_util::stream;
+use reqwest::multipart::{Form, Part};
+use reqwest::Body;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+use crate::http;
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" | "heif" => "image/heic",
+        "avif" => "image/avif",
+        "tif" | "tiff" => "image/tiff",
+        "bmp" => "image/bmp",
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        _ => "application/octet-stream",
+    }
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Upload a file as `multipart/form-data`, streaming it from disk
+///
+/// The file goes in the `file` part; `fields` are sent as extra text parts.
+/// Returns the number of bytes sent.
+pub async fn upload_file(
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+    fields: &[(&str, String)],
+) -> Result<u64, String> {
+    upload_file_paced(url, headers, path, fields, |_| async {}).await
+}
+
+/// Like `upload_file`, returning the server's JSON response instead of the size
+pub async fn upload_file_json(
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+    fields: &[(&str, String)],
+) -> Result<serde_json::Value, String> {
+    let (response, _) = send_file(url, headers, path, fields, |_| async {}).await?;
+    response.json().await.map_err(|e| e.to_string())
+}
+
+/// Like `upload_file`, awaiting `pace` with the size of each chunk as it's read
+pub async fn upload_file_paced<F, Fut>(
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+    fields: &[(&str, String)],
+    pace: F,
+) -> Result<u64, String>
+where
+    F: Fn(u64) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    send_file(url, headers, path, fields, pace)
+        .await
+        .map(|(_, size)| size)
+}
+
+/// Like `upload_file_paced`, also returning the server's response if it's JSON
+pub async fn upload_file_paced_json<F, Fut>(
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+    fields: &[(&str, String)],
+    pace: F,
+) -> Result<(Option<serde_json::Value>, u64), String>
+where
+    F: Fn(u64) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (response, size) = send_file(url, headers, path, fields, pace).await?;
+    Ok((response.json().await.ok(), size))
+}
+
+async fn send_file<F, Fut>(
+    url: &str,
+    headers: &HashMap<String, String>,
+    path: &Path,
+    fields: &[(&str, String)],
+    pace: F,
+) -> Result<(reqwest::Response, u64), String>
+where
+    F: Fn(u64) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let size = file.metadata().await.map_err(|e| e.to_string())?.len();
+    let filename = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "upload".to_string());
+
+    let chunks = stream::unfold(Some((file, pace)), |state| async move {
+        let (mut file, pace) = state?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                pace(read as u64).await;
+                Some((Ok(buffer), Some((file, pace))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    let part = Part::stream_with_length(Body::wrap_stream(chunks), size)
+        .file_name(filename)
+        .mime_str(mime_type(path))
+        .map_err(|e| e.to_string())?;
+    let mut form = Form::new().part("file", part);
+    for (name, value) in fields {
+        form = form.text(name.to_string(), value.clone());
+    }
+
+    let request = http::with_headers(http::client()?.post(url).multipart(form), url, headers)?;
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    Ok((response, size))
+}
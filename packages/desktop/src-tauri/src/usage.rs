@@ -0,0 +1,215 @@
+use chrono::Local;
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::db::{now, Database};
+use crate::transfers::TransferDirection;
+
+/// How often command counts and the session's end time are written out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// Features listed in the stats, most used first
+const TOP_FEATURES: u32 = 20;
+
+/// The current session and commands used since the last flush
+#[derive(Default)]
+pub struct Usage(Mutex<UsageState>);
+
+#[derive(Default)]
+struct UsageState {
+    session: Option<i64>,
+    features: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureUsage {
+    pub command: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageStats {
+    pub sessions: u64,
+    pub total_session_secs: i64,
+    pub longest_session_secs: i64,
+    /// Days the app was opened at least once
+    pub active_days: u64,
+    pub features: Vec<FeatureUsage>,
+    pub uploaded_files: u64,
+    pub uploaded_bytes: u64,
+    pub downloaded_files: u64,
+    pub downloaded_bytes: u64,
+}
+
+/// Local date the stats are grouped by, e.g. `2024-03-31`
+fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn lock(app: &AppHandle) -> std::sync::MutexGuard<'_, UsageState> {
+    app.state::<Usage>()
+        .inner()
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record the start of a session and flush counts until the app quits
+pub fn start(app: AppHandle) {
+    let at = now();
+    let session = {
+        let db = app.state::<Database>();
+        let conn = db.conn();
+        conn.execute(
+            "INSERT INTO usage_sessions (day, started_at, ended_at) VALUES (?1, ?2, ?2)",
+            params![today(), at],
+        )
+        .map(|_| conn.last_insert_rowid())
+    };
+    match session {
+        Ok(id) => lock(&app).session = Some(id),
+        Err(e) => log::warn!("Failed to record usage session: {}", e),
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            flush(&app);
+        }
+    });
+}
+
+/// Count a command the frontend used
+pub fn record_invoke(invoke: &Invoke<Wry>) {
+    let app = invoke.message.webview_ref().app_handle();
+    *lock(app)
+        .features
+        .entry(invoke.message.command().to_string())
+        .or_insert(0) += 1;
+}
+
+/// Add a completed transfer to today's volume
+pub fn record_transfer(app: &AppHandle, direction: TransferDirection, bytes: u64) {
+    let result = app.state::<Database>().conn().execute(
+        "INSERT INTO usage_transfers (day, direction, files, bytes) VALUES (?1, ?2, 1, ?3)
+         ON CONFLICT (day, direction)
+         DO UPDATE SET files = files + 1, bytes = bytes + excluded.bytes",
+        params![today(), direction.as_str(), bytes],
+    );
+    if let Err(e) = result {
+        log::warn!("Failed to record transfer usage: {}", e);
+    }
+}
+
+/// Write out command counts and extend the current session to now
+pub fn flush(app: &AppHandle) {
+    let (session, features) = {
+        let mut state = lock(app);
+        (state.session, std::mem::take(&mut state.features))
+    };
+    let day = today();
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    for (command, count) in features {
+        let _ = conn.execute(
+            "INSERT INTO usage_features (day, command, count) VALUES (?1, ?2, ?3)
+             ON CONFLICT (day, command) DO UPDATE SET count = count + excluded.count",
+            params![day, command, count],
+        );
+    }
+    if let Some(id) = session {
+        let _ = conn.execute(
+            "UPDATE usage_sessions SET ended_at = ?2 WHERE id = ?1",
+            params![id, now()],
+        );
+    }
+}
+
+/// Usage statistics, for the whole history or one calendar year
+///
+/// Everything is recorded and kept on this device only: session lengths,
+/// how often each command was used, and how much was uploaded and downloaded.
+#[tauri::command]
+pub async fn get_usage_stats(app: AppHandle, year: Option<i32>) -> Result<UsageStats, String> {
+    flush(&app);
+    let (from, to) = match year {
+        Some(year) => (
+            Some(format!("{:04}-01-01", year)),
+            Some(format!("{:04}-12-31", year)),
+        ),
+        None => (None, None),
+    };
+    let range = "(?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day <= ?2)";
+
+    let db = app.state::<Database>();
+    let conn = db.conn();
+    let mut stats = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(ended_at - started_at), 0),
+                        COALESCE(MAX(ended_at - started_at), 0), COUNT(DISTINCT day)
+                 FROM usage_sessions WHERE {}",
+                range
+            ),
+            params![from, to],
+            |row| {
+                Ok(UsageStats {
+                    sessions: row.get(0)?,
+                    total_session_secs: row.get(1)?,
+                    longest_session_secs: row.get(2)?,
+                    active_days: row.get(3)?,
+                    ..UsageStats::default()
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT command, SUM(count) AS total FROM usage_features WHERE {}
+             GROUP BY command ORDER BY total DESC LIMIT ?3",
+            range
+        ))
+        .map_err(|e| e.to_string())?;
+    stats.features = stmt
+        .query_map(params![from, to, TOP_FEATURES], |row| {
+            Ok(FeatureUsage {
+                command: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    for direction in [TransferDirection::Upload, TransferDirection::Download] {
+        let (files, bytes): (u64, u64) = conn
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(SUM(files), 0), COALESCE(SUM(bytes), 0) FROM usage_transfers
+                     WHERE {} AND direction = ?3",
+                    range
+                ),
+                params![from, to, direction.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        match direction {
+            TransferDirection::Upload => {
+                stats.uploaded_files = files;
+                stats.uploaded_bytes = bytes;
+            }
+            TransferDirection::Download => {
+                stats.downloaded_files = files;
+                stats.downloaded_bytes = bytes;
+            }
+        }
+    }
+    Ok(stats)
+}
@@ -0,0 +1,57 @@
+use tauri::{AppHandle, Window};
+
+use crate::http;
+use crate::profiles;
+
+const USER_AGENT_KEY: &str = "userAgent";
+
+/// A profile's user agent override, if it set one
+pub fn read(app: &AppHandle, id: &str) -> Option<String> {
+    profiles::profile_store(app, id)
+        .ok()?
+        .get(USER_AGENT_KEY)?
+        .as_str()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Send the active profile's user agent with native requests
+pub fn load(app: &AppHandle) {
+    http::set_user_agent(read(app, &profiles::active(app)));
+}
+
+/// Get the calling window's profile's user agent override
+#[tauri::command]
+pub async fn get_user_agent(app: AppHandle, window: Window) -> Result<Option<String>, String> {
+    Ok(read(&app, &profiles::for_window(&app, window.label())))
+}
+
+/// Override the user agent of the calling window's profile, or reset it with `None`
+///
+/// For reverse proxies and firewalls that filter on it. Native requests pick
+/// it up right away when it is the active profile; webviews when their
+/// windows are next created.
+#[tauri::command]
+pub async fn set_user_agent(
+    app: AppHandle,
+    window: Window,
+    user_agent: Option<String>,
+) -> Result<(), String> {
+    let id = profiles::for_window(&app, window.label());
+    let store = profiles::profile_store(&app, &id).map_err(|e| e.to_string())?;
+    match user_agent
+        .map(|ua| ua.trim().to_string())
+        .filter(|ua| !ua.is_empty())
+    {
+        Some(ua) => store.set(USER_AGENT_KEY, serde_json::json!(ua)),
+        None => {
+            store.delete(USER_AGENT_KEY);
+        }
+    }
+    store.save().map_err(|e| e.to_string())?;
+
+    if id == profiles::active(&app) {
+        load(&app);
+    }
+    Ok(())
+}
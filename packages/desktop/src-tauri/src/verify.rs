@@ -0,0 +1,253 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::cache;
+use crate::events::{self, Event};
+use crate::fs_scope;
+use crate::http_cache;
+use crate::import::{hash_file, walk_media};
+
+/// A file the server says should be in the backup
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExpectedFile {
+    /// Path relative to the backup folder; omitted to match by checksum only
+    pub path: Option<String>,
+    /// SHA-1, hex or base64 encoded
+    pub checksum: String,
+}
+
+/// What to verify, and the server's view of what it should contain
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BackupScope {
+    /// Export folder to verify
+    pub folder: Option<String>,
+    /// Also verify the offline media cache
+    pub include_cache: bool,
+    pub expected: Vec<ExpectedFile>,
+    /// Endpoint answering with a JSON list of `ExpectedFile`, merged with `expected`
+    pub manifest_url: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscrepancyKind {
+    /// Expected by the server but not found locally
+    Missing,
+    /// Present locally with different content
+    Mismatch,
+    /// Present locally but unknown to the server
+    Extra,
+    /// Present locally but could not be read
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    pub path: Option<String>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackupReport {
+    /// Local files hashed
+    pub checked: usize,
+    /// Expected files found with matching content
+    pub verified: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProgress {
+    pub processed: usize,
+    pub total: usize,
+}
+
+/// Lowercase hex, decoding base64 checksums as servers often report them
+pub fn normalize_checksum(checksum: &str) -> String {
+    let checksum = checksum.trim();
+    let is_hex = checksum.len() == 40 && checksum.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        return checksum.to_lowercase();
+    }
+
+    match STANDARD.decode(checksum) {
+        Ok(bytes) if bytes.len() == 20 => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        _ => checksum.to_lowercase(),
+    }
+}
+
+async fn fetch_manifest(app: &AppHandle, scope: &BackupScope) -> Result<Vec<ExpectedFile>, String> {
+    match &scope.manifest_url {
+        Some(url) => http_cache::get_json(app, url, &scope.headers).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+fn discrepancy(kind: DiscrepancyKind, path: Option<&Path>) -> Discrepancy {
+    Discrepancy {
+        kind,
+        path: path.map(|p| p.to_string_lossy().to_string()),
+        expected: None,
+        actual: None,
+        error: None,
+    }
+}
+
+/// Compare local backup files against server-reported checksums
+///
+/// Every file is hashed from scratch rather than trusting cached checksums,
+/// since silent corruption doesn't change size or mtime. Emits
+/// `verify-progress` while hashing.
+#[tauri::command]
+pub async fn verify_backup(app: AppHandle, scope: BackupScope) -> Result<BackupReport, String> {
+    let mut expected = scope.expected.clone();
+    expected.extend(fetch_manifest(&app, &scope).await?);
+
+    let folder = match &scope.folder {
+        Some(folder) => Some(fs_scope::check(&app, folder)?),
+        None => None,
+    };
+    if let Some(folder) = &folder {
+        if !folder.is_dir() {
+            return Err(format!("'{}' is not a directory", folder.display()));
+        }
+    }
+    let cache_dir = match scope.include_cache {
+        true => Some(cache::media_dir(&app)?),
+        false => None,
+    };
+    if folder.is_none() && cache_dir.is_none() {
+        return Err("Nothing to verify".to_string());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut report = BackupReport::default();
+        let roots: Vec<PathBuf> = folder.iter().chain(cache_dir.iter()).cloned().collect();
+        let files: Vec<PathBuf> = roots.iter().flat_map(|root| walk_media(root).0).collect();
+        let total = files.len();
+
+        let mut local: HashMap<PathBuf, String> = HashMap::with_capacity(total);
+        for (index, file) in files.into_iter().enumerate() {
+            match hash_file(&file) {
+                Ok(checksum) => {
+                    report.bytes += std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+                    local.insert(file, checksum);
+                }
+                Err(e) => report.discrepancies.push(Discrepancy {
+                    error: Some(e.to_string()),
+                    ..discrepancy(DiscrepancyKind::Unreadable, Some(&file))
+                }),
+            }
+            report.checked += 1;
+            events::publish(
+                &app,
+                Event::VerifyProgress(VerifyProgress {
+                    processed: index + 1,
+                    total,
+                }),
+            );
+        }
+
+        let local_checksums: HashSet<&String> = local.values().collect();
+        let mut accounted: HashSet<PathBuf> = HashSet::new();
+        let mut expected_checksums = HashSet::new();
+
+        for file in &expected {
+            let checksum = normalize_checksum(&file.checksum);
+            expected_checksums.insert(checksum.clone());
+
+            let path = file
+                .path
+                .as_ref()
+                .zip(folder.as_ref())
+                .map(|(path, folder)| folder.join(path));
+            match path {
+                Some(path) => match local.get(&path) {
+                    Some(actual) if *actual == checksum => {
+                        accounted.insert(path);
+                        report.verified += 1;
+                    }
+                    Some(actual) => {
+                        report.discrepancies.push(Discrepancy {
+                            expected: Some(checksum),
+                            actual: Some(actual.clone()),
+                            ..discrepancy(DiscrepancyKind::Mismatch, Some(&path))
+                        });
+                        accounted.insert(path);
+                    }
+                    None if path.exists() => {}
+                    None => report.discrepancies.push(Discrepancy {
+                        expected: Some(checksum),
+                        ..discrepancy(DiscrepancyKind::Missing, Some(&path))
+                    }),
+                },
+                None if local_checksums.contains(&checksum) => report.verified += 1,
+                None => report.discrepancies.push(Discrepancy {
+                    expected: Some(checksum),
+                    ..discrepancy(DiscrepancyKind::Missing, None)
+                }),
+            }
+        }
+
+        let mut extra: Vec<(&PathBuf, &String)> = local
+            .iter()
+            .filter(|(path, checksum)| {
+                !accounted.contains(*path) && !expected_checksums.contains(*checksum)
+            })
+            .collect();
+        extra.sort();
+        for (path, checksum) in extra {
+            report.discrepancies.push(Discrepancy {
+                actual: Some(checksum.clone()),
+                ..discrepancy(DiscrepancyKind::Extra, Some(path))
+            });
+        }
+
+        report
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-1 of the empty string
+    const EMPTY_SHA1: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+
+    #[test]
+    fn hex_checksums_are_lowercased() {
+        assert_eq!(normalize_checksum(EMPTY_SHA1), EMPTY_SHA1);
+        assert_eq!(
+            normalize_checksum(&format!(" {}\n", EMPTY_SHA1.to_uppercase())),
+            EMPTY_SHA1
+        );
+    }
+
+    #[test]
+    fn base64_checksums_are_decoded_to_hex() {
+        assert_eq!(
+            normalize_checksum("2jmj7l5rSw0yVb/vlWAYkK/YBwk="),
+            EMPTY_SHA1
+        );
+    }
+
+    #[test]
+    fn other_checksums_are_only_lowercased() {
+        // Base64, but not 20 bytes long
+        assert_eq!(normalize_checksum("AAECAw=="), "aaecaw==");
+        assert_eq!(normalize_checksum("Not-A-Checksum"), "not-a-checksum");
+        assert_eq!(normalize_checksum(""), "");
+    }
+}
@@ -0,0 +1,379 @@
+//! Native video player for what the webview can't play
+//!
+//! System webviews only decode what the OS ships codecs for, so HEVC, AV1 on
+//! older systems, and high-bitrate 4K often won't play in the app. With the
+//! `mpv-player` feature, off by default, such videos open in a native window
+//! instead, played by libmpv with hardware decoding where the GPU has it.
+//! Builds need libmpv on the system. One video plays at a time; opening
+//! another replaces it. Progress arrives as `video-position` and
+//! `video-buffering` events, and `video-player-closed` when the window goes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+use crate::fs_scope;
+
+/// A video to open, from a server or a local file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VideoSource {
+    /// An http(s) URL or a local path
+    pub source: String,
+    pub headers: HashMap<String, String>,
+    /// Window title, e.g. the video's name
+    pub title: Option<String>,
+    /// Seconds to start at
+    pub start: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackKind {
+    Video,
+    Audio,
+    Subtitle,
+}
+
+/// A video, audio, or subtitle track of the open video
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoTrack {
+    pub id: i64,
+    pub kind: TrackKind,
+    pub title: Option<String>,
+    /// Language tag, e.g. `eng`
+    pub lang: Option<String>,
+    pub codec: Option<String>,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum VideoCommand {
+    Play,
+    Pause,
+    /// Seconds into the video
+    Seek {
+        position: f64,
+    },
+    /// Switch to a track from `get_video_tracks`, or turn the kind off
+    /// without `id`
+    SelectTrack {
+        kind: TrackKind,
+        id: Option<i64>,
+    },
+    Close,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoPosition {
+    /// Seconds into the video
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub paused: bool,
+}
+
+/// Close the player before the app exits
+pub fn shutdown() {
+    player::close();
+}
+
+/// Play a video in the native player window
+///
+/// Local paths must be inside the file access scope.
+#[tauri::command]
+pub async fn open_video_player(app: AppHandle, mut video: VideoSource) -> Result<(), String> {
+    if !video.source.starts_with("http://") && !video.source.starts_with("https://") {
+        video.source = fs_scope::check(&app, &video.source)?
+            .to_string_lossy()
+            .to_string();
+    }
+    if video
+        .start
+        .is_some_and(|start| !start.is_finite() || start < 0.0)
+    {
+        return Err("The start isn't a position in the video".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || player::open(&app, video))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Play, pause, seek, switch tracks, or close the native player
+#[tauri::command]
+pub async fn video_control(command: VideoCommand) -> Result<(), String> {
+    if let VideoCommand::Seek { position } = &command {
+        if !position.is_finite() || *position < 0.0 {
+            return Err(format!("'{}' is not a position", position));
+        }
+    }
+    player::control(command)
+}
+
+/// List the tracks of the video in the native player
+#[tauri::command]
+pub async fn get_video_tracks() -> Result<Vec<VideoTrack>, String> {
+    player::tracks()
+}
+
+#[cfg(feature = "mpv-player")]
+mod player {
+    use super::{TrackKind, VideoCommand, VideoPosition, VideoSource, VideoTrack};
+    use crate::events::{self, Event};
+    use crate::http;
+    use libmpv2::events::{Event as MpvEvent, PropertyData};
+    use libmpv2::{Format, Mpv};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tauri::AppHandle;
+
+    /// How often the position is reported while playing
+    const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// The open player; its window closes when the last handle is dropped
+    static PLAYER: Mutex<Option<Arc<Mpv>>> = Mutex::new(None);
+
+    fn current() -> Result<Arc<Mpv>, String> {
+        PLAYER
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| "No video is open".to_string())
+    }
+
+    fn create(app: &AppHandle) -> Result<Arc<Mpv>, String> {
+        let mpv = Mpv::with_initializer(|init| {
+            init.set_property("force-window", "yes")?;
+            init.set_property("keep-open", "yes")?;
+            init.set_property("hwdec", "auto-safe")?;
+            init.set_property("osc", "yes")?;
+            init.set_property("input-default-bindings", "yes")?;
+            init.set_property("input-vo-keyboard", "yes")?;
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+        let mut client = mpv.create_client(None).map_err(|e| e.to_string())?;
+        for (name, format) in [
+            ("time-pos", Format::Double),
+            ("duration", Format::Double),
+            ("pause", Format::Flag),
+            ("paused-for-cache", Format::Flag),
+            ("cache-buffering-state", Format::Int64),
+        ] {
+            client
+                .observe_property(name, format, 0)
+                .map_err(|e| e.to_string())?;
+        }
+        let mpv = Arc::new(mpv);
+
+        let app = app.clone();
+        let player = Arc::downgrade(&mpv);
+        std::thread::spawn(move || {
+            let mut position = VideoPosition {
+                position: 0.0,
+                duration: None,
+                paused: false,
+            };
+            let mut buffering = (false, 100);
+            let mut reported: Option<Instant> = None;
+
+            loop {
+                let Some(event) = client.wait_event(1.0) else {
+                    continue;
+                };
+                let report = match event {
+                    Ok(MpvEvent::Shutdown) => break,
+                    Ok(MpvEvent::PlaybackRestart) => true,
+                    Ok(MpvEvent::PropertyChange { name, change, .. }) => match (name, change) {
+                        ("time-pos", PropertyData::Double(time)) => {
+                            position.position = time;
+                            reported.is_none_or(|at| at.elapsed() >= REPORT_INTERVAL)
+                        }
+                        ("duration", PropertyData::Double(duration)) => {
+                            position.duration = Some(duration);
+                            false
+                        }
+                        ("pause", PropertyData::Flag(paused)) => {
+                            position.paused = paused;
+                            true
+                        }
+                        ("paused-for-cache", PropertyData::Flag(paused)) => {
+                            buffering.0 = paused;
+                            events::publish(
+                                &app,
+                                Event::VideoBuffering {
+                                    buffering: buffering.0,
+                                    percent: buffering.1,
+                                },
+                            );
+                            false
+                        }
+                        ("cache-buffering-state", PropertyData::Int64(percent)) => {
+                            buffering.1 = percent;
+                            if buffering.0 {
+                                events::publish(
+                                    &app,
+                                    Event::VideoBuffering {
+                                        buffering: buffering.0,
+                                        percent: buffering.1,
+                                    },
+                                );
+                            }
+                            false
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                if report {
+                    reported = Some(Instant::now());
+                    events::publish(&app, Event::VideoPosition(position.clone()));
+                }
+            }
+
+            // The window was closed or `quit` ran; forget the player unless
+            // it was already replaced
+            let mut current = PLAYER.lock().unwrap_or_else(|e| e.into_inner());
+            if current
+                .as_ref()
+                .is_some_and(|mpv| std::ptr::eq(Arc::as_ptr(mpv), player.as_ptr()))
+            {
+                current.take();
+            }
+            drop(current);
+            events::publish(&app, Event::VideoPlayerClosed);
+        });
+        Ok(mpv)
+    }
+
+    pub fn open(app: &AppHandle, video: VideoSource) -> Result<(), String> {
+        let mpv = {
+            let mut player = PLAYER.lock().unwrap_or_else(|e| e.into_inner());
+            match player.as_ref() {
+                Some(mpv) => mpv.clone(),
+                None => {
+                    let mpv = create(app)?;
+                    *player = Some(mpv.clone());
+                    mpv
+                }
+            }
+        };
+
+        // The same headers and sign-in native requests to the server get
+        let mut fields = Vec::new();
+        if video.source.starts_with("http://") || video.source.starts_with("https://") {
+            let client = http::client()?;
+            let request =
+                http::with_headers(client.get(&video.source), &video.source, &video.headers)?
+                    .build()
+                    .map_err(|e| e.to_string())?;
+            fields = request
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    let value = value.to_str().ok()?.replace(',', "\\,");
+                    Some(format!("{}: {}", name, value))
+                })
+                .collect();
+        }
+        let set = |name: &str, value: &str| {
+            mpv.set_property(name, value)
+                .map_err(|e| format!("Couldn't set '{}': {}", name, e))
+        };
+        set("http-header-fields", &fields.join(","))?;
+        set("title", video.title.as_deref().unwrap_or("Apollo"))?;
+        set(
+            "start",
+            &video
+                .start
+                .map(|start| start.to_string())
+                .unwrap_or_else(|| "none".to_string()),
+        )?;
+        set("pause", "no")?;
+        mpv.command("loadfile", &[&video.source, "replace"])
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn control(command: VideoCommand) -> Result<(), String> {
+        let mpv = current()?;
+        let result = match command {
+            VideoCommand::Play => mpv.set_property("pause", false),
+            VideoCommand::Pause => mpv.set_property("pause", true),
+            VideoCommand::Seek { position } => {
+                mpv.command("seek", &[&position.to_string(), "absolute"])
+            }
+            VideoCommand::SelectTrack { kind, id } => {
+                let property = match kind {
+                    TrackKind::Video => "vid",
+                    TrackKind::Audio => "aid",
+                    TrackKind::Subtitle => "sid",
+                };
+                let id = id.map(|id| id.to_string());
+                mpv.set_property(property, id.as_deref().unwrap_or("no"))
+            }
+            VideoCommand::Close => mpv.command("quit", &[]),
+        };
+        result.map_err(|e| e.to_string())
+    }
+
+    pub fn tracks() -> Result<Vec<VideoTrack>, String> {
+        let mpv = current()?;
+        let count: i64 = mpv
+            .get_property("track-list/count")
+            .map_err(|e| e.to_string())?;
+        let string = |index: i64, field: &str| {
+            mpv.get_property::<String>(&format!("track-list/{}/{}", index, field))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok((0..count)
+            .filter_map(|index| {
+                let kind = match string(index, "type")?.as_str() {
+                    "video" => TrackKind::Video,
+                    "audio" => TrackKind::Audio,
+                    "sub" => TrackKind::Subtitle,
+                    _ => return None,
+                };
+                Some(VideoTrack {
+                    id: mpv.get_property(&format!("track-list/{}/id", index)).ok()?,
+                    kind,
+                    title: string(index, "title"),
+                    lang: string(index, "lang"),
+                    codec: string(index, "codec"),
+                    selected: mpv
+                        .get_property(&format!("track-list/{}/selected", index))
+                        .unwrap_or(false),
+                })
+            })
+            .collect())
+    }
+
+    pub fn close() {
+        if let Ok(mpv) = current() {
+            let _ = mpv.command("quit", &[]);
+        }
+    }
+}
+
+#[cfg(not(feature = "mpv-player"))]
+mod player {
+    use super::{VideoCommand, VideoSource, VideoTrack};
+    use tauri::AppHandle;
+
+    const UNAVAILABLE: &str = "This build of Apollo doesn't include the video player";
+
+    pub fn open(_app: &AppHandle, _video: VideoSource) -> Result<(), String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn control(_command: VideoCommand) -> Result<(), String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn tracks() -> Result<Vec<VideoTrack>, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn close() {}
+}
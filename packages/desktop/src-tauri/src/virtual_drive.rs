@@ -0,0 +1,557 @@
+//! The remote library as a read-only drive, for apps like Lightroom or video
+//! editors that want a file path rather than a download
+//!
+//! Mounting needs the `virtual-drive` feature, off by default, and FUSE on
+//! the system: libfuse on Linux or macFUSE on macOS. A WinFsp mount for
+//! Windows isn't wired up yet. Each album is a folder, listed once when the
+//! drive is mounted, so mounting again picks up changes. Files are read from
+//! their synced copy when there is one, and otherwise streamed from the
+//! server with range requests in blocks kept in the media cache, so apps that
+//! seek around don't fetch the same bytes twice. The blocks are removed on
+//! unmount.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+use crate::fs_scope;
+
+/// An album to show as a folder on the drive
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DriveAlbum {
+    pub server: String,
+    pub album_id: String,
+    /// Folder name; defaults to the album id
+    pub name: Option<String>,
+    /// Endpoint answering with a JSON list of `SyncItem` for the album
+    pub list_url: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VirtualDrive {
+    pub mount_point: String,
+    pub albums: usize,
+    pub files: usize,
+}
+
+/// Unmount the drive before the app exits
+pub fn shutdown(_app: &AppHandle) {
+    os::unmount();
+}
+
+/// Mount the albums as a read-only drive at `mount_point`, an empty folder
+///
+/// Replaces the drive if one is mounted. Fails when listing any album fails;
+/// files whose size can't be found are left out.
+#[tauri::command]
+pub async fn mount_virtual_drive(
+    app: AppHandle,
+    mount_point: String,
+    albums: Vec<DriveAlbum>,
+) -> Result<VirtualDrive, String> {
+    let mount_point = fs_scope::check(&app, &mount_point)?;
+    os::mount(&app, mount_point, albums).await
+}
+
+/// Unmount the drive, if one is mounted
+///
+/// Apps with files open on the drive may keep it from unmounting until they
+/// close them.
+#[tauri::command]
+pub async fn unmount_virtual_drive() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(os::unmount)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The mounted drive, if any
+#[tauri::command]
+pub async fn get_virtual_drive() -> Result<Option<VirtualDrive>, String> {
+    Ok(os::current())
+}
+
+#[cfg(all(feature = "virtual-drive", unix))]
+mod os {
+    use super::{DriveAlbum, VirtualDrive};
+    use fuser::{
+        BackgroundSession, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData,
+        ReplyDirectory, ReplyEntry, Request,
+    };
+    use futures_util::{stream, StreamExt};
+    use reqwest::header::{CONTENT_LENGTH, RANGE};
+    use reqwest::StatusCode;
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::OsStr;
+    use std::os::unix::fs::FileExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+    use tauri::AppHandle;
+
+    use crate::cache;
+    use crate::export::{partial_path, sanitize_component, unique_path};
+    use crate::http;
+    use crate::http_cache;
+    use crate::sync::{self, SyncItem};
+    use crate::transfers;
+
+    const DRIVE_SUBDIR: &str = "drive";
+    const ROOT: u64 = fuser::FUSE_ROOT_ID;
+    /// Listings never change while mounted, so the kernel may keep them
+    const TTL: Duration = Duration::from_secs(3600);
+    const BLOCK_SIZE: u64 = 1024 * 1024;
+    /// Size lookups in flight for items the listing gives no size for
+    const CONCURRENT_LOOKUPS: usize = 8;
+
+    struct Mounted {
+        info: VirtualDrive,
+        session: BackgroundSession,
+        cache: PathBuf,
+    }
+
+    static MOUNTED: Mutex<Option<Mounted>> = Mutex::new(None);
+
+    struct RemoteFile {
+        url: String,
+        headers: Arc<HashMap<String, String>>,
+        size: u64,
+        /// Synced copy, read instead of the server
+        local: Option<PathBuf>,
+    }
+
+    enum NodeKind {
+        Folder(Vec<u64>),
+        File(RemoteFile),
+    }
+
+    struct Node {
+        parent: u64,
+        name: String,
+        kind: NodeKind,
+    }
+
+    impl Node {
+        fn file_type(&self) -> FileType {
+            match self.kind {
+                NodeKind::Folder(_) => FileType::Directory,
+                NodeKind::File(_) => FileType::RegularFile,
+            }
+        }
+    }
+
+    /// Inode `n` is `nodes[n - 1]`, starting from the root folder
+    struct Drive {
+        nodes: Vec<Node>,
+        names: HashMap<(u64, String), u64>,
+        client: reqwest::Client,
+        cache: PathBuf,
+        mounted: SystemTime,
+        uid: u32,
+        gid: u32,
+    }
+
+    fn push(nodes: &mut Vec<Node>, parent: u64, name: PathBuf, kind: NodeKind) -> u64 {
+        let ino = nodes.len() as u64 + 1;
+        nodes.push(Node {
+            parent,
+            name: name.to_string_lossy().to_string(),
+            kind,
+        });
+        if let NodeKind::Folder(children) = &mut nodes[parent as usize - 1].kind {
+            children.push(ino);
+        }
+        ino
+    }
+
+    /// The synced copy's size, else the listed one, else asked of the server
+    async fn file_size(
+        client: &reqwest::Client,
+        item: &SyncItem,
+        local: Option<&Path>,
+        headers: &HashMap<String, String>,
+    ) -> Option<u64> {
+        if let Some(path) = local {
+            if let Ok(metadata) = tokio::fs::metadata(path).await {
+                return Some(metadata.len());
+            }
+        }
+        if item.size.is_some() {
+            return item.size;
+        }
+        let request = http::with_headers(client.head(&item.url), &item.url, headers).ok()?;
+        let response = request.send().await.ok()?.error_for_status().ok()?;
+        response
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    async fn build(
+        app: &AppHandle,
+        client: &reqwest::Client,
+        albums: &[DriveAlbum],
+    ) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![Node {
+            parent: ROOT,
+            name: String::new(),
+            kind: NodeKind::Folder(Vec::new()),
+        }];
+        let mut folders = HashSet::new();
+
+        for album in albums {
+            let mut items: Vec<SyncItem> =
+                http_cache::get_json(app, &album.list_url, &album.headers).await?;
+            // Same order as sync, so clashing names get the same suffixes
+            items.sort_by(|a, b| a.id.cmp(&b.id));
+            let synced = sync::synced_paths(app, &album.server, &album.album_id);
+            let headers = Arc::new(album.headers.clone());
+            let sized: Vec<_> = stream::iter(items)
+                .map(|item| {
+                    let local = synced
+                        .get(&item.id)
+                        .filter(|path| !transfers::is_pending(app, path))
+                        .cloned();
+                    let headers = headers.clone();
+                    async move {
+                        let size = file_size(client, &item, local.as_deref(), &headers).await;
+                        (item, local, size)
+                    }
+                })
+                .buffered(CONCURRENT_LOOKUPS)
+                .collect()
+                .await;
+
+            let name = album.name.as_deref().unwrap_or(&album.album_id);
+            let folder_name = unique_path(PathBuf::from(sanitize_component(name)), &mut folders);
+            let folder = push(&mut nodes, ROOT, folder_name, NodeKind::Folder(Vec::new()));
+            let mut used = HashSet::new();
+            for (item, local, size) in sized {
+                let Some(size) = size else {
+                    log::warn!(
+                        "Left '{}' off the virtual drive: unknown size",
+                        item.filename
+                    );
+                    continue;
+                };
+                let file_name =
+                    unique_path(PathBuf::from(sanitize_component(&item.filename)), &mut used);
+                let file = RemoteFile {
+                    url: item.url,
+                    headers: headers.clone(),
+                    size,
+                    local,
+                };
+                push(&mut nodes, folder, file_name, NodeKind::File(file));
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Fetch one block of a file with a range request
+    async fn fetch_block(
+        client: &reqwest::Client,
+        file: &RemoteFile,
+        index: u64,
+    ) -> Result<Vec<u8>, String> {
+        let start = index * BLOCK_SIZE;
+        let end = (start + BLOCK_SIZE).min(file.size);
+        let request = http::with_headers(client.get(&file.url), &file.url, &file.headers)?
+            .header(RANGE, format!("bytes={}-{}", start, end - 1));
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+        // A server ignoring the range sends the whole file, which only works
+        // for the first block
+        if response.status() != StatusCode::PARTIAL_CONTENT && start > 0 {
+            return Err("The server doesn't support range requests".to_string());
+        }
+        let mut block = response.bytes().await.map_err(|e| e.to_string())?.to_vec();
+        block.truncate((end - start) as usize);
+        match block.len() as u64 == end - start {
+            true => Ok(block),
+            false => Err("The server sent less than was asked for".to_string()),
+        }
+    }
+
+    fn read_local(path: &Path, offset: u64, size: u32) -> Result<Vec<u8>, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        let mut data = vec![0; size as usize];
+        let read = file.read_at(&mut data, offset).map_err(|e| e.to_string())?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    impl Drive {
+        fn node(&self, ino: u64) -> Option<&Node> {
+            ino.checked_sub(1)
+                .and_then(|index| self.nodes.get(index as usize))
+        }
+
+        fn attr(&self, ino: u64, node: &Node) -> FileAttr {
+            let (size, perm, nlink) = match &node.kind {
+                NodeKind::Folder(_) => (0, 0o555, 2),
+                NodeKind::File(file) => (file.size, 0o444, 1),
+            };
+            FileAttr {
+                ino,
+                size,
+                blocks: size.div_ceil(512),
+                atime: self.mounted,
+                mtime: self.mounted,
+                ctime: self.mounted,
+                crtime: self.mounted,
+                kind: node.file_type(),
+                perm,
+                nlink,
+                uid: self.uid,
+                gid: self.gid,
+                rdev: 0,
+                blksize: BLOCK_SIZE as u32,
+                flags: 0,
+            }
+        }
+
+        /// A block from the media cache, fetched first if it isn't there
+        fn block(&self, ino: u64, file: &RemoteFile, index: u64) -> Result<Vec<u8>, String> {
+            let path = self.cache.join(ino.to_string()).join(index.to_string());
+            if let Ok(block) = std::fs::read(&path) {
+                return Ok(block);
+            }
+            let block = tauri::async_runtime::block_on(fetch_block(&self.client, file, index))?;
+            let partial = partial_path(&path);
+            let stored = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&partial, &block))
+                .and_then(|_| std::fs::rename(&partial, &path));
+            if let Err(e) = stored {
+                log::warn!("Failed to cache a virtual drive block: {}", e);
+            }
+            Ok(block)
+        }
+
+        fn read_remote(
+            &self,
+            ino: u64,
+            file: &RemoteFile,
+            offset: u64,
+            size: u32,
+        ) -> Result<Vec<u8>, String> {
+            let end = (offset + size as u64).min(file.size);
+            let mut data = Vec::with_capacity(end.saturating_sub(offset) as usize);
+            let mut position = offset;
+            while position < end {
+                let index = position / BLOCK_SIZE;
+                let block = self.block(ino, file, index)?;
+                let start = (position - index * BLOCK_SIZE) as usize;
+                let take = ((end - position) as usize).min(block.len().saturating_sub(start));
+                if take == 0 {
+                    break;
+                }
+                data.extend_from_slice(&block[start..start + take]);
+                position += take as u64;
+            }
+            Ok(data)
+        }
+    }
+
+    impl Filesystem for Drive {
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            let ino = name
+                .to_str()
+                .and_then(|name| self.names.get(&(parent, name.to_string())))
+                .copied();
+            match ino.and_then(|ino| self.node(ino).map(|node| self.attr(ino, node))) {
+                Some(attr) => reply.entry(&TTL, &attr, 0),
+                None => reply.error(libc::ENOENT),
+            }
+        }
+
+        fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+            match self.node(ino) {
+                Some(node) => reply.attr(&TTL, &self.attr(ino, node)),
+                None => reply.error(libc::ENOENT),
+            }
+        }
+
+        fn read(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData,
+        ) {
+            let Some(NodeKind::File(file)) = self.node(ino).map(|node| &node.kind) else {
+                reply.error(libc::ENOENT);
+                return;
+            };
+            let offset = offset.max(0) as u64;
+            let result = match &file.local {
+                Some(path) => read_local(path, offset, size),
+                None => self.read_remote(ino, file, offset, size),
+            };
+            match result {
+                Ok(data) => reply.data(&data),
+                Err(e) => {
+                    log::warn!("Failed to read from the virtual drive: {}", e);
+                    reply.error(libc::EIO);
+                }
+            }
+        }
+
+        fn readdir(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            mut reply: ReplyDirectory,
+        ) {
+            let Some(Node {
+                parent,
+                kind: NodeKind::Folder(children),
+                ..
+            }) = self.node(ino)
+            else {
+                reply.error(libc::ENOTDIR);
+                return;
+            };
+            let entries = [
+                (ino, FileType::Directory, "."),
+                (*parent, FileType::Directory, ".."),
+            ]
+            .into_iter()
+            .chain(children.iter().filter_map(|&child| {
+                self.node(child)
+                    .map(|node| (child, node.file_type(), node.name.as_str()))
+            }));
+            for (i, (ino, kind, name)) in entries.enumerate().skip(offset.max(0) as usize) {
+                // The offset is where the next call picks up
+                if reply.add(ino, i as i64 + 1, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+    }
+
+    fn take() -> Option<Mounted> {
+        MOUNTED.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    pub fn current() -> Option<VirtualDrive> {
+        MOUNTED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|mounted| mounted.info.clone())
+    }
+
+    pub fn unmount() {
+        if let Some(mounted) = take() {
+            // Dropping the session unmounts the drive
+            drop(mounted.session);
+            let _ = std::fs::remove_dir_all(&mounted.cache);
+        }
+    }
+
+    pub async fn mount(
+        app: &AppHandle,
+        mount_point: PathBuf,
+        albums: Vec<DriveAlbum>,
+    ) -> Result<VirtualDrive, String> {
+        if !mount_point.is_dir() {
+            return Err(format!("'{}' is not a folder", mount_point.display()));
+        }
+        let client = http::client()?;
+        let nodes = build(app, &client, &albums).await?;
+        let names = nodes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(index, node)| ((node.parent, node.name.clone()), index as u64 + 1))
+            .collect();
+        let files = nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::File(_)))
+            .count();
+
+        tauri::async_runtime::spawn_blocking(unmount)
+            .await
+            .map_err(|e| e.to_string())?;
+        let cache = cache::media_dir(app)?.join(DRIVE_SUBDIR);
+        // Left over if the app didn't exit cleanly
+        let _ = tokio::fs::remove_dir_all(&cache).await;
+
+        let drive = Drive {
+            nodes,
+            names,
+            client,
+            cache: cache.clone(),
+            mounted: SystemTime::now(),
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+        };
+        let info = VirtualDrive {
+            mount_point: mount_point.to_string_lossy().to_string(),
+            albums: albums.len(),
+            files,
+        };
+        #[allow(unused_mut)]
+        let mut options = vec![
+            MountOption::RO,
+            MountOption::NoExec,
+            MountOption::FSName("apollo".to_string()),
+        ];
+        #[cfg(target_os = "macos")]
+        options.push(MountOption::CUSTOM("volname=Apollo".to_string()));
+        let session = tauri::async_runtime::spawn_blocking(move || {
+            fuser::spawn_mount2(drive, &mount_point, &options)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to mount the virtual drive: {}", e))?;
+
+        *MOUNTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Mounted {
+            info: info.clone(),
+            session,
+            cache,
+        });
+        Ok(info)
+    }
+}
+
+#[cfg(not(all(feature = "virtual-drive", unix)))]
+mod os {
+    use super::{DriveAlbum, VirtualDrive};
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+
+    pub fn current() -> Option<VirtualDrive> {
+        None
+    }
+
+    pub fn unmount() {}
+
+    pub async fn mount(
+        _app: &AppHandle,
+        _mount_point: PathBuf,
+        _albums: Vec<DriveAlbum>,
+    ) -> Result<VirtualDrive, String> {
+        match cfg!(unix) {
+            true => Err("This build of Apollo doesn't include the virtual drive".to_string()),
+            false => Err("The virtual drive is not supported on this platform yet".to_string()),
+        }
+    }
+}
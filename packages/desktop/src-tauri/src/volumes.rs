@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::Disks;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+use crate::notifications::{self, NotificationCategory};
+
+const VOLUME_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A mounted removable volume, such as an SD card or USB drive
+#[derive(Debug, Clone, Serialize)]
+pub struct Volume {
+    pub name: String,
+    pub mount_point: String,
+    pub file_system: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Camera folder (DCIM) at the root of the volume, if any
+    pub dcim: Option<String>,
+}
+
+/// Find the DCIM folder at the root of a volume; names are matched case-insensitively
+/// since FAT-formatted cards may report either case
+fn find_dcim(root: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case("dcim")
+                && entry.file_type().is_ok_and(|t| t.is_dir())
+        })
+        .map(|entry| entry.path())
+}
+
+pub fn list_volumes() -> Vec<Volume> {
+    Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| disk.is_removable())
+        .map(|disk| {
+            let mount_point = disk.mount_point();
+            let name = match disk.name().to_string_lossy().to_string() {
+                name if name.is_empty() || name.starts_with("/dev/") => mount_point
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| mount_point.to_string_lossy().to_string()),
+                name => name,
+            };
+
+            Volume {
+                name,
+                mount_point: mount_point.to_string_lossy().to_string(),
+                file_system: disk.file_system().to_string_lossy().to_string(),
+                total_bytes: disk.total_space(),
+                available_bytes: disk.available_space(),
+                dcim: find_dcim(mount_point).map(|p| p.to_string_lossy().to_string()),
+            }
+        })
+        .collect()
+}
+
+async fn announce(app: &AppHandle, volume: &Volume) {
+    events::publish(app, Event::VolumeMounted(volume.clone()));
+
+    if volume.dcim.is_some() {
+        let _ = notifications::notify(
+            app.clone(),
+            format!("{} connected", volume.name),
+            Some("Open Apollo to import photos and videos from this card.".to_string()),
+            None,
+            Some(NotificationCategory::Devices),
+        )
+        .await;
+    }
+}
+
+/// Poll mounted volumes, emitting `volume-mounted` and `volume-unmounted`
+///
+/// Volumes with a DCIM folder also get an import prompt via a native
+/// notification. Volumes already mounted at startup are not announced.
+pub fn watch_volumes(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(VOLUME_POLL_INTERVAL);
+        let mut last: Option<HashMap<String, Volume>> = None;
+
+        loop {
+            interval.tick().await;
+
+            let Ok(volumes) = tauri::async_runtime::spawn_blocking(list_volumes).await else {
+                continue;
+            };
+            let current: HashMap<String, Volume> = volumes
+                .into_iter()
+                .map(|v| (v.mount_point.clone(), v))
+                .collect();
+
+            if let Some(previous) = &last {
+                for (mount_point, volume) in &current {
+                    if !previous.contains_key(mount_point) {
+                        announce(&app, volume).await;
+                    }
+                }
+                for mount_point in previous.keys() {
+                    if !current.contains_key(mount_point) {
+                        events::publish(
+                            &app,
+                            Event::VolumeUnmounted {
+                                mount_point: mount_point.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+            last = Some(current);
+        }
+    });
+}
+
+/// List mounted removable volumes and whether they hold a camera folder
+#[tauri::command]
+pub async fn list_removable_volumes() -> Result<Vec<Volume>, String> {
+    tauri::async_runtime::spawn_blocking(list_volumes)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,242 @@
+//! Tailscale and WireGuard awareness
+//!
+//! A profile can list its server twice: the address it has at home and the
+//! one it has on the tailnet. With auto-switch on, Apollo uses the home
+//! address while it answers and falls back to the tailnet one when a VPN is up.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::events::{self, Event};
+use crate::http;
+use crate::profiles;
+use crate::DEFAULT_SERVER_KEY;
+
+const VPN_SETTINGS_KEY: &str = "vpn";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// A LAN server answers well within this; a slower reply means it's away
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Last status sent, so events only go out on changes
+static LAST_STATUS: Mutex<Option<VpnStatus>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VpnSettings {
+    /// The server's address on the local network
+    pub lan_url: Option<String>,
+    /// The server's address on the tailnet or VPN
+    pub tailnet_url: Option<String>,
+    /// Switch the default server between the two by reachability
+    pub auto_switch: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VpnKind {
+    Tailscale,
+    WireGuard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VpnInterface {
+    pub name: String,
+    pub kind: VpnKind,
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VpnStatus {
+    pub connected: bool,
+    pub interfaces: Vec<VpnInterface>,
+    /// The default server URL, after any automatic switch
+    pub server_url: Option<String>,
+}
+
+pub fn read_settings(app: &AppHandle) -> Result<VpnSettings, String> {
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    match store.get(VPN_SETTINGS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string()),
+        None => Ok(VpnSettings::default()),
+    }
+}
+
+/// Tailscale hands out addresses from 100.64.0.0/10 and fd7a:115c:a1e0::/48
+fn is_tailnet_address(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64,
+        IpAddr::V6(ip) => ip.segments()[..3] == [0xfd7a, 0x115c, 0xa1e0],
+    }
+}
+
+/// What kind of VPN an interface belongs to, from its name or address
+///
+/// macOS names every tunnel `utun<n>`, so there only Tailscale's addresses
+/// give it away.
+fn classify(name: &str, ip: &IpAddr) -> Option<VpnKind> {
+    let lower = name.to_ascii_lowercase();
+    if lower.contains("tailscale") || is_tailnet_address(ip) {
+        Some(VpnKind::Tailscale)
+    } else if lower.starts_with("wg") || lower.contains("wireguard") {
+        Some(VpnKind::WireGuard)
+    } else {
+        None
+    }
+}
+
+/// VPN interfaces that are up, with their addresses
+pub fn interfaces() -> Vec<VpnInterface> {
+    let addrs = match if_addrs::get_if_addrs() {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            log::warn!("Failed to list network interfaces: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut found: Vec<VpnInterface> = Vec::new();
+    for iface in addrs {
+        let ip = iface.ip();
+        let Some(kind) = classify(&iface.name, &ip) else {
+            continue;
+        };
+        match found.iter_mut().find(|f| f.name == iface.name) {
+            Some(existing) => {
+                // An interface named like WireGuard but carrying tailnet addresses is Tailscale
+                if kind == VpnKind::Tailscale {
+                    existing.kind = kind;
+                }
+                existing.addresses.push(ip.to_string());
+            }
+            None => found.push(VpnInterface {
+                name: iface.name,
+                kind,
+                addresses: vec![ip.to_string()],
+            }),
+        }
+    }
+    found
+}
+
+/// Whether anything answers at a server's health endpoint
+///
+/// Any HTTP response counts, including a proxy's sign-in challenge.
+async fn reachable(url: &str) -> bool {
+    let health_url = format!("{}/global/health", url.trim_end_matches('/'));
+    let request = match http::client().and_then(|client| {
+        http::with_headers(client.get(&health_url), &health_url, &HashMap::new())
+    }) {
+        Ok(request) => request,
+        Err(_) => return false,
+    };
+    request.timeout(PROBE_TIMEOUT).send().await.is_ok()
+}
+
+/// Point the default server at whichever address is reachable
+///
+/// Leaves it alone when neither is, so a brief outage doesn't flip it back
+/// and forth.
+async fn switch(app: &AppHandle, settings: &VpnSettings, connected: bool) -> Result<(), String> {
+    let (Some(lan), Some(tailnet)) = (&settings.lan_url, &settings.tailnet_url) else {
+        return Ok(());
+    };
+    let target = if reachable(lan).await {
+        lan
+    } else if connected && reachable(tailnet).await {
+        tailnet
+    } else {
+        return Ok(());
+    };
+
+    let store = profiles::store(app).map_err(|e| e.to_string())?;
+    let current = store
+        .get(DEFAULT_SERVER_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    if current.as_deref() == Some(target.as_str()) {
+        return Ok(());
+    }
+    log::info!("Switching the default server to {}", target);
+    store.set(DEFAULT_SERVER_KEY, serde_json::json!(target));
+    store.save().map_err(|e| e.to_string())
+}
+
+async fn status(app: &AppHandle) -> Result<VpnStatus, String> {
+    let interfaces = tauri::async_runtime::spawn_blocking(interfaces)
+        .await
+        .map_err(|e| e.to_string())?;
+    let connected = !interfaces.is_empty();
+    let settings = read_settings(app)?;
+    if settings.auto_switch {
+        switch(app, &settings, connected).await?;
+    }
+    let server_url = profiles::store(app)
+        .map_err(|e| e.to_string())?
+        .get(DEFAULT_SERVER_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    Ok(VpnStatus {
+        connected,
+        interfaces,
+        server_url,
+    })
+}
+
+/// Check the VPN interfaces and switch servers periodically
+///
+/// Sends a `vpn-status` event whenever the status changes.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match status(&app).await {
+                Ok(status) => {
+                    let mut last = LAST_STATUS.lock().unwrap_or_else(|e| e.into_inner());
+                    if last.as_ref() != Some(&status) {
+                        *last = Some(status.clone());
+                        drop(last);
+                        events::publish(&app, Event::VpnStatus(status));
+                    }
+                }
+                Err(e) => log::warn!("VPN check failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Whether a Tailscale or WireGuard interface is up, and the server in use
+///
+/// Also switches the server right away when auto-switch is on.
+#[tauri::command]
+pub async fn get_vpn_status(app: AppHandle) -> Result<VpnStatus, String> {
+    let status = status(&app).await?;
+    *LAST_STATUS.lock().unwrap_or_else(|e| e.into_inner()) = Some(status.clone());
+    Ok(status)
+}
+
+/// Get the profile's LAN and tailnet server addresses
+#[tauri::command]
+pub async fn get_vpn_settings(app: AppHandle) -> Result<VpnSettings, String> {
+    read_settings(&app)
+}
+
+/// Save the profile's LAN and tailnet server addresses, used from the next check
+#[tauri::command]
+pub async fn set_vpn_settings(app: AppHandle, settings: VpnSettings) -> Result<(), String> {
+    for url in [&settings.lan_url, &settings.tailnet_url]
+        .into_iter()
+        .flatten()
+    {
+        reqwest::Url::parse(url).map_err(|_| format!("'{}' is not a valid URL", url))?;
+    }
+    if settings.auto_switch && (settings.lan_url.is_none() || settings.tailnet_url.is_none()) {
+        return Err("Auto-switch needs both a LAN and a tailnet URL".to_string());
+    }
+
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(VPN_SETTINGS_KEY, serde_json::json!(settings));
+    store.save().map_err(|e| e.to_string())
+}
@@ -0,0 +1,127 @@
+use std::net::{SocketAddr, UdpSocket};
+
+/// Where magic packets go unless the caller names a broadcast address
+const DEFAULT_BROADCAST: &str = "255.255.255.255";
+/// The discard port, which Wake-on-LAN listeners conventionally use
+const WOL_PORT: u16 = 9;
+
+/// Parse `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff` or `aabbccddeeff`
+fn parse_mac(mac: &str) -> Option<[u8; 6]> {
+    let hex: String = mac
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    // from_str_radix alone would take a leading `+` in a pair
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Six `0xff` bytes followed by the MAC address sixteen times
+fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Resolve `192.168.1.255` or `192.168.1.255:7`, defaulting to port 9
+fn broadcast_addr(broadcast: Option<&str>) -> Result<SocketAddr, String> {
+    let broadcast = broadcast
+        .map(|b| b.trim())
+        .filter(|b| !b.is_empty())
+        .unwrap_or(DEFAULT_BROADCAST);
+    if let Ok(addr) = broadcast.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    broadcast
+        .parse::<std::net::IpAddr>()
+        .map(|ip| SocketAddr::new(ip, WOL_PORT))
+        .map_err(|_| format!("'{}' is not a broadcast address", broadcast))
+}
+
+/// Wake a sleeping server by sending a Wake-on-LAN magic packet
+///
+/// `broadcast` is the server network's broadcast address, such as
+/// `192.168.1.255`, and defaults to `255.255.255.255`. Broadcasts don't cross
+/// routers or VPNs, so this only works from the server's own network.
+#[tauri::command]
+pub async fn wake_server(mac: String, broadcast: Option<String>) -> Result<(), String> {
+    let mac = parse_mac(&mac).ok_or_else(|| format!("'{}' is not a MAC address", mac))?;
+    let addr = broadcast_addr(broadcast.as_deref())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+        socket.set_broadcast(true).map_err(|e| e.to_string())?;
+        socket
+            .send_to(&magic_packet(mac), addr)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0xaa, 0xbb, 0xcc, 0x01, 0x02, 0xff];
+
+    #[test]
+    fn macs_parse_with_any_separator() {
+        assert_eq!(parse_mac("aa:bb:cc:01:02:ff"), Some(MAC));
+        assert_eq!(parse_mac("AA-BB-CC-01-02-FF"), Some(MAC));
+        assert_eq!(parse_mac("aabb.cc01.02ff"), Some(MAC));
+        assert_eq!(parse_mac(" aabbcc0102ff\n"), Some(MAC));
+    }
+
+    #[test]
+    fn malformed_macs_are_rejected() {
+        assert_eq!(parse_mac(""), None);
+        assert_eq!(parse_mac("aa:bb:cc:01:02"), None);
+        assert_eq!(parse_mac("aa:bb:cc:01:02:ff:00"), None);
+        assert_eq!(parse_mac("aa:bb:cc:01:02:fg"), None);
+        assert_eq!(parse_mac("+a:bb:cc:01:02:ff"), None);
+        assert_eq!(parse_mac("aa:bb:cc:01:02:é"), None);
+    }
+
+    #[test]
+    fn magic_packets_repeat_the_mac() {
+        let packet = magic_packet(MAC);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet[..6], [0xff; 6]);
+        assert!(packet[6..].chunks(6).all(|chunk| chunk == MAC));
+    }
+
+    #[test]
+    fn broadcast_addresses_default_to_the_discard_port() {
+        assert_eq!(
+            broadcast_addr(None),
+            Ok("255.255.255.255:9".parse().unwrap())
+        );
+        assert_eq!(
+            broadcast_addr(Some(" ")),
+            Ok("255.255.255.255:9".parse().unwrap())
+        );
+        assert_eq!(
+            broadcast_addr(Some("192.168.1.255")),
+            Ok("192.168.1.255:9".parse().unwrap())
+        );
+        assert_eq!(
+            broadcast_addr(Some("192.168.1.255:7")),
+            Ok("192.168.1.255:7".parse().unwrap())
+        );
+        assert!(broadcast_addr(Some("server.local")).is_err());
+    }
+}
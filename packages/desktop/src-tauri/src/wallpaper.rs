@@ -0,0 +1,332 @@
+//! Setting a photo as the desktop wallpaper
+//!
+//! The image is copied into the app's data folder first, since the system
+//! reads the file again after every restart and the original may be a cache
+//! entry or a download that gets moved. Each display keeps its own copy, so
+//! setting one display leaves the others' wallpapers in place.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Url};
+
+use crate::data_dir;
+use crate::displays::{self, Display};
+use crate::export::{self, sanitize_component};
+use crate::fs_scope;
+use crate::http;
+
+const WALLPAPER_SUBDIR: &str = "wallpaper";
+/// Folder for a wallpaper shown on every display
+const ALL_DISPLAYS: &str = "all";
+#[cfg(target_os = "windows")]
+const LOCK_SCREEN_SUBDIR: &str = "lock-screen";
+
+fn remote_url(path: &str) -> Option<Url> {
+    Url::parse(path)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| !e.is_empty())
+        .unwrap_or("jpg")
+        .to_lowercase()
+}
+
+async fn remove_dir(dir: &Path) -> Result<(), String> {
+    match tokio::fs::remove_dir_all(dir).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Replace the copy kept in `dir`, returning the new file
+///
+/// Files get a new name each time, since macOS doesn't notice a wallpaper
+/// file changing in place.
+async fn store(
+    app: &AppHandle,
+    path: &str,
+    dir: &Path,
+    headers: &HashMap<String, String>,
+) -> Result<PathBuf, String> {
+    remove_dir(dir).await?;
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let millis = chrono::Utc::now().timestamp_millis();
+
+    match remote_url(path) {
+        Some(url) => {
+            let file = dir.join(format!("{}.{}", millis, extension(url.path())));
+            export::download_to(&http::client()?, path, headers, &file).await?;
+            Ok(file)
+        }
+        None => {
+            let source = fs_scope::check(app, path)?;
+            let file = dir.join(format!("{}.{}", millis, extension(path)));
+            tokio::fs::copy(&source, &file)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(file)
+        }
+    }
+}
+
+/// Set an image as the desktop wallpaper, on one display or on all of them
+///
+/// `path` is a local file or a server URL, fetched with `headers`. `monitor`
+/// is a display id from `get_displays`. Setting a single display works on
+/// Windows and macOS; on Linux the desktop portal always sets every display.
+#[tauri::command]
+pub async fn set_wallpaper(
+    app: AppHandle,
+    path: String,
+    monitor: Option<String>,
+    headers: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    let display = match &monitor {
+        Some(id) => Some(
+            displays::list_displays(&app)?
+                .into_iter()
+                .map(|(display, _)| display)
+                .find(|display| display.id == *id)
+                .ok_or_else(|| format!("Display '{}' not found", id))?,
+        ),
+        None => None,
+    };
+    let root = data_dir::data_dir(&app)?.join(WALLPAPER_SUBDIR);
+    let dir = match &display {
+        Some(display) => root.join(sanitize_component(&display.id)),
+        None => {
+            // A wallpaper for every display replaces the per-display ones too
+            remove_dir(&root).await?;
+            root.join(ALL_DISPLAYS)
+        }
+    };
+    let file = store(&app, &path, &dir, &headers.unwrap_or_default()).await?;
+
+    os::set(&app, file, display).await
+}
+
+/// Set an image as the Windows lock screen background
+///
+/// `path` is a local file or a server URL, like for `set_wallpaper`. Fails
+/// where group policy controls the lock screen, as on many work PCs, and on
+/// other systems, which don't let apps change it.
+#[tauri::command]
+pub async fn set_lock_screen_image(
+    app: AppHandle,
+    path: String,
+    headers: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let dir = data_dir::data_dir(&app)?.join(LOCK_SCREEN_SUBDIR);
+        let file = store(&app, &path, &dir, &headers.unwrap_or_default()).await?;
+        os::set_lock_screen(file).await
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = (app, path, headers);
+        Err("Setting the lock screen is only supported on Windows".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use super::Display;
+    use cocoa::base::{id, nil, BOOL, NO};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+
+    unsafe fn to_string(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let ptr = NSString::UTF8String(value);
+        if ptr.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
+    }
+
+    /// Screens are matched by their localized name, like in `displays`
+    unsafe fn apply(file: &str, name: Option<&str>) -> Result<(), String> {
+        let path = NSString::alloc(nil).init_str(file);
+        let url: id = msg_send![class!(NSURL), fileURLWithPath: path];
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let options: id = msg_send![class!(NSDictionary), dictionary];
+        let screens: id = msg_send![class!(NSScreen), screens];
+        let count: usize = msg_send![screens, count];
+
+        let mut found = false;
+        for i in 0..count {
+            let screen: id = msg_send![screens, objectAtIndex: i];
+            if let Some(name) = name {
+                let localized: id = msg_send![screen, localizedName];
+                if to_string(localized).as_deref() != Some(name) {
+                    continue;
+                }
+            }
+            found = true;
+            let mut error: id = nil;
+            let ok: BOOL = msg_send![
+                workspace,
+                setDesktopImageURL: url
+                forScreen: screen
+                options: options
+                error: &mut error
+            ];
+            if ok == NO {
+                let description: id = match error {
+                    e if e == nil => nil,
+                    e => msg_send![e, localizedDescription],
+                };
+                return Err(to_string(description)
+                    .unwrap_or_else(|| "Failed to set the wallpaper".to_string()));
+            }
+        }
+        match found {
+            true => Ok(()),
+            false => Err("Display not found".to_string()),
+        }
+    }
+
+    /// NSScreen belongs to the main thread
+    pub async fn set(
+        app: &AppHandle,
+        file: PathBuf,
+        display: Option<Display>,
+    ) -> Result<(), String> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        app.run_on_main_thread(move || {
+            let file = file.to_string_lossy().to_string();
+            let name = display.and_then(|display| display.name);
+            let _ = tx.send(unsafe { apply(&file, name.as_deref()) });
+        })
+        .map_err(|e| e.to_string())?;
+        rx.await
+            .map_err(|_| "The wallpaper was not set".to_string())?
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use super::Display;
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+    use windows::core::{HSTRING, PCWSTR};
+    use windows::Storage::StorageFile;
+    use windows::System::UserProfile::LockScreen;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_LOCAL_SERVER,
+        COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::{DesktopWallpaper, IDesktopWallpaper};
+
+    /// Monitors are matched by their position, which `IDesktopWallpaper` and
+    /// the display bounds report alike
+    unsafe fn apply(file: &HSTRING, display: Option<&Display>) -> windows::core::Result<bool> {
+        let wallpaper: IDesktopWallpaper =
+            CoCreateInstance(&DesktopWallpaper, None, CLSCTX_LOCAL_SERVER)?;
+        let Some(display) = display else {
+            wallpaper.SetWallpaper(PCWSTR::null(), file)?;
+            return Ok(true);
+        };
+        for i in 0..wallpaper.GetMonitorDevicePathCount()? {
+            let monitor = wallpaper.GetMonitorDevicePathAt(i)?;
+            let matches = wallpaper
+                .GetMonitorRECT(PCWSTR(monitor.0))
+                .is_ok_and(|rect| rect.left == display.bounds.x && rect.top == display.bounds.y);
+            let result = match matches {
+                true => wallpaper.SetWallpaper(PCWSTR(monitor.0), file),
+                false => Ok(()),
+            };
+            CoTaskMemFree(Some(monitor.0 as _));
+            result?;
+            if matches {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub async fn set(
+        _app: &AppHandle,
+        file: PathBuf,
+        display: Option<Display>,
+    ) -> Result<(), String> {
+        tauri::async_runtime::spawn_blocking(move || unsafe {
+            let initialized = CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok();
+            let result = apply(&HSTRING::from(file.as_os_str()), display.as_ref());
+            if initialized {
+                CoUninitialize();
+            }
+            match result {
+                Ok(true) => Ok(()),
+                Ok(false) => Err("Display not found".to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn set_lock_screen(file: PathBuf) -> Result<(), String> {
+        tauri::async_runtime::spawn_blocking(move || {
+            StorageFile::GetFileFromPathAsync(&HSTRING::from(file.as_os_str()))
+                .and_then(|operation| operation.join())
+                .and_then(|file| LockScreen::SetImageFileAsync(&file))
+                .and_then(|action| action.join())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use super::Display;
+    use ashpd::desktop::wallpaper::{SetOn, WallpaperRequest};
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+
+    /// The portal reaches GNOME, KDE, and sandboxed installs alike
+    pub async fn set(
+        _app: &AppHandle,
+        file: PathBuf,
+        _display: Option<Display>,
+    ) -> Result<(), String> {
+        let file = std::fs::File::open(&file).map_err(|e| e.to_string())?;
+        WallpaperRequest::default()
+            .set_on(SetOn::Background)
+            .show_preview(false)
+            .build_file(&file)
+            .await
+            .and_then(|request| request.response())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod os {
+    use super::Display;
+    use std::path::PathBuf;
+    use tauri::AppHandle;
+
+    pub async fn set(
+        _app: &AppHandle,
+        _file: PathBuf,
+        _display: Option<Display>,
+    ) -> Result<(), String> {
+        Err("Setting the wallpaper is not supported on this platform".to_string())
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tauri::{
+    AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder, Window, WindowEvent,
+};
+
+use crate::capabilities;
+use crate::integrated_auth;
+use crate::profiles;
+use crate::zoom;
+
+static PRIVATE_WINDOW_COUNT: AtomicU32 = AtomicU32::new(0);
+static GUEST_WINDOW_COUNT: AtomicU32 = AtomicU32::new(0);
+
+const GUEST_PREFIX: &str = "guest-";
+/// Webview data of guest windows, under the app cache directory
+const GUEST_SUBDIR: &str = "guest-sessions";
+
+pub fn get_window(app: &AppHandle, label: &str) -> Result<WebviewWindow, String> {
+    app.get_webview_window(label)
+        .ok_or_else(|| format!("Window '{}' not found", label))
+}
+
+/// Exclude a window from screenshots and screen sharing
+///
+/// Uses `SetWindowDisplayAffinity` on Windows and `NSWindow.sharingType` on
+/// macOS. Linux compositors offer no equivalent, so this is a no-op there.
+#[tauri::command]
+pub async fn set_content_protection(
+    app: AppHandle,
+    label: String,
+    enabled: bool,
+) -> Result<(), String> {
+    get_window(&app, &label)?
+        .set_content_protected(enabled)
+        .map_err(|e| e.to_string())
+}
+
+/// Open a window whose webview uses an ephemeral, in-memory data store
+///
+/// Cookies, cache, and storage live only as long as the window, so it can be
+/// used to sign in to another account without touching the main session.
+/// Returns the new window's label.
+#[tauri::command]
+pub async fn open_private_window(app: AppHandle, path: Option<String>) -> Result<String, String> {
+    let id = PRIVATE_WINDOW_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    let label = format!("private-{}", id);
+    let url = WebviewUrl::App(path.unwrap_or_else(|| "index.html".to_string()).into());
+    let scale = zoom::ui_scale(&app);
+
+    let builder = WebviewWindowBuilder::new(&app, &label, url)
+        .title("Apollo (Private)")
+        .inner_size(1200.0, 800.0)
+        .min_inner_size(800.0 * scale, 600.0 * scale)
+        .incognito(true);
+    // Shares the default profile's data directory, so WebView2 needs the same arguments
+    let builder = integrated_auth::with_browser_args(&app, builder, profiles::DEFAULT_PROFILE);
+
+    #[cfg(target_os = "macos")]
+    let builder = builder
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true);
+
+    // It signs in to another account, so it is as trusted as the main window
+    capabilities::grant(&app, &label, &capabilities::ALL);
+    if let Err(e) = builder.build() {
+        capabilities::revoke(&app, &label);
+        return Err(e.to_string());
+    }
+    Ok(label)
+}
+
+/// Whether a window label belongs to a guest window
+pub fn is_guest(label: &str) -> bool {
+    label.starts_with(GUEST_PREFIX)
+}
+
+fn guest_dir(app: &AppHandle, label: Option<&str>) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(GUEST_SUBDIR);
+    Ok(match label {
+        Some(label) => dir.join(label),
+        None => dir,
+    })
+}
+
+fn remove_guest_data(dir: PathBuf) {
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to wipe guest data {}: {}", dir.display(), e);
+            }
+        }
+    });
+}
+
+/// Delete guest data left behind by a crash or forced quit
+pub fn wipe_guest_data(app: &AppHandle) {
+    if let Ok(dir) = guest_dir(app, None) {
+        remove_guest_data(dir);
+    }
+}
+
+/// Wipe a guest window's data as soon as it closes
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) && is_guest(window.label()) {
+        if let Ok(dir) = guest_dir(window.app_handle(), Some(window.label())) {
+            remove_guest_data(dir);
+        }
+    }
+}
+
+/// Open a window for showing photos to someone without exposing the owner's session
+///
+/// The webview is incognito with its own throwaway data directory, wiped when
+/// the window closes, so nothing a guest signs in to is kept. Guest windows
+/// can only call a few commands that reveal nothing about the owner, such as
+/// the OS and display info. Returns the new window's label.
+#[tauri::command]
+pub async fn open_guest_window(app: AppHandle, path: Option<String>) -> Result<String, String> {
+    let id = GUEST_WINDOW_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    let label = format!("{}{}", GUEST_PREFIX, id);
+    let url = WebviewUrl::App(path.unwrap_or_else(|| "index.html".to_string()).into());
+    let scale = zoom::ui_scale(&app);
+
+    let builder = WebviewWindowBuilder::new(&app, &label, url)
+        .title("Apollo (Guest)")
+        .inner_size(1200.0, 800.0)
+        .min_inner_size(800.0 * scale, 600.0 * scale)
+        .incognito(true)
+        .data_directory(guest_dir(&app, Some(&label))?);
+
+    #[cfg(target_os = "macos")]
+    let builder = builder
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true);
+
+    builder.build().map_err(|e| e.to_string())?;
+    Ok(label)
+}
@@ -0,0 +1,206 @@
+//! Zoom level of each window
+//!
+//! Every window keeps its own zoom factor, saved by label in the store of
+//! the profile it runs as and applied whenever its page loads, so the main
+//! window, the slideshow and any other window come back at the size they were
+//! left.
+//! The View menu zooms the focused window with the usual CmdOrCtrl `+`, `-`
+//! and `0` accelerators. On Windows and Linux the app menu is hidden from
+//! every window, so only its accelerators are left.
+//!
+//! On top of that, the UI scale makes everything denser or roomier whatever
+//! the OS DPI: it multiplies every window's zoom and minimum size, for
+//! example to make the UI bigger on a 4K display.
+
+use std::collections::HashMap;
+use tauri::menu::{
+    IsMenuItem, Menu, MenuEvent, MenuItem, MenuItemKind, PredefinedMenuItem, Submenu,
+};
+use tauri::utils::config::WindowConfig;
+use tauri::webview::{PageLoadEvent, PageLoadPayload};
+use tauri::{AppHandle, Manager, Webview, WebviewWindow, Wry};
+
+use crate::profiles;
+use crate::window::get_window;
+
+const WINDOW_ZOOM_KEY: &str = "windowZoom";
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+const ZOOM_STEP: f64 = 0.1;
+const UI_SCALE_KEY: &str = "uiScale";
+const MIN_UI_SCALE: f64 = 0.75;
+const MAX_UI_SCALE: f64 = 2.0;
+
+const ZOOM_IN_ID: &str = "zoom-in";
+const ZOOM_OUT_ID: &str = "zoom-out";
+const ZOOM_RESET_ID: &str = "zoom-reset";
+
+/// Zoom levels saved in the store of the profile the window runs as
+fn read(app: &AppHandle, label: &str) -> HashMap<String, f64> {
+    profiles::window_store(app, label)
+        .ok()
+        .and_then(|store| store.get(WINDOW_ZOOM_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn write(app: &AppHandle, label: &str, levels: &HashMap<String, f64>) -> Result<(), String> {
+    let store = profiles::window_store(app, label).map_err(|e| e.to_string())?;
+    store.set(WINDOW_ZOOM_KEY, serde_json::json!(levels));
+    store.save().map_err(|e| e.to_string())
+}
+
+fn level(app: &AppHandle, label: &str) -> f64 {
+    read(app, label).get(label).copied().unwrap_or(1.0)
+}
+
+/// The UI scale, `1.0` unless the user changed it
+pub fn ui_scale(app: &AppHandle) -> f64 {
+    profiles::store(app)
+        .ok()
+        .and_then(|store| store.get(UI_SCALE_KEY))
+        .and_then(|value| value.as_f64())
+        .filter(|scale| scale.is_finite() && *scale > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Scale a window's configured minimum size by the UI scale, before it's built
+pub fn scale_min_size(app: &AppHandle, config: &mut WindowConfig) {
+    let scale = ui_scale(app);
+    config.min_width = config.min_width.map(|width| width * scale);
+    config.min_height = config.min_height.map(|height| height * scale);
+}
+
+/// Zoom a window and remember the factor, clamped and rounded to a percent
+fn set(window: &WebviewWindow, factor: f64) -> Result<f64, String> {
+    if !factor.is_finite() {
+        return Err(format!("'{}' is not a zoom factor", factor));
+    }
+    let factor = (factor.clamp(MIN_ZOOM, MAX_ZOOM) * 100.0).round() / 100.0;
+    let app = window.app_handle();
+    window
+        .set_zoom(factor * ui_scale(app))
+        .map_err(|e| e.to_string())?;
+
+    let label = window.label();
+    let mut levels = read(app, label);
+    match factor == 1.0 {
+        true => levels.remove(label),
+        false => levels.insert(label.to_string(), factor),
+    };
+    write(app, label, &levels)?;
+    Ok(factor)
+}
+
+/// The app menu, with zoom items in its View menu
+pub fn menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let zoom_in = MenuItem::with_id(app, ZOOM_IN_ID, "Zoom In", true, Some("CmdOrCtrl+="))?;
+    let zoom_out = MenuItem::with_id(app, ZOOM_OUT_ID, "Zoom Out", true, Some("CmdOrCtrl+-"))?;
+    let reset = MenuItem::with_id(app, ZOOM_RESET_ID, "Actual Size", true, Some("CmdOrCtrl+0"))?;
+    let items: [&dyn IsMenuItem<Wry>; 3] = [&reset, &zoom_in, &zoom_out];
+
+    // Keep the standard macOS menus, adding to the View menu they already have
+    if cfg!(target_os = "macos") {
+        let menu = Menu::default(app)?;
+        for item in menu.items()? {
+            if let MenuItemKind::Submenu(submenu) = item {
+                if submenu.text()? == "View" {
+                    submenu.prepend(&PredefinedMenuItem::separator(app)?)?;
+                    submenu.prepend_items(&items)?;
+                    return Ok(menu);
+                }
+            }
+        }
+        menu.append(&Submenu::with_items(app, "View", true, &items)?)?;
+        return Ok(menu);
+    }
+    Menu::with_items(app, &[&Submenu::with_items(app, "View", true, &items)?])
+}
+
+/// Zoom the focused window from the View menu
+pub fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let step = match event.id.as_ref() {
+        ZOOM_IN_ID => Some(ZOOM_STEP),
+        ZOOM_OUT_ID => Some(-ZOOM_STEP),
+        ZOOM_RESET_ID => None,
+        _ => return,
+    };
+    let Some(window) = app
+        .webview_windows()
+        .into_values()
+        .find(|window| window.is_focused().unwrap_or(false))
+    else {
+        return;
+    };
+    let factor = match step {
+        Some(step) => level(app, window.label()) + step,
+        None => 1.0,
+    };
+    if let Err(e) = set(&window, factor) {
+        log::warn!("Failed to zoom '{}': {}", window.label(), e);
+    }
+}
+
+/// Apply a window's saved zoom as its page starts loading
+pub fn handle_page_load(webview: &Webview, payload: &PageLoadPayload<'_>) {
+    if payload.event() != PageLoadEvent::Started {
+        return;
+    }
+    let app = webview.app_handle();
+    let factor = level(app, webview.label()) * ui_scale(app);
+    if factor != 1.0 {
+        if let Err(e) = webview.set_zoom(factor) {
+            log::warn!("Failed to zoom '{}': {}", webview.label(), e);
+        }
+    }
+
+    // The app menu is only there for its accelerators outside macOS
+    #[cfg(not(target_os = "macos"))]
+    if let Some(window) = app.get_webview_window(webview.label()) {
+        let _ = window.hide_menu();
+    }
+}
+
+/// Get a window's zoom factor, where `1.0` is actual size at the UI scale
+#[tauri::command]
+pub async fn get_window_zoom(app: AppHandle, label: String) -> Result<f64, String> {
+    get_window(&app, &label)?;
+    Ok(level(&app, &label))
+}
+
+/// Zoom a window and remember it for the next time it opens
+///
+/// The factor is clamped to 50%–300%. Returns the factor applied.
+#[tauri::command]
+pub async fn set_window_zoom(app: AppHandle, label: String, factor: f64) -> Result<f64, String> {
+    set(&get_window(&app, &label)?, factor)
+}
+
+/// Get the UI scale, where `1.0` is the OS's own density
+#[tauri::command]
+pub async fn get_ui_scale(app: AppHandle) -> Result<f64, String> {
+    Ok(ui_scale(&app))
+}
+
+/// Change the UI scale and rezoom every open window
+///
+/// The scale is clamped to 75%–200%. Minimum window sizes follow it from
+/// the next time each window opens. Returns the scale applied.
+#[tauri::command]
+pub async fn set_ui_scale(app: AppHandle, scale: f64) -> Result<f64, String> {
+    if !scale.is_finite() {
+        return Err(format!("'{}' is not a UI scale", scale));
+    }
+    let scale = (scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE) * 100.0).round() / 100.0;
+    let store = profiles::store(&app).map_err(|e| e.to_string())?;
+    store.set(UI_SCALE_KEY, serde_json::json!(scale));
+    store.save().map_err(|e| e.to_string())?;
+
+    for window in app.webview_windows().values() {
+        let factor = level(&app, window.label()) * scale;
+        if let Err(e) = window.set_zoom(factor) {
+            log::warn!("Failed to zoom '{}': {}", window.label(), e);
+        }
+    }
+    Ok(scale)
+}
@@ -1,7 +1,6 @@
 /* @refresh reload */
 import { render } from "solid-js/web"
 import { invoke } from "@tauri-apps/api/core"
-import { open } from "@tauri-apps/plugin-shell"
 import { relaunch } from "@tauri-apps/plugin-process"
 import { sendNotification } from "@tauri-apps/plugin-notification"
 import { LazyStore } from "@tauri-apps/plugin-store"
@@ -27,9 +26,9 @@ const desktopPlatform: Platform = {
   // App version from Tauri
   version: await invoke<string>("get_version"),
 
-  // Open URL in default browser
+  // Open URL in default browser (validated natively)
   openLink: (url: string) => {
-    open(url)
+    invoke("open_link", { url })
   },
 
   // Restart the application
@@ -61,6 +60,8 @@ const desktopPlatform: Platform = {
     const result = await invoke<string[] | null>("open_file_picker", {
       title: opts?.title,
       multiple: opts?.multiple,
+      filters: opts?.filters,
+      defaultPath: opts?.defaultPath,
     })
 
     if (!result) return null
@@ -69,10 +70,14 @@ const desktopPlatform: Platform = {
 
   // Save file picker dialog
   saveFilePickerDialog: async (opts) => {
-    return invoke<string | null>("save_file_picker", {
+    const result = await invoke<{ path: string; filter: string | null } | null>("save_file_picker", {
       title: opts?.title,
       defaultPath: opts?.defaultPath,
+      defaultDirectory: opts?.defaultDirectory,
+      filters: opts?.filters,
     })
+
+    return result?.path ?? null
   },
 
   // Persistent storage using Tauri store plugin